@@ -4,7 +4,7 @@ extern crate criterion;
 use criterion::Criterion;
 use libxml::parser::Parser;
 use libxml::readonly::RoNode;
-use libxml::tree::{Node, NodeType};
+use libxml::tree::{Document, Node, NodeType};
 use rayon::prelude::*;
 
 // -- workhorse functions
@@ -141,10 +141,38 @@ fn bench_multi_thread_work2(c: &mut Criterion) {
     })
   });
 }
+
+fn bench_small_documents(c: &mut Criterion) {
+  let parser = Parser::default();
+  let xml = r#"<order id="42"><item sku="a1" qty="2"/><item sku="b7" qty="1"/><note>fragile</note></order>"#;
+  c.bench_function("parse small documents", move |b| {
+    b.iter(|| {
+      let doc = parser.parse_string(xml).unwrap();
+      assert_eq!(doc.get_root_element().unwrap().get_child_elements().len(), 3)
+    })
+  });
+}
+fn bench_snapshots(c: &mut Criterion) {
+  let parser = Parser::default();
+  let items: String = (0..5_000)
+    .map(|i| format!(r#"<item sku="s{i}"><name>Item &amp; {i}</name></item>"#))
+    .collect();
+  let xml = format!("<catalog>{items}</catalog>");
+  let snapshot = parser.parse_string(&xml).unwrap().dump_snapshot();
+  let mut group = c.benchmark_group("snapshots");
+  group.bench_function("reparse markup", |b| {
+    b.iter(|| parser.parse_string(&xml).unwrap())
+  });
+  group.bench_function("load snapshot", |b| {
+    b.iter(|| Document::load_snapshot(&snapshot).unwrap())
+  });
+  group.finish();
+}
+
 criterion_group!(
   name = benches;
   config = Criterion::default().sample_size(10);
-  targets = bench_single_thread_classic,  bench_single_thread_classic_work2, bench_single_thread, bench_single_thread_work2, bench_multi_thread, bench_multi_thread_work2
+  targets = bench_single_thread_classic,  bench_single_thread_classic_work2, bench_single_thread, bench_single_thread_work2, bench_multi_thread, bench_multi_thread_work2, bench_small_documents, bench_snapshots
 );
 
 criterion_main!(benches);
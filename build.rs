@@ -20,14 +20,38 @@ fn main() {
   let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR not set"));
   let bindings_path = out_dir.join("bindings.rs");
 
-  // Pre-declare the custom cfg flag to inform Cargo about its existence.
-  println!("cargo:rustc-check-cfg=cfg(libxml_older_than_2_12)");
+  // Pre-declare the custom cfg flags to inform Cargo about their existence.
+  for (major, minor) in VERSION_CFGS {
+    println!("cargo:rustc-check-cfg=cfg(libxml_older_than_{major}_{minor})");
+    println!("cargo:rustc-check-cfg=cfg(libxml_at_least_{major}_{minor})");
+  }
   // Rerun this script if environment variables or source files change.
   println!("cargo:rerun-if-env-changed=LIBXML2");
   println!("cargo:rerun-if-changed=src/wrapper.h");
-  println!("cargo:rerun-if-changed=src/default_bindings.rs");
+  for (_, path) in PREGENERATED_BINDINGS {
+    println!("cargo:rerun-if-changed={path}");
+  }
 
-  if let Some(probed_lib) = find_libxml2() {
+  let probed_lib = find_libxml2();
+  if let Some(probed_lib) = &probed_lib {
+    check_min_version(&probed_lib.version);
+  }
+  if let Some(probed_lib) = &probed_lib
+    && feature_enabled("NO_BINDGEN")
+  {
+    // Skip bindgen, and with it the libclang requirement, in favour of the pre-generated
+    // bindings for the same release.
+    copy_pregenerated_bindings(Some(&probed_lib.version), &bindings_path);
+  } else if let Some(mut probed_lib) = probed_lib {
+    // Targets with their own SDK already point clang at its sysroot, others may need one to
+    // keep the host headers out of cross builds.
+    let has_sysroot = probed_lib
+      .clang_args
+      .iter()
+      .any(|arg| arg.starts_with("--sysroot") || arg == "-isysroot");
+    if !has_sysroot {
+      probed_lib.clang_args.extend(cross_clang_args());
+    }
     // If a library is found, generate fresh bindings from its headers.
     generate_bindings(
       &probed_lib.include_paths,
@@ -36,25 +60,112 @@ fn main() {
     );
 
     // Expose the library version to the code for conditional compilation.
-    // Parse the version string, e.g., "2.13.5" -> [2, 13, 5].
-    let version_parts: Vec<u32> = probed_lib
-      .version
+    emit_version_cfgs(&probed_lib.version);
+  } else {
+    // If the library is not found (e.g., on MSVC without pkg-config), use pre-generated default bindings.
+    copy_pregenerated_bindings(None, &bindings_path);
+  }
+}
+
+/// The bindings shipped with the crate, oldest first, by the libxml2 release whose headers
+/// they were generated from (see `scripts/generate-bindings.sh`).
+const PREGENERATED_BINDINGS: [(&str, &str); 1] = [("2.9.14", "src/default_bindings_2_9.rs")];
+
+/// Parses the `major.minor` part of a libxml2 `version`, e.g. "2.13.5".
+fn major_minor(version: &str) -> (u32, u32) {
+  let mut parts = version.split('.').map(|part| part.parse::<u32>().unwrap_or(0));
+  (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+}
+
+/// Copies the pre-generated bindings for the libxml2 `version` to `output_path`, the oldest
+/// set when no library was found. The version cfg flags follow the chosen bindings, which
+/// decide the API the wrapper can use.
+///
+/// Fails the build when no set matches the `major.minor` of a library found: the layout of
+/// libxml2's structures changes between releases, so bindings for another one would build
+/// but misbehave at runtime.
+fn copy_pregenerated_bindings(version: Option<&str>, output_path: &Path) {
+  let (bindings_version, path) = match version {
+    Some(version) => PREGENERATED_BINDINGS
+      .iter()
+      .find(|(bindings_version, _)| major_minor(bindings_version) == major_minor(version))
+      .unwrap_or_else(|| {
+        let available: Vec<&str> = PREGENERATED_BINDINGS
+          .iter()
+          .map(|(bindings_version, _)| *bindings_version)
+          .collect();
+        panic!(
+          "The `no-bindgen` feature has no pre-generated bindings for libxml2 {version} (only \
+           for {}). Build without `no-bindgen` to run bindgen, which needs libclang, or \
+           generate a set with `scripts/generate-bindings.sh`.",
+          available.join(", ")
+        )
+      }),
+    None => &PREGENERATED_BINDINGS[0],
+  };
+  fs::copy(path, output_path)
+    .unwrap_or_else(|e| panic!("Failed to copy {path} to the build directory: {e}"));
+  emit_version_cfgs(bindings_version);
+}
+
+/// The `major.minor` releases changing APIs the wrapper uses, each getting a pair of
+/// `libxml_older_than_X_Y` / `libxml_at_least_X_Y` cfg flags:
+/// - 2.11: entity amplification limit (`xmlCtxtSetMaxAmplification`)
+/// - 2.12: error callbacks take a `const xmlError *`
+/// - 2.13: per-context error handlers (`xmlCtxtSetErrorHandler`), deprecated parser globals
+/// - 2.14: reworked HTML tokenizer and encoding handlers
+const VERSION_CFGS: [(u32, u32); 4] = [(2, 11), (2, 12), (2, 13), (2, 14)];
+
+/// The oldest libxml2 release the wrapper supports, unless `LIBXML2_MIN_VERSION` asks for a
+/// newer one.
+const LIBXML2_MIN_VERSION_DEFAULT: &str = "2.9.0";
+
+/// The minimum libxml2 version to accept, from `LIBXML2_MIN_VERSION` (e.g. "2.12").
+fn libxml2_min_version() -> String {
+  println!("cargo:rerun-if-env-changed=LIBXML2_MIN_VERSION");
+  env::var("LIBXML2_MIN_VERSION")
+    .ok()
+    .map(|version| version.trim().to_string())
+    .filter(|version| !version.is_empty())
+    .unwrap_or_else(|| LIBXML2_MIN_VERSION_DEFAULT.to_string())
+}
+
+/// Fails the build when the libxml2 `version` found is older than the minimum version, rather
+/// than compiling against it and breaking at runtime.
+fn check_min_version(version: &str) {
+  let parts = |version: &str| -> Vec<u32> {
+    version
       .split('.')
       .map(|part| part.parse().unwrap_or(0))
-      .collect();
+      .collect()
+  };
+  let required = libxml2_min_version();
+  if parts(version) < parts(&required) {
+    panic!(
+      "libxml2 {version} is installed, but at least {required} is required{}. \
+       Upgrade libxml2, point pkg-config (PKG_CONFIG_PATH) at a newer one, or build one from \
+       source with the `vendored` feature.",
+      if env::var_os("LIBXML2_MIN_VERSION").is_some() {
+        " by LIBXML2_MIN_VERSION"
+      } else {
+        ""
+      }
+    );
+  }
+}
 
-    // Check if the version is older than 2.12.
-    if version_parts.len() >= 2
-      && (version_parts[0] < 2 || (version_parts[0] == 2 && version_parts[1] < 12))
-    {
-      println!("cargo:rustc-cfg=libxml_older_than_2_12");
+/// Sets the version cfg flags for the libxml2 `version`, e.g. "2.13.5", and records it as the
+/// version the bindings describe.
+fn emit_version_cfgs(version: &str) {
+  // Reported by `libxml::runtime_info()`.
+  println!("cargo:rustc-env=LIBXML2_BINDINGS_VERSION={version}");
+  let found = major_minor(version);
+  for (major, minor) in VERSION_CFGS {
+    if found < (major, minor) {
+      println!("cargo:rustc-cfg=libxml_older_than_{major}_{minor}");
+    } else {
+      println!("cargo:rustc-cfg=libxml_at_least_{major}_{minor}");
     }
-  } else {
-    // If the library is not found (e.g., on MSVC without pkg-config), use pre-generated default bindings.
-    fs::copy("src/default_bindings.rs", bindings_path)
-      .expect("Failed to copy the default bindings to the build directory");
-    // The default bindings are based on an older version, so we assume it's older than 2.12.
-    println!("cargo:rustc-cfg=libxml_older_than_2_12");
   }
 }
 
@@ -64,8 +175,11 @@ fn main() {
 /// 1. `LIBXML2` environment variable (all platforms).
 /// 2. Platform-specific search:
 ///    - Android: Build from source using the NDK.
-///    - iOS: Use the library from the Xcode SDK.
-///    - Windows (MSVC): Use vcpkg.
+///    - iOS, tvOS, watchOS, visionOS and Mac Catalyst: Use the library from the Xcode SDK.
+///    - wasm32: Build from source using the WASI SDK.
+///    - With the `vendored` feature: Build a pinned libxml2 from source with CMake.
+///    - musl: Use a static libxml2 from pkg-config, or build it from source.
+///    - Windows (MSVC): Use vcpkg, or build from source when it is missing.
 ///    - Unix-like (including Windows GNU): Use pkg-config.
 fn find_libxml2() -> Option<ProbedLib> {
   // 1. First, check the `LIBXML2` environment variable.
@@ -109,11 +223,23 @@ fn find_libxml2() -> Option<ProbedLib> {
     return find_libxml2_for_android(&target);
   }
 
-  if target.contains("apple-ios") {
-    return find_libxml2_for_ios(&target);
+  if let Some((sdk, clang_target)) = apple_sdk(&target) {
+    return find_libxml2_for_apple_sdk(sdk, &clang_target);
+  }
+
+  if target.starts_with("wasm32") {
+    return find_libxml2_for_wasm(&target);
+  }
+
+  if env::var_os("CARGO_FEATURE_VENDORED").is_some() {
+    return find_libxml2_vendored(&target);
+  }
+
+  if target.contains("musl") {
+    return find_libxml2_for_musl(&target);
   }
 
-  // For non-Android and non-iOS platforms, dispatch using cfg attributes.
+  // For non-Android and non-Apple SDK platforms, dispatch using cfg attributes.
   find_libxml2_via_pkgmgr()
 }
 
@@ -124,18 +250,40 @@ fn find_libxml2() -> Option<ProbedLib> {
 ))]
 fn find_libxml2_via_pkgmgr() -> Option<ProbedLib> {
   // For Unix-like systems and Windows GNU, use pkg-config.
-  match pkg_config::Config::new().probe("libxml-2.0") {
+  match pkg_config::Config::new()
+    .atleast_version(&libxml2_min_version())
+    .probe("libxml-2.0")
+  {
     Ok(lib) => Some(ProbedLib {
       include_paths: lib.include_paths,
       version: lib.version,
       clang_args: Vec::new(),
     }),
     Err(e) => {
+      // Tell an outdated library apart from a missing one.
+      if let Some(installed) = pkg_config_version() {
+        check_min_version(&installed);
+      }
       panic!("Could not find libxml2 using pkg-config: {}", e);
     }
   }
 }
 
+/// The version of the libxml2 known to pkg-config, whatever it is.
+#[cfg(any(
+  target_family = "unix",
+  target_os = "macos",
+  all(target_family = "windows", target_env = "gnu")
+))]
+fn pkg_config_version() -> Option<String> {
+  pkg_config::Config::new()
+    .cargo_metadata(false)
+    .env_metadata(false)
+    .probe("libxml-2.0")
+    .ok()
+    .map(|lib| lib.version)
+}
+
 #[cfg(all(target_family = "windows", target_env = "msvc"))]
 mod vcpkg_dep {
   use super::ProbedLib;
@@ -188,7 +336,16 @@ fn find_libxml2_via_pkgmgr() -> Option<ProbedLib> {
   if let Some(lib) = vcpkg_dep::find_libxml2() {
     return Some(lib);
   }
-  eprintln!("Could not find libxml2 via vcpkg. Please install it using: `vcpkg install libxml2`");
+  // Without vcpkg, build libxml2 from source when the tools for it are around.
+  if which::which("cmake").is_ok() && which::which("git").is_ok() {
+    println!("cargo:warning=Could not find libxml2 via vcpkg, building it from source");
+    let target = env::var("TARGET").expect("TARGET environment variable not set");
+    return find_libxml2_vendored(&target);
+  }
+  eprintln!(
+    "Could not find libxml2 via vcpkg. Please install it using: `vcpkg install libxml2`, \
+     or install CMake and Git to build it from source"
+  );
   None
 }
 
@@ -203,34 +360,54 @@ fn find_libxml2_via_pkgmgr() -> Option<ProbedLib> {
   panic!("Unsupported platform: Could not find a suitable method to locate libxml2.");
 }
 
-/// Finds libxml2 for iOS.
-fn find_libxml2_for_ios(target: &str) -> Option<ProbedLib> {
-  // iOS builds are only supported on macOS hosts.
-  if !cfg!(target_os = "macos") {
-    panic!("iOS builds are only supported on macOS hosts");
-  }
-
-  let sdk = if target.contains("-sim") {
-    "iphonesimulator"
+/// The Xcode SDK and clang target of the Apple `target`s built against an SDK other than
+/// the macOS one, e.g. `("appletvsimulator", "arm64-apple-tvos-simulator")` for
+/// `aarch64-apple-tvos-sim`. Mac Catalyst (`-ios-macabi`) uses the macOS SDK. `None` for
+/// macOS itself and for other targets.
+fn apple_sdk(target: &str) -> Option<(&'static str, String)> {
+  let (arch, rest) = target.split_once("-apple-")?;
+  let (os, abi) = rest.split_once('-').unwrap_or((rest, ""));
+  // Intel builds for mobile platforms always run in a simulator
+  let simulator = abi == "sim" || (abi.is_empty() && matches!(arch, "x86_64" | "i386"));
+  let catalyst = abi == "macabi";
+  let (sdk, clang_os) = match os {
+    "ios" if catalyst => ("macosx", "ios"),
+    "ios" if simulator => ("iphonesimulator", "ios"),
+    "ios" => ("iphoneos", "ios"),
+    "tvos" if simulator => ("appletvsimulator", "tvos"),
+    "tvos" => ("appletvos", "tvos"),
+    "watchos" if simulator => ("watchsimulator", "watchos"),
+    "watchos" => ("watchos", "watchos"),
+    "visionos" if simulator => ("xrsimulator", "xros"),
+    "visionos" => ("xros", "xros"),
+    _ => return None,
+  };
+  let clang_arch = if arch == "aarch64" { "arm64" } else { arch };
+  let suffix = if catalyst {
+    "-macabi"
+  } else if simulator {
+    "-simulator"
   } else {
-    "iphoneos"
+    ""
   };
+  Some((sdk, format!("{clang_arch}-apple-{clang_os}{suffix}")))
+}
+
+/// Finds libxml2 in the Xcode `sdk`, for the Apple mobile platforms and Mac Catalyst.
+fn find_libxml2_for_apple_sdk(sdk: &str, clang_target: &str) -> Option<ProbedLib> {
+  // Apple SDK builds are only supported on macOS hosts.
+  if !cfg!(target_os = "macos") {
+    panic!("{clang_target} builds are only supported on macOS hosts");
+  }
 
   let sdk_path = xcrun_sdk_path(sdk)
-    .unwrap_or_else(|| panic!("Failed to resolve iOS SDK path for '{}' via xcrun", sdk));
+    .unwrap_or_else(|| panic!("Failed to resolve the {} SDK path via xcrun", sdk));
   let include_dir = sdk_path.join("usr/include/libxml2");
   let lib_dir = sdk_path.join("usr/lib");
 
   println!("cargo:rustc-link-search=native={}", lib_dir.display());
   println!("cargo:rustc-link-lib=xml2");
 
-  let clang_target = match target {
-    "aarch64-apple-ios" => "arm64-apple-ios".to_string(),
-    "aarch64-apple-ios-sim" => "arm64-apple-ios-simulator".to_string(),
-    t if t.contains("-sim") => t.replace("-apple-ios-sim", "-apple-ios-simulator"),
-    _ => target.to_string(),
-  };
-
   let clang_args = vec![
     format!("--target={}", clang_target),
     "-isysroot".to_string(),
@@ -238,15 +415,37 @@ fn find_libxml2_for_ios(target: &str) -> Option<ProbedLib> {
     format!("-I{}", include_dir.display()),
   ];
 
+  // The SDK has no pkg-config file, its headers tell which release it ships.
+  let version = header_version(&include_dir).unwrap_or_else(|| {
+    println!(
+      "cargo:warning=Could not read the libxml2 version from {}, assuming 2.9.13",
+      include_dir.display()
+    );
+    "2.9.13".to_string()
+  });
+
   Some(ProbedLib {
-    // It's not easy to get the version from the iOS SDK, so we hardcode a known compatible version here.
-    version: "2.9.13".to_string(),
+    version,
     include_paths: vec![include_dir],
     clang_args,
   })
 }
 
-/// Gets the iOS SDK path via `xcrun`.
+/// Reads the libxml2 version (e.g. "2.9.13") from `libxml/xmlversion.h` below `include_dir`.
+fn header_version(include_dir: &Path) -> Option<String> {
+  let header = fs::read_to_string(include_dir.join("libxml/xmlversion.h")).ok()?;
+  header.lines().find_map(|line| {
+    let value = line
+      .trim()
+      .strip_prefix("#define")?
+      .trim_start()
+      .strip_prefix("LIBXML_DOTTED_VERSION")?;
+    let version = value.trim().trim_matches('"');
+    (!version.is_empty()).then(|| version.to_string())
+  })
+}
+
+/// Gets the path of an Apple SDK via `xcrun`.
 fn xcrun_sdk_path(sdk: &str) -> Option<PathBuf> {
   let out = Command::new("xcrun")
     .args(["--sdk", sdk, "--show-sdk-path"])
@@ -336,6 +535,7 @@ fn find_libxml2_for_android(target: &str) -> Option<ProbedLib> {
     dst.join("lib").display()
   );
   println!("cargo:rustc-link-lib=static=xml2");
+  link_module_dependencies(target);
 
   // Configure clang arguments for bindgen.
   let sysroot = ndk_root
@@ -361,7 +561,7 @@ fn find_libxml2_for_android(target: &str) -> Option<ProbedLib> {
   }
 
   Some(ProbedLib {
-    version: "2.13.5".to_string(), // Version from the source build.
+    version: source_build_version(&include_dir),
     include_paths: vec![include_dir],
     clang_args,
   })
@@ -413,42 +613,16 @@ fn map_clang_target_to_sysroot_arch(clang_target: &str) -> &'static str {
 
 /// Builds libxml2 for Android using CMake and the NDK.
 fn build_libxml2_for_android(ndk_root: &Path, abi: &str, api: u32) -> (PathBuf, PathBuf) {
-  let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
-  let src_dir = out_dir.join("libxml2-src");
-
-  // If libxml2 is already built and the include directory exists, skip clone and build for efficiency.
-  let build_dir = out_dir.join("build");
-  let dst = out_dir.join("libxml2-build");
-  let include_dir = dst.join("include").join("libxml2");
+  let (src_dir, dst, include_dir) = libxml2_build_dirs();
+  let build_dir = dst.join("build");
 
   if include_dir.exists() {
   // Already built, return the existing build and include directory.
     return (dst, include_dir);
   }
 
-  // Clone the libxml2 repository if it does not exist locally.
-  if !src_dir.exists() {
-    if which::which("git").is_err() {
-      panic!("Git not found. Please install git and ensure it is in your PATH.");
-    }
-    let repo_url = env::var("LIBXML2_GIT")
-      .unwrap_or_else(|_| "https://github.com/GNOME/libxml2.git".to_string());
-    let status = Command::new("git")
-      .args([
-        "clone",
-        "--depth",
-        "1",
-        "--branch",
-        "v2.13.5",
-        &repo_url,
-        src_dir.to_str().unwrap(),
-      ])
-      .status()
-      .expect("Failed to execute git. Is it installed and in PATH?");
-    if !status.success() {
-      panic!("'git clone' of libxml2 failed with status: {}", status);
-    }
-  }
+  // Fetch the libxml2 sources if they are not there yet.
+  let src_dir = prepare_libxml2_source(&src_dir);
 
   // remove CMake cache
   if build_dir.exists() {
@@ -457,23 +631,15 @@ fn build_libxml2_for_android(ndk_root: &Path, abi: &str, api: u32) -> (PathBuf,
   }
 
   // Configure CMake.
-  let mut cfg = cmake::Config::new(&src_dir);
+  let mut cfg = libxml2_cmake_config(&src_dir);
   cfg
-    .profile("Release")
+    .out_dir(&dst)
     .define(
       "CMAKE_TOOLCHAIN_FILE",
       ndk_root.join("build/cmake/android.toolchain.cmake"),
     )
     .define("ANDROID_ABI", abi)
-    .define("ANDROID_PLATFORM", api.to_string())
-    .define("BUILD_SHARED_LIBS", "OFF")
-    // Trim features to reduce binary size and dependencies.
-    .define("LIBXML2_WITH_PYTHON", "OFF")
-    .define("LIBXML2_WITH_LZMA", "OFF")
-    .define("LIBXML2_WITH_ZLIB", "OFF")
-    .define("LIBXML2_WITH_ICONV", "OFF")
-    .define("LIBXML2_WITH_TESTS", "OFF")
-    .define("LIBXML2_WITH_PROGRAMS", "OFF");
+    .define("ANDROID_PLATFORM", api.to_string());
 
   // Prefer using the Ninja generator.
   if let Ok(ninja_path) = which::which("ninja") {
@@ -493,10 +659,7 @@ fn build_libxml2_for_android(ndk_root: &Path, abi: &str, api: u32) -> (PathBuf,
   }
 
   // Run the build.
-  let build_result = cfg.build();
-  // 为兼容原逻辑，build() 返回的路径可能不是 dst，重新赋值。
-  let include_dir = build_result.join("include").join("libxml2");
-  let dst = build_result;
+  cfg.build();
 
   if !include_dir.exists() {
     panic!(
@@ -508,6 +671,472 @@ fn build_libxml2_for_android(ndk_root: &Path, abi: &str, api: u32) -> (PathBuf,
   (dst, include_dir)
 }
 
+/// The cargo features toggling the libxml2 modules of the same name in source builds.
+const MODULE_FEATURES: [(&str, &[&str]); 6] = [
+  ("HTML", &["LIBXML2_WITH_HTML"]),
+  ("SCHEMAS", &["LIBXML2_WITH_SCHEMAS", "LIBXML2_WITH_SCHEMATRON"]),
+  ("HTTP", &["LIBXML2_WITH_HTTP", "LIBXML2_WITH_FTP"]),
+  ("ICONV", &["LIBXML2_WITH_ICONV"]),
+  ("ZLIB", &["LIBXML2_WITH_ZLIB"]),
+  ("LZMA", &["LIBXML2_WITH_LZMA"]),
+];
+
+/// Whether the cargo feature `name` (upper case) is enabled.
+fn feature_enabled(name: &str) -> bool {
+  env::var_os(format!("CARGO_FEATURE_{}", name)).is_some()
+}
+
+/// The CMake configuration shared by all source builds of libxml2: a static release build
+/// without bindings and programs, with the optional modules selected by the cargo features.
+fn libxml2_cmake_config(src_dir: &Path) -> cmake::Config {
+  let mut cfg = cmake::Config::new(src_dir);
+  cfg
+    .profile("Release")
+    .define("BUILD_SHARED_LIBS", "OFF")
+    .define("LIBXML2_WITH_PYTHON", "OFF")
+    .define("LIBXML2_WITH_TESTS", "OFF")
+    .define("LIBXML2_WITH_PROGRAMS", "OFF");
+  for (feature, options) in MODULE_FEATURES {
+    let value = if feature_enabled(feature) { "ON" } else { "OFF" };
+    for option in options {
+      cfg.define(option, value);
+    }
+  }
+  cfg
+}
+
+/// Links the system libraries needed by the optional modules of a static libxml2.
+fn link_module_dependencies(target: &str) {
+  let msvc = target.contains("msvc");
+  if feature_enabled("ZLIB") {
+    println!("cargo:rustc-link-lib={}", if msvc { "zlib" } else { "z" });
+  }
+  if feature_enabled("LZMA") {
+    println!("cargo:rustc-link-lib=lzma");
+  }
+  if feature_enabled("ICONV") && (target.contains("apple") || target.contains("windows")) {
+    // iconv is part of the C library elsewhere
+    println!("cargo:rustc-link-lib=iconv");
+  }
+  if feature_enabled("HTTP") && target.contains("windows") {
+    println!("cargo:rustc-link-lib=ws2_32");
+  }
+}
+
+/// The libxml2 release built from source unless `LIBXML2_VERSION` selects another one.
+const LIBXML2_DEFAULT_VERSION: &str = "2.13.5";
+
+/// The libxml2 release to build from source, from `LIBXML2_VERSION` (e.g. "2.12.9").
+fn libxml2_source_version() -> String {
+  println!("cargo:rerun-if-env-changed=LIBXML2_VERSION");
+  env::var("LIBXML2_VERSION")
+    .ok()
+    .map(|version| version.trim().trim_start_matches('v').to_string())
+    .filter(|version| !version.is_empty())
+    .unwrap_or_else(|| LIBXML2_DEFAULT_VERSION.to_string())
+}
+
+/// The source, install and include directories of a source build in `OUT_DIR`, named after
+/// the release so that changing `LIBXML2_VERSION` or `LIBXML2_SRC_DIR` triggers a new build.
+fn libxml2_build_dirs() -> (PathBuf, PathBuf, PathBuf) {
+  println!("cargo:rerun-if-env-changed=LIBXML2_SRC_DIR");
+  let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+  let key = match env::var_os("LIBXML2_SRC_DIR") {
+    Some(local) => {
+      // A path based name, the release of local sources is only known after the build
+      let hash = local
+        .to_string_lossy()
+        .bytes()
+        .fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte.into()));
+      format!("local-{:08x}", hash)
+    }
+    None => libxml2_source_version(),
+  };
+  let src_dir = out_dir.join(format!("libxml2-{}-src", key));
+  let dst = out_dir.join(format!("libxml2-{}-build", key));
+  let include_dir = dst.join("include").join("libxml2");
+  (src_dir, dst, include_dir)
+}
+
+/// Makes the libxml2 sources available and returns their directory:
+/// - `LIBXML2_SRC_DIR` pointing at a source tree is used in place.
+/// - `LIBXML2_SRC_DIR` pointing at a release tarball (`.tar.gz`, `.tar.xz`) is extracted to
+///   `src_dir`, for air-gapped builds.
+/// - Otherwise the `LIBXML2_VERSION` tag (by default 2.13.5) is cloned to `src_dir` from
+///   `LIBXML2_GIT` or the GNOME repository.
+fn prepare_libxml2_source(src_dir: &Path) -> PathBuf {
+  if let Some(local) = env::var_os("LIBXML2_SRC_DIR").map(PathBuf::from) {
+    println!("cargo:rerun-if-changed={}", local.display());
+    if local.is_dir() {
+      return local;
+    }
+    if !local.is_file() {
+      panic!(
+        "LIBXML2_SRC_DIR points to neither a directory nor a tarball: {}",
+        local.display()
+      );
+    }
+    if !src_dir.exists() {
+      extract_tarball(&local, src_dir);
+    }
+    return src_dir.to_path_buf();
+  }
+
+  if !src_dir.exists() {
+    clone_libxml2(&libxml2_source_version(), src_dir);
+  }
+  src_dir.to_path_buf()
+}
+
+/// Extracts the libxml2 release `tarball` to `src_dir`, dropping its top-level directory.
+fn extract_tarball(tarball: &Path, src_dir: &Path) {
+  if which::which("tar").is_err() {
+    panic!("tar not found. Please install tar to build libxml2 from a tarball.");
+  }
+  fs::create_dir_all(src_dir).expect("Failed to create the libxml2 source directory");
+  let status = Command::new("tar")
+    .arg("-xf")
+    .arg(tarball)
+    .arg("-C")
+    .arg(src_dir)
+    .arg("--strip-components=1")
+    .status()
+    .expect("Failed to execute tar. Is it installed and in PATH?");
+  if !status.success() {
+    let _ = fs::remove_dir_all(src_dir);
+    panic!(
+      "Extracting libxml2 from {} failed with status: {}",
+      tarball.display(),
+      status
+    );
+  }
+}
+
+/// Clones the libxml2 release `version` into `src_dir`.
+fn clone_libxml2(version: &str, src_dir: &Path) {
+  if which::which("git").is_err() {
+    panic!("Git not found. Please install git and ensure it is in your PATH.");
+  }
+  println!("cargo:rerun-if-env-changed=LIBXML2_GIT");
+  let repo_url = env::var("LIBXML2_GIT")
+    .unwrap_or_else(|_| "https://github.com/GNOME/libxml2.git".to_string());
+  let status = Command::new("git")
+    .args([
+      "clone",
+      "--depth",
+      "1",
+      "--branch",
+      &format!("v{}", version),
+      &repo_url,
+      src_dir.to_str().unwrap(),
+    ])
+    .status()
+    .expect("Failed to execute git. Is it installed and in PATH?");
+  if !status.success() {
+    panic!("'git clone' of libxml2 failed with status: {}", status);
+  }
+}
+
+/// The release of a source build, as found in its installed headers.
+fn source_build_version(include_dir: &Path) -> String {
+  header_version(include_dir).unwrap_or_else(libxml2_source_version)
+}
+
+/// Finds and builds libxml2 for `wasm32-wasi*` and `wasm32-unknown-unknown`.
+///
+/// libxml2 is compiled against the wasi-libc sysroot of the WASI SDK. WASI targets provide
+/// the WASI imports at runtime; for `wasm32-unknown-unknown`, wasi-libc is linked statically
+/// and its imports are stubbed out by the crate (see `src/wasm.rs`), so file and network
+/// I/O fail while in-memory parsing, XPath and serialization work.
+fn find_libxml2_for_wasm(target: &str) -> Option<ProbedLib> {
+  println!("cargo:rerun-if-env-changed=WASI_SDK_PATH");
+
+  let sdk_root = env::var("WASI_SDK_PATH")
+    .map(PathBuf::from)
+    .expect("wasm32 target detected, but WASI_SDK_PATH is not set.");
+
+  if which::which("cmake").is_err() {
+    panic!("CMake not found. Please install CMake and ensure it is on your PATH.");
+  }
+
+  let sysroot = sdk_root.join("share/wasi-sysroot");
+  let (dst, include_dir) = build_libxml2_for_wasm(&sdk_root);
+
+  println!(
+    "cargo:rustc-link-search=native={}",
+    dst.join("lib").display()
+  );
+  println!("cargo:rustc-link-lib=static=xml2");
+  link_module_dependencies(target);
+
+  if target == "wasm32-unknown-unknown" {
+    // Rust does not bring a C library for this target, use the one of the WASI SDK.
+    let libc_dir = ["lib/wasm32-wasip1", "lib/wasm32-wasi"]
+      .iter()
+      .map(|dir| sysroot.join(dir))
+      .find(|dir| dir.join("libc.a").exists())
+      .unwrap_or_else(|| panic!("libc.a not found in WASI sysroot {}", sysroot.display()));
+    println!("cargo:rustc-link-search=native={}", libc_dir.display());
+    println!("cargo:rustc-link-lib=static=c");
+  }
+
+  let clang_args = vec![
+    "--target=wasm32-wasi".to_string(),
+    format!("--sysroot={}", sysroot.display()),
+    format!("-I{}", include_dir.display()),
+    "-fvisibility=default".to_string(),
+  ];
+
+  Some(ProbedLib {
+    version: source_build_version(&include_dir),
+    include_paths: vec![include_dir],
+    clang_args,
+  })
+}
+
+/// Builds libxml2 for wasm32 using CMake and the WASI SDK toolchain file.
+fn build_libxml2_for_wasm(sdk_root: &Path) -> (PathBuf, PathBuf) {
+  let (src_dir, dst, include_dir) = libxml2_build_dirs();
+
+  if include_dir.exists() {
+    return (dst, include_dir);
+  }
+
+  let src_dir = prepare_libxml2_source(&src_dir);
+
+  let mut cfg = libxml2_cmake_config(&src_dir);
+  cfg
+    .out_dir(&dst)
+    .define(
+      "CMAKE_TOOLCHAIN_FILE",
+      sdk_root.join("share/cmake/wasi-sdk.cmake"),
+    )
+    .define("WASI_SDK_PREFIX", sdk_root)
+    // There are no threads, sockets or dynamic loading to build against.
+    .define("LIBXML2_WITH_THREADS", "OFF")
+    .define("LIBXML2_WITH_HTTP", "OFF")
+    .define("LIBXML2_WITH_FTP", "OFF")
+    .define("LIBXML2_WITH_MODULES", "OFF");
+
+  cfg.build();
+  if !include_dir.exists() {
+    panic!(
+      "libxml2 include directory not found after build at {}",
+      include_dir.display()
+    );
+  }
+
+  (dst, include_dir)
+}
+
+/// Builds libxml2 from source for the `vendored` feature on desktop targets
+/// (Linux, macOS, Windows), with the C toolchain the `cmake` crate picks for `target`.
+fn find_libxml2_vendored(target: &str) -> Option<ProbedLib> {
+  if which::which("cmake").is_err() {
+    panic!("CMake not found. The `vendored` feature needs CMake on your PATH.");
+  }
+
+  let (src_dir, dst, include_dir) = libxml2_build_dirs();
+
+  if !include_dir.exists() {
+    let src_dir = prepare_libxml2_source(&src_dir);
+    let mut cfg = libxml2_cmake_config(&src_dir);
+    cfg.out_dir(&dst);
+    if target.contains("msvc") {
+      // Match the C runtime Rust links against, unless `crt-static` asks for the static one.
+      cfg.static_crt(target_crt_static());
+    }
+    cfg.build();
+    if !include_dir.exists() {
+      panic!(
+        "libxml2 include directory not found after build at {}",
+        include_dir.display()
+      );
+    }
+  }
+
+  println!(
+    "cargo:rustc-link-search=native={}",
+    dst.join("lib").display()
+  );
+  let mut clang_args = vec![format!("-I{}", include_dir.display())];
+  clang_args.extend(musl_clang_args(target));
+  if target.contains("windows") {
+    // The static library has no `__declspec(dllimport)` exports.
+    clang_args.push("-DLIBXML_STATIC".to_string());
+    if target.contains("msvc") {
+      // CMake names the static release library `libxml2s.lib` on MSVC.
+      println!("cargo:rustc-link-lib=static=libxml2s");
+    } else {
+      println!("cargo:rustc-link-lib=static=xml2");
+    }
+  } else {
+    println!("cargo:rustc-link-lib=static=xml2");
+    println!("cargo:rustc-link-lib=m");
+  }
+  link_module_dependencies(target);
+
+  Some(ProbedLib {
+    version: source_build_version(&include_dir),
+    include_paths: vec![include_dir],
+    clang_args,
+  })
+}
+
+/// Whether the target links the C runtime statically, as musl targets do by default.
+fn target_crt_static() -> bool {
+  env::var("CARGO_CFG_TARGET_FEATURE")
+    .is_ok_and(|features| features.split(',').any(|f| f == "crt-static"))
+}
+
+/// Finds libxml2 for `*-linux-musl` targets, e.g. for Alpine or `scratch` images.
+///
+/// Rust links musl binaries statically, so a shared libxml2 can't be used: ask pkg-config for
+/// the static library and its private dependencies (as provided by Alpine's `libxml2-static`),
+/// and fall back to the vendored build when there is none or it is older than the minimum
+/// version, e.g. when cross-compiling from a glibc host.
+#[cfg(target_family = "unix")]
+fn find_libxml2_for_musl(target: &str) -> Option<ProbedLib> {
+  let mut config = pkg_config::Config::new();
+  config
+    .statik(target_crt_static())
+    .atleast_version(&libxml2_min_version());
+  match config.probe("libxml-2.0") {
+    Ok(lib) => Some(ProbedLib {
+      include_paths: lib.include_paths,
+      version: lib.version,
+      clang_args: musl_clang_args(target),
+    }),
+    Err(e) => {
+      println!(
+        "cargo:warning=No libxml2 for {} via pkg-config ({}), building it from source",
+        target,
+        e.to_string().lines().next().unwrap_or_default()
+      );
+      find_libxml2_vendored(target)
+    }
+  }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn find_libxml2_for_musl(target: &str) -> Option<ProbedLib> {
+  // There is no pkg-config for musl outside of Unix hosts.
+  find_libxml2_vendored(target)
+}
+
+/// Extra clang arguments for musl targets: the musl headers (e.g. from Debian's `musl-dev`)
+/// must be preferred to the glibc ones of the host.
+fn musl_clang_args(target: &str) -> Vec<String> {
+  if !target.contains("musl") {
+    return Vec::new();
+  }
+  let arch = target.split('-').next().unwrap_or_default();
+  [
+    format!("/usr/include/{}-linux-musl", arch),
+    "/usr/lib/musl/include".to_string(),
+  ]
+  .iter()
+  .map(PathBuf::from)
+  .find(|dir| dir.join("stdio.h").exists())
+  .map(|dir| vec!["-isystem".to_string(), dir.display().to_string()])
+  .unwrap_or_default()
+}
+
+/// Extra clang arguments for cross builds, so that bindgen sees the headers of the target
+/// rather than the ones of the host (bindgen already passes `--target` itself):
+/// - `PKG_CONFIG_SYSROOT_DIR` (also used by pkg-config) or the sysroot of the
+///   `${CROSS_COMPILE}gcc` toolchain is passed as `--sysroot`.
+/// - When the C compiler is `zig cc` (e.g. with cargo-zigbuild), the libc headers bundled
+///   with zig are used.
+fn cross_clang_args() -> Vec<String> {
+  println!("cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR");
+  println!("cargo:rerun-if-env-changed=CROSS_COMPILE");
+  let target = env::var("TARGET").unwrap_or_default();
+  let host = env::var("HOST").unwrap_or_default();
+  if target == host {
+    return Vec::new();
+  }
+
+  if let Some(sysroot) = env::var_os("PKG_CONFIG_SYSROOT_DIR").filter(|dir| !dir.is_empty()) {
+    return vec![format!("--sysroot={}", Path::new(&sysroot).display())];
+  }
+  if target_c_compiler(&target).is_some_and(|cc| cc.contains("zig"))
+    && let Some(args) = zig_clang_args(&target)
+  {
+    return args;
+  }
+  if let Ok(prefix) = env::var("CROSS_COMPILE") {
+    let sysroot = Command::new(format!("{}gcc", prefix))
+      .arg("-print-sysroot")
+      .output()
+      .ok()
+      .filter(|out| out.status.success())
+      .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+      .filter(|dir| !dir.is_empty() && Path::new(dir).is_dir());
+    if let Some(sysroot) = sysroot {
+      return vec![format!("--sysroot={}", sysroot)];
+    }
+  }
+  Vec::new()
+}
+
+/// The C compiler configured for `target`, looked up the way the `cc` crate does.
+fn target_c_compiler(target: &str) -> Option<String> {
+  let names = [
+    format!("CC_{}", target),
+    format!("CC_{}", target.replace('-', "_")),
+    "TARGET_CC".to_string(),
+    "CC".to_string(),
+  ];
+  names.iter().find_map(|name| {
+    println!("cargo:rerun-if-env-changed={}", name);
+    env::var(name).ok().filter(|cc| !cc.is_empty())
+  })
+}
+
+/// Include arguments for the libc headers zig ships for the Linux `target`, found through the
+/// `lib_dir` of `zig env`.
+fn zig_clang_args(target: &str) -> Option<Vec<String>> {
+  let mut parts = target.split('-');
+  let arch = match parts.next()? {
+    arch if arch.starts_with("armv7") || arch == "arm" => "arm",
+    "i586" | "i686" => "x86",
+    arch => arch,
+  };
+  if !target.contains("-linux-") {
+    return None;
+  }
+  let abi = parts.next_back()?;
+
+  let out = Command::new("zig").arg("env").output().ok()?;
+  let env_text = String::from_utf8_lossy(&out.stdout);
+  // `zig env` prints JSON or ZON depending on the version, both with a quoted `lib_dir`
+  let after_key = &env_text[env_text.find("lib_dir")? + "lib_dir".len()..];
+  let start = after_key.find('"')? + 1;
+  let end = start + after_key[start..].find('"')?;
+  let libc_include = PathBuf::from(&after_key[start..end]).join("libc/include");
+
+  let generic = if abi.starts_with("musl") {
+    "generic-musl"
+  } else {
+    "generic-glibc"
+  };
+  let dirs = [
+    format!("{}-linux-{}", arch, abi),
+    generic.to_string(),
+    format!("{}-linux-any", arch),
+    "any-linux-any".to_string(),
+  ];
+  let mut args = vec!["-nostdlibinc".to_string()];
+  for dir in dirs.iter().map(|dir| libc_include.join(dir)) {
+    if dir.is_dir() {
+      args.push("-isystem".to_string());
+      args.push(dir.display().to_string());
+    }
+  }
+  Some(args)
+}
+
 /// Generates Rust bindings using bindgen.
 fn generate_bindings(include_paths: &[PathBuf], extra_clang_args: &[String], output_path: &Path) {
   let mut builder = bindgen::Builder::default()
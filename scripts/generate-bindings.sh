@@ -0,0 +1,18 @@
+#!/bin/bash
+# Regenerates the pre-generated bindings from the headers of a libxml2 release, with the
+# same options as build.rs, e.g.:
+#   scripts/generate-bindings.sh /usr/include/libxml2
+# writes src/default_bindings_2_9.rs for libxml2 2.9.x headers. Needs bindgen-cli and libclang.
+# New major.minor sets must be added to `PREGENERATED_BINDINGS` in build.rs.
+set -eu
+
+include_dir="${1:?usage: $0 <libxml2 include directory>}"
+version=$(sed -n 's/^#define LIBXML_DOTTED_VERSION "\(.*\)"/\1/p' "${include_dir}/libxml/xmlversion.h")
+major_minor=$(echo "${version}" | cut -d '.' -f 1,2 | tr '.' '_')
+
+bindgen src/wrapper.h \
+  --opaque-type max_align_t \
+  --output "src/default_bindings_${major_minor}.rs" \
+  -- -DPKG-CONFIG -DLIBXML_C14N_ENABLED -DLIBXML_OUTPUT_ENABLED "-I${include_dir}"
+
+echo "Generated src/default_bindings_${major_minor}.rs from libxml2 ${version}"
@@ -2,8 +2,8 @@
 #![allow(non_snake_case)]
 
 use crate::bindings::*;
-use libc::{c_char, c_int, size_t};
-use std::os::raw::c_void;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::slice;
 // error handling functions
@@ -96,8 +96,39 @@ pub fn xmlNodeGetName(cur: xmlNodePtr) -> *const c_char {
   unsafe { (*cur).name as *const c_char }
 }
 
+/// The name of the element or attribute `cur`, prefixed with the prefix of its namespace
+pub fn qualified_name(cur: xmlNodePtr) -> String {
+  let name_ptr = xmlNodeGetName(cur);
+  let name = if name_ptr.is_null() {
+    String::new()
+  } else {
+    unsafe { CStr::from_ptr(name_ptr) }.to_string_lossy().into_owned()
+  };
+  let ns = xmlNodeNs(cur);
+  let prefix = if ns.is_null() { ptr::null() } else { xmlNsPrefix(ns) };
+  if prefix.is_null() {
+    return name;
+  }
+  match unsafe { CStr::from_ptr(prefix) }.to_string_lossy() {
+    prefix if prefix.is_empty() => name,
+    prefix => format!("{prefix}:{name}"),
+  }
+}
+
+/// Makes `text` fit for the content of a comment, which can't contain `--` or end with `-`
+pub fn escape_comment(text: &str) -> String {
+  let mut text = text.to_string();
+  while text.contains("--") {
+    text = text.replace("--", "- -");
+  }
+  if text.ends_with('-') {
+    text.push(' ');
+  }
+  text
+}
+
 // dummy function: no debug output at all
-#[cfg(libxml_older_than_2_12)]
+#[cfg(all(feature = "html", libxml_older_than_2_12))]
 unsafe extern "C" fn _ignoreInvalidTagsErrorFunc(_user_data: *mut c_void, error: xmlErrorPtr) {
   unsafe {
     if !error.is_null() && (*error).code as xmlParserErrors == xmlParserErrors_XML_HTML_UNKNOWN_TAG {
@@ -106,7 +137,7 @@ unsafe extern "C" fn _ignoreInvalidTagsErrorFunc(_user_data: *mut c_void, error:
     }
   }
 }
-#[cfg(not(libxml_older_than_2_12))]
+#[cfg(all(feature = "html", not(libxml_older_than_2_12)))]
 unsafe extern "C" fn _ignoreInvalidTagsErrorFunc(_user_data: *mut c_void, error: *const xmlError) {
   unsafe {
     if !error.is_null() && (*error).code as xmlParserErrors == xmlParserErrors_XML_HTML_UNKNOWN_TAG {
@@ -116,13 +147,23 @@ unsafe extern "C" fn _ignoreInvalidTagsErrorFunc(_user_data: *mut c_void, error:
   }
 }
 
+#[cfg(all(feature = "html", libxml_older_than_2_13))]
 pub fn setWellFormednessHandler(ctxt: *mut xmlParserCtxt) {
   unsafe {
     HACKY_WELL_FORMED = false;
     xmlSetStructuredErrorFunc(ctxt as *mut c_void, Some(_ignoreInvalidTagsErrorFunc));
   }
 }
+#[cfg(all(feature = "html", libxml_at_least_2_13))]
+pub fn setWellFormednessHandler(ctxt: *mut xmlParserCtxt) {
+  unsafe {
+    HACKY_WELL_FORMED = false;
+    // the global handler is deprecated, and would leak to the other parsers of this thread
+    xmlCtxtSetErrorHandler(ctxt, Some(_ignoreInvalidTagsErrorFunc), ptr::null_mut());
+  }
+}
 // helper for parser
+#[cfg(feature = "html")]
 pub fn htmlWellFormed(ctxt: *mut xmlParserCtxt) -> bool {
   unsafe { (!ctxt.is_null() && (*ctxt).wellFormed > 0) || HACKY_WELL_FORMED }
 }
@@ -140,7 +181,7 @@ pub fn xmlXPathObjectNumberOfNodes(val: xmlXPathObjectPtr) -> c_int {
   }
 }
 
-pub fn xmlXPathObjectGetNodes(val: xmlXPathObjectPtr, size: size_t) -> Vec<xmlNodePtr> {
+pub fn xmlXPathObjectGetNodes(val: xmlXPathObjectPtr, size: usize) -> Vec<xmlNodePtr> {
   unsafe { slice::from_raw_parts((*(*val).nodesetval).nodeTab, size).to_vec() }
 }
 
@@ -154,6 +195,14 @@ pub fn bindgenFree(val: *mut c_void) {
     }
   }
 }
+#[cfg(target_family = "wasm")]
+pub fn bindgenFree(val: *mut c_void) {
+  unsafe {
+    if let Some(xml_free_fn) = xmlFree {
+      xml_free_fn(val);
+    }
+  }
+}
 #[cfg(all(target_family="windows", target_env="msvc"))]
 pub fn bindgenFree(val: *mut c_void) {
   unsafe { libc::free(val as *mut c_void); }
@@ -0,0 +1,250 @@
+//! Wrapping of XML Catalogs (xmlCatalog)
+//!
+//! A [`Catalog`] is an in-memory OASIS XML catalog, independent of the global catalogs
+//! libxml2 consults while parsing. Entries can be added and removed programmatically,
+//! identifiers resolved against them and the result serialized as a catalog document.
+//!
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bindings::*;
+use crate::c_helpers::bindgenFree;
+
+const EMPTY_CATALOG: &str =
+  r#"<catalog xmlns="urn:oasis:names:tc:entity:xmlns:xml:catalog"/>"#;
+
+/// The kind of a catalog entry, see the OASIS XML Catalogs specification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogEntry {
+  /// Maps a public identifier to a URI
+  Public,
+  /// Maps a system identifier to a URI
+  System,
+  /// Rewrites the prefix of system identifiers
+  RewriteSystem,
+  /// Maps a URI reference to another URI
+  Uri,
+  /// Rewrites the prefix of URI references
+  RewriteUri,
+  /// Delegates public identifiers with a given prefix to another catalog
+  DelegatePublic,
+  /// Delegates system identifiers with a given prefix to another catalog
+  DelegateSystem,
+  /// Delegates URI references with a given prefix to another catalog
+  DelegateUri,
+  /// Chains another catalog file
+  NextCatalog,
+}
+
+impl CatalogEntry {
+  fn as_str(self) -> &'static str {
+    match self {
+      CatalogEntry::Public => "public",
+      CatalogEntry::System => "system",
+      CatalogEntry::RewriteSystem => "rewriteSystem",
+      CatalogEntry::Uri => "uri",
+      CatalogEntry::RewriteUri => "rewriteURI",
+      CatalogEntry::DelegatePublic => "delegatePublic",
+      CatalogEntry::DelegateSystem => "delegateSystem",
+      CatalogEntry::DelegateUri => "delegateURI",
+      CatalogEntry::NextCatalog => "nextCatalog",
+    }
+  }
+}
+
+/// An XML catalog held in memory
+pub struct Catalog {
+  inner: xmlCatalogPtr,
+}
+
+impl Catalog {
+  /// Creates a new, empty XML catalog
+  pub fn new() -> Result<Self, ()> {
+    // xmlNewCatalog() leaves the catalog without its top-level entry, which only
+    // xmlLoadACatalog() sets up, so start from an empty catalog file instead.
+    let dir = private_dir().map_err(|_| ())?;
+    let path = dir.join("catalog.xml");
+    let catalog = write_new(&path, EMPTY_CATALOG)
+      .map_err(|_| ())
+      .and_then(|_| Catalog::from_file(path.to_str().ok_or(())?));
+    // libxml2 only reads the file once the catalog is first used, which it is not afterwards
+    if let Ok(catalog) = &catalog {
+      catalog.resolve_uri("");
+    }
+    let _ = fs::remove_dir_all(&dir);
+    catalog
+  }
+
+  /// Loads a catalog from a file (XML or SGML catalog format)
+  pub fn from_file(path: &str) -> Result<Self, ()> {
+    let c_path = CString::new(path).map_err(|_| ())?;
+    let inner = unsafe { xmlLoadACatalog(c_path.as_ptr()) };
+    if inner.is_null() {
+      return Err(());
+    }
+    Ok(Catalog { inner })
+  }
+
+  /// Adds an entry of the given kind mapping `orig` to `replace`.
+  /// For [`CatalogEntry::NextCatalog`] `orig` is ignored and `replace` is the catalog URL.
+  pub fn add(&mut self, kind: CatalogEntry, orig: &str, replace: &str) -> Result<(), ()> {
+    let c_kind = CString::new(kind.as_str()).unwrap();
+    let c_orig = CString::new(orig).map_err(|_| ())?;
+    let c_replace = CString::new(replace).map_err(|_| ())?;
+    let status = unsafe {
+      xmlACatalogAdd(
+        self.inner,
+        c_kind.as_bytes().as_ptr(),
+        c_orig.as_bytes().as_ptr(),
+        c_replace.as_bytes().as_ptr(),
+      )
+    };
+    if status == 0 { Ok(()) } else { Err(()) }
+  }
+
+  /// Maps the public identifier `public_id` to `uri`
+  pub fn add_public(&mut self, public_id: &str, uri: &str) -> Result<(), ()> {
+    self.add(CatalogEntry::Public, public_id, uri)
+  }
+
+  /// Maps the system identifier `system_id` to `uri`
+  pub fn add_system(&mut self, system_id: &str, uri: &str) -> Result<(), ()> {
+    self.add(CatalogEntry::System, system_id, uri)
+  }
+
+  /// Rewrites system identifiers starting with `prefix` to start with `rewrite_prefix`
+  pub fn add_rewrite_system(&mut self, prefix: &str, rewrite_prefix: &str) -> Result<(), ()> {
+    self.add(CatalogEntry::RewriteSystem, prefix, rewrite_prefix)
+  }
+
+  /// Rewrites URI references starting with `prefix` to start with `rewrite_prefix`
+  pub fn add_rewrite_uri(&mut self, prefix: &str, rewrite_prefix: &str) -> Result<(), ()> {
+    self.add(CatalogEntry::RewriteUri, prefix, rewrite_prefix)
+  }
+
+  /// Removes all entries whose original value is `value`
+  pub fn remove(&mut self, value: &str) -> Result<(), ()> {
+    let c_value = CString::new(value).map_err(|_| ())?;
+    let status = unsafe { xmlACatalogRemove(self.inner, c_value.as_bytes().as_ptr()) };
+    if status < 0 { Err(()) } else { Ok(()) }
+  }
+
+  /// Resolves an external identifier, either part may be omitted
+  pub fn resolve(&self, public_id: Option<&str>, system_id: Option<&str>) -> Option<String> {
+    let c_public = public_id.and_then(|id| CString::new(id).ok());
+    let c_system = system_id.and_then(|id| CString::new(id).ok());
+    let as_ptr = |s: &Option<CString>| s.as_ref().map_or(ptr::null(), |s| s.as_bytes().as_ptr());
+    take_string(unsafe { xmlACatalogResolve(self.inner, as_ptr(&c_public), as_ptr(&c_system)) })
+  }
+
+  /// Resolves a public identifier
+  pub fn resolve_public(&self, public_id: &str) -> Option<String> {
+    let c_public = CString::new(public_id).ok()?;
+    take_string(unsafe { xmlACatalogResolvePublic(self.inner, c_public.as_bytes().as_ptr()) })
+  }
+
+  /// Resolves a system identifier
+  pub fn resolve_system(&self, system_id: &str) -> Option<String> {
+    let c_system = CString::new(system_id).ok()?;
+    take_string(unsafe { xmlACatalogResolveSystem(self.inner, c_system.as_bytes().as_ptr()) })
+  }
+
+  /// Resolves a URI reference
+  pub fn resolve_uri(&self, uri: &str) -> Option<String> {
+    let c_uri = CString::new(uri).ok()?;
+    take_string(unsafe { xmlACatalogResolveURI(self.inner, c_uri.as_bytes().as_ptr()) })
+  }
+
+  /// Whether the catalog has no entries
+  pub fn is_empty(&self) -> bool {
+    unsafe { xmlCatalogIsEmpty(self.inner) != 0 }
+  }
+
+  /// Serializes the catalog as an XML catalog document
+  #[cfg(not(target_family = "wasm"))]
+  pub fn to_xml(&self) -> Result<String, ()> {
+    unsafe {
+      let file = libc::tmpfile();
+      if file.is_null() {
+        return Err(());
+      }
+      xmlACatalogDump(self.inner, file as *mut FILE);
+      libc::fflush(file);
+      libc::rewind(file);
+
+      let mut bytes = Vec::new();
+      let mut chunk = [0u8; 4096];
+      loop {
+        let read = libc::fread(chunk.as_mut_ptr() as *mut c_void, 1, chunk.len(), file);
+        if read == 0 {
+          break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+      }
+      libc::fclose(file);
+
+      let mut xml = String::new();
+      bytes.as_slice().read_to_string(&mut xml).map_err(|_| ())?;
+      Ok(xml)
+    }
+  }
+
+  /// Return a raw pointer to the underlying xmlCatalog structure
+  pub fn as_ptr(&self) -> xmlCatalogPtr {
+    self.inner
+  }
+}
+
+impl Drop for Catalog {
+  fn drop(&mut self) {
+    unsafe { xmlFreeCatalog(self.inner) }
+  }
+}
+
+/// Creates a new directory in the temporary directory, which only the current user can access
+fn private_dir() -> io::Result<PathBuf> {
+  static COUNTER: AtomicUsize = AtomicUsize::new(0);
+  let mut builder = fs::DirBuilder::new();
+  #[cfg(unix)]
+  std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+  let nanos = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |since| since.subsec_nanos());
+  loop {
+    let dir = std::env::temp_dir().join(format!(
+      "libxml-catalog-{}-{}-{nanos:x}",
+      std::process::id(),
+      COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    // Never reuse a directory someone else made
+    match builder.create(&dir) {
+      Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+      result => return result.map(|_| dir),
+    }
+  }
+}
+
+/// Writes `content` to `path`, which must not exist yet
+fn write_new(path: &Path, content: &str) -> io::Result<()> {
+  let mut file = fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+  file.write_all(content.as_bytes())
+}
+
+/// Converts a resolution result owned by the caller into a `String`
+fn take_string(result: *mut xmlChar) -> Option<String> {
+  // libxml2 uses (xmlChar *) -1 internally to stop resolution
+  if result.is_null() || result as isize == -1 {
+    return None;
+  }
+  let value = unsafe { CStr::from_ptr(result as *const c_char) }
+    .to_string_lossy()
+    .into_owned();
+  bindgenFree(result as *mut c_void);
+  Some(value)
+}
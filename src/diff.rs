@@ -0,0 +1,441 @@
+//! Structural XML diff
+//!
+//! [`diff`] compares two documents and returns an edit script describing how to turn the old
+//! document into the new one. Paths of deleted and updated nodes refer to the old document,
+//! while insertion targets refer to the new document. Identical subtrees which were removed
+//! in one place and inserted in another are reported as a single [`Edit::Move`].
+//!
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::{Document, NodeType};
+
+/// Options controlling which differences are significant
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffOptions {
+  /// Drop whitespace-only text nodes and compare text with normalized whitespace
+  pub ignore_whitespace: bool,
+  /// Do not compare comment nodes
+  pub ignore_comments: bool,
+  /// Treat attributes as an unordered set
+  pub ignore_attribute_order: bool,
+}
+
+/// A single step of an edit script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+  /// A node serialized as `xml` was inserted at child `position` of `parent` (new document)
+  Insert {
+    /// Path of the parent in the new document
+    parent: String,
+    /// Index among the parent's compared children
+    position: usize,
+    /// Serialization of the inserted node
+    xml: String,
+  },
+  /// The node at `path` (old document) was removed
+  Delete {
+    /// Path of the removed node in the old document
+    path: String,
+  },
+  /// The text content of the node at `path` changed
+  UpdateText {
+    /// Path of the text node in the old document
+    path: String,
+    /// Previous content
+    old: String,
+    /// New content
+    new: String,
+  },
+  /// An attribute was added (`old` is `None`), removed (`new` is `None`) or changed
+  UpdateAttribute {
+    /// Path of the owning element in the old document
+    path: String,
+    /// Qualified attribute name
+    name: String,
+    /// Previous value
+    old: Option<String>,
+    /// New value
+    new: Option<String>,
+  },
+  /// The attributes of an element are equal but appear in a different order
+  ReorderAttributes {
+    /// Path of the element in the old document
+    path: String,
+    /// Qualified attribute names in their new order
+    order: Vec<String>,
+  },
+  /// The root element was renamed
+  Rename {
+    /// Path of the element in the old document
+    path: String,
+    /// Previous qualified name
+    old: String,
+    /// New qualified name
+    new: String,
+  },
+  /// An unchanged subtree moved to another position
+  Move {
+    /// Path of the node in the old document
+    from: String,
+    /// Path of the new parent in the new document
+    to_parent: String,
+    /// Index among the new parent's compared children
+    position: usize,
+  },
+}
+
+/// Computes the edit script turning `old` into `new`
+pub fn diff(old: &Document, new: &Document, options: &DiffOptions) -> Vec<Edit> {
+  let mut differ = Differ {
+    options,
+    new_doc: new,
+    edits: Vec::new(),
+    deleted: Vec::new(),
+    inserted: Vec::new(),
+  };
+  match (old.get_root_readonly(), new.get_root_readonly()) {
+    (Some(old_root), Some(new_root)) => {
+      let (old_name, new_name) = (qualified_name(old_root.node_ptr()), qualified_name(new_root.node_ptr()));
+      if old_name != new_name || namespace_href(old_root) != namespace_href(new_root) {
+        differ.edits.push(Edit::Rename {
+          path: node_path(old_root),
+          old: old_name,
+          new: new_name,
+        });
+      }
+      differ.compare_elements(old_root, new_root);
+    }
+    (Some(old_root), None) => differ.deleted.push(old_root),
+    (None, Some(new_root)) => differ.inserted.push((new_root, "/".to_string(), 0)),
+    (None, None) => {}
+  }
+  differ.finish()
+}
+
+struct Differ<'a> {
+  options: &'a DiffOptions,
+  new_doc: &'a Document,
+  edits: Vec<Edit>,
+  deleted: Vec<RoNode>,
+  inserted: Vec<(RoNode, String, usize)>,
+}
+
+impl Differ<'_> {
+  fn compare_elements(&mut self, old: RoNode, new: RoNode) {
+    self.compare_attributes(old, new);
+
+    let old_children = self.significant_children(old);
+    let new_children = self.significant_children(new);
+    let old_keys: Vec<String> = old_children.iter().map(|n| self.match_key(*n)).collect();
+    let new_keys: Vec<String> = new_children.iter().map(|n| self.match_key(*n)).collect();
+    let pairs = lcs(&old_keys, &new_keys);
+
+    let mut old_matched = vec![false; old_children.len()];
+    let mut new_matched = vec![false; new_children.len()];
+    for (i, j) in pairs {
+      old_matched[i] = true;
+      new_matched[j] = true;
+      let (old_child, new_child) = (old_children[i], new_children[j]);
+      if old_child.is_element_node() {
+        self.compare_elements(old_child, new_child);
+      } else {
+        let (old_text, new_text) = (self.text_of(old_child), self.text_of(new_child));
+        if old_text != new_text {
+          self.edits.push(Edit::UpdateText {
+            path: node_path(old_child),
+            old: old_child.get_content(),
+            new: new_child.get_content(),
+          });
+        }
+      }
+    }
+
+    for (i, matched) in old_matched.into_iter().enumerate() {
+      if !matched {
+        self.deleted.push(old_children[i]);
+      }
+    }
+    let parent_path = node_path(new);
+    for (j, matched) in new_matched.into_iter().enumerate() {
+      if !matched {
+        self.inserted.push((new_children[j], parent_path.clone(), j));
+      }
+    }
+  }
+
+  fn compare_attributes(&mut self, old: RoNode, new: RoNode) {
+    let old_attrs = keyed_attributes(old);
+    let new_attrs = keyed_attributes(new);
+    let path = node_path(old);
+    let new_map: HashMap<&str, &Attribute> = new_attrs.iter().map(|a| (a.key.as_str(), a)).collect();
+    let old_map: HashMap<&str, &Attribute> = old_attrs.iter().map(|a| (a.key.as_str(), a)).collect();
+
+    for old_attr in &old_attrs {
+      match new_map.get(old_attr.key.as_str()) {
+        Some(new_attr) if new_attr.value == old_attr.value => {}
+        new_attr => self.edits.push(Edit::UpdateAttribute {
+          path: path.clone(),
+          name: old_attr.name.clone(),
+          old: Some(old_attr.value.clone()),
+          new: new_attr.map(|a| a.value.clone()),
+        }),
+      }
+    }
+    for new_attr in &new_attrs {
+      if !old_map.contains_key(new_attr.key.as_str()) {
+        self.edits.push(Edit::UpdateAttribute {
+          path: path.clone(),
+          name: new_attr.name.clone(),
+          old: None,
+          new: Some(new_attr.value.clone()),
+        });
+      }
+    }
+
+    if !self.options.ignore_attribute_order {
+      let common_old: Vec<&str> = old_attrs
+        .iter()
+        .map(|a| a.key.as_str())
+        .filter(|k| new_map.contains_key(k))
+        .collect();
+      let common_new: Vec<&str> = new_attrs
+        .iter()
+        .map(|a| a.key.as_str())
+        .filter(|k| old_map.contains_key(k))
+        .collect();
+      if common_old != common_new {
+        self.edits.push(Edit::ReorderAttributes {
+          path,
+          order: new_attrs.into_iter().map(|a| a.name).collect(),
+        });
+      }
+    }
+  }
+
+  fn significant_children(&self, node: RoNode) -> Vec<RoNode> {
+    node
+      .get_child_nodes()
+      .into_iter()
+      .filter(|child| match child.get_type() {
+        Some(NodeType::ElementNode) | Some(NodeType::PiNode) | Some(NodeType::EntityRefNode) => {
+          true
+        }
+        Some(NodeType::CommentNode) => !self.options.ignore_comments,
+        Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => {
+          !(self.options.ignore_whitespace && child.get_content().trim().is_empty())
+        }
+        _ => false,
+      })
+      .collect()
+  }
+
+  /// Nodes with equal keys are candidates to be matched with each other
+  fn match_key(&self, node: RoNode) -> String {
+    match node.get_type() {
+      Some(NodeType::ElementNode) => format!(
+        "e:{}:{}",
+        namespace_href(node).unwrap_or_default(),
+        node.get_name()
+      ),
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => "#text".to_string(),
+      Some(NodeType::CommentNode) => "#comment".to_string(),
+      _ => format!("#other:{}", node.get_name()),
+    }
+  }
+
+  fn text_of(&self, node: RoNode) -> String {
+    let content = node.get_content();
+    if self.options.ignore_whitespace {
+      content.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+      content
+    }
+  }
+
+  /// A string identifying a subtree up to the differences ignored by the options
+  fn fingerprint(&self, node: RoNode, out: &mut String) {
+    out.push_str(&self.match_key(node));
+    if node.is_element_node() {
+      let mut attrs: Vec<(String, String)> =
+        keyed_attributes(node).into_iter().map(|a| (a.key, a.value)).collect();
+      if self.options.ignore_attribute_order {
+        attrs.sort();
+      }
+      for (key, value) in attrs {
+        out.push_str(&format!("[{key}={value}]"));
+      }
+      out.push('{');
+      for child in self.significant_children(node) {
+        self.fingerprint(child, out);
+      }
+      out.push('}');
+    } else {
+      out.push_str(&format!("({})", self.text_of(node)));
+    }
+  }
+
+  fn finish(mut self) -> Vec<Edit> {
+    let mut pending: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, node) in self.deleted.iter().enumerate() {
+      let mut key = String::new();
+      self.fingerprint(*node, &mut key);
+      pending.entry(key).or_default().push(index);
+    }
+
+    let mut moved = vec![false; self.deleted.len()];
+    let mut insert_edits = Vec::new();
+    for (node, parent, position) in std::mem::take(&mut self.inserted) {
+      let mut key = String::new();
+      self.fingerprint(node, &mut key);
+      let source = pending.get_mut(&key).and_then(|indices| indices.pop());
+      match source {
+        // Only element subtrees are worth reporting as moves
+        Some(index) if node.is_element_node() => {
+          moved[index] = true;
+          insert_edits.push(Edit::Move {
+            from: node_path(self.deleted[index]),
+            to_parent: parent,
+            position,
+          });
+        }
+        _ => insert_edits.push(Edit::Insert {
+          parent,
+          position,
+          xml: self.new_doc.ronode_to_string(&node),
+        }),
+      }
+    }
+
+    for (index, node) in self.deleted.iter().enumerate() {
+      if !moved[index] {
+        self.edits.push(Edit::Delete {
+          path: node_path(*node),
+        });
+      }
+    }
+    self.edits.extend(insert_edits);
+    self.edits
+  }
+}
+
+/// Longest common subsequence of two key lists, as matched index pairs, found with
+/// Hirschberg's algorithm in space linear in the length of the lists
+fn lcs(old: &[String], new: &[String]) -> Vec<(usize, usize)> {
+  let old: Vec<&str> = old.iter().map(String::as_str).collect();
+  let new: Vec<&str> = new.iter().map(String::as_str).collect();
+  let mut pairs = Vec::new();
+  lcs_into(&old, &new, (0, 0), &mut pairs);
+  pairs
+}
+
+/// Appends the pairs of the longest common subsequence of `old` and `new` to `pairs`, their
+/// indices shifted by `offset`
+fn lcs_into(old: &[&str], new: &[&str], offset: (usize, usize), pairs: &mut Vec<(usize, usize)>) {
+  // Children mostly stay the same, matching the common ends first keeps the split small
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  pairs.extend((0..prefix).map(|k| (offset.0 + k, offset.1 + k)));
+  let (old, new) = (&old[prefix..], &new[prefix..]);
+  let suffix = old.iter().rev().zip(new.iter().rev()).take_while(|(a, b)| a == b).count();
+  let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+  let offset = (offset.0 + prefix, offset.1 + prefix);
+
+  if old.len() == 1 {
+    if let Some(j) = new.iter().position(|key| *key == old[0]) {
+      pairs.push((offset.0, offset.1 + j));
+    }
+  } else if !old.is_empty() && !new.is_empty() {
+    // Split `new` where the halves of `old` have the longest common subsequences with it
+    let middle = old.len() / 2;
+    let forward = lcs_lengths(old[..middle].iter(), new.iter());
+    let backward = lcs_lengths(old[middle..].iter().rev(), new.iter().rev());
+    let split = (0..=new.len())
+      .max_by_key(|&k| (forward[k] + backward[new.len() - k], std::cmp::Reverse(k)))
+      .unwrap_or(0);
+    lcs_into(&old[..middle], &new[..split], offset, pairs);
+    lcs_into(&old[middle..], &new[split..], (offset.0 + middle, offset.1 + split), pairs);
+  }
+
+  pairs.extend((0..suffix).map(|k| (offset.0 + old.len() + k, offset.1 + new.len() + k)));
+}
+
+/// The lengths of the longest common subsequences of `old` with each prefix of `new`
+fn lcs_lengths<'a>(
+  old: impl Iterator<Item = &'a &'a str>,
+  new: impl Iterator<Item = &'a &'a str> + Clone,
+) -> Vec<usize> {
+  let mut row = vec![0; new.clone().count() + 1];
+  for key in old {
+    let mut diagonal = 0;
+    for (j, other) in new.clone().enumerate() {
+      let above = row[j + 1];
+      row[j + 1] = if key == other {
+        diagonal + 1
+      } else {
+        above.max(row[j])
+      };
+      diagonal = above;
+    }
+  }
+  row
+}
+
+/// An attribute as compared: namespaced attributes are identified by their namespace URI
+/// rather than their prefix
+struct Attribute {
+  /// The local name, within braces after the namespace URI if any
+  key: String,
+  /// The qualified name
+  name: String,
+  value: String,
+}
+
+fn keyed_attributes(node: RoNode) -> Vec<Attribute> {
+  let mut attrs = Vec::new();
+  let mut attr_ptr = xmlGetFirstProperty(node.node_ptr());
+  while !attr_ptr.is_null() {
+    let attr = RoNode(attr_ptr as xmlNodePtr);
+    let key = match namespace_href(attr) {
+      Some(href) => format!("{{{href}}}{}", attr.get_name()),
+      None => attr.get_name(),
+    };
+    attrs.push(Attribute {
+      key,
+      name: qualified_name(attr.node_ptr()),
+      value: attr.get_content(),
+    });
+    attr_ptr = xmlNextPropertySibling(attr_ptr);
+  }
+  attrs
+}
+
+pub(crate) fn attributes(node: RoNode) -> Vec<(String, String)> {
+  let mut attrs = Vec::new();
+  let mut attr_ptr = xmlGetFirstProperty(node.node_ptr());
+  while !attr_ptr.is_null() {
+    let attr = RoNode(attr_ptr as xmlNodePtr);
+    attrs.push((qualified_name(attr.node_ptr()), attr.get_content()));
+    attr_ptr = xmlNextPropertySibling(attr_ptr);
+  }
+  attrs
+}
+
+fn namespace_href(node: RoNode) -> Option<String> {
+  node.get_namespace().map(|ns| ns.get_href())
+}
+
+pub(crate) fn node_path(node: RoNode) -> String {
+  let path_ptr = unsafe { xmlGetNodePath(node.node_ptr()) };
+  if path_ptr.is_null() {
+    return String::new();
+  }
+  let path = unsafe { CStr::from_ptr(path_ptr as *const c_char) }
+    .to_string_lossy()
+    .into_owned();
+  bindgenFree(path_ptr as *mut c_void);
+  path
+}
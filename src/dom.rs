@@ -0,0 +1,354 @@
+//! A DOM Core / DOM Load and Save flavoured facade
+//!
+//! [`DomDocument`] wraps a [`Document`] and [`DomNode`] extends [`Node`] with methods named
+//! after their W3C DOM counterparts (`createElementNS` becomes `create_element_ns`,
+//! `getElementsByTagNameNS` becomes `get_elements_by_tag_name_ns`, ...), which eases porting
+//! code written against the Java, JavaScript or .NET XML stacks. Everything is implemented on
+//! top of the regular `tree` types, so both APIs can be mixed freely.
+//!
+use std::error::Error;
+use std::ffi::CString;
+use std::ops::{Deref, DerefMut};
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::parser::{Parser, XmlParseError};
+use crate::tree::{Document, Namespace, Node, NodeType};
+
+/// A `Document` exposing DOM-style factory and lookup methods
+pub struct DomDocument(Document);
+
+impl DomDocument {
+  /// Creates an empty document (`new Document()`)
+  pub fn new() -> Result<Self, ()> {
+    Document::new().map(DomDocument)
+  }
+
+  /// Creates a document with a root element, as `DOMImplementation.createDocument`
+  pub fn create_document(namespace_uri: Option<&str>, qualified_name: &str) -> Result<Self, ()> {
+    let mut doc = DomDocument::new()?;
+    let root = doc.create_element_ns(namespace_uri, qualified_name)?;
+    doc.0.set_root_element(&root);
+    Ok(doc)
+  }
+
+  /// Parses a document from a file or URI (`LSParser.parseURI`)
+  pub fn load(uri: &str) -> Result<Self, XmlParseError> {
+    Parser::default().parse_file(uri).map(DomDocument)
+  }
+
+  /// Parses a document from a string (`LSParser.parse` on a string input)
+  pub fn load_xml(xml: &str) -> Result<Self, XmlParseError> {
+    Parser::default().parse_string(xml).map(DomDocument)
+  }
+
+  /// Serializes the document, or only `node` when given (`LSSerializer.writeToString`)
+  pub fn save_xml(&self, node: Option<&Node>) -> String {
+    match node {
+      Some(node) => self.0.node_to_string(node),
+      None => self.0.to_string(),
+    }
+  }
+
+  /// The root element (`documentElement`)
+  pub fn document_element(&self) -> Option<Node> {
+    self.0.get_root_element()
+  }
+
+  /// Creates an element without a namespace (`createElement`)
+  pub fn create_element(&self, tag_name: &str) -> Result<Node, ()> {
+    Node::new(tag_name, None, &self.0)
+  }
+
+  /// Creates an element in `namespace_uri`, declaring the namespace on the new element
+  /// (`createElementNS`)
+  pub fn create_element_ns(
+    &self,
+    namespace_uri: Option<&str>,
+    qualified_name: &str,
+  ) -> Result<Node, ()> {
+    let (prefix, local_name) = match qualified_name.split_once(':') {
+      Some((prefix, local_name)) => (prefix, local_name),
+      None => ("", qualified_name),
+    };
+    let namespace_uri = match namespace_uri {
+      Some(uri) if !uri.is_empty() => uri,
+      _ if prefix.is_empty() => return self.create_element(qualified_name),
+      // a prefix without a namespace is a NAMESPACE_ERR
+      _ => return Err(()),
+    };
+    let mut element = Node::new(local_name, None, &self.0)?;
+    let ns = Namespace::new(prefix, namespace_uri, &mut element).map_err(|_| ())?;
+    element.set_namespace(&ns).map_err(|_| ())?;
+    Ok(element)
+  }
+
+  /// Creates a text node (`createTextNode`)
+  pub fn create_text_node(&self, data: &str) -> Result<Node, ()> {
+    Node::new_text(data, &self.0)
+  }
+
+  /// Creates a comment node (`createComment`)
+  pub fn create_comment(&self, data: &str) -> Result<Node, ()> {
+    let c_data = CString::new(data).map_err(|_| ())?;
+    let node_ptr = unsafe { xmlNewDocComment(self.0.doc_ptr(), c_data.as_bytes().as_ptr()) };
+    self.wrap_new(node_ptr)
+  }
+
+  /// Creates a CDATA section (`createCDATASection`)
+  pub fn create_cdata_section(&self, data: &str) -> Result<Node, ()> {
+    self.0.create_cdata_section(data)
+  }
+
+  /// Copies a node from any document into this one, leaving the original untouched
+  /// (`importNode`). Without `deep` only the node itself and its attributes are copied.
+  pub fn import_node(&self, node: &Node, deep: bool) -> Result<Node, ()> {
+    self.0.import_node(node, deep)
+  }
+
+  /// All elements with the given qualified name, `"*"` matching any (`getElementsByTagName`)
+  pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<Node> {
+    match self.document_element() {
+      Some(root) => collect_elements(&root, true, |node| {
+        tag_name == "*" || tag_name == qualified_name(node.node_ptr())
+      }),
+      None => Vec::new(),
+    }
+  }
+
+  /// All elements with the given namespace and local name, `"*"` matching any
+  /// (`getElementsByTagNameNS`)
+  pub fn get_elements_by_tag_name_ns(&self, namespace_uri: &str, local_name: &str) -> Vec<Node> {
+    match self.document_element() {
+      Some(root) => collect_elements(&root, true, |node| {
+        matches_ns(node, namespace_uri, local_name)
+      }),
+      None => Vec::new(),
+    }
+  }
+
+  /// The wrapped `Document`
+  pub fn into_document(self) -> Document {
+    self.0
+  }
+
+  fn wrap_new(&self, node_ptr: xmlNodePtr) -> Result<Node, ()> {
+    if node_ptr.is_null() {
+      Err(())
+    } else {
+      Ok(Node::wrap_new(node_ptr, &self.0.0))
+    }
+  }
+}
+
+impl From<Document> for DomDocument {
+  fn from(doc: Document) -> Self {
+    DomDocument(doc)
+  }
+}
+
+impl Deref for DomDocument {
+  type Target = Document;
+  fn deref(&self) -> &Document {
+    &self.0
+  }
+}
+
+impl DerefMut for DomDocument {
+  fn deref_mut(&mut self) -> &mut Document {
+    &mut self.0
+  }
+}
+
+/// DOM `Node`/`Element` style accessors for `Node`
+pub trait DomNode {
+  /// The qualified name of elements and attributes, or `#text`, `#comment`, ... (`nodeName`)
+  fn node_name(&self) -> String;
+  /// The qualified name of an element (`tagName`)
+  fn tag_name(&self) -> String;
+  /// The local part of an element or attribute name (`localName`)
+  fn local_name(&self) -> Option<String>;
+  /// The namespace URI of an element or attribute (`namespaceURI`)
+  fn namespace_uri(&self) -> Option<String>;
+  /// The namespace prefix of an element or attribute (`prefix`)
+  fn prefix(&self) -> Option<String>;
+  /// The content of character data, PI and attribute nodes, `None` otherwise (`nodeValue`)
+  fn node_value(&self) -> Option<String>;
+  /// The concatenated text of all descendants (`textContent`)
+  fn text_content(&self) -> String;
+  /// Replaces all children by a single text node (`textContent` setter)
+  fn set_text_content(&mut self, text: &str) -> Result<(), Box<dyn Error + Send + Sync>>;
+  /// Whether the node has any children (`hasChildNodes`)
+  fn has_child_nodes(&self) -> bool;
+  /// Whether the element has any attributes (`hasAttributes`)
+  fn has_attributes(&self) -> bool;
+  /// Appends `child` as the last child (`appendChild`)
+  fn append_child(&mut self, child: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>>;
+  /// Inserts `child` before `reference`, or appends it without one (`insertBefore`)
+  fn insert_before(
+    &mut self,
+    child: &mut Node,
+    reference: Option<&mut Node>,
+  ) -> Result<(), Box<dyn Error + Send + Sync>>;
+  /// Detaches `child` from this node (`removeChild`)
+  fn remove_child(&mut self, child: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>>;
+  /// Copies the node into the same document, unattached (`cloneNode`)
+  fn clone_node(&self, deep: bool) -> Result<Node, ()>;
+  /// Whether both handles refer to the same node (`isSameNode`)
+  fn is_same_node(&self, other: &Node) -> bool;
+  /// Descendant elements with the given qualified name, `"*"` matching any
+  /// (`getElementsByTagName`)
+  fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<Node>;
+  /// Descendant elements with the given namespace and local name, `"*"` matching any
+  /// (`getElementsByTagNameNS`)
+  fn get_elements_by_tag_name_ns(&self, namespace_uri: &str, local_name: &str) -> Vec<Node>;
+}
+
+impl DomNode for Node {
+  fn node_name(&self) -> String {
+    match self.get_type() {
+      Some(NodeType::ElementNode) | Some(NodeType::AttributeNode) => qualified_name(self.node_ptr()),
+      Some(NodeType::TextNode) => "#text".to_string(),
+      Some(NodeType::CDataSectionNode) => "#cdata-section".to_string(),
+      Some(NodeType::CommentNode) => "#comment".to_string(),
+      Some(NodeType::DocumentNode) | Some(NodeType::HtmlDocumentNode) => "#document".to_string(),
+      Some(NodeType::DocumentFragNode) => "#document-fragment".to_string(),
+      _ => self.get_name(),
+    }
+  }
+
+  fn tag_name(&self) -> String {
+    qualified_name(self.node_ptr())
+  }
+
+  fn local_name(&self) -> Option<String> {
+    match self.get_type() {
+      Some(NodeType::ElementNode) | Some(NodeType::AttributeNode) => Some(self.get_name()),
+      _ => None,
+    }
+  }
+
+  fn namespace_uri(&self) -> Option<String> {
+    self.get_namespace().map(|ns| ns.get_href())
+  }
+
+  fn prefix(&self) -> Option<String> {
+    self
+      .get_namespace()
+      .map(|ns| ns.get_prefix())
+      .filter(|prefix| !prefix.is_empty())
+  }
+
+  fn node_value(&self) -> Option<String> {
+    match self.get_type() {
+      Some(NodeType::TextNode)
+      | Some(NodeType::CDataSectionNode)
+      | Some(NodeType::CommentNode)
+      | Some(NodeType::PiNode)
+      | Some(NodeType::AttributeNode) => Some(self.get_content()),
+      _ => None,
+    }
+  }
+
+  fn text_content(&self) -> String {
+    self.get_content()
+  }
+
+  fn set_text_content(&mut self, text: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for mut child in self.get_child_nodes() {
+      child.unlink();
+    }
+    self.append_text(text)
+  }
+
+  fn has_child_nodes(&self) -> bool {
+    self.get_first_child().is_some()
+  }
+
+  fn has_attributes(&self) -> bool {
+    !xmlGetFirstProperty(self.node_ptr()).is_null()
+  }
+
+  fn append_child(&mut self, child: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    child.unlink();
+    self.add_child(child).map_err(From::from)
+  }
+
+  fn insert_before(
+    &mut self,
+    child: &mut Node,
+    reference: Option<&mut Node>,
+  ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match reference {
+      None => self.append_child(child),
+      Some(reference) => {
+        if reference.get_parent().as_ref() != Some(self) {
+          return Err(From::from("insert_before: reference is not a child of this node"));
+        }
+        child.unlink();
+        reference.add_prev_sibling(child)
+      }
+    }
+  }
+
+  fn remove_child(&mut self, child: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if child.get_parent().as_ref() != Some(self) {
+      return Err(From::from("remove_child: node is not a child of this node"));
+    }
+    child.unlink();
+    Ok(())
+  }
+
+  fn clone_node(&self, deep: bool) -> Result<Node, ()> {
+    let recursive = if deep { 1 } else { 2 };
+    let doc_ptr = xmlGetDoc(self.node_ptr());
+    let node_ptr = unsafe { xmlDocCopyNode(self.node_ptr(), doc_ptr, recursive) };
+    if node_ptr.is_null() {
+      return Err(());
+    }
+    let docref = self.get_docref().upgrade().ok_or(())?;
+    Ok(Node::wrap_new(node_ptr, &docref))
+  }
+
+  fn is_same_node(&self, other: &Node) -> bool {
+    self == other
+  }
+
+  fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<Node> {
+    collect_elements(self, false, |node| {
+      tag_name == "*" || tag_name == qualified_name(node.node_ptr())
+    })
+  }
+
+  fn get_elements_by_tag_name_ns(&self, namespace_uri: &str, local_name: &str) -> Vec<Node> {
+    collect_elements(self, false, |node| matches_ns(node, namespace_uri, local_name))
+  }
+}
+
+/// Elements below `node` (and `node` itself if `include_self`) in document order
+fn collect_elements<F: Fn(&Node) -> bool>(node: &Node, include_self: bool, predicate: F) -> Vec<Node> {
+  fn walk<F: Fn(&Node) -> bool>(node: &Node, predicate: &F, found: &mut Vec<Node>) {
+    for child in node.get_child_elements() {
+      if predicate(&child) {
+        found.push(child.clone());
+      }
+      walk(&child, predicate, found);
+    }
+  }
+  let mut found = Vec::new();
+  if include_self && predicate(node) {
+    found.push(node.clone());
+  }
+  walk(node, &predicate, &mut found);
+  found
+}
+
+fn matches_ns(node: &Node, namespace_uri: &str, local_name: &str) -> bool {
+  let ns_matches = match namespace_uri {
+    "*" => true,
+    "" => node.get_namespace().is_none(),
+    uri => node.get_namespace().map(|ns| ns.get_href()).as_deref() == Some(uri),
+  };
+  ns_matches && (local_name == "*" || local_name == node.get_name())
+}
+
@@ -0,0 +1,164 @@
+//! Transcoding of legacy encodings with `encoding_rs`
+//!
+//! libxml2 relies on iconv for anything but UTF-8, UTF-16 and ISO-8859-1, and builds without
+//! iconv (such as the Android one) reject documents in other encodings. The helpers here do
+//! the conversion in Rust instead: input is decoded to UTF-8 before it reaches the parser, and
+//! serialized output is encoded to the requested encoding afterwards.
+//!
+//! The encoding of an input is detected from its byte order mark, the `encoding` of its XML
+//! declaration or, for HTML, a `<meta charset>` / `<meta http-equiv="Content-Type">` element.
+//! Labels are interpreted as in the WHATWG Encoding Standard.
+//!
+use std::borrow::Cow;
+
+pub use encoding_rs::Encoding;
+use encoding_rs::{UTF_16BE, UTF_16LE, UTF_8};
+
+use crate::parser::{Parser, ParserOptions, XmlParseError};
+use crate::tree::{Document, SaveOptions};
+
+/// How far into a document the XML declaration or `<meta>` element is looked for
+const SNIFF_LIMIT: usize = 1024;
+
+/// Detects the encoding of `input`, `None` if it carries no indication
+pub fn detect(input: &[u8]) -> Option<&'static Encoding> {
+  if let Some((encoding, _)) = Encoding::for_bom(input) {
+    return Some(encoding);
+  }
+  // UTF-16 without a byte order mark, recognizable from the leading "<"
+  if input.starts_with(b"<\0") {
+    return Some(UTF_16LE);
+  }
+  if input.starts_with(b"\0<") {
+    return Some(UTF_16BE);
+  }
+  let head = &input[..input.len().min(SNIFF_LIMIT)];
+  let (start, end) = find_label(head)?;
+  // A document which can be sniffed as ASCII is not UTF-16, whatever it claims
+  Encoding::for_label(&head[start..end]).map(|encoding| encoding.output_encoding())
+}
+
+/// Decodes `input` to UTF-8, returning the encoding which was used.
+/// Undetectable input is taken to be UTF-8; malformed sequences are replaced with U+FFFD.
+pub fn decode(input: &[u8]) -> (Cow<'_, str>, &'static Encoding) {
+  let encoding = detect(input).unwrap_or(UTF_8);
+  let (text, encoding, _) = encoding.decode(input);
+  (text, encoding)
+}
+
+impl Parser {
+  /// Parses `input` after transcoding it to UTF-8 with `encoding_rs`
+  pub fn parse_string_transcoded<Bytes: AsRef<[u8]>>(
+    &self,
+    input: Bytes,
+  ) -> Result<Document, XmlParseError> {
+    self.parse_string_transcoded_with_options(input, ParserOptions::default())
+  }
+
+  /// Parses `input` after transcoding it to UTF-8 with `encoding_rs`.
+  /// The `encoding` of `parser_options` is overridden.
+  pub fn parse_string_transcoded_with_options<Bytes: AsRef<[u8]>>(
+    &self,
+    input: Bytes,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    let (text, _) = decode(input.as_ref());
+    // The declared label no longer describes the input, it must not make libxml2 convert again
+    let text = relabel(&text, UTF_8);
+    self.parse_string_with_options(
+      text.as_bytes(),
+      ParserOptions {
+        encoding: Some("UTF-8"),
+        ignore_enc: true,
+        ..parser_options
+      },
+    )
+  }
+}
+
+impl Document {
+  /// Serializes the `Document` into `encoding` with `encoding_rs`.
+  ///
+  /// The XML declaration or HTML `<meta>` charset is updated to name the encoding. Characters
+  /// the encoding can not represent are written as numeric character references, which is
+  /// only correct in text and attribute values. UTF-16 is not an output encoding of the
+  /// Encoding Standard, UTF-8 is produced instead.
+  pub fn to_encoded_bytes(&self, encoding: &'static Encoding, options: SaveOptions) -> Vec<u8> {
+    let encoding = encoding.output_encoding();
+    let text = self.to_string_with_options(options);
+    let text = relabel(&text, encoding);
+    let (bytes, _, _) = encoding.encode(&text);
+    bytes.into_owned()
+  }
+}
+
+/// Replaces the encoding label of the XML declaration or `<meta>` element with `encoding`'s
+fn relabel<'a>(text: &'a str, encoding: &'static Encoding) -> Cow<'a, str> {
+  let head = &text.as_bytes()[..text.len().min(SNIFF_LIMIT)];
+  match find_label(head) {
+    // The label only spans ASCII bytes, which no multi-byte character starts or ends with
+    Some((start, end)) => Cow::Owned(format!(
+      "{}{}{}",
+      &text[..start],
+      encoding.name(),
+      &text[end..]
+    )),
+    None => Cow::Borrowed(text),
+  }
+}
+
+/// Byte range of the encoding label declared in `head`
+fn find_label(head: &[u8]) -> Option<(usize, usize)> {
+  let lower = head.to_ascii_lowercase();
+  if lower.starts_with(b"<?xml") {
+    let end = find(&lower, b"?>", 0).unwrap_or(lower.len());
+    let attr = find(&lower[..end], b"encoding", 0)?;
+    return attribute_value(&lower[..end], attr + b"encoding".len());
+  }
+  let mut from = 0;
+  while let Some(meta) = find(&lower, b"<meta", from) {
+    let end = find(&lower, b">", meta).unwrap_or(lower.len());
+    if let Some(charset) = find(&lower[..end], b"charset", meta)
+      && let Some(span) = attribute_value(&lower[..end], charset + b"charset".len())
+    {
+      return Some(span);
+    }
+    from = end;
+  }
+  None
+}
+
+/// Span of the value of `name = "value"`, starting right after `name`, ending at the first
+/// byte which is no part of an encoding label or is not ASCII
+fn attribute_value(tag: &[u8], mut pos: usize) -> Option<(usize, usize)> {
+  let skip_space = |mut pos: usize| {
+    while pos < tag.len() && tag[pos].is_ascii_whitespace() {
+      pos += 1;
+    }
+    pos
+  };
+  pos = skip_space(pos);
+  if tag.get(pos) != Some(&b'=') {
+    return None;
+  }
+  pos = skip_space(pos + 1);
+  if matches!(tag.get(pos), Some(b'"') | Some(b'\'')) {
+    pos += 1;
+  }
+  let start = pos;
+  while pos < tag.len() && !matches!(tag[pos], b'"' | b'\'' | b';' | b'>' | b'/') {
+    if tag[pos].is_ascii_whitespace() || !tag[pos].is_ascii() {
+      break;
+    }
+    pos += 1;
+  }
+  if pos == start { None } else { Some((start, pos)) }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+  haystack
+    .get(from..)?
+    .windows(needle.len())
+    .position(|window| window == needle)
+    .map(|pos| pos + from)
+}
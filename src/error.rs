@@ -6,7 +6,7 @@ use super::bindings;
 use std::ffi::{c_char, c_int, CStr};
 
 /// Rust enum variant of libxml2's xmlErrorLevel
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum XmlErrorLevel {
   /// No error
   None,
@@ -33,7 +33,7 @@ impl XmlErrorLevel {
 
 /// Wrapper around xmlErrorPtr.
 /// Some fields have been omitted for simplicity/safety
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StructuredError {
   /// Human-friendly error message, lossily converted into UTF-8 from the underlying
   /// C string. May be `None` if an error message is not provided by libxml2.
@@ -0,0 +1,585 @@
+//! An lxml / ElementTree flavoured facade
+//!
+//! [`Element`] follows the mental model of Python's `lxml.etree`: an element owns its
+//! attributes, its leading `text` and the `tail` text following it, and children are other
+//! elements. Tags and attribute names use Clark notation (`{namespace}local`), namespace
+//! declarations are created as needed with `ns0`, `ns1`, ... prefixes. [`sub_element`],
+//! [`tostring`] and [`fromstring`] stand in for the module level functions of the same names.
+//!
+//! `find`, `findall`, `iterfind` and `findtext` accept ElementPath expressions, which are
+//! translated to XPath: `{uri}tag` steps are bound to generated prefixes and `{*}tag` matches
+//! `tag` in any namespace.
+//!
+//! Like a [`Node`], an `Element` can only be modified while no other handle to the same
+//! element is alive.
+//!
+use std::collections::HashMap;
+use std::error::Error;
+use std::ptr;
+use std::vec;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::parser::{Parser, XmlParseError};
+use crate::readonly::RoNode;
+use crate::tree::{Document, Namespace, Node, NodeType};
+use crate::xpath::Context;
+
+/// An element of a document, with lxml-like accessors
+#[derive(Clone)]
+pub struct Element {
+  document: Document,
+  node: Node,
+}
+
+/// Dict-like view on the attributes of an [`Element`], as `element.attrib`
+pub struct Attrib<'a> {
+  element: &'a mut Element,
+}
+
+/// Parses `text` and returns its root element
+pub fn fromstring(text: &str) -> Result<Element, XmlParseError> {
+  let document = Parser::default().parse_string(text)?;
+  match document.get_root_element() {
+    Some(node) => Ok(Element { document, node }),
+    None => Err(XmlParseError::GotNullPointer),
+  }
+}
+
+/// Serializes `element` and its descendants, without its tail
+pub fn tostring(element: &Element) -> String {
+  element.document.node_to_string(&element.node)
+}
+
+/// Creates an element named `tag` as last child of `parent`
+pub fn sub_element(parent: &mut Element, tag: &str) -> Result<Element, Box<dyn Error + Send + Sync>> {
+  parent.sub_element(tag)
+}
+
+impl Element {
+  /// Creates an element named `tag` as the root of a new document
+  pub fn new(tag: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+    let mut document = Document::new().map_err(|_| "could not create a document")?;
+    let (namespace, local) = split_clark(tag);
+    let mut node = Node::new(local, None, &document).map_err(|_| "could not create an element")?;
+    document.set_root_element(&node);
+    if let Some(href) = namespace {
+      let ns = namespace_for(&mut node, &document, href, false)?;
+      node.set_namespace(&ns)?;
+    }
+    Ok(Element { document, node })
+  }
+
+  /// Wraps `node`, an element of `document`
+  pub fn from_node(document: &Document, node: Node) -> Option<Self> {
+    if node.get_type() == Some(NodeType::ElementNode) {
+      Some(Element {
+        document: document.clone(),
+        node,
+      })
+    } else {
+      None
+    }
+  }
+
+  /// The underlying `Node`
+  pub fn as_node(&self) -> &Node {
+    &self.node
+  }
+
+  /// The document the element belongs to
+  pub fn document(&self) -> &Document {
+    &self.document
+  }
+
+  /// The tag in Clark notation
+  pub fn tag(&self) -> String {
+    match self.node.get_namespace() {
+      Some(ns) if !ns.get_href().is_empty() => format!("{{{}}}{}", ns.get_href(), self.node.get_name()),
+      _ => self.node.get_name(),
+    }
+  }
+
+  /// Renames the element, `tag` being in Clark notation
+  pub fn set_tag(&mut self, tag: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (namespace, local) = split_clark(tag);
+    self.node.set_name(local)?;
+    match namespace {
+      Some(href) => {
+        let ns = namespace_for(&mut self.node, &self.document, href, false)?;
+        self.node.set_namespace(&ns)
+      }
+      None => {
+        if self.node.get_namespace().is_some() {
+          unsafe { xmlSetNs(self.node.node_ptr_mut()?, ptr::null_mut()) };
+        }
+        Ok(())
+      }
+    }
+  }
+
+  /// The namespace prefix of the tag, if any
+  pub fn prefix(&self) -> Option<String> {
+    self
+      .node
+      .get_namespace()
+      .map(|ns| ns.get_prefix())
+      .filter(|prefix| !prefix.is_empty())
+  }
+
+  /// The text before the first child element, `None` if there is none
+  pub fn text(&self) -> Option<String> {
+    collect_text(self.node.get_first_child())
+  }
+
+  /// Replaces the text before the first child element
+  pub fn set_text(&mut self, text: Option<&str>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    remove_text(self.node.get_first_child());
+    if let Some(text) = text {
+      let mut text_node = Node::new_text(text, &self.document).map_err(|_| "could not create text")?;
+      match self.node.get_first_child() {
+        Some(mut first) => first.add_prev_sibling(&mut text_node)?,
+        None => self.node.add_child(&mut text_node)?,
+      }
+    }
+    Ok(())
+  }
+
+  /// The text between the end of this element and the next sibling element
+  pub fn tail(&self) -> Option<String> {
+    collect_text(self.node.get_next_sibling())
+  }
+
+  /// Replaces the text following this element
+  pub fn set_tail(&mut self, tail: Option<&str>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    remove_text(self.node.get_next_sibling());
+    if let Some(tail) = tail {
+      let mut text_node = Node::new_text(tail, &self.document).map_err(|_| "could not create text")?;
+      self.node.add_next_sibling(&mut text_node)?;
+    }
+    Ok(())
+  }
+
+  /// The value of attribute `key` (Clark notation)
+  pub fn get(&self, key: &str) -> Option<String> {
+    match split_clark(key) {
+      (Some(href), local) => self.node.get_attribute_ns(local, href),
+      (None, local) => self.node.get_attribute_no_ns(local),
+    }
+  }
+
+  /// Sets attribute `key` (Clark notation) to `value`
+  pub fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    match split_clark(key) {
+      (Some(href), local) => {
+        let ns = namespace_for(&mut self.node, &self.document, href, true)?;
+        self.node.set_attribute_ns(local, value, &ns)
+      }
+      (None, local) => self.node.set_attribute(local, value),
+    }
+  }
+
+  /// The attribute names in Clark notation, in document order
+  pub fn keys(&self) -> Vec<String> {
+    self.items().into_iter().map(|(key, _)| key).collect()
+  }
+
+  /// The attribute values, in document order
+  pub fn values(&self) -> Vec<String> {
+    self.items().into_iter().map(|(_, value)| value).collect()
+  }
+
+  /// The attributes as `(name, value)` pairs, in document order
+  pub fn items(&self) -> Vec<(String, String)> {
+    let mut items = Vec::new();
+    let mut attr_ptr = xmlGetFirstProperty(self.node.node_ptr());
+    while !attr_ptr.is_null() {
+      let attr = RoNode(attr_ptr as xmlNodePtr);
+      let name = match attr.get_namespace() {
+        Some(ns) => format!("{{{}}}{}", ns.get_href(), attr.get_name()),
+        None => attr.get_name(),
+      };
+      items.push((name, attr.get_content()));
+      attr_ptr = xmlNextPropertySibling(attr_ptr);
+    }
+    items
+  }
+
+  /// Dict-like access to the attributes
+  pub fn attrib(&mut self) -> Attrib<'_> {
+    Attrib { element: self }
+  }
+
+  /// Number of child elements
+  pub fn len(&self) -> usize {
+    self.node.get_child_elements().len()
+  }
+
+  /// Whether the element has no child elements
+  pub fn is_empty(&self) -> bool {
+    self.node.get_first_element_child().is_none()
+  }
+
+  /// The child elements
+  pub fn getchildren(&self) -> Vec<Element> {
+    self
+      .node
+      .get_child_elements()
+      .into_iter()
+      .map(|node| self.wrap(node))
+      .collect()
+  }
+
+  /// The child element at `index`, as `element[index]`
+  pub fn child(&self, index: usize) -> Option<Element> {
+    self
+      .node
+      .get_child_elements()
+      .into_iter()
+      .nth(index)
+      .map(|node| self.wrap(node))
+  }
+
+  /// The parent element, `None` for the root
+  pub fn getparent(&self) -> Option<Element> {
+    self
+      .node
+      .get_parent()
+      .filter(|parent| parent.get_type() == Some(NodeType::ElementNode))
+      .map(|parent| self.wrap(parent))
+  }
+
+  /// The next sibling element
+  pub fn getnext(&self) -> Option<Element> {
+    self.node.get_next_element_sibling().map(|node| self.wrap(node))
+  }
+
+  /// The previous sibling element
+  pub fn getprevious(&self) -> Option<Element> {
+    self.node.get_prev_element_sibling().map(|node| self.wrap(node))
+  }
+
+  /// Creates an element named `tag` (Clark notation) as last child
+  pub fn sub_element(&mut self, tag: &str) -> Result<Element, Box<dyn Error + Send + Sync>> {
+    let (namespace, local) = split_clark(tag);
+    let mut node = self.node.new_child(None, local)?;
+    if let Some(href) = namespace {
+      let ns = namespace_for(&mut node, &self.document, href, false)?;
+      node.set_namespace(&ns)?;
+    }
+    Ok(self.wrap(node))
+  }
+
+  /// Moves `child`, with its tail, to the end of the children.
+  /// Elements of other documents are copied into this one and `child` is updated to the copy.
+  pub fn append(&mut self, child: &mut Element) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tail = self.adopt(child)?;
+    self.node.add_child(&mut child.node)?;
+    child.set_tail(tail.as_deref())
+  }
+
+  /// Moves `child`, with its tail, to position `index` among the child elements
+  pub fn insert(&mut self, index: usize, child: &mut Element) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tail = self.adopt(child)?;
+    match self.node.get_child_elements().into_iter().nth(index) {
+      Some(mut sibling) => sibling.add_prev_sibling(&mut child.node)?,
+      None => self.node.add_child(&mut child.node)?,
+    }
+    child.set_tail(tail.as_deref())
+  }
+
+  /// Removes the child element `child`, with its tail
+  pub fn remove(&mut self, child: &mut Element) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if child.node.get_parent().as_ref() != Some(&self.node) {
+      return Err(From::from("element is not a child"));
+    }
+    remove_text(child.node.get_next_sibling());
+    child.node.unlink_node();
+    Ok(())
+  }
+
+  /// First element matching the ElementPath `path`
+  pub fn find(&self, path: &str) -> Result<Option<Element>, ()> {
+    Ok(self.findall(path)?.into_iter().next())
+  }
+
+  /// All elements matching the ElementPath `path`, in document order
+  pub fn findall(&self, path: &str) -> Result<Vec<Element>, ()> {
+    let context = Context::from_node(&self.node)?;
+    let xpath = element_path_to_xpath(path, &context)?;
+    let nodes = context.node_evaluate(&xpath, &self.node)?.get_nodes_as_vec();
+    Ok(
+      nodes
+        .into_iter()
+        .filter(|node| node.get_type() == Some(NodeType::ElementNode))
+        .map(|node| self.wrap(node))
+        .collect(),
+    )
+  }
+
+  /// Iterates over the elements matching the ElementPath `path`
+  pub fn iterfind(&self, path: &str) -> Result<vec::IntoIter<Element>, ()> {
+    Ok(self.findall(path)?.into_iter())
+  }
+
+  /// The text of the first element matching `path`, `Some("")` if it has none
+  pub fn findtext(&self, path: &str) -> Result<Option<String>, ()> {
+    Ok(self.find(path)?.map(|element| element.text().unwrap_or_default()))
+  }
+
+  /// Iterates over this element and its descendants in document order, keeping those
+  /// matching `tag` (Clark notation, `{*}local` or `*`) when given
+  pub fn iter(&self, tag: Option<&str>) -> vec::IntoIter<Element> {
+    let mut found = Vec::new();
+    let mut stack = vec![self.node.clone()];
+    while let Some(node) = stack.pop() {
+      if tag.is_none_or(|tag| tag_matches(&node, tag)) {
+        found.push(self.wrap(node.clone()));
+      }
+      stack.extend(node.get_child_elements().into_iter().rev());
+    }
+    found.into_iter()
+  }
+
+  /// Iterates over the text content of this element and its descendants, in document order
+  pub fn itertext(&self) -> vec::IntoIter<String> {
+    let mut texts = Vec::new();
+    let mut stack: Vec<Node> = self.node.get_child_nodes().into_iter().rev().collect();
+    while let Some(node) = stack.pop() {
+      match node.get_type() {
+        Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => texts.push(node.get_content()),
+        Some(NodeType::ElementNode) => stack.extend(node.get_child_nodes().into_iter().rev()),
+        _ => {}
+      }
+    }
+    texts.into_iter()
+  }
+
+  fn wrap(&self, node: Node) -> Element {
+    Element {
+      document: self.document.clone(),
+      node,
+    }
+  }
+
+  /// Detaches `child` for insertion below this element and returns its tail
+  fn adopt(&mut self, child: &mut Element) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let tail = child.tail();
+    if child.node.get_parent().is_some() {
+      remove_text(child.node.get_next_sibling());
+    }
+    if child.document.doc_ptr() == self.document.doc_ptr() {
+      child.node.unlink_node();
+    } else {
+      let copy = unsafe { xmlDocCopyNode(child.node.node_ptr(), self.document.doc_ptr(), 1) };
+      if copy.is_null() {
+        return Err(From::from("could not copy the element"));
+      }
+      *child = self.wrap(Node::wrap_new(copy, &self.document.0));
+    }
+    Ok(tail)
+  }
+}
+
+impl Attrib<'_> {
+  /// The value of attribute `key`
+  pub fn get(&self, key: &str) -> Option<String> {
+    self.element.get(key)
+  }
+
+  /// Sets attribute `key` to `value`
+  pub fn insert(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    self.element.set(key, value)
+  }
+
+  /// Removes attribute `key`, returning its previous value
+  pub fn remove(&mut self, key: &str) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    let previous = self.element.get(key);
+    if previous.is_some() {
+      match split_clark(key) {
+        (Some(href), local) => self.element.node.remove_attribute_ns(local, href)?,
+        (None, local) => self.element.node.remove_attribute_no_ns(local)?,
+      }
+    }
+    Ok(previous)
+  }
+
+  /// Whether attribute `key` is present
+  pub fn contains_key(&self, key: &str) -> bool {
+    self.element.get(key).is_some()
+  }
+
+  /// Number of attributes
+  pub fn len(&self) -> usize {
+    self.element.items().len()
+  }
+
+  /// Whether there are no attributes
+  pub fn is_empty(&self) -> bool {
+    xmlGetFirstProperty(self.element.node.node_ptr()).is_null()
+  }
+
+  /// Attribute names in Clark notation
+  pub fn keys(&self) -> Vec<String> {
+    self.element.keys()
+  }
+
+  /// Attribute values
+  pub fn values(&self) -> Vec<String> {
+    self.element.values()
+  }
+
+  /// `(name, value)` pairs
+  pub fn items(&self) -> Vec<(String, String)> {
+    self.element.items()
+  }
+
+  /// Removes all attributes
+  pub fn clear(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    for key in self.keys() {
+      self.remove(&key)?;
+    }
+    Ok(())
+  }
+}
+
+/// Splits `{namespace}local` into its parts
+fn split_clark(name: &str) -> (Option<&str>, &str) {
+  if let Some(rest) = name.strip_prefix('{')
+    && let Some(end) = rest.find('}')
+  {
+    let namespace = &rest[..end];
+    return (
+      if namespace.is_empty() { None } else { Some(namespace) },
+      &rest[end + 1..],
+    );
+  }
+  (None, name)
+}
+
+/// A namespace for `href` in scope at `node`, declared on `node` when there is none.
+/// Attributes need a prefixed namespace, the default namespace does not qualify them.
+fn namespace_for(
+  node: &mut Node,
+  document: &Document,
+  href: &str,
+  prefixed: bool,
+) -> Result<Namespace, Box<dyn Error + Send + Sync>> {
+  let in_scope = node.get_namespaces(document);
+  if let Some(ns) = in_scope
+    .iter()
+    .find(|ns| ns.get_href() == href && !(prefixed && ns.get_prefix().is_empty()))
+  {
+    return Ok(ns.clone());
+  }
+  let taken: Vec<String> = in_scope.iter().map(|ns| ns.get_prefix()).collect();
+  let prefix = (0..)
+    .map(|i| format!("ns{i}"))
+    .find(|prefix| !taken.contains(prefix))
+    .unwrap();
+  Namespace::new(&prefix, href, node)
+}
+
+/// Concatenated content of the run of text nodes starting at `node`
+fn collect_text(mut node: Option<Node>) -> Option<String> {
+  let mut text: Option<String> = None;
+  while let Some(current) = node {
+    match current.get_type() {
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => {
+        text.get_or_insert_with(String::new).push_str(&current.get_content());
+      }
+      _ => break,
+    }
+    node = current.get_next_sibling();
+  }
+  text
+}
+
+/// Unlinks the run of text nodes starting at `node`
+fn remove_text(mut node: Option<Node>) {
+  while let Some(mut current) = node {
+    match current.get_type() {
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => {
+        node = current.get_next_sibling();
+        current.unlink_node();
+      }
+      _ => break,
+    }
+  }
+}
+
+fn tag_matches(node: &Node, tag: &str) -> bool {
+  if tag == "*" {
+    return true;
+  }
+  let href = node.get_namespace().map(|ns| ns.get_href());
+  match tag.strip_prefix("{*}") {
+    Some(local) => local == "*" || node.get_name() == local,
+    None => {
+      let (namespace, local) = split_clark(tag);
+      namespace == href.as_deref() && (local == "*" || node.get_name() == local)
+    }
+  }
+}
+
+/// Translates an ElementPath expression into a relative XPath expression, registering the
+/// namespaces it mentions on `context`
+fn element_path_to_xpath(path: &str, context: &Context) -> Result<String, ()> {
+  if path.starts_with('/') {
+    // ElementPath does not allow absolute paths on elements
+    return Err(());
+  }
+  let mut prefixes: HashMap<&str, String> = HashMap::new();
+  let mut xpath = String::new();
+  if !path.starts_with('.') {
+    xpath.push_str("./");
+  }
+  let mut rest = path;
+  let mut quote: Option<char> = None;
+  while let Some(c) = rest.chars().next() {
+    if let Some(q) = quote {
+      if c == q {
+        quote = None;
+      }
+      xpath.push(c);
+      rest = &rest[c.len_utf8()..];
+      continue;
+    }
+    match c {
+      '"' | '\'' => {
+        quote = Some(c);
+        xpath.push(c);
+        rest = &rest[1..];
+      }
+      '{' => {
+        let end = rest.find('}').ok_or(())?;
+        let namespace = &rest[1..end];
+        rest = &rest[end + 1..];
+        let name_len = rest
+          .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '*')))
+          .unwrap_or(rest.len());
+        let local = &rest[..name_len];
+        rest = &rest[name_len..];
+        match namespace {
+          "*" if local == "*" => xpath.push('*'),
+          "*" => xpath.push_str(&format!("*[local-name()='{local}']")),
+          "" => xpath.push_str(local),
+          _ => {
+            let next = prefixes.len();
+            let prefix = prefixes
+              .entry(namespace)
+              .or_insert_with(|| format!("etree{next}"))
+              .clone();
+            context.register_namespace(&prefix, namespace)?;
+            xpath.push_str(&format!("{prefix}:{local}"));
+          }
+        }
+      }
+      _ => {
+        xpath.push(c);
+        rest = &rest[c.len_utf8()..];
+      }
+    }
+  }
+  Ok(xpath)
+}
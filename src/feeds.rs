@@ -0,0 +1,361 @@
+//! RSS, Atom and XML sitemap parsing
+//!
+//! [`Feed`] reads RSS 0.9x / 2.0, RSS 1.0 (RDF) and Atom 1.0 documents, [`Sitemap`] reads
+//! sitemaps and sitemap indexes. Elements are matched on their local names, so feeds which
+//! forget their namespace, put RSS into a default namespace or mix in Atom, Dublin Core and
+//! `content:encoded` elements are read as well. Dates are returned as written.
+//!
+use std::error::Error;
+use std::fmt;
+
+use crate::parser::{Parser, XmlParseError};
+use crate::readonly::RoNode;
+use crate::tree::Document;
+
+/// Errors raised while reading a feed or sitemap
+#[derive(Debug)]
+pub enum FeedError {
+  /// The input is not well-formed XML
+  Parse(XmlParseError),
+  /// The document has no root element
+  Empty,
+  /// The root element is not one of a feed or sitemap, its name is given
+  UnknownFormat(String),
+}
+
+impl Error for FeedError {}
+
+impl fmt::Display for FeedError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FeedError::Parse(e) => write!(f, "failed to parse feed: {e}"),
+      FeedError::Empty => write!(f, "feed document has no root element"),
+      FeedError::UnknownFormat(name) => write!(f, "unknown feed format with root element <{name}>"),
+    }
+  }
+}
+
+impl From<XmlParseError> for FeedError {
+  fn from(e: XmlParseError) -> Self {
+    FeedError::Parse(e)
+  }
+}
+
+/// The syntax a [`Feed`] was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedKind {
+  /// RSS 0.9x and 2.0, rooted at `<rss>`
+  Rss,
+  /// RSS 1.0, rooted at `<rdf:RDF>`
+  Rdf,
+  /// Atom 1.0, rooted at `<feed>`
+  Atom,
+}
+
+/// A syndication feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+  /// The syntax of the feed
+  pub kind: FeedKind,
+  /// The Atom `id` of the feed
+  pub id: Option<String>,
+  /// The feed title
+  pub title: Option<String>,
+  /// The web site of the feed
+  pub link: Option<String>,
+  /// The RSS `description` or Atom `subtitle`
+  pub description: Option<String>,
+  /// The RSS `lastBuildDate`, Atom `updated` or Dublin Core `date`
+  pub updated: Option<String>,
+  /// The entries of the feed, in document order
+  pub entries: Vec<FeedEntry>,
+}
+
+/// An RSS item or Atom entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedEntry {
+  /// The RSS `guid` or Atom `id`
+  pub id: Option<String>,
+  /// The entry title
+  pub title: Option<String>,
+  /// The link to the entry, resolved against `xml:base`
+  pub link: Option<String>,
+  /// The RSS `description` or Atom `summary`
+  pub summary: Option<String>,
+  /// The `content:encoded` or Atom `content`, markup of XHTML content serialized
+  pub content: Option<String>,
+  /// The RSS `pubDate`, Atom `published` or Dublin Core `date`
+  pub published: Option<String>,
+  /// The Atom `updated` date
+  pub updated: Option<String>,
+  /// The names of the RSS `author`s, Dublin Core `creator`s or Atom `author`s
+  pub authors: Vec<String>,
+  /// The category names, from the Atom `term` attribute or the RSS element text
+  pub categories: Vec<String>,
+  /// The RSS `enclosure` or Atom `rel="enclosure"` links
+  pub enclosures: Vec<Enclosure>,
+}
+
+/// A media file attached to a [`FeedEntry`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enclosure {
+  /// The URL of the file
+  pub url: String,
+  /// The media type, when given
+  pub media_type: Option<String>,
+  /// The size in bytes, when given
+  pub length: Option<u64>,
+}
+
+impl Feed {
+  /// Parses an RSS or Atom feed
+  pub fn parse<Bytes: AsRef<[u8]>>(input: Bytes) -> Result<Self, FeedError> {
+    Feed::from_document(&Parser::default().parse_string(input)?)
+  }
+
+  /// Reads an RSS or Atom feed from a parsed document
+  pub fn from_document(doc: &Document) -> Result<Self, FeedError> {
+    let root = doc.get_root_readonly().ok_or(FeedError::Empty)?;
+    match root.get_name().as_str() {
+      "rss" => {
+        let channel = child(root, "channel").unwrap_or(root);
+        let items = child_elements(channel, "item");
+        Ok(Feed::from_channel(doc, FeedKind::Rss, channel, items))
+      }
+      "RDF" => {
+        let channel = child(root, "channel").unwrap_or(root);
+        // RSS 1.0 items are siblings of the channel
+        let mut items = child_elements(root, "item");
+        if items.is_empty() {
+          items = child_elements(channel, "item");
+        }
+        Ok(Feed::from_channel(doc, FeedKind::Rdf, channel, items))
+      }
+      "feed" => Ok(Feed {
+        kind: FeedKind::Atom,
+        id: child_text(root, "id"),
+        title: child_text(root, "title"),
+        link: atom_link(doc, root),
+        description: child_text(root, "subtitle"),
+        updated: child_text(root, "updated"),
+        entries: child_elements(root, "entry")
+          .into_iter()
+          .map(|entry| atom_entry(doc, entry))
+          .collect(),
+      }),
+      other => Err(FeedError::UnknownFormat(other.to_string())),
+    }
+  }
+
+  fn from_channel(doc: &Document, kind: FeedKind, channel: RoNode, items: Vec<RoNode>) -> Self {
+    Feed {
+      kind,
+      id: None,
+      title: child_text(channel, "title"),
+      link: rss_link(doc, channel),
+      description: child_text(channel, "description"),
+      updated: child_text(channel, "lastBuildDate")
+        .or_else(|| child_text(channel, "date"))
+        .or_else(|| child_text(channel, "pubDate")),
+      entries: items.into_iter().map(|item| rss_item(doc, item)).collect(),
+    }
+  }
+}
+
+fn rss_item(doc: &Document, item: RoNode) -> FeedEntry {
+  let mut entry = FeedEntry {
+    id: child_text(item, "guid").or_else(|| item.get_attribute("about")),
+    title: child_text(item, "title"),
+    link: rss_link(doc, item),
+    summary: child_text(item, "description"),
+    content: child_text(item, "encoded"),
+    published: child_text(item, "pubDate").or_else(|| child_text(item, "date")),
+    updated: child_text(item, "updated"),
+    ..FeedEntry::default()
+  };
+  for element in item.get_child_elements() {
+    match element.get_name().as_str() {
+      "author" | "creator" => entry.authors.extend(text(element)),
+      "category" | "subject" => entry.categories.extend(text(element)),
+      "enclosure" => entry.enclosures.extend(enclosure(doc, element, "url")),
+      _ => {}
+    }
+  }
+  entry
+}
+
+fn atom_entry(doc: &Document, node: RoNode) -> FeedEntry {
+  let mut entry = FeedEntry {
+    id: child_text(node, "id"),
+    title: child_text(node, "title"),
+    link: atom_link(doc, node),
+    summary: child_text(node, "summary"),
+    content: child(node, "content").and_then(|content| atom_content(doc, content)),
+    published: child_text(node, "published").or_else(|| child_text(node, "issued")),
+    updated: child_text(node, "updated").or_else(|| child_text(node, "modified")),
+    ..FeedEntry::default()
+  };
+  for element in node.get_child_elements() {
+    match element.get_name().as_str() {
+      "author" => entry
+        .authors
+        .extend(child_text(element, "name").or_else(|| text(element))),
+      "category" => entry.categories.extend(
+        element
+          .get_attribute("term")
+          .or_else(|| element.get_attribute("label"))
+          .or_else(|| text(element)),
+      ),
+      "link" if element.get_attribute("rel").as_deref() == Some("enclosure") => {
+        entry.enclosures.extend(enclosure(doc, element, "href"))
+      }
+      _ => {}
+    }
+  }
+  entry
+}
+
+/// The text of an Atom `content`, the serialized children of XHTML content
+fn atom_content(doc: &Document, content: RoNode) -> Option<String> {
+  if content.get_attribute("type").as_deref() == Some("xhtml") {
+    let markup = content
+      .get_child_nodes()
+      .iter()
+      .map(|child| doc.ronode_to_string(child))
+      .collect::<String>();
+    let markup = markup.trim();
+    return (!markup.is_empty()).then(|| markup.to_string());
+  }
+  if let Some(src) = content.get_attribute("src") {
+    return Some(doc.resolve_url(content, src.trim()));
+  }
+  text(content)
+}
+
+/// The RSS `link` text, or an `atom:link` when there is none
+fn rss_link(doc: &Document, node: RoNode) -> Option<String> {
+  node
+    .get_child_elements()
+    .into_iter()
+    .filter(|element| element.get_name() == "link")
+    .find_map(|element| match element.get_attribute("href") {
+      Some(href) => {
+        let rel = element.get_attribute("rel");
+        matches!(rel.as_deref(), None | Some("alternate"))
+          .then(|| doc.resolve_url(element, href.trim()))
+      }
+      None => text(element).map(|link| doc.resolve_url(element, &link)),
+    })
+}
+
+/// The `alternate` Atom link, or the first link without a `rel`
+fn atom_link(doc: &Document, node: RoNode) -> Option<String> {
+  let links: Vec<RoNode> = child_elements(node, "link");
+  links
+    .iter()
+    .find(|link| link.get_attribute("rel").as_deref() == Some("alternate"))
+    .or_else(|| links.iter().find(|link| link.get_attribute("rel").is_none()))
+    .and_then(|link| {
+      link
+        .get_attribute("href")
+        .map(|href| doc.resolve_url(*link, href.trim()))
+    })
+}
+
+fn enclosure(doc: &Document, node: RoNode, url_attribute: &str) -> Option<Enclosure> {
+  let url = node.get_attribute(url_attribute)?;
+  Some(Enclosure {
+    url: doc.resolve_url(node, url.trim()),
+    media_type: node.get_attribute("type"),
+    length: node
+      .get_attribute("length")
+      .and_then(|length| length.trim().parse().ok()),
+  })
+}
+
+/// Whether a [`Sitemap`] lists pages or other sitemaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SitemapKind {
+  /// A `<urlset>` of pages
+  UrlSet,
+  /// A `<sitemapindex>` of sitemaps
+  Index,
+}
+
+/// An XML sitemap or sitemap index
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sitemap {
+  /// Whether the entries are pages or sitemaps
+  pub kind: SitemapKind,
+  /// The `url` or `sitemap` entries, in document order
+  pub entries: Vec<SitemapEntry>,
+}
+
+/// A page of a sitemap, or a sitemap of a sitemap index
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SitemapEntry {
+  /// The `loc` URL
+  pub loc: String,
+  /// The `lastmod` date
+  pub lastmod: Option<String>,
+  /// The `changefreq`, e.g. `daily`
+  pub changefreq: Option<String>,
+  /// The `priority`, between 0 and 1
+  pub priority: Option<f64>,
+}
+
+impl Sitemap {
+  /// Parses a sitemap or sitemap index
+  pub fn parse<Bytes: AsRef<[u8]>>(input: Bytes) -> Result<Self, FeedError> {
+    Sitemap::from_document(&Parser::default().parse_string(input)?)
+  }
+
+  /// Reads a sitemap or sitemap index from a parsed document, entries without a `loc` are
+  /// skipped
+  pub fn from_document(doc: &Document) -> Result<Self, FeedError> {
+    let root = doc.get_root_readonly().ok_or(FeedError::Empty)?;
+    let (kind, entry_name) = match root.get_name().as_str() {
+      "urlset" => (SitemapKind::UrlSet, "url"),
+      "sitemapindex" => (SitemapKind::Index, "sitemap"),
+      other => return Err(FeedError::UnknownFormat(other.to_string())),
+    };
+    let entries = child_elements(root, entry_name)
+      .into_iter()
+      .filter_map(|entry| {
+        Some(SitemapEntry {
+          loc: child_text(entry, "loc")?,
+          lastmod: child_text(entry, "lastmod"),
+          changefreq: child_text(entry, "changefreq").map(|freq| freq.to_ascii_lowercase()),
+          priority: child_text(entry, "priority").and_then(|priority| priority.parse().ok()),
+        })
+      })
+      .collect();
+    Ok(Sitemap { kind, entries })
+  }
+}
+
+fn child(node: RoNode, name: &str) -> Option<RoNode> {
+  node
+    .get_child_elements()
+    .into_iter()
+    .find(|element| element.get_name() == name)
+}
+
+fn child_elements(node: RoNode, name: &str) -> Vec<RoNode> {
+  node
+    .get_child_elements()
+    .into_iter()
+    .filter(|element| element.get_name() == name)
+    .collect()
+}
+
+/// The trimmed text of the first child element called `name`, `None` when missing or blank
+fn child_text(node: RoNode, name: &str) -> Option<String> {
+  child(node, name).and_then(text)
+}
+
+fn text(node: RoNode) -> Option<String> {
+  let content = node.get_content();
+  let content = content.trim();
+  (!content.is_empty()).then(|| content.to_string())
+}
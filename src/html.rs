@@ -0,0 +1,729 @@
+//! Utilities for documents parsed as HTML
+//!
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::ptr;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::parser::try_usize_to_i32;
+use crate::readonly::RoNode;
+use crate::tree::{Document, NodeType};
+
+/// The XHTML namespace
+pub const XHTML_NS: &str = "http://www.w3.org/1999/xhtml";
+
+/// Elements which never have content
+pub const VOID_ELEMENTS: &[&str] = &[
+  "area", "base", "basefont", "br", "col", "embed", "frame", "hr", "img", "input", "isindex",
+  "keygen", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Attributes whose presence alone carries their meaning
+const BOOLEAN_ATTRIBUTES: &[&str] = &[
+  "allowfullscreen", "async", "autofocus", "autoplay", "checked", "compact", "controls",
+  "declare", "default", "defer", "disabled", "formnovalidate", "hidden", "ismap", "itemscope",
+  "loop", "multiple", "muted", "nohref", "noresize", "noshade", "novalidate", "nowrap", "open",
+  "playsinline", "readonly", "required", "reversed", "selected",
+];
+
+impl Document {
+  /// Converts an HTML document into a well-formed XHTML one.
+  ///
+  /// Elements are put into the XHTML namespace and get lowercase names, void elements are left
+  /// empty (and so serialize self-closed) while other empty elements keep an end tag.
+  /// Attribute names are lowercased, minimized boolean attributes get their name as value,
+  /// duplicated attributes, `xmlns` declarations and names which are not valid XML are dropped.
+  /// Elements whose names are not valid XML are replaced by their content.
+  /// Script and style content containing markup characters is wrapped into CDATA sections and
+  /// comments containing `--` are fixed up. The doctype becomes `<!DOCTYPE html>`.
+  pub fn to_xhtml(&self) -> Result<Document, ()> {
+    let root = self.get_root_readonly().ok_or(())?;
+    let xhtml = Document::new()?;
+    let doc_ptr = xhtml.doc_ptr();
+    unsafe {
+      let c_html = CString::new("html").unwrap();
+      xmlCreateIntSubset(doc_ptr, c_html.as_bytes().as_ptr(), ptr::null(), ptr::null());
+    }
+    let root_ptr = copy_element(doc_ptr, ptr::null_mut(), root);
+    if root_ptr.is_null() {
+      return Err(());
+    }
+    unsafe { xmlDocSetRootElement(doc_ptr, root_ptr) };
+    Ok(xhtml)
+  }
+}
+
+/// Copies `source` into `doc`, in the namespace `ns` (declared on the copy when null). Returns
+/// null if the name of `source` is not a valid XML name.
+fn copy_element(doc: xmlDocPtr, ns: xmlNsPtr, source: RoNode) -> xmlNodePtr {
+  let name = source.get_name().to_ascii_lowercase();
+  let c_name = match CString::new(name.as_str()) {
+    Ok(c_name) => c_name,
+    Err(_) => return ptr::null_mut(),
+  };
+  unsafe {
+    if xmlValidateName(c_name.as_bytes().as_ptr(), 0) != 0 {
+      return ptr::null_mut();
+    }
+    let element = xmlNewDocNode(doc, ns, c_name.as_bytes().as_ptr(), ptr::null());
+    let ns = if ns.is_null() {
+      let c_href = CString::new(XHTML_NS).unwrap();
+      let ns = xmlNewNs(element, c_href.as_bytes().as_ptr(), ptr::null());
+      xmlSetNs(element, ns);
+      ns
+    } else {
+      ns
+    };
+
+    copy_attributes(element, source);
+    let raw_text = name == "script" || name == "style";
+    copy_children(doc, ns, element, source, raw_text);
+
+    if xmlGetFirstChild(element).is_null() && !VOID_ELEMENTS.contains(&name.as_str()) {
+      // An empty text child keeps the end tag in the serialization
+      xmlAddChild(element, xmlNewDocText(doc, c"".as_ptr() as *const xmlChar));
+    }
+    element
+  }
+}
+
+/// Appends copies of the children of `source` to `element`, in the namespace `ns`. The children
+/// of elements which can't be copied take their place.
+fn copy_children(doc: xmlDocPtr, ns: xmlNsPtr, element: xmlNodePtr, source: RoNode, raw_text: bool) {
+  for child in source.get_child_nodes() {
+    let copy = match child.get_type() {
+      Some(NodeType::ElementNode) => {
+        let copy = copy_element(doc, ns, child);
+        if copy.is_null() {
+          copy_children(doc, ns, element, child, raw_text);
+        }
+        copy
+      }
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => {
+        let content = child.get_content();
+        let c_content = CString::new(content.as_str()).unwrap_or_default();
+        match try_usize_to_i32(c_content.as_bytes().len()) {
+          Ok(len) if raw_text && content.contains(['<', '&']) && !content.contains("]]>") => unsafe {
+            xmlNewCDataBlock(doc, c_content.as_bytes().as_ptr(), len)
+          },
+          _ => unsafe { xmlNewDocText(doc, c_content.as_bytes().as_ptr()) },
+        }
+      }
+      Some(NodeType::CommentNode) => {
+        let content = escape_comment(&child.get_content());
+        let c_content = CString::new(content).unwrap_or_default();
+        unsafe { xmlNewDocComment(doc, c_content.as_bytes().as_ptr()) }
+      }
+      _ => ptr::null_mut(),
+    };
+    if !copy.is_null() {
+      unsafe { xmlAddChild(element, copy) };
+    }
+  }
+}
+
+fn copy_attributes(element: xmlNodePtr, source: RoNode) {
+  let mut seen = HashSet::new();
+  let mut attr_ptr = xmlGetFirstProperty(source.node_ptr());
+  while !attr_ptr.is_null() {
+    let attr = RoNode(attr_ptr as xmlNodePtr);
+    attr_ptr = xmlNextPropertySibling(attr_ptr);
+
+    let name = attr.get_name().to_ascii_lowercase();
+    if name == "xmlns" || name.starts_with("xmlns:") || !seen.insert(name.clone()) {
+      continue;
+    }
+    let mut value = attr.get_content();
+    if value.is_empty() && BOOLEAN_ATTRIBUTES.contains(&name.as_str()) {
+      value = name.clone();
+    }
+    let (Ok(c_name), Ok(c_value)) = (CString::new(name), CString::new(value)) else {
+      continue;
+    };
+    unsafe {
+      if xmlValidateName(c_name.as_bytes().as_ptr(), 0) == 0 {
+        xmlNewProp(element, c_name.as_bytes().as_ptr(), c_value.as_bytes().as_ptr());
+      }
+    }
+  }
+}
+
+/// Elements whose content is never rendered as text
+const SKIPPED_ELEMENTS: &[&str] = &[
+  "head", "script", "style", "noscript", "template", "iframe", "object", "embed", "canvas", "svg",
+  "math", "select", "datalist",
+];
+
+/// Blocks separated from their surroundings by a blank line
+const PARAGRAPH_ELEMENTS: &[&str] = &[
+  "p", "h1", "h2", "h3", "h4", "h5", "h6", "blockquote", "pre", "table", "ul", "ol", "dl",
+  "figure", "hr", "address", "fieldset",
+];
+
+/// Blocks starting on a line of their own
+const LINE_ELEMENTS: &[&str] = &[
+  "div", "li", "tr", "dt", "dd", "section", "article", "header", "footer", "nav", "aside",
+  "main", "form", "figcaption", "caption", "details", "summary", "legend", "option", "body",
+  "html", "center", "thead", "tbody", "tfoot",
+];
+
+/// Extracts the readable text of an HTML document.
+///
+/// Whitespace is collapsed as a browser would, blocks such as paragraphs, headings and list
+/// items start on new lines (paragraph-level blocks are separated by a blank line), `<br>`
+/// breaks the line and table cells are separated by tabs. The content of `<head>`, scripts,
+/// styles, templates and elements hidden with the `hidden` attribute, `aria-hidden="true"`
+/// or an inline `display: none` / `visibility: hidden` style is skipped.
+pub fn text_extract(doc: &Document) -> String {
+  match doc.get_root_readonly() {
+    Some(root) => text_extract_node(root),
+    None => String::new(),
+  }
+}
+
+/// Extracts the readable text below `node`, see [`text_extract`]
+pub fn text_extract_node(node: RoNode) -> String {
+  let mut extractor = TextExtractor::default();
+  extractor.walk(node, false);
+  extractor.out.trim_end().to_string()
+}
+
+#[derive(Default)]
+struct TextExtractor {
+  out: String,
+  /// Line breaks owed before the next text: 1 for a new line, 2 for a blank line
+  pending_breaks: usize,
+  pending_space: bool,
+  pending_tab: bool,
+}
+
+impl TextExtractor {
+  fn walk(&mut self, node: RoNode, preformatted: bool) {
+    match node.get_type() {
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => {
+        self.text(&node.get_content(), preformatted)
+      }
+      Some(NodeType::ElementNode) => {
+        let name = node.get_name().to_ascii_lowercase();
+        if SKIPPED_ELEMENTS.contains(&name.as_str()) || is_hidden(node) {
+          return;
+        }
+        let breaks = if PARAGRAPH_ELEMENTS.contains(&name.as_str()) {
+          2
+        } else if LINE_ELEMENTS.contains(&name.as_str()) {
+          1
+        } else {
+          0
+        };
+        match name.as_str() {
+          "br" => {
+            self.out.push('\n');
+            self.pending_space = false;
+            return;
+          }
+          "td" | "th" => self.pending_tab = true,
+          _ => self.block_break(breaks),
+        }
+        let preformatted = preformatted || name == "pre" || name == "textarea";
+        for child in node.get_child_nodes() {
+          self.walk(child, preformatted);
+        }
+        self.block_break(breaks);
+      }
+      _ => {}
+    }
+  }
+
+  fn block_break(&mut self, breaks: usize) {
+    self.pending_breaks = self.pending_breaks.max(breaks);
+    if breaks > 0 {
+      self.pending_tab = false;
+    }
+  }
+
+  fn text(&mut self, text: &str, preformatted: bool) {
+    if preformatted {
+      if !text.is_empty() {
+        self.separate();
+        self.out.push_str(text);
+      }
+      return;
+    }
+    if text.starts_with(|c: char| c.is_whitespace()) {
+      self.pending_space = true;
+    }
+    let mut words = text.split_whitespace().peekable();
+    if words.peek().is_none() {
+      return;
+    }
+    self.separate();
+    for (i, word) in words.enumerate() {
+      if i > 0 {
+        self.out.push(' ');
+      }
+      self.out.push_str(word);
+    }
+    self.pending_space = text.ends_with(|c: char| c.is_whitespace());
+  }
+
+  /// Writes the separator owed before the next piece of text
+  fn separate(&mut self) {
+    let at_line_start = self.out.is_empty() || self.out.ends_with('\n');
+    if self.pending_breaks > 0 {
+      if !self.out.is_empty() {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+        let present = self.out.len() - self.out.trim_end_matches('\n').len();
+        for _ in present..self.pending_breaks {
+          self.out.push('\n');
+        }
+      }
+    } else if self.pending_tab && !at_line_start {
+      self.out.push('\t');
+    } else if self.pending_space && !at_line_start {
+      self.out.push(' ');
+    }
+    self.pending_breaks = 0;
+    self.pending_space = false;
+    self.pending_tab = false;
+  }
+}
+
+fn is_hidden(node: RoNode) -> bool {
+  if node.get_attribute("hidden").is_some() {
+    return true;
+  }
+  if node
+    .get_attribute("aria-hidden")
+    .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"))
+  {
+    return true;
+  }
+  match node.get_attribute("style") {
+    Some(style) => {
+      let style: String = style
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+      style.contains("display:none") || style.contains("visibility:hidden")
+    }
+    None => false,
+  }
+}
+
+/// Element and attribute pairs holding a URL
+const LINK_ATTRIBUTES: &[(&str, &str)] = &[
+  ("a", "href"),
+  ("applet", "codebase"),
+  ("area", "href"),
+  ("audio", "src"),
+  ("blockquote", "cite"),
+  ("body", "background"),
+  ("button", "formaction"),
+  ("del", "cite"),
+  ("embed", "src"),
+  ("form", "action"),
+  ("frame", "longdesc"),
+  ("frame", "src"),
+  ("iframe", "longdesc"),
+  ("iframe", "src"),
+  ("img", "longdesc"),
+  ("img", "src"),
+  ("img", "usemap"),
+  ("input", "formaction"),
+  ("input", "src"),
+  ("ins", "cite"),
+  ("link", "href"),
+  ("object", "codebase"),
+  ("object", "data"),
+  ("q", "cite"),
+  ("script", "src"),
+  ("source", "src"),
+  ("track", "src"),
+  ("video", "poster"),
+  ("video", "src"),
+];
+
+/// A URL found in an HTML document by [`links`]
+#[derive(Debug, Clone)]
+pub struct Link {
+  /// The element carrying the URL
+  pub element: RoNode,
+  /// The name of the attribute holding the URL
+  pub attribute: String,
+  /// The URL resolved against the base URI of the element
+  pub url: String,
+}
+
+/// Lists the URLs referenced by an HTML document, in document order.
+///
+/// The links of `<a>`, `<img>`, `<script>`, `<link>`, `<form>` and the other elements with a
+/// URL valued attribute are returned as absolute URLs, resolved against the `href` of the
+/// `<base>` element or else the URL the document was parsed from. Links which cannot be
+/// resolved, for lack of a base URI or because they are malformed, are returned as written.
+/// Empty attributes are skipped.
+pub fn links(doc: &Document) -> Vec<Link> {
+  let mut links = Vec::new();
+  if let Some(root) = doc.get_root_readonly() {
+    collect_links(doc, root, &mut links);
+  }
+  links
+}
+
+/// Replaces every URL listed by [`links`] with the value returned by `f` for it, links for
+/// which `f` returns `None` are left untouched.
+///
+/// `f` receives the resolved URL, so returning it unchanged makes all the links of the
+/// document absolute.
+pub fn rewrite_links<F>(doc: &mut Document, mut f: F)
+where
+  F: FnMut(&str) -> Option<String>,
+{
+  for link in links(doc) {
+    let Some(url) = f(&link.url) else {
+      continue;
+    };
+    let (Ok(c_name), Ok(c_url)) = (CString::new(link.attribute), CString::new(url)) else {
+      continue;
+    };
+    unsafe {
+      xmlSetProp(
+        link.element.node_ptr(),
+        c_name.as_bytes().as_ptr(),
+        c_url.as_bytes().as_ptr(),
+      );
+    }
+  }
+}
+
+fn collect_links(doc: &Document, node: RoNode, links: &mut Vec<Link>) {
+  let name = node.get_name().to_ascii_lowercase();
+  for &(element, attribute) in LINK_ATTRIBUTES {
+    if element != name {
+      continue;
+    }
+    let Some(value) = node.get_attribute(attribute) else {
+      continue;
+    };
+    let value = value.trim();
+    if value.is_empty() {
+      continue;
+    }
+    links.push(Link {
+      element: node,
+      attribute: attribute.to_string(),
+      url: doc.resolve_url(node, value),
+    });
+  }
+  for child in node.get_child_elements() {
+    collect_links(doc, child, links);
+  }
+}
+
+/// An HTML `<form>` and its controls, as found by [`forms`]
+#[derive(Debug, Clone)]
+pub struct Form {
+  /// The `<form>` element
+  pub element: RoNode,
+  /// The `id` or else `name` of the form
+  pub name: Option<String>,
+  /// The `action` attribute, resolved against the base URI of the form
+  pub action: Option<String>,
+  /// The lowercased submission method, `get` unless given
+  pub method: String,
+  /// The `enctype` attribute, `application/x-www-form-urlencoded` unless given
+  pub enctype: String,
+  /// The named controls of the form, in document order
+  pub fields: Vec<FormField>,
+}
+
+impl Form {
+  /// The name and value pairs submitted when the form is sent untouched: enabled controls with
+  /// a default value, skipping buttons, file inputs and image inputs.
+  pub fn default_values(&self) -> Vec<(String, String)> {
+    self
+      .fields
+      .iter()
+      .filter(|field| !field.disabled)
+      .filter(|field| {
+        !matches!(
+          field.kind.as_str(),
+          "submit" | "reset" | "button" | "image" | "file"
+        )
+      })
+      .flat_map(|field| {
+        field
+          .values
+          .iter()
+          .map(move |value| (field.name.clone(), value.clone()))
+      })
+      .collect()
+  }
+}
+
+/// A named control of a [`Form`]
+#[derive(Debug, Clone)]
+pub struct FormField {
+  /// The control element
+  pub element: RoNode,
+  /// The `name` attribute
+  pub name: String,
+  /// The `type` of an `<input>` or `<button>` (lowercased, `text` and `submit` by default),
+  /// else `select`, `select-multiple` or `textarea`
+  pub kind: String,
+  /// The default values: the value of a text input or textarea, the value of a checked box, the
+  /// selected options of a select. Empty for unchecked boxes.
+  pub values: Vec<String>,
+  /// The values to choose from: the options of a select, the value of a checkbox or radio
+  /// button. Empty for free-form controls.
+  pub options: Vec<String>,
+  /// Whether the control or an enclosing `<fieldset>` is disabled
+  pub disabled: bool,
+}
+
+/// Lists the forms of an HTML document with their named controls.
+///
+/// Controls are attached to the form named by their `form` attribute, else to their enclosing
+/// form; controls outside of any form are left out.
+pub fn forms(doc: &Document) -> Vec<Form> {
+  let mut forms = Vec::new();
+  let mut controls = Vec::new();
+  if let Some(root) = doc.get_root_readonly() {
+    collect_forms(doc, root, None, false, &mut forms, &mut controls);
+  }
+  for (owner, control) in controls {
+    let index = match control.element.get_attribute("form") {
+      Some(id) => forms
+        .iter()
+        .position(|form| form.element.get_attribute("id").as_deref() == Some(id.as_str())),
+      None => owner,
+    };
+    if let Some(index) = index {
+      forms[index].fields.push(control);
+    }
+  }
+  forms
+}
+
+fn collect_forms(
+  doc: &Document,
+  node: RoNode,
+  owner: Option<usize>,
+  disabled: bool,
+  forms: &mut Vec<Form>,
+  controls: &mut Vec<(Option<usize>, FormField)>,
+) {
+  let name = node.get_name().to_ascii_lowercase();
+  let mut owner = owner;
+  let mut disabled = disabled;
+  match name.as_str() {
+    "form" => {
+      let action = node
+        .get_attribute("action")
+        .map(|action| doc.resolve_url(node, action.trim()));
+      let method = node
+        .get_attribute("method")
+        .map(|method| method.trim().to_ascii_lowercase())
+        .unwrap_or_else(|| "get".to_string());
+      let enctype = node
+        .get_attribute("enctype")
+        .map(|enctype| enctype.trim().to_ascii_lowercase())
+        .unwrap_or_else(|| "application/x-www-form-urlencoded".to_string());
+      forms.push(Form {
+        element: node,
+        name: node.get_attribute("id").or_else(|| node.get_attribute("name")),
+        action,
+        method,
+        enctype,
+        fields: Vec::new(),
+      });
+      owner = Some(forms.len() - 1);
+    }
+    "fieldset" => disabled = disabled || node.has_attribute("disabled"),
+    "input" | "select" | "textarea" | "button" => {
+      if let Some(field) = form_field(node, &name, disabled) {
+        controls.push((owner, field));
+      }
+      // The options of a select are read by form_field
+      return;
+    }
+    _ => {}
+  }
+  for child in node.get_child_elements() {
+    collect_forms(doc, child, owner, disabled, forms, controls);
+  }
+}
+
+fn form_field(node: RoNode, element: &str, disabled: bool) -> Option<FormField> {
+  let name = node.get_attribute("name").filter(|name| !name.is_empty())?;
+  let disabled = disabled || node.has_attribute("disabled");
+  let mut values = Vec::new();
+  let mut options = Vec::new();
+  let kind = match element {
+    "input" | "button" => {
+      let default_kind = if element == "input" { "text" } else { "submit" };
+      let kind = node
+        .get_attribute("type")
+        .map(|kind| kind.trim().to_ascii_lowercase())
+        .filter(|kind| !kind.is_empty())
+        .unwrap_or_else(|| default_kind.to_string());
+      let value = node.get_attribute("value");
+      if kind == "checkbox" || kind == "radio" {
+        let value = value.unwrap_or_else(|| "on".to_string());
+        if node.has_attribute("checked") {
+          values.push(value.clone());
+        }
+        options.push(value);
+      } else {
+        values.push(value.unwrap_or_default());
+      }
+      kind
+    }
+    "textarea" => {
+      values.push(node.get_content());
+      "textarea".to_string()
+    }
+    _ => {
+      let multiple = node.has_attribute("multiple");
+      let mut first = None;
+      collect_options(node, &mut options, &mut values, &mut first);
+      if values.is_empty() && !multiple {
+        values.extend(first);
+      }
+      if !multiple && values.len() > 1 {
+        // Only the last selected option of a single select stays selected
+        values.drain(..values.len() - 1);
+      }
+      if multiple { "select-multiple" } else { "select" }.to_string()
+    }
+  };
+  Some(FormField {
+    element: node,
+    name,
+    kind,
+    values,
+    options,
+    disabled,
+  })
+}
+
+/// Collects the options of a select, looking into `<optgroup>`s
+fn collect_options(
+  node: RoNode,
+  options: &mut Vec<String>,
+  selected: &mut Vec<String>,
+  first: &mut Option<String>,
+) {
+  for child in node.get_child_elements() {
+    match child.get_name().to_ascii_lowercase().as_str() {
+      "option" => {
+        let value = child
+          .get_attribute("value")
+          .unwrap_or_else(|| child.get_content().split_whitespace().collect::<Vec<_>>().join(" "));
+        if child.has_attribute("selected") {
+          selected.push(value.clone());
+        }
+        if first.is_none() && !child.has_attribute("disabled") {
+          *first = Some(value.clone());
+        }
+        options.push(value);
+      }
+      "optgroup" => collect_options(child, options, selected, first),
+      _ => {}
+    }
+  }
+}
+
+/// The rows of every `<table>` of an HTML document, in document order, see [`table_rows`]
+pub fn tables(doc: &Document) -> Vec<Vec<Vec<String>>> {
+  let mut tables = Vec::new();
+  if let Some(root) = doc.get_root_readonly() {
+    collect_tables(root, &mut tables);
+  }
+  tables
+}
+
+fn collect_tables(node: RoNode, tables: &mut Vec<Vec<Vec<String>>>) {
+  if node.get_name().eq_ignore_ascii_case("table") {
+    tables.push(table_rows(node));
+  }
+  for child in node.get_child_elements() {
+    collect_tables(child, tables);
+  }
+}
+
+/// Converts a `<table>` element into rows of cell texts.
+///
+/// Cells spanning several rows or columns through `rowspan` and `colspan` have their text
+/// repeated in each of the grid positions they cover, and short rows are padded with empty
+/// strings so that all rows have the same length. The text of a cell is extracted as by
+/// [`text_extract_node`]; nested tables are part of their cell's text.
+pub fn table_rows(table: RoNode) -> Vec<Vec<String>> {
+  let mut rows = Vec::new();
+  for child in table.get_child_elements() {
+    match child.get_name().to_ascii_lowercase().as_str() {
+      "tr" => rows.push(child),
+      "thead" | "tbody" | "tfoot" => rows.extend(
+        child
+          .get_child_elements()
+          .into_iter()
+          .filter(|row| row.get_name().eq_ignore_ascii_case("tr")),
+      ),
+      _ => {}
+    }
+  }
+
+  let mut grid: Vec<Vec<Option<String>>> = vec![Vec::new(); rows.len()];
+  for (r, row) in rows.iter().enumerate() {
+    let mut column = 0;
+    for cell in row.get_child_elements() {
+      let name = cell.get_name().to_ascii_lowercase();
+      if name != "td" && name != "th" {
+        continue;
+      }
+      while grid[r].get(column).is_some_and(Option::is_some) {
+        column += 1;
+      }
+      let colspan = span(cell, "colspan", 1000);
+      // rowspan="0" extends the cell to the end of the table
+      let rowspan = match span(cell, "rowspan", 65534) {
+        0 => rows.len() - r,
+        rowspan => rowspan.min(rows.len() - r),
+      };
+      let text = text_extract_node(cell);
+      for grid_row in &mut grid[r..r + rowspan] {
+        if grid_row.len() < column + colspan {
+          grid_row.resize(column + colspan, None);
+        }
+        for slot in &mut grid_row[column..column + colspan] {
+          *slot = Some(text.clone());
+        }
+      }
+      column += colspan;
+    }
+  }
+
+  let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+  grid
+    .into_iter()
+    .map(|row| {
+      let mut row: Vec<String> = row.into_iter().map(Option::unwrap_or_default).collect();
+      row.resize(width, String::new());
+      row
+    })
+    .collect()
+}
+
+/// The value of a span attribute, 1 when missing or invalid, capped at `max`
+fn span(cell: RoNode, attribute: &str, max: usize) -> usize {
+  match cell.get_attribute(attribute) {
+    Some(value) => match value.trim().parse::<usize>() {
+      Ok(0) if attribute == "rowspan" => 0,
+      Ok(0) | Err(_) => 1,
+      Ok(span) => span.min(max),
+    },
+    None => 1,
+  }
+}
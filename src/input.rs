@@ -0,0 +1,143 @@
+//! Custom input sources
+//!
+//! libxml2 loads external DTDs and entities and XInclude targets by URI through a table of
+//! input callbacks. [`register`] adds sources to it from Rust, e.g. to serve a `zip://` or
+//! `s3://` scheme or an in-memory virtual filesystem, and [`Parser::parse_file`] reads the
+//! documents they match through them too.
+//!
+//! ```
+//! use std::io::Cursor;
+//! use libxml::input;
+//! use libxml::parser::Parser;
+//!
+//! let id = input::register(
+//!   |uri| uri.starts_with("memo:"),
+//!   |uri| Ok(Box::new(Cursor::new(format!("<memo>{}</memo>", &uri[5..])))),
+//! );
+//! let doc = Parser::default().parse_file("memo:hello").unwrap();
+//! assert_eq!(doc.get_root_element().unwrap().get_content(), "hello");
+//! input::unregister(id);
+//! ```
+//!
+//! [`Parser::parse_file`]: crate::parser::Parser::parse_file
+use std::ffi::CStr;
+use std::io::{self, Read};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Once, RwLock};
+
+use crate::bindings::*;
+use crate::parser::Parser;
+
+type Matcher = dyn Fn(&str) -> bool + Send + Sync;
+type Opener = dyn Fn(&str) -> io::Result<Box<dyn Read>> + Send + Sync;
+
+/// Identifies a source added with [`register`], to remove it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputId(u64);
+
+struct Source {
+  id: u64,
+  matches: Box<Matcher>,
+  open: Box<Opener>,
+}
+
+/// The registered sources, the latest first, and the next id
+static SOURCES: RwLock<(Vec<Arc<Source>>, u64)> = RwLock::new((Vec::new(), 0));
+
+static REGISTER_CALLBACKS: Once = Once::new();
+
+/// Adds a source of input: the URIs for which `matches` returns `true` are read from what
+/// `open` returns for them. Sources registered later take precedence, and all of them over
+/// libxml2's own file and network loading.
+///
+/// The callbacks may be called from any thread parsing, and must not panic.
+pub fn register<M, O>(matches: M, open: O) -> InputId
+where
+  M: Fn(&str) -> bool + Send + Sync + 'static,
+  O: Fn(&str) -> io::Result<Box<dyn Read>> + Send + Sync + 'static,
+{
+  REGISTER_CALLBACKS.call_once(|| {
+    // libxml2 has to be initialized first, or it would register its defaults after ours
+    let _ = Parser::default();
+    unsafe {
+      xmlRegisterInputCallbacks(Some(input_match), Some(input_open), Some(input_read), Some(input_close));
+    }
+  });
+  let mut sources = SOURCES.write().unwrap_or_else(|e| e.into_inner());
+  let id = sources.1;
+  sources.1 += 1;
+  sources.0.insert(
+    0,
+    Arc::new(Source {
+      id,
+      matches: Box::new(matches),
+      open: Box::new(open),
+    }),
+  );
+  InputId(id)
+}
+
+/// Removes a source added with [`register`], returning whether it was still registered
+pub fn unregister(id: InputId) -> bool {
+  let mut sources = SOURCES.write().unwrap_or_else(|e| e.into_inner());
+  let count = sources.0.len();
+  sources.0.retain(|source| source.id != id.0);
+  sources.0.len() != count
+}
+
+/// The registered source matching `uri`, if any
+fn source_for(uri: &str) -> Option<Arc<Source>> {
+  let sources = SOURCES.read().unwrap_or_else(|e| e.into_inner());
+  // Matchers run with the lock held, they are not expected to register sources
+  sources.0.iter().find(|source| (source.matches)(uri)).cloned()
+}
+
+/// Opens `uri` with the registered source matching it, `None` when there is none
+pub(crate) fn open(uri: &str) -> Option<io::Result<Box<dyn Read>>> {
+  source_for(uri).map(|source| (source.open)(uri))
+}
+
+/// The URI libxml2 passes to the callbacks
+unsafe fn uri<'a>(filename: *const c_char) -> Option<&'a str> {
+  if filename.is_null() {
+    return None;
+  }
+  unsafe { CStr::from_ptr(filename) }.to_str().ok()
+}
+
+unsafe extern "C" fn input_match(filename: *const c_char) -> c_int {
+  unsafe { uri(filename) }.is_some_and(|uri| source_for(uri).is_some()) as c_int
+}
+
+unsafe extern "C" fn input_open(filename: *const c_char) -> *mut c_void {
+  match unsafe { uri(filename) }.and_then(open) {
+    Some(Ok(reader)) => Box::into_raw(Box::new(reader)) as *mut c_void,
+    _ => ptr::null_mut(),
+  }
+}
+
+unsafe extern "C" fn input_read(context: *mut c_void, buffer: *mut c_char, len: c_int) -> c_int {
+  if context.is_null() || len < 0 {
+    return -1;
+  }
+  unsafe {
+    let reader = &mut *(context as *mut Box<dyn Read>);
+    let buf = slice::from_raw_parts_mut(buffer as *mut u8, len as usize);
+    loop {
+      match reader.read(buf) {
+        Ok(read) => return read as c_int,
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+        Err(_) => return -1,
+      }
+    }
+  }
+}
+
+unsafe extern "C" fn input_close(context: *mut c_void) -> c_int {
+  if !context.is_null() {
+    drop(unsafe { Box::from_raw(context as *mut Box<dyn Read>) });
+  }
+  0
+}
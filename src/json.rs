@@ -0,0 +1,341 @@
+//! Mapping between XML documents and `serde_json` values
+//!
+//! Enabled with the `json` cargo feature. Two common conventions are supported:
+//!
+//! * [`JsonStyle::BadgerFish`]: every element becomes an object, attributes are keys
+//!   prefixed with `@`, text lives under `$` and namespace declarations under `@xmlns`.
+//! * [`JsonStyle::AttributePrefix`]: attributes use a configurable prefix, elements holding
+//!   only text collapse to a plain string and mixed text is stored under a configurable key.
+//!
+//! Repeated child elements are grouped into arrays in both styles. Comments and processing
+//! instructions are not represented.
+//!
+use std::error::Error;
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::c_helpers::qualified_name;
+use crate::tree::{Document, Namespace, Node, NodeType};
+
+/// The XML to JSON convention to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonStyle {
+  /// The BadgerFish convention (`@attr`, `$` text, `@xmlns` declarations)
+  BadgerFish,
+  /// Prefixed attribute keys, with text-only elements collapsed to strings
+  #[default]
+  AttributePrefix,
+}
+
+/// Options for converting between XML and JSON
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+  /// Convention used for the mapping
+  pub style: JsonStyle,
+  /// Prefix marking attribute keys (`AttributePrefix` style only, BadgerFish always uses `@`)
+  pub attribute_prefix: String,
+  /// Key holding text content of elements that also carry attributes or children
+  /// (`AttributePrefix` style only, BadgerFish always uses `$`)
+  pub text_key: String,
+  /// Keep whitespace-only text nodes instead of dropping them
+  pub keep_whitespace: bool,
+}
+
+impl Default for JsonOptions {
+  fn default() -> Self {
+    JsonOptions {
+      style: JsonStyle::AttributePrefix,
+      attribute_prefix: "@".to_string(),
+      text_key: "#text".to_string(),
+      keep_whitespace: false,
+    }
+  }
+}
+
+impl JsonOptions {
+  /// Options for the BadgerFish convention
+  pub fn badgerfish() -> Self {
+    JsonOptions {
+      style: JsonStyle::BadgerFish,
+      ..Default::default()
+    }
+  }
+
+  fn attr_prefix(&self) -> &str {
+    match self.style {
+      JsonStyle::BadgerFish => "@",
+      JsonStyle::AttributePrefix => &self.attribute_prefix,
+    }
+  }
+
+  fn text_key(&self) -> &str {
+    match self.style {
+      JsonStyle::BadgerFish => "$",
+      JsonStyle::AttributePrefix => &self.text_key,
+    }
+  }
+}
+
+/// Errors raised while building a `Document` from JSON
+#[derive(Debug)]
+pub enum JsonError {
+  /// The top-level value was not an object with exactly one (root element) key
+  InvalidRoot,
+  /// A value could not be mapped onto XML at the given key
+  InvalidValue(String),
+  /// libxml2 refused to create or attach a node
+  Tree(String),
+}
+
+impl Error for JsonError {}
+
+impl fmt::Display for JsonError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      JsonError::InvalidRoot => write!(
+        f,
+        "JSON document must be an object with a single root element key"
+      ),
+      JsonError::InvalidValue(key) => write!(f, "unsupported JSON value at key {key:?}"),
+      JsonError::Tree(e) => write!(f, "failed to build tree: {e}"),
+    }
+  }
+}
+
+impl Document {
+  /// Converts the document into a JSON value of the form `{ "root": ... }`
+  pub fn to_json(&self, options: &JsonOptions) -> Value {
+    let mut map = Map::new();
+    if let Some(root) = self.get_root_element() {
+      map.insert(qualified_name(root.node_ptr()), element_to_json(&root, options));
+    }
+    Value::Object(map)
+  }
+
+  /// Builds a new document from a JSON value of the form `{ "root": ... }`
+  pub fn from_json(value: &Value, options: &JsonOptions) -> Result<Document, JsonError> {
+    let (name, content) = match value {
+      Value::Object(map) if map.len() == 1 => map.iter().next().unwrap(),
+      _ => return Err(JsonError::InvalidRoot),
+    };
+    if content.is_array() {
+      return Err(JsonError::InvalidRoot);
+    }
+    let mut doc = Document::new().map_err(|_| JsonError::Tree("xmlNewDoc returned NULL".into()))?;
+    let mut root = create_element(&doc, name)?;
+    doc.set_root_element(&root);
+    fill_element(&doc, &mut root, name, content, options)?;
+    Ok(doc)
+  }
+}
+
+fn element_to_json(node: &Node, options: &JsonOptions) -> Value {
+  let prefix = options.attr_prefix();
+  let mut map = Map::new();
+
+  let declarations = node.get_namespace_declarations();
+  match options.style {
+    JsonStyle::BadgerFish if !declarations.is_empty() => {
+      let mut xmlns = Map::new();
+      for ns in declarations {
+        let ns_prefix = ns.get_prefix();
+        let key = if ns_prefix.is_empty() { "$".to_string() } else { ns_prefix };
+        xmlns.insert(key, Value::String(ns.get_href()));
+      }
+      map.insert("@xmlns".to_string(), Value::Object(xmlns));
+    }
+    JsonStyle::BadgerFish => {}
+    JsonStyle::AttributePrefix => {
+      for ns in declarations {
+        let ns_prefix = ns.get_prefix();
+        let key = if ns_prefix.is_empty() {
+          format!("{prefix}xmlns")
+        } else {
+          format!("{prefix}xmlns:{ns_prefix}")
+        };
+        map.insert(key, Value::String(ns.get_href()));
+      }
+    }
+  }
+
+  let mut attributes: Vec<(String, String)> = node
+    .get_properties_ns()
+    .into_iter()
+    .map(|((name, ns), value)| (qualify(ns.as_ref(), &name), value))
+    .collect();
+  attributes.sort();
+  for (name, value) in attributes {
+    map.insert(format!("{prefix}{name}"), Value::String(value));
+  }
+
+  let mut text = String::new();
+  for child in node.get_child_nodes() {
+    match child.get_type() {
+      Some(NodeType::ElementNode) => {
+        insert_child(&mut map, qualified_name(child.node_ptr()), element_to_json(&child, options))
+      }
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => {
+        text.push_str(&child.get_content())
+      }
+      _ => {}
+    }
+  }
+  let keep_text = if options.keep_whitespace {
+    !text.is_empty()
+  } else {
+    !text.trim().is_empty()
+  };
+
+  match options.style {
+    JsonStyle::AttributePrefix if map.is_empty() => {
+      if keep_text {
+        Value::String(text)
+      } else {
+        Value::Null
+      }
+    }
+    _ => {
+      if keep_text {
+        map.insert(options.text_key().to_string(), Value::String(text));
+      }
+      Value::Object(map)
+    }
+  }
+}
+
+fn insert_child(map: &mut Map<String, Value>, key: String, value: Value) {
+  match map.get_mut(&key) {
+    Some(Value::Array(items)) => items.push(value),
+    Some(existing) => {
+      let first = existing.take();
+      *existing = Value::Array(vec![first, value]);
+    }
+    None => {
+      map.insert(key, value);
+    }
+  }
+}
+
+fn fill_element(
+  doc: &Document,
+  node: &mut Node,
+  qname: &str,
+  value: &Value,
+  options: &JsonOptions,
+) -> Result<(), JsonError> {
+  let map = match value {
+    Value::Null => return resolve_element_namespace(node, qname),
+    Value::Object(map) => map,
+    Value::Array(_) => return Err(JsonError::InvalidValue(qname.to_string())),
+    scalar => {
+      resolve_element_namespace(node, qname)?;
+      return append_text(node, &scalar_to_string(scalar, qname)?);
+    }
+  };
+  let prefix = options.attr_prefix();
+
+  // Namespace declarations come first, so that prefixes used below are in scope.
+  for (key, value) in map {
+    match options.style {
+      JsonStyle::BadgerFish if key == "@xmlns" => {
+        let declarations = value
+          .as_object()
+          .ok_or_else(|| JsonError::InvalidValue(key.clone()))?;
+        for (ns_prefix, href) in declarations {
+          let ns_prefix = if ns_prefix == "$" { "" } else { ns_prefix.as_str() };
+          declare_namespace(node, ns_prefix, &scalar_to_string(href, key)?)?;
+        }
+      }
+      JsonStyle::AttributePrefix => {
+        if let Some(name) = key.strip_prefix(prefix) {
+          if name == "xmlns" {
+            declare_namespace(node, "", &scalar_to_string(value, key)?)?;
+          } else if let Some(ns_prefix) = name.strip_prefix("xmlns:") {
+            declare_namespace(node, ns_prefix, &scalar_to_string(value, key)?)?;
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+  resolve_element_namespace(node, qname)?;
+
+  for (key, value) in map {
+    if key == options.text_key() {
+      append_text(node, &scalar_to_string(value, key)?)?;
+    } else if let Some(name) = key.strip_prefix(prefix) {
+      if name == "xmlns" || name.starts_with("xmlns:") {
+        continue;
+      }
+      set_attribute(node, name, &scalar_to_string(value, key)?)?;
+    } else {
+      let items = match value {
+        Value::Array(items) => items.iter().collect(),
+        single => vec![single],
+      };
+      for item in items {
+        let mut child = create_element(doc, key)?;
+        node.add_child(&mut child).map_err(JsonError::Tree)?;
+        fill_element(doc, &mut child, key, item, options)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+fn create_element(doc: &Document, qname: &str) -> Result<Node, JsonError> {
+  let local = qname.split_once(':').map_or(qname, |(_, local)| local);
+  Node::new(local, None, doc).map_err(|_| JsonError::Tree(format!("could not create <{qname}>")))
+}
+
+fn declare_namespace(node: &mut Node, prefix: &str, href: &str) -> Result<(), JsonError> {
+  Namespace::new(prefix, href, node)
+    .map(|_| ())
+    .map_err(|e| JsonError::Tree(e.to_string()))
+}
+
+fn resolve_element_namespace(node: &mut Node, qname: &str) -> Result<(), JsonError> {
+  let prefix = qname.split_once(':').map(|(prefix, _)| prefix);
+  match node.search_namespace(prefix) {
+    Some(ns) => node
+      .set_namespace(&ns)
+      .map_err(|e| JsonError::Tree(e.to_string())),
+    None if prefix.is_some() => Err(JsonError::Tree(format!("unbound prefix in <{qname}>"))),
+    None => Ok(()),
+  }
+}
+
+fn set_attribute(node: &mut Node, qname: &str, value: &str) -> Result<(), JsonError> {
+  let result = match qname.split_once(':') {
+    Some((prefix, local)) => match node.search_namespace(Some(prefix)) {
+      Some(ns) => node.set_property_ns(local, value, &ns),
+      None => return Err(JsonError::Tree(format!("unbound prefix in attribute {qname}"))),
+    },
+    None => node.set_property(qname, value),
+  };
+  result.map_err(|e| JsonError::Tree(e.to_string()))
+}
+
+fn append_text(node: &mut Node, text: &str) -> Result<(), JsonError> {
+  node
+    .append_text(text)
+    .map_err(|e| JsonError::Tree(e.to_string()))
+}
+
+fn scalar_to_string(value: &Value, key: &str) -> Result<String, JsonError> {
+  match value {
+    Value::String(s) => Ok(s.clone()),
+    Value::Number(n) => Ok(n.to_string()),
+    Value::Bool(b) => Ok(b.to_string()),
+    Value::Null => Ok(String::new()),
+    _ => Err(JsonError::InvalidValue(key.to_string())),
+  }
+}
+
+fn qualify(ns: Option<&Namespace>, name: &str) -> String {
+  match ns.map(Namespace::get_prefix) {
+    Some(prefix) if !prefix.is_empty() => format!("{prefix}:{name}"),
+    _ => name.to_string(),
+  }
+}
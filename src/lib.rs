@@ -8,10 +8,25 @@
 /// Bindings to the C interface
 pub mod bindings;
 mod c_helpers;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod wasm;
+
+/// Version and features of the libxml2 in use
+pub mod runtime;
+pub use self::runtime::runtime_info;
 
 /// XML and HTML parsing
 pub mod parser;
 
+/// Custom input sources for URIs
+pub mod input;
+
+/// Event based parsing without a DOM
+pub mod sax;
+
+/// Pull parsing in constant memory
+pub mod reader;
+
 /// Manipulations on the DOM representation
 pub mod tree;
 
@@ -22,7 +37,56 @@ pub mod error;
 pub mod xpath;
 
 /// Schema Validation
+#[cfg(feature = "schemas")]
 pub mod schemas;
 
 /// Read-only parallel primitives
 pub mod readonly;
+
+/// Structural comparison of documents
+pub mod diff;
+
+/// Three-way merging of documents
+pub mod merge;
+
+/// XML Schema regular expressions
+pub mod regexp;
+
+/// XML Catalogs
+pub mod catalog;
+
+/// DOM Core and Load & Save style facade
+pub mod dom;
+
+/// XInclude processing with provenance tracking
+pub mod xinclude;
+
+/// lxml / ElementTree style facade
+pub mod etree;
+
+/// HTML specific utilities
+#[cfg(feature = "html")]
+pub mod html;
+
+/// RSS, Atom and sitemap parsing
+pub mod feeds;
+
+/// Conversions between the DOM and `quick-xml` events
+#[cfg(feature = "quick-xml")]
+pub mod quickxml;
+
+/// Mapping between XML documents and `serde_json` values
+#[cfg(feature = "json")]
+pub mod json;
+
+/// SOAP 1.1/1.2 envelope helpers
+#[cfg(feature = "soap")]
+pub mod soap;
+
+/// `xml-rs` compatible event reader over the libxml2 pull parser
+#[cfg(feature = "xml-rs")]
+pub mod xmlrs;
+
+/// Transcoding of non-UTF-8 input and output with `encoding_rs`
+#[cfg(feature = "encoding_rs")]
+pub mod encoding;
@@ -0,0 +1,468 @@
+//! Three-way XML merge
+//!
+//! [`merge`] combines two documents (`ours` and `theirs`) derived from a common `base`.
+//! Element children are aligned by qualified name and `id` attribute (or position among
+//! equally named siblings); attributes and the text of leaf elements are merged
+//! individually. Changes made on only one side are taken over, and changes made on both
+//! sides are resolved according to a [`MergePolicy`]. Text and comments interleaved with
+//! child elements (mixed content) are taken from `ours`.
+//!
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::diff::{attributes, node_path};
+use crate::readonly::RoNode;
+use crate::tree::Document;
+
+/// How a conflict is recorded in the merged document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictMarker {
+  /// A `<!-- merge conflict ... -->` comment before the conflicting node
+  Comment,
+  /// A `<?merge-conflict ...?>` processing instruction before the conflicting node
+  ProcessingInstruction,
+}
+
+/// Policy applied when both sides changed the same item differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+  /// Keep our version
+  OursWins,
+  /// Take their version
+  TheirsWins,
+  /// Keep our version and insert a marker describing the conflict
+  Mark(ConflictMarker),
+}
+
+/// A conflicting change found during a merge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+  /// Path of the item, in `ours` when it exists there, otherwise in `theirs`
+  pub path: String,
+  /// Human-readable description of the conflict
+  pub description: String,
+  /// Our version (`None` if we deleted it)
+  pub ours: Option<String>,
+  /// Their version (`None` if they deleted it)
+  pub theirs: Option<String>,
+}
+
+/// The outcome of a three-way merge
+pub struct MergeResult {
+  /// The merged document
+  pub document: Document,
+  /// All conflicts encountered, whatever the policy
+  pub conflicts: Vec<Conflict>,
+}
+
+/// Merges `ours` and `theirs`, both derived from `base`, into a new document
+pub fn merge(
+  base: &Document,
+  ours: &Document,
+  theirs: &Document,
+  policy: MergePolicy,
+) -> Result<MergeResult, ()> {
+  let (base_root, our_root, their_root) = match (
+    base.get_root_readonly(),
+    ours.get_root_readonly(),
+    theirs.get_root_readonly(),
+  ) {
+    (Some(b), Some(o), Some(t)) => (b, o, t),
+    _ => return Err(()),
+  };
+
+  if element_key(our_root) != element_key(their_root) {
+    // Replacing the root element on both sides cannot be merged below the root.
+    let conflict = Conflict {
+      path: "/".to_string(),
+      description: "root element differs".to_string(),
+      ours: Some(serialize(our_root)),
+      theirs: Some(serialize(their_root)),
+    };
+    let document = if policy == MergePolicy::TheirsWins {
+      theirs.dup()?
+    } else {
+      ours.dup()?
+    };
+    let mut merger = Merger {
+      doc_ptr: document.doc_ptr(),
+      policy,
+      conflicts: Vec::new(),
+    };
+    let out_root = unsafe { xmlDocGetRootElement(merger.doc_ptr) };
+    merger.record(conflict, out_root, ptr::null_mut());
+    return Ok(MergeResult {
+      conflicts: merger.conflicts,
+      document,
+    });
+  }
+
+  let document = ours.dup()?;
+  let mut merger = Merger {
+    doc_ptr: document.doc_ptr(),
+    policy,
+    conflicts: Vec::new(),
+  };
+  let out_root = unsafe { xmlDocGetRootElement(merger.doc_ptr) };
+  if element_key(base_root) == element_key(our_root) {
+    merger.merge_element(base_root, our_root, their_root, out_root);
+  } else {
+    merger.merge_element(our_root, our_root, their_root, out_root);
+  }
+  Ok(MergeResult {
+    conflicts: merger.conflicts,
+    document,
+  })
+}
+
+struct Merger {
+  doc_ptr: xmlDocPtr,
+  policy: MergePolicy,
+  conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+  fn merge_element(&mut self, base: RoNode, ours: RoNode, theirs: RoNode, out: xmlNodePtr) {
+    self.merge_attributes(base, ours, theirs, out);
+
+    if base.get_first_element_child().is_none()
+      && ours.get_first_element_child().is_none()
+      && theirs.get_first_element_child().is_none()
+    {
+      self.merge_text(base, ours, theirs, out);
+      return;
+    }
+
+    let base_map: HashMap<String, RoNode> = child_keys(base).into_iter().collect();
+    let our_keys = child_keys(ours);
+    let our_map: HashMap<String, RoNode> = our_keys.iter().cloned().collect();
+    let their_keys = child_keys(theirs);
+    let their_map: HashMap<String, RoNode> = their_keys.iter().cloned().collect();
+    let mut out_map: HashMap<String, xmlNodePtr> = child_keys(RoNode(out))
+      .into_iter()
+      .map(|(key, node)| (key, node.node_ptr()))
+      .collect();
+
+    for (key, our_child) in &our_keys {
+      let out_child = out_map[key];
+      match (base_map.get(key), their_map.get(key)) {
+        (Some(base_child), Some(their_child)) => {
+          self.merge_element(*base_child, *our_child, *their_child, out_child)
+        }
+        (Some(base_child), None) => {
+          if same(*base_child, *our_child) {
+            remove_node(out_child);
+            out_map.remove(key);
+          } else {
+            let conflict = Conflict {
+              path: node_path(*our_child),
+              description: "modified in ours, deleted in theirs".to_string(),
+              ours: Some(serialize(*our_child)),
+              theirs: None,
+            };
+            if self.record(conflict, out_child, out) {
+              remove_node(out_child);
+              out_map.remove(key);
+            }
+          }
+        }
+        (None, Some(their_child)) => {
+          if !same(*our_child, *their_child) {
+            let conflict = Conflict {
+              path: node_path(*our_child),
+              description: "added differently on both sides".to_string(),
+              ours: Some(serialize(*our_child)),
+              theirs: Some(serialize(*their_child)),
+            };
+            if self.record(conflict, out_child, out) {
+              let copy = self.copy_node(*their_child);
+              unsafe { xmlAddNextSibling(out_child, copy) };
+              remove_node(out_child);
+              out_map.insert(key.clone(), copy);
+            }
+          }
+        }
+        // added in ours only
+        (None, None) => {}
+      }
+    }
+
+    for (index, (key, their_child)) in their_keys.iter().enumerate() {
+      if our_map.contains_key(key) {
+        continue;
+      }
+      let take_theirs = match base_map.get(key) {
+        // added in theirs only
+        None => true,
+        Some(base_child) if same(*base_child, *their_child) => false,
+        Some(_) => {
+          let conflict = Conflict {
+            path: node_path(*their_child),
+            description: "deleted in ours, modified in theirs".to_string(),
+            ours: None,
+            theirs: Some(serialize(*their_child)),
+          };
+          let anchor = self.insert_at(out, &their_keys[..index], &out_map, ptr::null_mut());
+          self.record(conflict, anchor, out)
+        }
+      };
+      if take_theirs {
+        let copy = self.copy_node(*their_child);
+        self.insert_at(out, &their_keys[..index], &out_map, copy);
+        out_map.insert(key.clone(), copy);
+      }
+    }
+  }
+
+  fn merge_attributes(&mut self, base: RoNode, ours: RoNode, theirs: RoNode, out: xmlNodePtr) {
+    let base_attrs: HashMap<String, String> = attributes(base).into_iter().collect();
+    let our_attrs: HashMap<String, String> = attributes(ours).into_iter().collect();
+    let their_list = attributes(theirs);
+    let their_attrs: HashMap<String, String> = their_list.iter().cloned().collect();
+
+    let mut names: Vec<String> = attributes(ours).into_iter().map(|(name, _)| name).collect();
+    for (name, _) in their_list.iter().chain(attributes(base).iter()) {
+      if !names.contains(name) {
+        names.push(name.clone());
+      }
+    }
+
+    for name in names {
+      let (b, o, t) = (
+        base_attrs.get(&name),
+        our_attrs.get(&name),
+        their_attrs.get(&name),
+      );
+      if o == t || t == b {
+        continue;
+      }
+      if o == b {
+        set_attribute(out, &name, t.map(String::as_str));
+        continue;
+      }
+      let conflict = Conflict {
+        path: format!("{}/@{}", node_path(ours), name),
+        description: "attribute changed on both sides".to_string(),
+        ours: o.cloned(),
+        theirs: t.cloned(),
+      };
+      if self.record(conflict, out, ptr::null_mut()) {
+        set_attribute(out, &name, t.map(String::as_str));
+      }
+    }
+  }
+
+  fn merge_text(&mut self, base: RoNode, ours: RoNode, theirs: RoNode, out: xmlNodePtr) {
+    let (b, o, t) = (base.get_content(), ours.get_content(), theirs.get_content());
+    if o == t || t == b {
+      return;
+    }
+    if o == b {
+      set_text(out, &t);
+      return;
+    }
+    let conflict = Conflict {
+      path: node_path(ours),
+      description: "text changed on both sides".to_string(),
+      ours: Some(o),
+      theirs: Some(t.clone()),
+    };
+    if self.record(conflict, out, ptr::null_mut()) {
+      set_text(out, &t);
+    }
+  }
+
+  /// Records a conflict before `anchor` (or at the end of `parent` if `anchor` is NULL);
+  /// returns true if their version should be applied
+  fn record(&mut self, conflict: Conflict, anchor: xmlNodePtr, parent: xmlNodePtr) -> bool {
+    let take_theirs = match self.policy {
+      MergePolicy::TheirsWins => true,
+      MergePolicy::OursWins => false,
+      MergePolicy::Mark(marker) => {
+        let text = format!(
+          "merge conflict at {}: {}; ours: {}; theirs: {}",
+          conflict.path,
+          conflict.description,
+          conflict.ours.as_deref().unwrap_or("(deleted)"),
+          conflict.theirs.as_deref().unwrap_or("(deleted)"),
+        );
+        let marker_ptr = unsafe {
+          match marker {
+            ConflictMarker::Comment => {
+              let c_text = CString::new(escape_comment(&format!(" {text} "))).unwrap();
+              xmlNewDocComment(self.doc_ptr, c_text.as_bytes().as_ptr())
+            }
+            ConflictMarker::ProcessingInstruction => {
+              let c_target = CString::new("merge-conflict").unwrap();
+              let c_text = CString::new(text.replace("?>", "? >")).unwrap();
+              xmlNewDocPI(
+                self.doc_ptr,
+                c_target.as_bytes().as_ptr(),
+                c_text.as_bytes().as_ptr(),
+              )
+            }
+          }
+        };
+        unsafe {
+          if anchor.is_null() {
+            xmlAddChild(parent, marker_ptr);
+          } else {
+            xmlAddPrevSibling(anchor, marker_ptr);
+          }
+        }
+        false
+      }
+    };
+    self.conflicts.push(conflict);
+    take_theirs
+  }
+
+  fn copy_node(&self, node: RoNode) -> xmlNodePtr {
+    unsafe { xmlDocCopyNode(node.node_ptr(), self.doc_ptr, 1) }
+  }
+
+  /// Inserts `node` after the last already merged sibling among `preceding`, or first.
+  /// With a NULL `node`, only returns the node which an insertion would precede.
+  fn insert_at(
+    &self,
+    parent: xmlNodePtr,
+    preceding: &[(String, RoNode)],
+    out_map: &HashMap<String, xmlNodePtr>,
+    node: xmlNodePtr,
+  ) -> xmlNodePtr {
+    let previous = preceding
+      .iter()
+      .rev()
+      .find_map(|(key, _)| out_map.get(key).copied());
+    unsafe {
+      match previous {
+        Some(previous) => {
+          if node.is_null() {
+            return xmlNextSibling(previous);
+          }
+          xmlAddNextSibling(previous, node);
+        }
+        None => {
+          let first = xmlGetFirstChild(parent);
+          if node.is_null() {
+            return first;
+          }
+          if first.is_null() {
+            xmlAddChild(parent, node);
+          } else {
+            xmlAddPrevSibling(first, node);
+          }
+        }
+      }
+    }
+    node
+  }
+}
+
+/// Keys aligning the element children of a node across the three versions
+fn child_keys(node: RoNode) -> Vec<(String, RoNode)> {
+  let mut counts: HashMap<String, usize> = HashMap::new();
+  node
+    .get_child_elements()
+    .into_iter()
+    .map(|child| {
+      let name = element_key(child);
+      let key = match child.get_attribute("id") {
+        Some(id) => format!("{name}[@id={id}]"),
+        None => {
+          let count = counts.entry(name.clone()).or_default();
+          *count += 1;
+          format!("{name}[{count}]")
+        }
+      };
+      (key, child)
+    })
+    .collect()
+}
+
+fn element_key(node: RoNode) -> String {
+  let href = node.get_namespace().map(|ns| ns.get_href()).unwrap_or_default();
+  format!("{{{}}}{}", href, qualified_name(node.node_ptr()))
+}
+
+fn same(a: RoNode, b: RoNode) -> bool {
+  serialize(a) == serialize(b)
+}
+
+/// Breaks up the `--` a comment can't contain, which runs of dashes form again after a
+/// single pass
+fn serialize(node: RoNode) -> String {
+  unsafe {
+    let buf = xmlBufferCreate();
+    if buf.is_null() {
+      return String::new();
+    }
+    xmlNodeDump(buf, xmlGetDoc(node.node_ptr()), node.node_ptr(), 0, 0);
+    let content = CStr::from_ptr(xmlBufferContent(buf) as *const c_char)
+      .to_string_lossy()
+      .into_owned();
+    xmlBufferFree(buf);
+    content
+  }
+}
+
+fn remove_node(node: xmlNodePtr) {
+  unsafe {
+    xmlUnlinkNode(node);
+    xmlFreeNode(node);
+  }
+}
+
+fn set_text(node: xmlNodePtr, text: &str) {
+  unsafe {
+    let mut child = xmlGetFirstChild(node);
+    while !child.is_null() {
+      let next = xmlNextSibling(child);
+      remove_node(child);
+      child = next;
+    }
+    let c_text = CString::new(text).unwrap();
+    xmlAddChild(node, xmlNewDocText(xmlGetDoc(node), c_text.as_bytes().as_ptr()));
+  }
+}
+
+/// Sets (or with `None` removes) the attribute with qualified name `qname`
+fn set_attribute(node: xmlNodePtr, qname: &str, value: Option<&str>) {
+  unsafe {
+    let mut attr = xmlGetFirstProperty(node);
+    while !attr.is_null() && qualified_name(attr as xmlNodePtr) != qname {
+      attr = xmlNextPropertySibling(attr);
+    }
+    match value {
+      None => {
+        if !attr.is_null() {
+          xmlRemoveProp(attr);
+        }
+      }
+      Some(value) => {
+        let (ns_ptr, local) = match qname.split_once(':') {
+          Some((prefix, local)) => {
+            let c_prefix = CString::new(prefix).unwrap();
+            (
+              xmlSearchNs(xmlGetDoc(node), node, c_prefix.as_bytes().as_ptr()),
+              local,
+            )
+          }
+          None => (ptr::null_mut(), qname),
+        };
+        let c_name = CString::new(local).unwrap();
+        let c_value = CString::new(value).unwrap();
+        xmlSetNsProp(
+          node,
+          ns_ptr,
+          c_name.as_bytes().as_ptr(),
+          c_value.as_bytes().as_ptr(),
+        );
+      }
+    }
+  }
+}
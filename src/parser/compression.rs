@@ -0,0 +1,92 @@
+//! Compressed documents, read and written by libxml2 itself
+//!
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+
+use crate::bindings::*;
+
+/// The compression formats libxml2 can decompress when parsing files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+  /// gzip (`.gz`), needs the `zlib` feature
+  Gzip,
+  /// xz (`.xz`), needs the `lzma` feature; libxml2 can read it but not write it
+  Xz,
+}
+
+impl Compression {
+  /// Recognizes compressed `input` from its magic bytes
+  pub fn detect(input: &[u8]) -> Option<Self> {
+    if input.starts_with(&[0x1f, 0x8b]) {
+      Some(Compression::Gzip)
+    } else if input.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+      Some(Compression::Xz)
+    } else {
+      None
+    }
+  }
+
+  /// The compression implied by the extension of `path`
+  pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+    match path.as_ref().extension()?.to_str()? {
+      "gz" | "gzip" | "svgz" => Some(Compression::Gzip),
+      "xz" => Some(Compression::Xz),
+      _ => None,
+    }
+  }
+
+  /// Whether documents compressed this way can be parsed: the cargo feature must be enabled
+  /// and the libxml2 in use built with the library
+  pub fn is_available(self) -> bool {
+    let (enabled, feature) = match self {
+      Compression::Gzip => (cfg!(feature = "zlib"), xmlFeature_XML_WITH_ZLIB),
+      Compression::Xz => (cfg!(feature = "lzma"), xmlFeature_XML_WITH_LZMA),
+    };
+    enabled && unsafe { xmlHasFeature(feature) } != 0
+  }
+
+  /// The cargo feature enabling this compression
+  pub(crate) fn feature(self) -> &'static str {
+    match self {
+      Compression::Gzip => "zlib",
+      Compression::Xz => "lzma",
+    }
+  }
+}
+
+impl fmt::Display for Compression {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Compression::Gzip => write!(f, "gzip"),
+      Compression::Xz => write!(f, "xz"),
+    }
+  }
+}
+
+/// Reads the whole file `filename`, decompressed by libxml2 as when parsing it, or `None` if
+/// it can't be opened or read
+pub(crate) fn read_decompressed(filename: &CStr) -> Option<Vec<u8>> {
+  unsafe {
+    let input =
+      xmlParserInputBufferCreateFilename(filename.as_ptr(), xmlCharEncoding_XML_CHAR_ENCODING_NONE);
+    if input.is_null() {
+      return None;
+    }
+    let mut content = Vec::new();
+    let mut chunk = [0u8; 16_384];
+    let complete = loop {
+      let Some(read) = (*input).readcallback else {
+        break false;
+      };
+      let len = read((*input).context, chunk.as_mut_ptr() as *mut c_char, chunk.len() as c_int);
+      if len <= 0 {
+        break len == 0;
+      }
+      content.extend_from_slice(&chunk[..len as usize]);
+    };
+    xmlFreeParserInputBuffer(input);
+    complete.then_some(content)
+  }
+}
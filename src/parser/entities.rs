@@ -0,0 +1,203 @@
+//! Checks of entity expansion made before parsing, for the limits libxml2 can't enforce
+//!
+use std::collections::HashMap;
+
+/// The general entities with a literal value, by name
+type Entities<'a> = HashMap<&'a [u8], &'a [u8]>;
+
+/// An entity declaration: the name of a general entity (`None` for a parameter entity), its
+/// literal value (empty for an external entity) and where the declaration ends
+type Declaration<'a> = (Option<&'a [u8]>, &'a [u8], usize);
+
+/// How much expanding the general entities declared in the internal subset grows a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Expansion {
+  /// The size of the document with its entities expanded, in bytes
+  pub size: u64,
+  /// The number of entity references expanded, including those within entity values
+  pub references: u64,
+}
+
+/// Measures the entity expansion of the document `input`. References within comments and
+/// CDATA sections are counted as well, which errs on the safe side.
+pub(crate) fn expansion(input: &[u8]) -> Expansion {
+  let Some((entities, body_start)) = internal_subset(input) else {
+    return Expansion {
+      size: input.len() as u64,
+      references: 0,
+    };
+  };
+  let mut memo = HashMap::new();
+  let mut expansion = Expansion {
+    size: input.len() as u64,
+    references: 0,
+  };
+  for (name, reference_len) in references(&input[body_start..], &entities) {
+    let child = expand(name, &entities, &mut memo, &mut Vec::new());
+    expansion.size = expansion
+      .size
+      .saturating_sub(reference_len as u64)
+      .saturating_add(child.size);
+    expansion.references = expansion
+      .references
+      .saturating_add(1)
+      .saturating_add(child.references);
+  }
+  expansion
+}
+
+/// The expansion of the entity `name`, memoized; entities referencing themselves are left to
+/// libxml2, which rejects them.
+fn expand<'a>(
+  name: &'a [u8],
+  entities: &Entities<'a>,
+  memo: &mut HashMap<&'a [u8], Expansion>,
+  visiting: &mut Vec<&'a [u8]>,
+) -> Expansion {
+  if let Some(expansion) = memo.get(name) {
+    return *expansion;
+  }
+  let value = entities.get(name).copied().unwrap_or_default();
+  let mut expansion = Expansion {
+    size: value.len() as u64,
+    references: 0,
+  };
+  if visiting.contains(&name) {
+    return expansion;
+  }
+  visiting.push(name);
+  for (child_name, reference_len) in references(value, entities) {
+    let child = expand(child_name, entities, memo, visiting);
+    expansion.size = expansion
+      .size
+      .saturating_sub(reference_len as u64)
+      .saturating_add(child.size);
+    expansion.references = expansion
+      .references
+      .saturating_add(1)
+      .saturating_add(child.references);
+  }
+  visiting.pop();
+  memo.insert(name, expansion);
+  expansion
+}
+
+/// The references `&name;` to the declared `entities` in `text`, with their length
+fn references<'a>(
+  text: &'a [u8],
+  entities: &Entities<'a>,
+) -> impl Iterator<Item = (&'a [u8], usize)> {
+  let mut found = Vec::new();
+  let mut rest = text;
+  while let Some(amp) = rest.iter().position(|b| *b == b'&') {
+    rest = &rest[amp + 1..];
+    let name_len = rest
+      .iter()
+      .position(|b| !is_name_byte(*b))
+      .unwrap_or(rest.len());
+    if rest.get(name_len) == Some(&b';') {
+      let name = &rest[..name_len];
+      if let Some((declared, _)) = entities.get_key_value(name) {
+        found.push((*declared, name_len + 2));
+      }
+    }
+  }
+  found.into_iter()
+}
+
+fn is_name_byte(b: u8) -> bool {
+  b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b':') || b >= 0x80
+}
+
+/// The general entities declared with a literal value in the internal subset of `input`,
+/// and where the document continues after it
+fn internal_subset(input: &[u8]) -> Option<(Entities<'_>, usize)> {
+  let doctype = find(input, b"<!DOCTYPE")?;
+  let mut pos = doctype + b"<!DOCTYPE".len();
+  // The internal subset opens with `[` before the end of the doctype declaration
+  loop {
+    match *input.get(pos)? {
+      b'[' => break,
+      b'>' => return None,
+      quote @ (b'"' | b'\'') => pos = skip_literal(input, pos, quote)?,
+      _ => pos += 1,
+    }
+  }
+  pos += 1;
+  let mut entities = HashMap::new();
+  loop {
+    let rest = &input[pos..];
+    if rest.starts_with(b"]") {
+      let close = pos + rest.iter().position(|b| *b == b'>')? + 1;
+      return Some((entities, close));
+    } else if rest.starts_with(b"<!--") {
+      pos += find(rest, b"-->")? + 3;
+    } else if rest.starts_with(b"<?") {
+      pos += find(rest, b"?>")? + 2;
+    } else if rest.starts_with(b"<!ENTITY") {
+      let (name, value, end) = entity_declaration(input, pos + b"<!ENTITY".len())?;
+      if let Some(name) = name {
+        // The first declaration of an entity is binding
+        entities.entry(name).or_insert(value);
+      }
+      pos = end;
+    } else if rest.starts_with(b"<") {
+      pos = skip_declaration(input, pos)?;
+    } else {
+      pos += 1;
+    }
+  }
+}
+
+/// Reads the entity declaration starting at `pos`, after `<!ENTITY`
+fn entity_declaration(input: &[u8], mut pos: usize) -> Option<Declaration<'_>> {
+  pos = skip_spaces(input, pos);
+  let parameter = input.get(pos) == Some(&b'%');
+  if parameter {
+    pos = skip_spaces(input, pos + 1);
+  }
+  let name_len = input[pos..].iter().position(|b| !is_name_byte(*b))?;
+  let name = &input[pos..pos + name_len];
+  pos = skip_spaces(input, pos + name_len);
+  let value: &[u8] = match input.get(pos) {
+    Some(&quote @ (b'"' | b'\'')) => {
+      let end = skip_literal(input, pos, quote)?;
+      &input[pos + 1..end - 1]
+    }
+    _ => &[],
+  };
+  let end = skip_declaration(input, pos)?;
+  Some(((!parameter).then_some(name), value, end))
+}
+
+/// The position after the markup declaration at or around `pos`, ending with `>` outside of
+/// literals
+fn skip_declaration(input: &[u8], mut pos: usize) -> Option<usize> {
+  loop {
+    match *input.get(pos)? {
+      b'>' => return Some(pos + 1),
+      quote @ (b'"' | b'\'') => pos = skip_literal(input, pos, quote)?,
+      _ => pos += 1,
+    }
+  }
+}
+
+/// The position after the literal opening with `quote` at `pos`
+fn skip_literal(input: &[u8], pos: usize, quote: u8) -> Option<usize> {
+  let len = input[pos + 1..].iter().position(|b| *b == quote)?;
+  Some(pos + len + 2)
+}
+
+fn skip_spaces(input: &[u8], pos: usize) -> usize {
+  pos
+    + input[pos..]
+      .iter()
+      .position(|b| !b.is_ascii_whitespace())
+      .unwrap_or(input.len() - pos)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window == needle)
+}
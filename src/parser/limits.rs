@@ -0,0 +1,267 @@
+//! Limits on the nesting depth and number of nodes of parsed documents
+//!
+//! libxml2 only knows a hardcoded depth limit, which `huge` lifts, so these are enforced from
+//! the SAX callbacks building the tree: while a limited parse runs, the element, text and
+//! other node callbacks of its context are wrapped to count what they add, and the parser
+//! is stopped as soon as a limit is exceeded.
+//!
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::bindings::*;
+use crate::parser::{ParserOptions, XmlParseError};
+
+/// What a limited parse has seen so far, and the callbacks it wraps
+struct State {
+  ctxt: xmlParserCtxtPtr,
+  sax: xmlSAXHandler,
+  max_depth: Option<usize>,
+  max_nodes: Option<u64>,
+  depth: usize,
+  nodes: u64,
+  exceeded: bool,
+}
+
+thread_local! {
+  /// The state of the limited parse running on this thread
+  static ACTIVE: Cell<*mut State> = const { Cell::new(ptr::null_mut()) };
+}
+
+/// Runs `parse`, reading a document with the parser context `ctxt`, within the depth and
+/// node limits of `options`, failing with [`XmlParseError::LimitExceeded`] if it exceeds one
+pub(crate) fn enforce(
+  ctxt: xmlParserCtxtPtr,
+  options: &ParserOptions,
+  parse: impl FnOnce() -> xmlDocPtr,
+) -> Result<xmlDocPtr, XmlParseError> {
+  let sax = unsafe { (*ctxt).sax };
+  if (options.max_depth.is_none() && options.max_nodes.is_none()) || sax.is_null() {
+    return Ok(parse());
+  }
+  let mut state = State {
+    ctxt,
+    sax: unsafe { *sax },
+    max_depth: options.max_depth,
+    max_nodes: options.max_nodes,
+    depth: 0,
+    nodes: 0,
+    exceeded: false,
+  };
+  // Only the callbacks in use are wrapped, libxml2 picks the SAX1 or SAX2 ones by which are set
+  unsafe {
+    (*sax).startElementNs = state.sax.startElementNs.and(Some(start_element_ns));
+    (*sax).endElementNs = state.sax.endElementNs.and(Some(end_element_ns));
+    (*sax).startElement = state.sax.startElement.and(Some(start_element));
+    (*sax).endElement = state.sax.endElement.and(Some(end_element));
+    (*sax).characters = state.sax.characters.and(Some(characters));
+    (*sax).cdataBlock = state.sax.cdataBlock.and(Some(cdata_block));
+    (*sax).comment = state.sax.comment.and(Some(comment));
+    (*sax).processingInstruction = state
+      .sax
+      .processingInstruction
+      .and(Some(processing_instruction));
+    (*sax).reference = state.sax.reference.and(Some(reference));
+  }
+  let outer = ACTIVE.with(|active| active.replace(&mut state));
+  let doc_ptr = parse();
+  ACTIVE.with(|active| active.set(outer));
+  // The context may be reused, with other limits or none
+  unsafe {
+    (*sax).startElementNs = state.sax.startElementNs;
+    (*sax).endElementNs = state.sax.endElementNs;
+    (*sax).startElement = state.sax.startElement;
+    (*sax).endElement = state.sax.endElement;
+    (*sax).characters = state.sax.characters;
+    (*sax).cdataBlock = state.sax.cdataBlock;
+    (*sax).comment = state.sax.comment;
+    (*sax).processingInstruction = state.sax.processingInstruction;
+    (*sax).reference = state.sax.reference;
+  }
+  if state.exceeded {
+    if !doc_ptr.is_null() {
+      unsafe { xmlFreeDoc(doc_ptr) };
+    }
+    return Err(XmlParseError::LimitExceeded);
+  }
+  Ok(doc_ptr)
+}
+
+/// Counts the `nodes` the callback of `ctx` is about to add, an element if `opens`,
+/// returning the callbacks to hand them on to, or `None` once a limit is exceeded
+unsafe fn count(ctx: *mut c_void, nodes: u64, opens: bool) -> Option<*const xmlSAXHandler> {
+  let state = ACTIVE.with(Cell::get);
+  if state.is_null() {
+    return None;
+  }
+  let state = unsafe { &mut *state };
+  if state.exceeded {
+    return None;
+  }
+  state.nodes += nodes;
+  if opens {
+    state.depth += 1;
+  }
+  let too_deep = state.max_depth.is_some_and(|max| state.depth > max);
+  let too_many = state.max_nodes.is_some_and(|max| state.nodes > max);
+  if too_deep || too_many {
+    state.exceeded = true;
+    // Entities are parsed with contexts of their own, the document's has to stop as well
+    unsafe {
+      xmlStopParser(ctx as xmlParserCtxtPtr);
+      if ctx != state.ctxt as *mut c_void {
+        xmlStopParser(state.ctxt);
+      }
+    }
+    return None;
+  }
+  Some(&state.sax)
+}
+
+/// Leaves an element, returning the callbacks to hand its end on to
+unsafe fn close() -> Option<*const xmlSAXHandler> {
+  let state = ACTIVE.with(Cell::get);
+  if state.is_null() {
+    return None;
+  }
+  let state = unsafe { &mut *state };
+  state.depth = state.depth.saturating_sub(1);
+  Some(&state.sax)
+}
+
+/// Whether text reported to `ctxt` is appended to the last node added
+unsafe fn extends_text(ctxt: xmlParserCtxtPtr) -> bool {
+  unsafe {
+    let node = (*ctxt).node;
+    !node.is_null()
+      && !(*node).last.is_null()
+      && (*(*node).last).type_ == xmlElementType_XML_TEXT_NODE
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn start_element_ns(
+  ctx: *mut c_void,
+  localname: *const xmlChar,
+  prefix: *const xmlChar,
+  uri: *const xmlChar,
+  nb_namespaces: c_int,
+  namespaces: *mut *const xmlChar,
+  nb_attributes: c_int,
+  nb_defaulted: c_int,
+  attributes: *mut *const xmlChar,
+) {
+  unsafe {
+    if let Some(sax) = count(ctx, 1, true)
+      && let Some(callback) = (*sax).startElementNs
+    {
+      callback(
+        ctx,
+        localname,
+        prefix,
+        uri,
+        nb_namespaces,
+        namespaces,
+        nb_attributes,
+        nb_defaulted,
+        attributes,
+      );
+    }
+  }
+}
+
+unsafe extern "C" fn end_element_ns(
+  ctx: *mut c_void,
+  localname: *const xmlChar,
+  prefix: *const xmlChar,
+  uri: *const xmlChar,
+) {
+  unsafe {
+    if let Some(sax) = close()
+      && let Some(callback) = (*sax).endElementNs
+    {
+      callback(ctx, localname, prefix, uri);
+    }
+  }
+}
+
+unsafe extern "C" fn start_element(
+  ctx: *mut c_void,
+  name: *const xmlChar,
+  atts: *mut *const xmlChar,
+) {
+  unsafe {
+    if let Some(sax) = count(ctx, 1, true)
+      && let Some(callback) = (*sax).startElement
+    {
+      callback(ctx, name, atts);
+    }
+  }
+}
+
+unsafe extern "C" fn end_element(ctx: *mut c_void, name: *const xmlChar) {
+  unsafe {
+    if let Some(sax) = close()
+      && let Some(callback) = (*sax).endElement
+    {
+      callback(ctx, name);
+    }
+  }
+}
+
+unsafe extern "C" fn characters(ctx: *mut c_void, ch: *const xmlChar, len: c_int) {
+  unsafe {
+    // Text comes in chunks, which are appended to the text node the first one started
+    let added = !extends_text(ctx as xmlParserCtxtPtr);
+    if let Some(sax) = count(ctx, added as u64, false)
+      && let Some(callback) = (*sax).characters
+    {
+      callback(ctx, ch, len);
+    }
+  }
+}
+
+unsafe extern "C" fn cdata_block(ctx: *mut c_void, value: *const xmlChar, len: c_int) {
+  unsafe {
+    if let Some(sax) = count(ctx, 1, false)
+      && let Some(callback) = (*sax).cdataBlock
+    {
+      callback(ctx, value, len);
+    }
+  }
+}
+
+unsafe extern "C" fn comment(ctx: *mut c_void, value: *const xmlChar) {
+  unsafe {
+    if let Some(sax) = count(ctx, 1, false)
+      && let Some(callback) = (*sax).comment
+    {
+      callback(ctx, value);
+    }
+  }
+}
+
+unsafe extern "C" fn processing_instruction(
+  ctx: *mut c_void,
+  target: *const xmlChar,
+  data: *const xmlChar,
+) {
+  unsafe {
+    if let Some(sax) = count(ctx, 1, false)
+      && let Some(callback) = (*sax).processingInstruction
+    {
+      callback(ctx, target, data);
+    }
+  }
+}
+
+unsafe extern "C" fn reference(ctx: *mut c_void, name: *const xmlChar) {
+  unsafe {
+    if let Some(sax) = count(ctx, 1, false)
+      && let Some(callback) = (*sax).reference
+    {
+      callback(ctx, name);
+    }
+  }
+}
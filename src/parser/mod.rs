@@ -0,0 +1,1225 @@
+//! The parser functionality
+
+pub mod compression;
+mod entities;
+mod limits;
+pub mod multidoc;
+mod pool;
+mod progress;
+pub mod push;
+
+pub use self::compression::Compression;
+pub use self::multidoc::MultiDocParser;
+pub use self::push::PushParser;
+
+use crate::bindings::*;
+#[cfg(feature = "html")]
+use crate::c_helpers::*;
+use crate::error::{StructuredError, XmlErrorLevel};
+use crate::input;
+use crate::tree::*;
+
+use std::convert::AsRef;
+use std::error::Error;
+use std::ffi::c_void;
+#[cfg(feature = "html")]
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::ops::ControlFlow;
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::ptr;
+use std::slice;
+use std::str;
+use std::sync::{Mutex, Once};
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+static INIT_LIBXML_PARSER: Once = Once::new();
+
+enum XmlParserOption {
+  Recover = 1,
+  Dtdload = 4,
+  Dtdattr = 8,
+  Dtdvalid = 16,
+  Noerror = 32,
+  Nowarning = 64,
+  Pedantic = 128,
+  Noblanks = 256,
+  Nonet = 2048,
+  Noimplied = 8192,
+  Compact = 65_536,
+  Huge = 524_288,
+  Ignoreenc = 2_097_152,
+  Biglines = 4_194_304,
+}
+
+#[cfg(feature = "html")]
+enum HtmlParserOption {
+  Recover = 1,
+  Nodefdtd = 4,
+  Noerror = 32,
+  Nowarning = 64,
+  Pedantic = 128,
+  Noblanks = 256,
+  Nonet = 2048,
+  Noimplied = 8192,
+  Huge = 524_288,
+  Compact = 65_536,
+  Ignoreenc = 2_097_152,
+}
+
+/// Parser Options
+#[derive(Debug, Clone, Copy)]
+pub struct ParserOptions<'a> {
+  /// Relaxed parsing
+  pub recover: bool,
+  /// do not default a doctype if not found (HTML only)
+  pub no_def_dtd: bool,
+  /// load the external subset of the DTD (XML only)
+  pub dtd_load: bool,
+  /// add the default attributes declared in the DTD (XML only)
+  pub dtd_attr: bool,
+  /// validate against the DTD, loading it (XML only)
+  pub dtd_valid: bool,
+  /// record line numbers past 65535 for [`Node::get_line_number`], which libxml2 otherwise
+  /// caps (XML only)
+  pub big_lines: bool,
+  /// do not default a doctype if not found
+  pub no_error: bool,
+  /// suppress printed warning reports, the parse methods of [`Parser`] collect warnings for
+  /// [`Parser::last_warnings`] regardless
+  pub no_warning: bool,
+  /// pedantic error reporting
+  pub pedantic: bool,
+  /// remove the whitespace-only text nodes between elements while parsing, like
+  /// [`Document::strip_ignorable_whitespace`] does afterwards
+  pub no_blanks: bool,
+  /// Forbid network access: external DTDs and entities on http and ftp URLs fail to load
+  /// instead of being fetched (`XML_PARSE_NONET`)
+  pub no_net: bool,
+  /// Do not add implied html/body... elements
+  pub no_implied: bool,
+  /// relax any hardcoded limit from the parser (`XML_PARSE_HUGE`): attribute values,
+  /// comments and text nodes over 10MB, and elements nested deeper than 256 levels
+  pub huge: bool,
+  /// compact small text nodes
+  pub compact: bool,
+  /// ignore internal document encoding hint
+  pub ignore_enc: bool,
+  /// manually-specified encoding
+  pub encoding: Option<&'a str>,
+  /// maximum amplification factor of entity expansion: the size of the document with its
+  /// entities expanded over its own size (libxml2's default is 5). Enforced by libxml2 2.11
+  /// and newer past an allowance of about 1MB, for older releases the input is checked
+  /// before parsing, see `max_entity_expansions`. Has no effect with `huge`.
+  pub max_amplification: Option<u32>,
+  /// maximum number of entity references expanded, checked before parsing by all the parse
+  /// methods of [`Parser`]. Files and readers are then read whole before parsing starts. The
+  /// [`PushParser`](push::PushParser) can't check its input ahead and leaves this out.
+  pub max_entity_expansions: Option<u64>,
+  /// decompress gzip and xz files in [`Parser::parse_file`], when this build supports it
+  /// (see [`Compression::is_available`]); otherwise they are parsed as they are, and fail
+  /// to, e.g. to refuse decompression bombs. [`Parser::parse_compressed_file`] always does.
+  pub decompress: bool,
+  /// maximum nesting depth of elements, the root element being at depth 1, enforced by the
+  /// parse methods of [`Parser`] whatever `huge` says
+  pub max_depth: Option<usize>,
+  /// maximum number of nodes: elements, text nodes, comments, processing instructions and
+  /// entity references, enforced by the parse methods of [`Parser`]
+  pub max_nodes: Option<u64>,
+}
+
+impl ParserOptions<'_> {
+  pub(crate) fn to_flags(self, format: &ParseFormat) -> i32 {
+    macro_rules! to_option_flag {
+      (
+        $condition:expr => $variant:ident
+      ) => {
+        if $condition {
+          match format {
+            #[cfg(feature = "html")]
+            ParseFormat::HTML => HtmlParserOption::$variant as i32,
+            ParseFormat::XML => XmlParserOption::$variant as i32,
+          }
+        } else {
+          0
+        }
+      };
+    }
+    // return the combined flags
+    to_option_flag!(self.recover => Recover)
+      + to_option_flag!(self.no_error => Noerror)
+      + to_option_flag!(self.no_warning => Nowarning)
+      + to_option_flag!(self.pedantic => Pedantic)
+      + to_option_flag!(self.no_blanks => Noblanks)
+      + to_option_flag!(self.no_net => Nonet)
+      + to_option_flag!(self.no_implied => Noimplied)
+      + to_option_flag!(self.huge => Huge)
+      + to_option_flag!(self.compact => Compact)
+      + to_option_flag!(self.ignore_enc => Ignoreenc)
+      + self.format_flags(format)
+  }
+
+  /// The flags only one of the formats has
+  fn format_flags(&self, format: &ParseFormat) -> i32 {
+    let flag = |condition: bool, option: i32| if condition { option } else { 0 };
+    match format {
+      #[cfg(feature = "html")]
+      ParseFormat::HTML => flag(self.no_def_dtd, HtmlParserOption::Nodefdtd as i32),
+      ParseFormat::XML => {
+        flag(self.dtd_load, XmlParserOption::Dtdload as i32)
+          + flag(self.dtd_attr, XmlParserOption::Dtdattr as i32)
+          + flag(self.dtd_valid, XmlParserOption::Dtdvalid as i32)
+          + flag(self.big_lines, XmlParserOption::Biglines as i32)
+      }
+    }
+  }
+
+  /// The amplification limit libxml2 does not enforce itself
+  fn unenforced_amplification(&self) -> Option<u32> {
+    if cfg!(libxml_older_than_2_11) && !self.huge {
+      self.max_amplification
+    } else {
+      None
+    }
+  }
+
+  /// Whether `check_entities` has a limit to check, for which it needs the whole input
+  fn checks_entities(&self) -> bool {
+    self.unenforced_amplification().is_some() || self.max_entity_expansions.is_some()
+  }
+
+  /// Checks the entity expansion of the whole `input` against the limits libxml2 does not
+  /// enforce itself.
+  fn check_entities(&self, input: &[u8]) -> Result<(), XmlParseError> {
+    if !self.checks_entities() {
+      return Ok(());
+    }
+    let amplification = self.unenforced_amplification();
+    let expansion = entities::expansion(input);
+    let too_large = amplification
+      .is_some_and(|factor| expansion.size > (input.len() as u64).saturating_mul(factor as u64));
+    let too_many = self
+      .max_entity_expansions
+      .is_some_and(|max| expansion.references > max);
+    if too_large || too_many {
+      Err(XmlParseError::EntityLimitExceeded)
+    } else {
+      Ok(())
+    }
+  }
+
+  /// Hands the amplification limit to the parser context `ctxt`, libxml2's default without
+  /// one: a reset leaves the limit of the previous document in a pooled context.
+  #[cfg(libxml_at_least_2_11)]
+  pub(crate) fn set_max_amplification(&self, ctxt: xmlParserCtxtPtr) {
+    let factor = self.max_amplification.unwrap_or(DEFAULT_MAX_AMPLIFICATION);
+    unsafe { xmlCtxtSetMaxAmplification(ctxt, factor) };
+  }
+}
+
+impl Default for ParserOptions<'_> {
+  fn default() -> Self {
+    ParserOptions {
+      recover: true,
+      no_def_dtd: false,
+      dtd_load: false,
+      dtd_attr: false,
+      dtd_valid: false,
+      big_lines: false,
+      no_error: true,
+      no_warning: true,
+      pedantic: false,
+      no_blanks: false,
+      no_net: false,
+      no_implied: false,
+      huge: false,
+      compact: false,
+      ignore_enc: false,
+      encoding: None,
+      max_amplification: None,
+      max_entity_expansions: None,
+      decompress: true,
+      max_depth: None,
+      max_nodes: None,
+    }
+  }
+}
+
+///Parser Errors
+#[non_exhaustive]
+pub enum XmlParseError {
+  ///Parsing returned a null pointer as document pointer
+  GotNullPointer,
+  ///Could not open file error.
+  FileOpenError,
+  ///Document too large for libxml2.
+  DocumentTooLarge,
+  ///Reading the input failed.
+  ReadError(io::Error),
+  ///The file is compressed in a format this build can't decompress.
+  CompressionUnavailable(Compression),
+  ///The input is not well-formed, and recovery was disabled.
+  Malformed(StructuredError),
+  ///Expanding the entities of the input exceeds the limits of the parser options.
+  EntityLimitExceeded,
+  ///libxml2 has no converter for the requested encoding.
+  UnknownEncoding(String),
+  ///The progress callback cancelled the parse.
+  Cancelled,
+  ///libxml2 produced no document, for the problems it reported.
+  Failed(Vec<StructuredError>),
+  ///The document is nested deeper or has more nodes than the parser options allow.
+  LimitExceeded,
+}
+
+impl XmlParseError {
+  /// The problems libxml2 reported for the input, with their location, when that is why
+  /// parsing failed
+  pub fn diagnostics(&self) -> &[StructuredError] {
+    match self {
+      XmlParseError::Malformed(error) => slice::from_ref(error),
+      XmlParseError::Failed(diagnostics) => diagnostics,
+      _ => &[],
+    }
+  }
+}
+
+impl Error for XmlParseError {}
+
+impl fmt::Debug for XmlParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{self}")
+  }
+}
+
+impl fmt::Display for XmlParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      XmlParseError::GotNullPointer => write!(f, "Got a Null pointer"),
+      XmlParseError::FileOpenError => write!(f, "Unable to open path to file."),
+      XmlParseError::DocumentTooLarge => write!(f, "Document too large for i32."),
+      XmlParseError::ReadError(e) => write!(f, "Unable to read input: {e}"),
+      XmlParseError::CompressionUnavailable(compression) => write!(
+        f,
+        "The file is {compression} compressed, which needs the `{}` feature and a libxml2 built with it.",
+        compression.feature()
+      ),
+      XmlParseError::Malformed(error) => write!(
+        f,
+        "Malformed input: {}",
+        error.message.as_deref().unwrap_or("unknown error").trim_end()
+      ),
+      XmlParseError::EntityLimitExceeded => write!(f, "Entity expansion exceeds the parser limits."),
+      XmlParseError::UnknownEncoding(encoding) => write!(f, "Unknown encoding `{encoding}`."),
+      XmlParseError::Cancelled => write!(f, "Parsing was cancelled."),
+      XmlParseError::Failed(diagnostics) => {
+        let first = diagnostics.first().and_then(|error| error.message.as_deref());
+        write!(f, "Parsing failed: {}", first.unwrap_or("unknown error").trim_end())?;
+        if diagnostics.len() > 1 {
+          write!(f, " (and {} more)", diagnostics.len() - 1)?;
+        }
+        Ok(())
+      }
+      XmlParseError::LimitExceeded => write!(f, "Document exceeds the depth or node limits of the parser."),
+    }
+  }
+}
+
+/// Whether libxml2 can convert from `encoding`
+pub(crate) fn encoding_known(encoding: &str) -> bool {
+  let Ok(name) = CString::new(encoding) else {
+    return false;
+  };
+  unsafe {
+    let handler = xmlFindCharEncodingHandler(name.as_ptr());
+    if handler.is_null() {
+      return false;
+    }
+    xmlCharEncCloseFunc(handler);
+  }
+  true
+}
+
+/// Parses the DTD declarations `dtd`.
+fn parse_dtd(dtd: &str) -> Result<xmlDtdPtr, XmlParseError> {
+  let len = try_usize_to_i32(dtd.len())?;
+  unsafe {
+    xmlResetLastError();
+    let buffer = xmlParserInputBufferCreateMem(
+      dtd.as_ptr() as *const c_char,
+      len,
+      xmlCharEncoding_XML_CHAR_ENCODING_UTF8,
+    );
+    if buffer.is_null() {
+      return Err(XmlParseError::GotNullPointer);
+    }
+    // The default handler, minus the error output: the last error is returned instead
+    let mut sax: xmlSAXHandler = std::mem::zeroed();
+    xmlSAXVersion(&mut sax, 2);
+    sax.serror = Some(ignore_error);
+    // Takes ownership of the buffer
+    let dtd_ptr = xmlIOParseDTD(&mut sax, buffer, xmlCharEncoding_XML_CHAR_ENCODING_UTF8);
+    if !dtd_ptr.is_null() {
+      return Ok(dtd_ptr);
+    }
+    let error = xmlGetLastError();
+    if error.is_null() {
+      Err(XmlParseError::GotNullPointer)
+    } else {
+      Err(XmlParseError::Malformed(StructuredError::from_raw(error)))
+    }
+  }
+}
+
+/// Error callback discarding the error.
+unsafe extern "C" fn ignore_error(_ctx: *mut c_void, _error: ErrorPtr) {}
+
+/// Default encoding when not provided.
+const DEFAULT_ENCODING: *const c_char = ptr::null();
+
+/// Default URL when not provided.
+const DEFAULT_URL: *const c_char = ptr::null();
+
+/// libxml2's amplification limit, when `max_amplification` is not provided.
+#[cfg(libxml_at_least_2_11)]
+const DEFAULT_MAX_AMPLIFICATION: u32 = 5;
+
+/// Bytes read from an async reader and handed to the push parser at a time
+#[cfg(feature = "async")]
+const ASYNC_READ_SIZE: usize = 8192;
+
+/// The compression of the file `filename` going by its first bytes, if it can be read.
+fn sniff_compression(filename: &Path) -> Option<Compression> {
+  let mut magic = [0u8; 6];
+  let mut file = fs::File::open(filename).ok()?;
+  let mut read = 0;
+  while read < magic.len() {
+    match io::Read::read(&mut file, &mut magic[read..]) {
+      Ok(0) => break,
+      Ok(n) => read += n,
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+      Err(_) => return None,
+    }
+  }
+  Compression::detect(&magic[..read])
+}
+
+/// The file name handed to libxml2 for `path`: its bytes on Unix, and UTF-8 elsewhere, which
+/// libxml2 converts to the wide-character Windows APIs itself
+fn path_to_cstring(path: &Path) -> Option<CString> {
+  #[cfg(unix)]
+  let bytes = {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes()
+  };
+  #[cfg(not(unix))]
+  let bytes = path.to_str()?.as_bytes();
+  CString::new(bytes).ok()
+}
+
+/// The context of the IO callbacks: the reader, and where to report its error.
+struct IoContext<R: Read> {
+  reader: R,
+  error: *mut Option<io::Error>,
+}
+
+/// Read callback for a Rust reader.
+unsafe extern "C" fn xml_read<R: Read>(context: *mut c_void, buffer: *mut c_char, len: c_int) -> c_int { unsafe {
+  // Len is always positive, typically 40-4000 bytes.
+  let context = &mut *(context as *mut IoContext<R>);
+  let buf = slice::from_raw_parts_mut(buffer as *mut u8, len as usize);
+  loop {
+    match context.reader.read(buf) {
+      Ok(v) => return v as c_int,
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+      Err(e) => {
+        *context.error = Some(e);
+        return -1;
+      }
+    }
+  }
+}}
+
+type XmlReadCallback = unsafe extern "C" fn(*mut c_void, *mut c_char, c_int) -> c_int;
+
+/// Close callback for a Rust reader.
+unsafe extern "C" fn xml_close<R: Read>(context: *mut c_void) -> c_int { unsafe {
+  // Take rust ownership of the context and then drop it.
+  let _ = Box::from_raw(context as *mut IoContext<R>);
+  0
+}}
+
+type XmlCloseCallback = unsafe extern "C" fn(*mut c_void) -> c_int;
+
+/// The error argument of the structured error callbacks, const since libxml2 2.12.
+#[cfg(libxml_older_than_2_12)]
+pub(crate) type ErrorPtr = xmlErrorPtr;
+#[cfg(not(libxml_older_than_2_12))]
+pub(crate) type ErrorPtr = *const xmlError;
+
+/// Reports the errors met by the parser context `ctxt` to `errors`, which must outlive it.
+unsafe fn collect_parser_errors(ctxt: xmlParserCtxtPtr, errors: *mut Vec<StructuredError>) {
+  // Older releases only have the SAX handler, called with the context itself.
+  #[cfg(libxml_older_than_2_13)]
+  unsafe {
+    (*ctxt)._private = errors as *mut c_void;
+    (*(*ctxt).sax).serror = Some(collect_parser_error);
+    // which is only called for handlers marked as SAX2, those of the HTML parser aren't
+    if (*ctxt).html != 0 {
+      (*(*ctxt).sax).initialized = XML_SAX2_MAGIC;
+    }
+  }
+  #[cfg(not(libxml_older_than_2_13))]
+  unsafe {
+    xmlCtxtSetErrorHandler(ctxt, Some(collect_parser_error), errors as *mut c_void);
+  }
+}
+
+/// Error callback for `collect_parser_errors`.
+unsafe extern "C" fn collect_parser_error(ctx: *mut c_void, error: ErrorPtr) {
+  if ctx.is_null() || error.is_null() {
+    return;
+  }
+  #[cfg(libxml_older_than_2_13)]
+  let errors = unsafe { (*(ctx as xmlParserCtxtPtr))._private } as *mut Vec<StructuredError>;
+  #[cfg(not(libxml_older_than_2_13))]
+  let errors = ctx as *mut Vec<StructuredError>;
+  if !errors.is_null() {
+    unsafe { (*errors).push(StructuredError::from_raw(error)) };
+  }
+}
+
+///Convert usize to i32 safely.
+pub(crate) fn try_usize_to_i32(value: usize) -> Result<i32, XmlParseError> {
+  if cfg!(target_pointer_width = "16") || (value < i32::MAX as usize) {
+    // Cannot safely use our value comparison, but the conversion if always safe.
+    // Or, if the value can be safely represented as a 32-bit signed integer.
+    Ok(value as i32)
+  } else {
+    // Document too large, cannot parse using libxml2.
+    Err(XmlParseError::DocumentTooLarge)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Enum for the parse formats supported by libxml2
+pub enum ParseFormat {
+  /// Strict parsing for XML
+  XML,
+  /// Relaxed parsing for HTML
+  #[cfg(feature = "html")]
+  HTML,
+}
+/// Parsing API wrapper for libxml2
+pub struct Parser {
+  /// The `ParseFormat` for this parser
+  pub format: ParseFormat,
+  /// The options of the parse methods not taking any
+  options: ParserOptions<'static>,
+  /// The errors and warnings reported during the last parse
+  diagnostics: Mutex<Vec<StructuredError>>,
+}
+impl Default for Parser {
+  /// Create a parser for XML documents
+  fn default() -> Self {
+    // avoid deadlocks from using multiple parsers
+    INIT_LIBXML_PARSER.call_once(|| unsafe {
+      crate::bindings::xmlInitParser();
+    });
+    Parser {
+      format: ParseFormat::XML,
+      options: ParserOptions::default(),
+      diagnostics: Mutex::new(Vec::new()),
+    }
+  }
+}
+impl Parser {
+  /// Create a parser for HTML documents
+  #[cfg(feature = "html")]
+  pub fn default_html() -> Self {
+    Parser::with_options(ParseFormat::HTML, ParserOptions::default())
+  }
+
+  /// Create a parser for untrusted XML documents: neither external DTDs nor external
+  /// entities are loaded, network access is forbidden, libxml2's hardcoded size and depth
+  /// limits stay on (no `huge`), malformed documents fail rather than being recovered, and
+  /// documents referencing the entities their DTD declares are rejected with
+  /// [`XmlParseError::EntityLimitExceeded`], whichever parse method reads them. Other entity
+  /// references are left unexpanded in the tree.
+  pub fn secure() -> Self {
+    Parser::with_options(
+      ParseFormat::XML,
+      ParserOptions {
+        recover: false,
+        dtd_load: false,
+        dtd_attr: false,
+        dtd_valid: false,
+        no_net: true,
+        huge: false,
+        max_amplification: Some(1),
+        max_entity_expansions: Some(0),
+        ..ParserOptions::default()
+      },
+    )
+  }
+
+  /// Create a parser for documents in `format`, parsed with `options` by the methods not
+  /// taking any, e.g. an HTML parser with `no_implied` set
+  pub fn with_options(format: ParseFormat, options: ParserOptions<'static>) -> Self {
+    // avoid deadlocks from using multiple parsers
+    INIT_LIBXML_PARSER.call_once(|| unsafe {
+      crate::bindings::xmlInitParser();
+    });
+    Parser {
+      format,
+      options,
+      diagnostics: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// The options of the parse methods not taking any
+  pub fn options(&self) -> &ParserOptions<'static> {
+    &self.options
+  }
+
+  /// The errors libxml2 reported during the last parse with this parser, each with its
+  /// level, domain, code, message, file, line and column, whether the parse failed or
+  /// recovered from them
+  pub fn last_diagnostics(&self) -> Vec<StructuredError> {
+    self.reported(|level| level != XmlErrorLevel::Warning)
+  }
+
+  /// The warnings libxml2 reported during the last parse with this parser, e.g. for
+  /// namespace URIs that are not absolute, which don't keep it from succeeding. They are
+  /// collected whatever `no_warning` says, to be logged by the application.
+  pub fn last_warnings(&self) -> Vec<StructuredError> {
+    self.reported(|level| level == XmlErrorLevel::Warning)
+  }
+
+  fn reported(&self, keep: impl Fn(XmlErrorLevel) -> bool) -> Vec<StructuredError> {
+    let diagnostics = self.diagnostics.lock().unwrap_or_else(|e| e.into_inner());
+    diagnostics.iter().filter(|error| keep(error.level)).cloned().collect()
+  }
+
+  fn set_diagnostics(&self, diagnostics: Vec<StructuredError>) {
+    *self.diagnostics.lock().unwrap_or_else(|e| e.into_inner()) = diagnostics;
+  }
+
+  /// Wraps the document libxml2 parsed, or fails with the problems it reported when there is
+  /// none, keeping them for `last_diagnostics` and `last_warnings` either way
+  fn finish_parse(
+    &self,
+    doc: Result<xmlDocPtr, XmlParseError>,
+    diagnostics: Vec<StructuredError>,
+  ) -> Result<Document, XmlParseError> {
+    let result = match doc {
+      Ok(doc_ptr) if !doc_ptr.is_null() => Ok(Document::new_ptr(doc_ptr)),
+      Err(e) => Err(e),
+      Ok(_) => {
+        let errors: Vec<_> = diagnostics
+          .iter()
+          .filter(|error| error.level != XmlErrorLevel::Warning)
+          .cloned()
+          .collect();
+        Err(if errors.is_empty() {
+          XmlParseError::GotNullPointer
+        } else {
+          XmlParseError::Failed(errors)
+        })
+      }
+    };
+    self.set_diagnostics(diagnostics);
+    result
+  }
+
+  /// Parses the XML/HTML file `filename` to generate a new `Document`
+  pub fn parse_file<P: AsRef<Path>>(&self, filename: P) -> Result<Document, XmlParseError> {
+    self.parse_file_with_options(filename, self.options)
+  }
+
+  /// Parses the XML/HTML file `filename` with a manually-specified parser-options
+  /// to generate a new `Document`
+  pub fn parse_file_with_options<P: AsRef<Path>>(
+    &self,
+    filename: P,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    let filename = filename.as_ref();
+    // URIs of the registered input sources
+    if let Some(uri) = filename.to_str()
+      && let Some(reader) = input::open(uri)
+    {
+      let reader = reader.map_err(|_| XmlParseError::FileOpenError)?;
+      return self.parse_io(reader, Some(uri), parser_options);
+    }
+    // Compressed files are left to libxml2, which decompresses them while reading.
+    if parser_options.decompress && sniff_compression(filename).is_some() {
+      return self.parse_compressed_file_with_options(filename, parser_options);
+    }
+
+    let file = fs::File::open(filename).map_err(|_| XmlParseError::FileOpenError)?;
+    self.parse_reader_with_options(file, parser_options)
+  }
+
+  /// Parses the XML/HTML read from `reader` to generate a new `Document`, streaming it into
+  /// libxml2 rather than loading it all first
+  pub fn parse_reader<R: Read>(&self, reader: R) -> Result<Document, XmlParseError> {
+    self.parse_reader_with_options(reader, self.options)
+  }
+
+  /// Parses the XML/HTML read from `reader` with a manually-specified parser-options
+  /// to generate a new `Document`
+  pub fn parse_reader_with_options<R: Read>(
+    &self,
+    reader: R,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    self.parse_io(reader, None, parser_options)
+  }
+
+  fn parse_io<R: Read>(
+    &self,
+    mut reader: R,
+    url: Option<&str>,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    if !parser_options.checks_entities() {
+      return self.read_io(reader, url, parser_options);
+    }
+    // The entity limits are checked on the whole input
+    self.set_diagnostics(Vec::new());
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input).map_err(XmlParseError::ReadError)?;
+    parser_options.check_entities(&input)?;
+    self.read_io(input.as_slice(), url, parser_options)
+  }
+
+  fn read_io<R: Read>(
+    &self,
+    reader: R,
+    url: Option<&str>,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    self.set_diagnostics(Vec::new());
+    // Process encoding.
+    let encoding_cstring: Option<CString> =
+      parser_options.encoding.map(|v| CString::new(v).unwrap());
+    let encoding_ptr = match &encoding_cstring {
+      Some(v) => v.as_ptr(),
+      None => DEFAULT_ENCODING,
+    };
+
+    // Process url.
+    let url_cstring = match url {
+      Some(url) => Some(CString::new(url).map_err(|_| XmlParseError::GotNullPointer)?),
+      None => None,
+    };
+    let url_ptr = match &url_cstring {
+      Some(v) => v.as_ptr(),
+      None => DEFAULT_URL,
+    };
+
+    // Create extern C callbacks to read and close the Rust reader through
+    // a void pointer.
+    let ioread: Option<XmlReadCallback> = Some(xml_read::<R>);
+    let ioclose: Option<XmlCloseCallback> = Some(xml_close::<R>);
+    let mut read_error: Option<io::Error> = None;
+    let ioctx = Box::into_raw(Box::new(IoContext {
+      reader,
+      error: &mut read_error,
+    })) as *mut c_void;
+
+    // Blanks are kept by default, newer releases only take this from the parser options
+    #[cfg(libxml_older_than_2_13)]
+    unsafe {
+      xmlKeepBlanksDefault(1);
+    }
+
+    // Errors and warnings are reported to the diagnostics rather than printed
+    let options = ParserOptions {
+      no_error: false,
+      no_warning: false,
+      ..parser_options
+    }
+    .to_flags(&self.format);
+
+    let mut diagnostics: Vec<StructuredError> = Vec::new();
+    let format = self.format;
+    let doc = pool::with_context(format, options, |ctxt| unsafe {
+      collect_parser_errors(ctxt, &mut diagnostics);
+      #[cfg(libxml_at_least_2_11)]
+      parser_options.set_max_amplification(ctxt);
+      limits::enforce(ctxt, &parser_options, || match format {
+        ParseFormat::XML => xmlCtxtReadIO(ctxt, ioread, ioclose, ioctx, url_ptr, encoding_ptr, options),
+        #[cfg(feature = "html")]
+        ParseFormat::HTML => htmlCtxtReadIO(ctxt, ioread, ioclose, ioctx, url_ptr, encoding_ptr, options),
+      })
+    });
+    let doc = match doc {
+      Some(doc) => doc,
+      None => {
+        // The reader was never handed to libxml2, close it here
+        unsafe { xml_close::<R>(ioctx) };
+        return Err(XmlParseError::GotNullPointer);
+      }
+    };
+    // A failing reader cuts the input short, don't pass that off as the document
+    if let Some(e) = read_error {
+      if let Ok(doc_ptr) = doc
+        && !doc_ptr.is_null()
+      {
+        unsafe { xmlFreeDoc(doc_ptr) };
+      }
+      self.set_diagnostics(diagnostics);
+      return Err(XmlParseError::ReadError(e));
+    }
+    self.finish_parse(doc, diagnostics)
+  }
+
+  /// Parses the XML/HTML read from `reader` like [`Parser::parse_reader_with_options`], calling
+  /// `callback` with the number of bytes read so far every `interval` bytes. libxml2 reads
+  /// its input as it parses, so this tracks the progress of the parse; returning
+  /// [`ControlFlow::Break`] stops it with [`XmlParseError::Cancelled`], e.g. once a time
+  /// budget is spent. In-memory input can be passed as a `&[u8]` reader.
+  pub fn parse_reader_with_progress<R: Read, F: FnMut(u64) -> ControlFlow<()>>(
+    &self,
+    reader: R,
+    parser_options: ParserOptions,
+    interval: usize,
+    callback: F,
+  ) -> Result<Document, XmlParseError> {
+    let reader = progress::ProgressReader::new(reader, interval, callback);
+    match self.parse_reader_with_options(reader, parser_options) {
+      Err(XmlParseError::ReadError(e)) if progress::is_cancellation(&e) => Err(XmlParseError::Cancelled),
+      result => result,
+    }
+  }
+
+  /// Parses the XML/HTML read from the tokio `reader` to generate a new `Document`, handing
+  /// each chunk to the push parser as it arrives, so that the executor is never blocked on I/O
+  #[cfg(feature = "async")]
+  pub async fn parse_async_reader<R: AsyncRead + Unpin>(
+    &self,
+    reader: R,
+  ) -> Result<Document, XmlParseError> {
+    self
+      .parse_async_reader_with_options(reader, self.options)
+      .await
+  }
+
+  /// Parses the XML/HTML read from the tokio `reader` with a manually-specified
+  /// parser-options to generate a new `Document`. With entity, depth or node limits the
+  /// whole input is read before parsing it, as the limits are only checked that way.
+  #[cfg(feature = "async")]
+  pub async fn parse_async_reader_with_options<R: AsyncRead + Unpin>(
+    &self,
+    mut reader: R,
+    parser_options: ParserOptions<'_>,
+  ) -> Result<Document, XmlParseError> {
+    self.set_diagnostics(Vec::new());
+    // The entity limits are checked on the whole input, which the push parser never has, and
+    // the depth and node limits within a single call into libxml2, which it never makes
+    if parser_options.checks_entities()
+      || parser_options.max_depth.is_some()
+      || parser_options.max_nodes.is_some()
+    {
+      let mut input = Vec::new();
+      reader.read_to_end(&mut input).await.map_err(XmlParseError::ReadError)?;
+      return self.parse_memory(&input, None, parser_options);
+    }
+    // Declared before the parser, which reports to it until it is dropped
+    let mut diagnostics: Box<Vec<StructuredError>> = Box::default();
+    // Errors and warnings are reported to the diagnostics rather than printed
+    let options = ParserOptions {
+      no_error: false,
+      no_warning: false,
+      ..parser_options
+    };
+    let mut parser = PushParser::with_options(self.format, options)?;
+    unsafe { collect_parser_errors(parser.ctxt(), &mut *diagnostics) };
+    let mut buffer = vec![0u8; ASYNC_READ_SIZE];
+    let result = loop {
+      let read = match reader.read(&mut buffer).await {
+        Ok(read) => read,
+        Err(e) => break Err(XmlParseError::ReadError(e)),
+      };
+      if read == 0 {
+        break parser.finish();
+      }
+      if let Err(e) = parser.parse_chunk(&buffer[..read]) {
+        break Err(e);
+      }
+    };
+    self.set_diagnostics(std::mem::take(&mut *diagnostics));
+    result
+  }
+
+  /// Parses the gzip or xz compressed XML/HTML file `filename` to generate a new `Document`,
+  /// decompressing it whatever `decompress` says. Uncompressed files are parsed as they are.
+  pub fn parse_compressed_file<P: AsRef<Path>>(&self, filename: P) -> Result<Document, XmlParseError> {
+    self.parse_compressed_file_with_options(filename, self.options)
+  }
+
+  /// Parses the compressed XML/HTML file `filename` with a manually-specified parser-options
+  /// to generate a new `Document`, libxml2 doing the reading. Fails with
+  /// [`XmlParseError::CompressionUnavailable`] when this build can't decompress it.
+  pub fn parse_compressed_file_with_options<P: AsRef<Path>>(
+    &self,
+    filename: P,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    let filename = filename.as_ref();
+    if let Some(compression) = sniff_compression(filename)
+      && !compression.is_available()
+    {
+      return Err(XmlParseError::CompressionUnavailable(compression));
+    }
+    self.set_diagnostics(Vec::new());
+    let c_filename = path_to_cstring(filename).ok_or(XmlParseError::FileOpenError)?;
+    // The entity limits are checked on the whole input, decompressed beforehand
+    if parser_options.checks_entities() {
+      let input = compression::read_decompressed(&c_filename).ok_or(XmlParseError::FileOpenError)?;
+      return self.parse_memory(&input, filename.to_str(), parser_options);
+    }
+    let encoding_cstring: Option<CString> =
+      parser_options.encoding.map(|v| CString::new(v).unwrap());
+    let encoding_ptr = match &encoding_cstring {
+      Some(v) => v.as_ptr(),
+      None => DEFAULT_ENCODING,
+    };
+    let options = ParserOptions {
+      no_error: false,
+      no_warning: false,
+      ..parser_options
+    }
+    .to_flags(&self.format);
+
+    let mut diagnostics: Vec<StructuredError> = Vec::new();
+    let format = self.format;
+    let doc = pool::with_context(format, options, |ctxt| unsafe {
+      collect_parser_errors(ctxt, &mut diagnostics);
+      #[cfg(libxml_at_least_2_11)]
+      parser_options.set_max_amplification(ctxt);
+      limits::enforce(ctxt, &parser_options, || match format {
+        ParseFormat::XML => xmlCtxtReadFile(ctxt, c_filename.as_ptr(), encoding_ptr, options),
+        #[cfg(feature = "html")]
+        ParseFormat::HTML => htmlCtxtReadFile(ctxt, c_filename.as_ptr(), encoding_ptr, options),
+      })
+    })
+    .ok_or(XmlParseError::GotNullPointer)?;
+    self.finish_parse(doc, diagnostics)
+  }
+
+  ///Parses the XML/HTML bytes `input` to generate a new `Document`
+  pub fn parse_string<Bytes: AsRef<[u8]>>(&self, input: Bytes) -> Result<Document, XmlParseError> {
+    self.parse_string_with_options(input, self.options)
+  }
+
+  ///Parses the XML/HTML bytes `input` with a manually-specified
+  ///parser-options to generate a new `Document`
+  pub fn parse_string_with_options<Bytes: AsRef<[u8]>>(
+    &self,
+    input: Bytes,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    self.parse_memory(input.as_ref(), None, parser_options)
+  }
+
+  ///Parses the XML/HTML document in `data` with `parser_options`, handing the buffer to
+  ///libxml2 as is: nothing is copied or validated as UTF-8, and the encoding is taken from
+  ///`parser_options.encoding`, a byte order mark or the XML declaration, so that latin-1 or
+  ///UTF-16 input needs no transcoding first
+  pub fn parse_bytes(
+    &self,
+    data: &[u8],
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    self.parse_memory(data, None, parser_options)
+  }
+
+  ///Parses the XML/HTML document in `data` as text in `encoding`, e.g. `"ISO-8859-1"`,
+  ///whatever its byte order mark or encoding declaration claim, for documents mislabeled by
+  ///their producer. Which encodings are known depends on the iconv or ICU support of libxml2.
+  pub fn parse_bytes_with_encoding(
+    &self,
+    data: &[u8],
+    encoding: &str,
+  ) -> Result<Document, XmlParseError> {
+    // libxml2 would otherwise fall back to the declared encoding
+    if !encoding_known(encoding) {
+      return Err(XmlParseError::UnknownEncoding(encoding.to_owned()));
+    }
+    let parser_options = ParserOptions {
+      encoding: Some(encoding),
+      ignore_enc: true,
+      ..self.options
+    };
+    self.parse_memory(data, None, parser_options)
+  }
+
+  ///Parses the XML/HTML bytes `input` as the document found at `url`, against which
+  ///relative references (external entities, XIncludes, `xml:base`) are resolved
+  pub fn parse_string_with_base_url<Bytes: AsRef<[u8]>>(
+    &self,
+    input: Bytes,
+    url: &str,
+  ) -> Result<Document, XmlParseError> {
+    self.parse_memory(input.as_ref(), Some(url), self.options)
+  }
+
+  fn parse_memory(
+    &self,
+    input_bytes: &[u8],
+    url: Option<&str>,
+    parser_options: ParserOptions,
+  ) -> Result<Document, XmlParseError> {
+    self.set_diagnostics(Vec::new());
+    // Process input bytes.
+    let input_ptr = input_bytes.as_ptr() as *const c_char;
+    let input_len = try_usize_to_i32(input_bytes.len())?;
+
+    // Process encoding.
+    let encoding_cstring: Option<CString> =
+      parser_options.encoding.map(|v| CString::new(v).unwrap());
+    let encoding_ptr = match &encoding_cstring {
+      Some(v) => v.as_ptr(),
+      None => DEFAULT_ENCODING,
+    };
+
+    // Process url.
+    let url_cstring = match url {
+      Some(url) => Some(CString::new(url).map_err(|_| XmlParseError::GotNullPointer)?),
+      None => None,
+    };
+    let url_ptr = match &url_cstring {
+      Some(v) => v.as_ptr(),
+      None => DEFAULT_URL,
+    };
+
+    // Errors and warnings are reported to the diagnostics rather than printed
+    let options = ParserOptions {
+      no_error: false,
+      no_warning: false,
+      ..parser_options
+    }
+    .to_flags(&self.format);
+    parser_options.check_entities(input_bytes)?;
+
+    let mut diagnostics: Vec<StructuredError> = Vec::new();
+    // Small documents are parsed with a pooled context, its setup would dominate otherwise
+    let format = self.format;
+    let doc = pool::with_context(format, options, |ctxt| unsafe {
+      collect_parser_errors(ctxt, &mut diagnostics);
+      #[cfg(libxml_at_least_2_11)]
+      parser_options.set_max_amplification(ctxt);
+      limits::enforce(ctxt, &parser_options, || match format {
+        ParseFormat::XML => xmlCtxtReadMemory(ctxt, input_ptr, input_len, url_ptr, encoding_ptr, options),
+        #[cfg(feature = "html")]
+        ParseFormat::HTML => htmlCtxtReadMemory(ctxt, input_ptr, input_len, url_ptr, encoding_ptr, options),
+      })
+    })
+    .ok_or(XmlParseError::GotNullPointer)?;
+    self.finish_parse(doc, diagnostics)
+  }
+
+  /// Parses the XML/HTML bytes `input` in recovery mode (`XML_PARSE_RECOVER`), returning the
+  /// best-effort `Document` together with the errors met on the way, for dirty real-world
+  /// input. Only fails when nothing could be recovered at all, e.g. for empty input.
+  pub fn parse_string_recover<Bytes: AsRef<[u8]>>(
+    &self,
+    input: Bytes,
+  ) -> Result<(Document, Vec<StructuredError>), XmlParseError> {
+    let input_bytes = input.as_ref();
+    let input_ptr = input_bytes.as_ptr() as *const c_char;
+    let input_len = try_usize_to_i32(input_bytes.len())?;
+    let parser_options = ParserOptions {
+      recover: true,
+      no_error: false,
+      ..self.options
+    };
+    let options = parser_options.to_flags(&self.format);
+    self.set_diagnostics(Vec::new());
+    parser_options.check_entities(input_bytes)?;
+
+    let mut errors: Vec<StructuredError> = Vec::new();
+    let format = self.format;
+    let doc_ptr = pool::with_context(format, options, |ctxt| unsafe {
+      collect_parser_errors(ctxt, &mut errors);
+      #[cfg(libxml_at_least_2_11)]
+      parser_options.set_max_amplification(ctxt);
+      limits::enforce(ctxt, &parser_options, || match format {
+        ParseFormat::XML => xmlCtxtReadMemory(ctxt, input_ptr, input_len, DEFAULT_URL, DEFAULT_ENCODING, options),
+        #[cfg(feature = "html")]
+        ParseFormat::HTML => htmlCtxtReadMemory(ctxt, input_ptr, input_len, DEFAULT_URL, DEFAULT_ENCODING, options),
+      })
+    })
+    .ok_or(XmlParseError::GotNullPointer)?;
+    self.set_diagnostics(errors.clone());
+    let doc_ptr = doc_ptr?;
+    if doc_ptr.is_null() {
+      Err(XmlParseError::GotNullPointer)
+    } else {
+      Ok((Document::new_ptr(doc_ptr), errors))
+    }
+  }
+
+  /// Parses the XML bytes `input` and validates it while parsing, returning the `Document`
+  /// together with the validity errors found and the well-formedness errors recovered from.
+  ///
+  /// Without `dtd`, the document is validated against the DTD of its doctype, loaded as for
+  /// [`ParserOptions::dtd_valid`]. Otherwise `dtd` holds the declarations to validate against,
+  /// whatever the document declares, and its default attributes are not added. The document
+  /// is always parsed as XML, and errors are reported even with `no_error`.
+  pub fn parse_string_validating<Bytes: AsRef<[u8]>>(
+    &self,
+    input: Bytes,
+    dtd: Option<&str>,
+    parser_options: ParserOptions,
+  ) -> Result<(Document, Vec<StructuredError>), XmlParseError> {
+    let input_bytes = input.as_ref();
+    let input_ptr = input_bytes.as_ptr() as *const c_char;
+    let input_len = try_usize_to_i32(input_bytes.len())?;
+    let options = ParserOptions {
+      dtd_valid: parser_options.dtd_valid || dtd.is_none(),
+      no_error: false,
+      ..parser_options
+    };
+    options.check_entities(input_bytes)?;
+    let external_dtd = match dtd {
+      Some(dtd) => Some(parse_dtd(dtd)?),
+      None => None,
+    };
+
+    let mut errors: Vec<StructuredError> = Vec::new();
+    // Not pooled: validating against `dtd` leaves the validity state of its document behind
+    // in the context
+    let doc_ptr = unsafe {
+      let ctxt = xmlNewParserCtxt();
+      if ctxt.is_null() {
+        if let Some(dtd) = external_dtd {
+          xmlFreeDtd(dtd);
+        }
+        return Err(XmlParseError::GotNullPointer);
+      }
+      collect_parser_errors(ctxt, &mut errors);
+      #[cfg(libxml_at_least_2_11)]
+      options.set_max_amplification(ctxt);
+      let doc = limits::enforce(ctxt, &options, || {
+        xmlCtxtReadMemory(
+          ctxt,
+          input_ptr,
+          input_len,
+          DEFAULT_URL,
+          DEFAULT_ENCODING,
+          options.to_flags(&ParseFormat::XML),
+        )
+      });
+      if let Some(dtd) = external_dtd {
+        // Validity errors go through the context like those found while parsing
+        if let Ok(doc_ptr) = doc
+          && !doc_ptr.is_null()
+        {
+          xmlValidateDtd(&mut (*ctxt).vctxt, doc_ptr, dtd);
+        }
+        xmlFreeDtd(dtd);
+      }
+      xmlFreeParserCtxt(ctxt);
+      doc
+    };
+    self.set_diagnostics(errors.clone());
+    let doc_ptr = doc_ptr?;
+    if doc_ptr.is_null() {
+      Err(match errors.into_iter().next() {
+        Some(error) => XmlParseError::Malformed(error),
+        None => XmlParseError::GotNullPointer,
+      })
+    } else {
+      Ok((Document::new_ptr(doc_ptr), errors))
+    }
+  }
+
+  /// Checks a string for well-formedness.
+  #[cfg(feature = "html")]
+  pub fn is_well_formed_html<Bytes: AsRef<[u8]>>(&self, input: Bytes) -> bool {
+    self.is_well_formed_html_with_encoding(input, None)
+  }
+
+  /// Checks a string for well-formedness with manually-specified encoding.
+  /// IMPORTANT: This function is currently implemented in a HACKY way, to ignore invalid errors for HTML5 elements (such as <math>)
+  ///            this means you should NEVER USE IT WHILE THREADING, it is CERTAIN TO BREAK
+  ///
+  /// Help is welcome in implementing it correctly.
+  #[cfg(feature = "html")]
+  pub fn is_well_formed_html_with_encoding<Bytes: AsRef<[u8]>>(
+    &self,
+    input: Bytes,
+    encoding: Option<&str>,
+  ) -> bool {
+    // Process input string.
+    let input_bytes = input.as_ref();
+    if input_bytes.is_empty() {
+      return false;
+    }
+    let input_ptr = input_bytes.as_ptr() as *const c_char;
+    let input_len = match try_usize_to_i32(input_bytes.len()) {
+      Ok(v) => v,
+      Err(_) => return false,
+    };
+
+    // Process encoding.
+    let encoding_cstring: Option<CString> = encoding.map(|v| CString::new(v).unwrap());
+    let encoding_ptr = match &encoding_cstring {
+      Some(v) => v.as_ptr(),
+      None => DEFAULT_ENCODING,
+    };
+
+    // Process url.
+    let url_ptr = DEFAULT_URL;
+    // disable generic error lines from libxml2
+    match self.format {
+      ParseFormat::XML => false, // TODO: Add support for XML at some point
+      ParseFormat::HTML => unsafe {
+        let ctxt = htmlNewParserCtxt();
+        setWellFormednessHandler(ctxt);
+        let docptr = htmlCtxtReadMemory(ctxt, input_ptr, input_len, url_ptr, encoding_ptr, 10_596); // htmlParserOption = 4+32+64+256+2048+8192
+        let well_formed_final = if htmlWellFormed(ctxt) {
+          // Basic well-formedness passes, let's check if we have an <html> element as root too
+          if !docptr.is_null() {
+            let node_ptr = xmlDocGetRootElement(docptr);
+            if node_ptr.is_null() {
+              return false
+            }
+            let name_ptr = xmlNodeGetName(node_ptr);
+            if name_ptr.is_null() {
+              false
+            }
+            //empty string
+            else {
+              let c_root_name = CStr::from_ptr(name_ptr);
+              let root_name = str::from_utf8(c_root_name.to_bytes()).unwrap().to_owned();
+              root_name == "html"
+            }
+          } else {
+            false
+          }
+        } else {
+          false
+        };
+
+        if !ctxt.is_null() {
+          htmlFreeParserCtxt(ctxt);
+        }
+        if !docptr.is_null() {
+          xmlFreeDoc(docptr);
+        }
+        well_formed_final
+      },
+    }
+  }
+}
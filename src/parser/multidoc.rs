@@ -0,0 +1,191 @@
+//! Parsing of streams of concatenated XML documents
+//!
+use std::io::{self, Read};
+
+use crate::parser::{Parser, ParserOptions, XmlParseError};
+use crate::tree::Document;
+
+/// Bytes requested from the reader at a time
+const READ_SIZE: usize = 8192;
+
+/// Reads XML documents written back to back into a single stream, as done by log shipping and
+/// message framing formats, yielding one `Document` at a time.
+///
+/// A document ends with the end tag of its root element; the XML declaration, doctype,
+/// comments and processing instructions following it belong to the next document. An XML
+/// declaration met inside an unfinished document starts a new one, so a truncated document
+/// only spoils itself: it is parsed on its own (with the usual recovery, unless disabled in the
+/// options) and reading resumes at the declaration. Input left at the end of the stream
+/// without any element is ignored.
+///
+/// ```
+/// use libxml::parser::MultiDocParser;
+///
+/// let stream = "<?xml version=\"1.0\"?><a/>\n<?xml version=\"1.0\"?><b>text</b>";
+/// let names: Vec<String> = MultiDocParser::new(stream.as_bytes())
+///   .map(|doc| doc.unwrap().get_root_element().unwrap().get_name())
+///   .collect();
+/// assert_eq!(names, ["a", "b"]);
+/// ```
+pub struct MultiDocParser<'a, R: Read> {
+  reader: R,
+  options: ParserOptions<'a>,
+  buffer: Vec<u8>,
+  scanner: Scanner,
+  eof: bool,
+}
+
+impl<R: Read> MultiDocParser<'_, R> {
+  /// Reads documents from `reader` with the default parser options
+  pub fn new(reader: R) -> Self {
+    MultiDocParser::with_options(reader, ParserOptions::default())
+  }
+}
+
+impl<'a, R: Read> MultiDocParser<'a, R> {
+  /// Reads documents from `reader`, parsing each with `options`
+  pub fn with_options(reader: R, options: ParserOptions<'a>) -> Self {
+    MultiDocParser {
+      reader,
+      options,
+      buffer: Vec::new(),
+      scanner: Scanner::default(),
+      eof: false,
+    }
+  }
+
+  /// Returns the underlying reader
+  pub fn into_inner(self) -> R {
+    self.reader
+  }
+
+  fn parse(&self, end: usize) -> Result<Document, XmlParseError> {
+    // The XML declaration must come first, drop the whitespace separating documents
+    let start = self.buffer[..end]
+      .iter()
+      .position(|b| !b.is_ascii_whitespace())
+      .unwrap_or(end);
+    Parser::default().parse_string_with_options(&self.buffer[start..end], self.options)
+  }
+}
+
+impl<R: Read> Iterator for MultiDocParser<'_, R> {
+  type Item = Result<Document, XmlParseError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      if let Some(end) = self.scanner.scan(&self.buffer) {
+        let result = self.parse(end);
+        self.buffer.drain(..end);
+        self.scanner = Scanner::default();
+        return Some(result);
+      }
+      if self.eof {
+        let has_root = self.scanner.seen_root;
+        let end = self.buffer.len();
+        let result = has_root.then(|| self.parse(end));
+        self.buffer.clear();
+        self.scanner = Scanner::default();
+        return result;
+      }
+
+      let start = self.buffer.len();
+      self.buffer.resize(start + READ_SIZE, 0);
+      match self.reader.read(&mut self.buffer[start..]) {
+        Ok(read) => {
+          self.buffer.truncate(start + read);
+          self.eof = read == 0;
+        }
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => self.buffer.truncate(start),
+        Err(e) => {
+          self.buffer.truncate(start);
+          self.eof = true;
+          return Some(Err(XmlParseError::ReadError(e)));
+        }
+      }
+    }
+  }
+}
+
+/// Finds where the first document of a buffer ends, picking up where the previous scan of
+/// the same (grown) buffer stopped
+#[derive(Default)]
+struct Scanner {
+  /// Offset of the first markup construct not scanned yet
+  position: usize,
+  /// Open elements
+  depth: usize,
+  /// Whether the root element has started
+  seen_root: bool,
+}
+
+impl Scanner {
+  /// The offset at which the first document ends, `None` when more input is needed
+  fn scan(&mut self, buffer: &[u8]) -> Option<usize> {
+    while let Some(offset) = buffer[self.position..].iter().position(|&b| b == b'<') {
+      let start = self.position + offset;
+      let rest = &buffer[start..];
+      let end = if rest.starts_with(b"<?") {
+        let end = find(rest, b"?>")? + 2;
+        let declaration = rest.starts_with(b"<?xml")
+          && rest.get(5).is_some_and(|b| b.is_ascii_whitespace() || *b == b'?');
+        if declaration && self.seen_root {
+          return Some(start);
+        }
+        end
+      } else if rest.starts_with(b"<!--") {
+        find(rest, b"-->")? + 3
+      } else if rest.starts_with(b"<![CDATA[") {
+        find(rest, b"]]>")? + 3
+      } else if rest.starts_with(b"<!") {
+        markup_end(rest)?
+      } else if rest.starts_with(b"</") {
+        let end = find(rest, b">")? + 1;
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 && self.seen_root {
+          return Some(start + end);
+        }
+        end
+      } else {
+        let end = markup_end(rest)?;
+        self.seen_root = true;
+        if rest[..end].ends_with(b"/>") {
+          if self.depth == 0 {
+            return Some(start + end);
+          }
+        } else {
+          self.depth += 1;
+        }
+        end
+      };
+      self.position = start + end;
+    }
+    self.position = buffer.len();
+    None
+  }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window == needle)
+}
+
+/// The length of a start tag or doctype up to its closing `>`, skipping quoted values and
+/// internal subsets
+fn markup_end(markup: &[u8]) -> Option<usize> {
+  let mut quote = None;
+  let mut brackets = 0usize;
+  for (i, &b) in markup.iter().enumerate().skip(1) {
+    match (quote, b) {
+      (Some(q), _) if b == q => quote = None,
+      (Some(_), _) => {}
+      (None, b'"') | (None, b'\'') => quote = Some(b),
+      (None, b'[') => brackets += 1,
+      (None, b']') => brackets = brackets.saturating_sub(1),
+      (None, b'>') if brackets == 0 => return Some(i + 1),
+      _ => {}
+    }
+  }
+  None
+}
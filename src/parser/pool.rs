@@ -0,0 +1,103 @@
+//! Parser contexts kept between the documents parsed on a thread
+//!
+//! Setting up a context costs more than parsing a small document, so each thread keeps the
+//! contexts it used last and resets one for the next document instead. Parser options leave
+//! state behind in a context that a reset doesn't clear, so contexts are only reused for the
+//! same format and options.
+//!
+use std::cell::RefCell;
+
+use crate::bindings::*;
+use crate::parser::ParseFormat;
+
+/// Documents parsed with a context before it is replaced, so that its dictionary of names
+/// doesn't grow without bound
+const MAX_USES: usize = 1024;
+
+/// Contexts kept per thread
+const MAX_POOLED: usize = 4;
+
+/// A parser context, what it parses, and the number of documents it parsed
+struct Pooled {
+  ctxt: xmlParserCtxtPtr,
+  format: ParseFormat,
+  options: i32,
+  uses: usize,
+}
+
+impl Drop for Pooled {
+  fn drop(&mut self) {
+    free_context(self.format, self.ctxt);
+  }
+}
+
+thread_local! {
+  static POOL: RefCell<Vec<Pooled>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Calls `parse` with a parser context for `format` and `options`, reused from a previous
+/// call on this thread if possible, or returns `None` if no context could be allocated. The
+/// context is taken out of the pool meanwhile, so that `parse` may parse other documents.
+pub(crate) fn with_context<T>(
+  format: ParseFormat,
+  options: i32,
+  parse: impl FnOnce(xmlParserCtxtPtr) -> T,
+) -> Option<T> {
+  let pooled = POOL
+    .try_with(|pool| {
+      let mut pool = pool.borrow_mut();
+      let index = pool
+        .iter()
+        .position(|pooled| pooled.format == format && pooled.options == options)?;
+      Some(pool.remove(index))
+    })
+    .ok()
+    .flatten();
+  let mut pooled = match pooled {
+    Some(pooled) => pooled,
+    None => Pooled {
+      ctxt: new_context(format)?,
+      format,
+      options,
+      uses: 0,
+    },
+  };
+
+  let result = parse(pooled.ctxt);
+
+  pooled.uses += 1;
+  if pooled.uses < MAX_USES {
+    // Whatever is left when the thread goes away is freed then
+    let _ = POOL.try_with(|pool| {
+      let mut pool = pool.borrow_mut();
+      if pool.len() == MAX_POOLED {
+        pool.remove(0);
+      }
+      pool.push(pooled);
+    });
+  }
+  Some(result)
+}
+
+/// A new parser context for `format`, to be freed with `free_context`
+pub(crate) fn new_context(format: ParseFormat) -> Option<xmlParserCtxtPtr> {
+  let ctxt = unsafe {
+    match format {
+      ParseFormat::XML => xmlNewParserCtxt(),
+      #[cfg(feature = "html")]
+      ParseFormat::HTML => htmlNewParserCtxt(),
+    }
+  };
+  (!ctxt.is_null()).then_some(ctxt)
+}
+
+/// Frees a parser context created with `new_context`
+pub(crate) fn free_context(format: ParseFormat, ctxt: xmlParserCtxtPtr) {
+  unsafe {
+    match format {
+      ParseFormat::XML => xmlFreeParserCtxt(ctxt),
+      #[cfg(feature = "html")]
+      ParseFormat::HTML => htmlFreeParserCtxt(ctxt),
+    }
+  }
+}
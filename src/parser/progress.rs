@@ -0,0 +1,62 @@
+//! Progress reports while reading the input, which may cancel the parse
+//!
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::ops::ControlFlow;
+
+/// The read error cancelling a parse, told apart from the errors of the reader
+#[derive(Debug)]
+struct Cancellation;
+
+impl fmt::Display for Cancellation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "parse cancelled")
+  }
+}
+
+impl Error for Cancellation {}
+
+/// Whether the read error `error` is a cancellation
+pub(crate) fn is_cancellation(error: &io::Error) -> bool {
+  error.get_ref().is_some_and(|error| error.is::<Cancellation>())
+}
+
+/// Reads from `reader`, reporting the number of bytes read to `callback` every `interval`
+/// bytes, and failing with a cancellation once it breaks
+pub(crate) struct ProgressReader<R, F> {
+  reader: R,
+  callback: F,
+  interval: u64,
+  read: u64,
+  next_report: u64,
+}
+
+impl<R: Read, F: FnMut(u64) -> ControlFlow<()>> ProgressReader<R, F> {
+  pub(crate) fn new(reader: R, interval: usize, callback: F) -> Self {
+    let interval = interval.max(1) as u64;
+    ProgressReader {
+      reader,
+      callback,
+      interval,
+      read: 0,
+      next_report: interval,
+    }
+  }
+}
+
+impl<R: Read, F: FnMut(u64) -> ControlFlow<()>> Read for ProgressReader<R, F> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    // Stops short of the next report, so that the callback sees every interval
+    let until_report = (self.next_report - self.read).min(buf.len() as u64) as usize;
+    let read = self.reader.read(&mut buf[..until_report])?;
+    self.read += read as u64;
+    if self.read == self.next_report {
+      self.next_report += self.interval;
+      if (self.callback)(self.read).is_break() {
+        return Err(io::Error::other(Cancellation));
+      }
+    }
+    Ok(read)
+  }
+}
@@ -0,0 +1,219 @@
+//! Incremental parsing of input arriving in chunks
+//!
+use std::ffi::CString;
+use std::io;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::bindings::*;
+use crate::error::StructuredError;
+use crate::parser::{ParseFormat, Parser, ParserOptions, XmlParseError, try_usize_to_i32};
+use crate::readonly::RoNode;
+use crate::tree::Document;
+
+/// Parses a document fed in chunks as they arrive, e.g. from a network socket, with
+/// libxml2's push parser, instead of buffering the whole payload first.
+///
+/// Chunks may split the input anywhere, even inside a multi-byte character.
+/// [`PushParser::finish`] parses what remains and returns the document.
+///
+/// ```
+/// use libxml::parser::PushParser;
+///
+/// let mut parser = PushParser::new().unwrap();
+/// for chunk in ["<list><it", "em>one</item>", "</list>"] {
+///   parser.parse_chunk(chunk.as_bytes()).unwrap();
+/// }
+/// let doc = parser.finish().unwrap();
+/// assert_eq!(doc.get_root_element().unwrap().get_child_elements().len(), 1);
+/// ```
+///
+/// `PushParser` also implements [`io::Write`], to be filled with [`io::copy`].
+pub struct PushParser {
+  ctxt: xmlParserCtxtPtr,
+  format: ParseFormat,
+  recover: bool,
+}
+
+// The context is only ever used by its owner, and libxml2 allows it to move between threads
+unsafe impl Send for PushParser {}
+
+impl PushParser {
+  /// Creates a push parser for XML with the default parser options
+  pub fn new() -> Result<Self, XmlParseError> {
+    PushParser::with_options(ParseFormat::XML, ParserOptions::default())
+  }
+
+  /// Creates a push parser for HTML with the default parser options, e.g. for an HTTP
+  /// response body read as it arrives
+  #[cfg(feature = "html")]
+  pub fn new_html() -> Result<Self, XmlParseError> {
+    PushParser::with_options(ParseFormat::HTML, ParserOptions::default())
+  }
+
+  /// Creates a push parser for documents in `format`, parsed with `options`
+  pub fn with_options(format: ParseFormat, options: ParserOptions) -> Result<Self, XmlParseError> {
+    // Initializes libxml2 like the other parsers
+    let _ = Parser::default();
+    let flags = options.to_flags(&format);
+    let ctxt = unsafe {
+      match format {
+        ParseFormat::XML => {
+          let ctxt = xmlCreatePushParserCtxt(ptr::null_mut(), ptr::null_mut(), ptr::null(), 0, ptr::null());
+          if !ctxt.is_null() {
+            xmlCtxtUseOptions(ctxt, flags);
+            #[cfg(libxml_at_least_2_11)]
+            options.set_max_amplification(ctxt);
+          }
+          ctxt
+        }
+        #[cfg(feature = "html")]
+        ParseFormat::HTML => {
+          let ctxt = htmlCreatePushParserCtxt(
+            ptr::null_mut(),
+            ptr::null_mut(),
+            ptr::null(),
+            0,
+            ptr::null(),
+            xmlCharEncoding_XML_CHAR_ENCODING_NONE,
+          );
+          if !ctxt.is_null() {
+            htmlCtxtUseOptions(ctxt, flags);
+          }
+          ctxt
+        }
+      }
+    };
+    if ctxt.is_null() {
+      return Err(XmlParseError::GotNullPointer);
+    }
+    let parser = PushParser {
+      ctxt,
+      format,
+      recover: options.recover,
+    };
+    if let Some(encoding) = options.encoding {
+      switch_encoding(parser.ctxt, encoding)?;
+    }
+    Ok(parser)
+  }
+
+  /// Parses the next `chunk` of the input. Without recovery, fails once the input turned
+  /// out not to be well-formed, after which further chunks are ignored.
+  pub fn parse_chunk(&mut self, chunk: &[u8]) -> Result<(), XmlParseError> {
+    self.push(chunk, false)
+  }
+
+  /// The root element of what was parsed so far, to look for a target element and stop
+  /// feeding chunks once it was seen. The nodes stay valid in the document [`PushParser::finish`]
+  /// returns, and are freed with the parser if it is dropped instead.
+  pub fn get_root_readonly(&self) -> Option<RoNode> {
+    unsafe {
+      let doc_ptr = (*self.ctxt).myDoc;
+      if doc_ptr.is_null() {
+        return None;
+      }
+      let node_ptr = xmlDocGetRootElement(doc_ptr);
+      if node_ptr.is_null() {
+        None
+      } else {
+        Some(RoNode(node_ptr))
+      }
+    }
+  }
+
+  /// Parses the end of the input and returns the document
+  pub fn finish(mut self) -> Result<Document, XmlParseError> {
+    self.push(&[], true)?;
+    unsafe {
+      let docptr = (*self.ctxt).myDoc;
+      (*self.ctxt).myDoc = ptr::null_mut();
+      if docptr.is_null() {
+        Err(XmlParseError::GotNullPointer)
+      } else if (*self.ctxt).wellFormed == 0 && !self.recover {
+        xmlFreeDoc(docptr);
+        Err(self.last_error())
+      } else {
+        Ok(Document::new_ptr(docptr))
+      }
+    }
+  }
+
+  fn push(&mut self, chunk: &[u8], terminate: bool) -> Result<(), XmlParseError> {
+    let len = try_usize_to_i32(chunk.len())?;
+    let chunk_ptr = chunk.as_ptr() as *const c_char;
+    let status = unsafe {
+      match self.format {
+        ParseFormat::XML => xmlParseChunk(self.ctxt, chunk_ptr, len, terminate as i32),
+        #[cfg(feature = "html")]
+        ParseFormat::HTML => htmlParseChunk(self.ctxt, chunk_ptr, len, terminate as i32),
+      }
+    };
+    if status != 0 && !self.recover {
+      Err(self.last_error())
+    } else {
+      Ok(())
+    }
+  }
+
+  /// The parser context, to be configured further before parsing
+  #[cfg(feature = "async")]
+  pub(crate) fn ctxt(&self) -> xmlParserCtxtPtr {
+    self.ctxt
+  }
+
+  fn last_error(&self) -> XmlParseError {
+    last_error(self.ctxt)
+  }
+}
+
+/// Makes the push parser context `ctxt` decode its input from `encoding`; an unknown encoding
+/// fails with [`XmlParseError::UnknownEncoding`].
+pub(crate) fn switch_encoding(ctxt: xmlParserCtxtPtr, encoding: &str) -> Result<(), XmlParseError> {
+  let unknown = || XmlParseError::UnknownEncoding(encoding.to_owned());
+  let name = CString::new(encoding).map_err(|_| unknown())?;
+  unsafe {
+    let handler = xmlFindCharEncodingHandler(name.as_ptr());
+    if handler.is_null() || xmlSwitchToEncoding(ctxt, handler) != 0 {
+      return Err(unknown());
+    }
+  }
+  Ok(())
+}
+
+/// The last error met by the parser context `ctxt`.
+pub(crate) fn last_error(ctxt: xmlParserCtxtPtr) -> XmlParseError {
+  let error = unsafe { xmlCtxtGetLastError(ctxt as *mut _) };
+  if error.is_null() {
+    XmlParseError::GotNullPointer
+  } else {
+    XmlParseError::Malformed(unsafe { StructuredError::from_raw(error) })
+  }
+}
+
+impl io::Write for PushParser {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.parse_chunk(buf).map_err(io::Error::other)?;
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Drop for PushParser {
+  fn drop(&mut self) {
+    unsafe {
+      let docptr = (*self.ctxt).myDoc;
+      if !docptr.is_null() {
+        xmlFreeDoc(docptr);
+      }
+      match self.format {
+        ParseFormat::XML => xmlFreeParserCtxt(self.ctxt),
+        #[cfg(feature = "html")]
+        ParseFormat::HTML => htmlFreeParserCtxt(self.ctxt),
+      }
+    }
+  }
+}
@@ -0,0 +1,370 @@
+//! Bridge between the libxml2 DOM and `quick-xml` events
+//!
+//! Enabled with the `quick-xml` cargo feature. A `Document` (or a single `Node`) can be
+//! flattened into a stream of owned [`Event`]s, and a `Document` can be built from a
+//! [`Reader`], which makes it possible to mix streaming and DOM code in one pipeline.
+//!
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::io::BufRead;
+use std::os::raw::{c_char, c_void};
+
+use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesPI, BytesRef, BytesStart, BytesText, Event};
+use quick_xml::Reader;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::parser::try_usize_to_i32;
+use crate::tree::{Document, Namespace, Node, NodeType};
+
+/// Errors raised while building a `Document` from `quick-xml` events
+#[derive(Debug)]
+pub enum QuickXmlError {
+  /// The underlying `quick-xml` reader failed
+  Reader(quick_xml::Error),
+  /// An event contained bytes that were not valid UTF-8
+  Encoding(String),
+  /// An end tag did not match the currently open element
+  UnbalancedEnd(String),
+  /// Character data other than whitespace outside of the root element
+  TextOutsideRoot(String),
+  /// libxml2 refused to create or attach a node
+  Tree(String),
+}
+
+impl Error for QuickXmlError {}
+
+impl fmt::Display for QuickXmlError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      QuickXmlError::Reader(e) => write!(f, "quick-xml reader error: {e}"),
+      QuickXmlError::Encoding(e) => write!(f, "invalid UTF-8 in event: {e}"),
+      QuickXmlError::UnbalancedEnd(name) => write!(f, "unexpected end tag </{name}>"),
+      QuickXmlError::TextOutsideRoot(text) => write!(f, "text {text:?} outside of the root element"),
+      QuickXmlError::Tree(e) => write!(f, "failed to build tree: {e}"),
+    }
+  }
+}
+
+impl From<quick_xml::Error> for QuickXmlError {
+  fn from(e: quick_xml::Error) -> Self {
+    QuickXmlError::Reader(e)
+  }
+}
+
+/// Flattens a whole `Document` into `quick-xml` events, starting with the XML declaration
+pub fn document_to_events(doc: &Document) -> Vec<Event<'static>> {
+  let doc_ptr = doc.doc_ptr();
+  let mut events = Vec::new();
+  unsafe {
+    let version = xml_str((*doc_ptr).version).unwrap_or_else(|| "1.0".to_string());
+    let encoding = xml_str((*doc_ptr).encoding);
+    events.push(Event::Decl(BytesDecl::new(&version, encoding.as_deref(), None)));
+    let mut child = (*doc_ptr).children;
+    while !child.is_null() {
+      push_node_events(child, &mut events);
+      child = xmlNextSibling(child);
+    }
+  }
+  events.push(Event::Eof);
+  events
+}
+
+/// Flattens a `Node` and its descendants into `quick-xml` events
+pub fn node_to_events(node: &Node) -> Vec<Event<'static>> {
+  let mut events = Vec::new();
+  push_node_events(node.node_ptr(), &mut events);
+  events
+}
+
+fn push_node_events(node_ptr: xmlNodePtr, events: &mut Vec<Event<'static>>) {
+  match NodeType::from_int(xmlGetNodeType(node_ptr)) {
+    Some(NodeType::ElementNode) => {
+      let name = qualified_name(node_ptr);
+      let mut start = BytesStart::new(name.clone());
+      let mut ns_ptr = xmlNodeNsDeclarations(node_ptr);
+      while !ns_ptr.is_null() {
+        let ns = Namespace { ns_ptr };
+        let prefix = ns.get_prefix();
+        let key = if prefix.is_empty() {
+          "xmlns".to_string()
+        } else {
+          format!("xmlns:{prefix}")
+        };
+        start.push_attribute((key.as_str(), ns.get_href().as_str()));
+        ns_ptr = xmlNextNsSibling(ns_ptr);
+      }
+      let mut attr_ptr = xmlGetFirstProperty(node_ptr);
+      while !attr_ptr.is_null() {
+        let key = qualified_name(attr_ptr as xmlNodePtr);
+        let value = node_content(attr_ptr as xmlNodePtr);
+        start.push_attribute((key.as_str(), value.as_str()));
+        attr_ptr = xmlNextPropertySibling(attr_ptr);
+      }
+
+      let mut child = xmlGetFirstChild(node_ptr);
+      if child.is_null() {
+        events.push(Event::Empty(start));
+      } else {
+        events.push(Event::Start(start));
+        while !child.is_null() {
+          push_node_events(child, events);
+          child = xmlNextSibling(child);
+        }
+        events.push(Event::End(BytesEnd::new(name)));
+      }
+    }
+    Some(NodeType::TextNode) => {
+      events.push(Event::Text(BytesText::new(&node_content(node_ptr)).into_owned()));
+    }
+    Some(NodeType::CDataSectionNode) => {
+      events.push(Event::CData(BytesCData::new(node_content(node_ptr))));
+    }
+    Some(NodeType::CommentNode) => {
+      events.push(Event::Comment(BytesText::from_escaped(node_content(node_ptr))));
+    }
+    Some(NodeType::PiNode) => {
+      let target = node_name(node_ptr);
+      let data = node_content(node_ptr);
+      let pi = if data.is_empty() {
+        target
+      } else {
+        format!("{target} {data}")
+      };
+      events.push(Event::PI(BytesPI::new(pi)));
+    }
+    Some(NodeType::EntityRefNode) => {
+      events.push(Event::GeneralRef(BytesRef::new(node_name(node_ptr))));
+    }
+    Some(NodeType::DTDNode) => {
+      let dtd = node_ptr as xmlDtdPtr;
+      let mut doctype = node_name(node_ptr);
+      unsafe {
+        let public_id = xml_str((*dtd).ExternalID);
+        let system_id = xml_str((*dtd).SystemID);
+        match (public_id, system_id) {
+          (Some(public_id), Some(system_id)) => {
+            doctype.push_str(&format!(" PUBLIC \"{public_id}\" \"{system_id}\""))
+          }
+          (None, Some(system_id)) => doctype.push_str(&format!(" SYSTEM \"{system_id}\"")),
+          _ => {}
+        }
+      }
+      events.push(Event::DocType(BytesText::from_escaped(doctype)));
+    }
+    _ => {}
+  }
+}
+
+/// Builds a new `Document` from the events produced by a `quick-xml` reader.
+///
+/// The XML declaration and any DOCTYPE are not carried over; entity references other than
+/// the predefined ones and character references are kept as entity reference nodes.
+pub fn document_from_reader<R: BufRead>(reader: &mut Reader<R>) -> Result<Document, QuickXmlError> {
+  let mut doc = Document::new().map_err(|_| QuickXmlError::Tree("xmlNewDoc returned NULL".into()))?;
+  let mut stack: Vec<Node> = Vec::new();
+  let mut buf = Vec::new();
+
+  loop {
+    match reader.read_event_into(&mut buf)? {
+      Event::Start(start) => {
+        let node = open_element(&mut doc, stack.last_mut(), &start)?;
+        stack.push(node);
+      }
+      Event::Empty(start) => {
+        open_element(&mut doc, stack.last_mut(), &start)?;
+      }
+      Event::End(end) => {
+        let name = utf8(end.name().as_ref())?;
+        match stack.pop() {
+          Some(node) if qualified_name(node.node_ptr()) == name => {}
+          _ => return Err(QuickXmlError::UnbalancedEnd(name)),
+        }
+      }
+      Event::Text(text) => {
+        let content = text
+          .decode()
+          .map_err(|e| QuickXmlError::Encoding(e.to_string()))?;
+        append_text(stack.last_mut(), &content)?;
+      }
+      Event::GeneralRef(reference) => {
+        let name = utf8(&reference)?;
+        if let Some(ch) = reference.resolve_char_ref()? {
+          append_text(stack.last_mut(), ch.encode_utf8(&mut [0; 4]))?;
+        } else if let Some(resolved) = quick_xml::escape::resolve_predefined_entity(&name) {
+          append_text(stack.last_mut(), resolved)?;
+        } else {
+          let c_name = c_string(name)?;
+          let node_ptr = unsafe { xmlNewReference(doc.doc_ptr(), c_name.as_bytes().as_ptr()) };
+          append_node(&doc, stack.last_mut(), node_ptr)?;
+        }
+      }
+      Event::CData(cdata) => {
+        let content = utf8(&cdata)?;
+        let len = try_usize_to_i32(content.len()).map_err(|e| QuickXmlError::Tree(e.to_string()))?;
+        let node_ptr = unsafe { xmlNewCDataBlock(doc.doc_ptr(), content.as_ptr(), len) };
+        append_node(&doc, stack.last_mut(), node_ptr)?;
+      }
+      Event::Comment(comment) => {
+        let c_content = c_string(utf8(&comment)?)?;
+        let node_ptr = unsafe { xmlNewDocComment(doc.doc_ptr(), c_content.as_bytes().as_ptr()) };
+        append_node(&doc, stack.last_mut(), node_ptr)?;
+      }
+      Event::PI(pi) => {
+        let target = utf8(pi.target())?;
+        let content = utf8(pi.content())?;
+        let node = doc
+          .create_processing_instruction(&target, content.trim_start())
+          .map_err(|_| QuickXmlError::Tree("xmlNewDocPI returned NULL".into()))?;
+        append_node(&doc, stack.last_mut(), node.node_ptr())?;
+        node.set_linked();
+      }
+      Event::Decl(_) | Event::DocType(_) => {}
+      Event::Eof => break,
+    }
+    buf.clear();
+  }
+
+  if let Some(node) = stack.pop() {
+    return Err(QuickXmlError::Tree(format!(
+      "unclosed element <{}> at end of input",
+      node.get_name()
+    )));
+  }
+  Ok(doc)
+}
+
+fn open_element(
+  doc: &mut Document,
+  parent: Option<&mut Node>,
+  start: &BytesStart,
+) -> Result<Node, QuickXmlError> {
+  let qname = utf8(start.name().as_ref())?;
+  let (prefix, local) = split_qname(&qname);
+  let mut node = Node::new(local, None, doc)
+    .map_err(|_| QuickXmlError::Tree(format!("could not create element <{qname}>")))?;
+  match parent {
+    Some(parent) => parent.add_child(&mut node).map_err(QuickXmlError::Tree)?,
+    None => doc.set_root_element(&node),
+  }
+
+  let mut attributes = Vec::new();
+  for attr in start.attributes() {
+    let attr = attr.map_err(quick_xml::Error::from)?;
+    let key = utf8(attr.key.as_ref())?;
+    let value = attr.unescape_value()?.into_owned();
+    if key == "xmlns" {
+      Namespace::new("", &value, &mut node).map_err(|e| QuickXmlError::Tree(e.to_string()))?;
+    } else if let Some(ns_prefix) = key.strip_prefix("xmlns:") {
+      Namespace::new(ns_prefix, &value, &mut node)
+        .map_err(|e| QuickXmlError::Tree(e.to_string()))?;
+    } else {
+      attributes.push((key, value));
+    }
+  }
+
+  if let Some(ns) = node.search_namespace(prefix) {
+    node
+      .set_namespace(&ns)
+      .map_err(|e| QuickXmlError::Tree(e.to_string()))?;
+  } else if prefix.is_some() {
+    return Err(QuickXmlError::Tree(format!("unbound prefix in <{qname}>")));
+  }
+
+  for (key, value) in attributes {
+    let result = match split_qname(&key) {
+      (Some(attr_prefix), local) => match node.search_namespace(Some(attr_prefix)) {
+        Some(ns) => node.set_property_ns(local, &value, &ns),
+        None => return Err(QuickXmlError::Tree(format!("unbound prefix in attribute {key}"))),
+      },
+      (None, local) => node.set_property(local, &value),
+    };
+    result.map_err(|e| QuickXmlError::Tree(e.to_string()))?;
+  }
+  Ok(node)
+}
+
+fn append_text(parent: Option<&mut Node>, content: &str) -> Result<(), QuickXmlError> {
+  match parent {
+    Some(parent) => parent
+      .append_text(content)
+      .map_err(|e| QuickXmlError::Tree(e.to_string())),
+    // character data outside of the root element can only be whitespace
+    None if content.chars().all(|c| matches!(c, ' ' | '\t' | '\r' | '\n')) => Ok(()),
+    None => Err(QuickXmlError::TextOutsideRoot(content.to_string())),
+  }
+}
+
+fn append_node(
+  doc: &Document,
+  parent: Option<&mut Node>,
+  node_ptr: xmlNodePtr,
+) -> Result<(), QuickXmlError> {
+  if node_ptr.is_null() {
+    return Err(QuickXmlError::Tree("libxml2 returned a NULL node".into()));
+  }
+  let parent_ptr = match parent {
+    Some(parent) => parent
+      .node_ptr_mut()
+      .map_err(QuickXmlError::Tree)?,
+    None => doc.doc_ptr() as xmlNodePtr,
+  };
+  if unsafe { xmlAddChild(parent_ptr, node_ptr) }.is_null() {
+    Err(QuickXmlError::Tree("xmlAddChild returned NULL".into()))
+  } else {
+    Ok(())
+  }
+}
+
+fn split_qname(qname: &str) -> (Option<&str>, &str) {
+  match qname.split_once(':') {
+    Some((prefix, local)) => (Some(prefix), local),
+    None => (None, qname),
+  }
+}
+
+fn node_name(node_ptr: xmlNodePtr) -> String {
+  let name_ptr = xmlNodeGetName(node_ptr);
+  if name_ptr.is_null() {
+    String::new()
+  } else {
+    unsafe { CStr::from_ptr(name_ptr) }
+      .to_string_lossy()
+      .into_owned()
+  }
+}
+
+fn node_content(node_ptr: xmlNodePtr) -> String {
+  let content_ptr = unsafe { xmlNodeGetContent(node_ptr) };
+  if content_ptr.is_null() {
+    return String::new();
+  }
+  let content = unsafe { CStr::from_ptr(content_ptr as *const c_char) }
+    .to_string_lossy()
+    .into_owned();
+  bindgenFree(content_ptr as *mut c_void);
+  content
+}
+
+fn xml_str(ptr: *const xmlChar) -> Option<String> {
+  if ptr.is_null() {
+    None
+  } else {
+    Some(
+      unsafe { CStr::from_ptr(ptr as *const c_char) }
+        .to_string_lossy()
+        .into_owned(),
+    )
+  }
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, QuickXmlError> {
+  std::str::from_utf8(bytes)
+    .map(str::to_owned)
+    .map_err(|e| QuickXmlError::Encoding(e.to_string()))
+}
+
+fn c_string(text: String) -> Result<CString, QuickXmlError> {
+  CString::new(text).map_err(|e| QuickXmlError::Tree(e.to_string()))
+}
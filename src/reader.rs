@@ -0,0 +1,315 @@
+//! A pull parser over libxml2's `xmlTextReader`
+//!
+//! [`TextReader`] walks a document node by node in document order, keeping only the current
+//! node in memory, so that documents of any size are processed in constant memory.
+//!
+//! ```
+//! use libxml::reader::{ReaderNodeType, TextReader};
+//!
+//! let mut reader = TextReader::new(&b"<list><item>one</item><item>two</item></list>"[..]);
+//! let mut texts = Vec::new();
+//! while reader.read().unwrap() {
+//!   if reader.node_type() == ReaderNodeType::Text {
+//!     texts.push(reader.value().unwrap());
+//!   }
+//! }
+//! assert_eq!(texts, ["one", "two"]);
+//! ```
+//!
+use std::ffi::{CStr, CString};
+use std::io::{ErrorKind, Read};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::slice;
+
+use crate::bindings::*;
+use crate::c_helpers::bindgenFree;
+use crate::error::StructuredError;
+use crate::parser::{ParseFormat, Parser, ParserOptions, XmlParseError};
+
+/// The kind of node a [`TextReader`] is positioned on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReaderNodeType {
+  /// Not positioned on a node, before the first `read` or after the last one
+  None,
+  /// The start of an element
+  Element,
+  /// An attribute, after moving to it
+  Attribute,
+  /// Text
+  Text,
+  /// A CDATA section
+  CData,
+  /// An entity reference left unexpanded
+  EntityReference,
+  /// An entity declaration
+  Entity,
+  /// A processing instruction
+  ProcessingInstruction,
+  /// A comment
+  Comment,
+  /// The document node
+  Document,
+  /// The document type declaration
+  DocumentType,
+  /// A document fragment
+  DocumentFragment,
+  /// A notation declaration
+  Notation,
+  /// Whitespace between markup
+  Whitespace,
+  /// Whitespace in mixed content or within `xml:space="preserve"`
+  SignificantWhitespace,
+  /// The end of an element
+  EndElement,
+  /// The end of an entity reference
+  EndEntity,
+  /// The XML declaration
+  XmlDeclaration,
+}
+
+impl ReaderNodeType {
+  /// Converts a value of libxml2's `xmlReaderTypes`
+  fn from_raw(node_type: c_int) -> Self {
+    match node_type {
+      1 => ReaderNodeType::Element,
+      2 => ReaderNodeType::Attribute,
+      3 => ReaderNodeType::Text,
+      4 => ReaderNodeType::CData,
+      5 => ReaderNodeType::EntityReference,
+      6 => ReaderNodeType::Entity,
+      7 => ReaderNodeType::ProcessingInstruction,
+      8 => ReaderNodeType::Comment,
+      9 => ReaderNodeType::Document,
+      10 => ReaderNodeType::DocumentType,
+      11 => ReaderNodeType::DocumentFragment,
+      12 => ReaderNodeType::Notation,
+      13 => ReaderNodeType::Whitespace,
+      14 => ReaderNodeType::SignificantWhitespace,
+      15 => ReaderNodeType::EndElement,
+      16 => ReaderNodeType::EndEntity,
+      17 => ReaderNodeType::XmlDeclaration,
+      _ => ReaderNodeType::None,
+    }
+  }
+}
+
+unsafe extern "C" fn read_source<R: Read>(
+  context: *mut c_void,
+  buffer: *mut c_char,
+  len: c_int,
+) -> c_int {
+  unsafe {
+    let source = context as *mut R;
+    let buf = slice::from_raw_parts_mut(buffer as *mut u8, len as usize);
+    loop {
+      match (*source).read(buf) {
+        Ok(v) => return v as c_int,
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(_) => return -1,
+      }
+    }
+  }
+}
+
+/// The error libxml2 reported last on this thread, for a failed read
+fn last_error() -> XmlParseError {
+  let last = unsafe { xmlGetLastError() };
+  if last.is_null() {
+    XmlParseError::GotNullPointer
+  } else {
+    XmlParseError::Malformed(unsafe { StructuredError::from_raw(last) })
+  }
+}
+
+/// A forward-only cursor over the nodes of a document read from any [`Read`] source
+pub struct TextReader<R: Read> {
+  reader: xmlTextReaderPtr,
+  // Boxed so that the address handed to libxml2 stays valid when the reader moves
+  source: Box<R>,
+}
+
+impl<R: Read> TextReader<R> {
+  /// Creates a reader pulling its input from `source`, with the default parser options
+  pub fn new(source: R) -> Self {
+    TextReader::with_options(source, ParserOptions::default())
+  }
+
+  /// Creates a reader pulling its input from `source`, parsed with `options`
+  pub fn with_options(source: R, options: ParserOptions) -> Self {
+    // Initializes libxml2 like the other parsers
+    let _ = Parser::default();
+    let mut source = Box::new(source);
+    let context = &mut *source as *mut R as *mut c_void;
+    let encoding = options.encoding.and_then(|encoding| CString::new(encoding).ok());
+    let reader = unsafe {
+      xmlReaderForIO(
+        Some(read_source::<R>),
+        None,
+        context,
+        ptr::null(),
+        encoding.as_ref().map_or(ptr::null(), |encoding| encoding.as_ptr()),
+        options.to_flags(&ParseFormat::XML),
+      )
+    };
+    TextReader { reader, source }
+  }
+
+  /// Moves to the next node in document order, returning `false` at the end of the document
+  pub fn read(&mut self) -> Result<bool, XmlParseError> {
+    if self.reader.is_null() {
+      return Err(XmlParseError::GotNullPointer);
+    }
+    unsafe { xmlResetLastError() };
+    match unsafe { xmlTextReaderRead(self.reader) } {
+      1 => Ok(true),
+      0 => Ok(false),
+      _ => Err(last_error()),
+    }
+  }
+
+  /// Skips the children of the current node, moving to its next sibling, and returns
+  /// `false` at the end of the document
+  pub fn next_sibling(&mut self) -> Result<bool, XmlParseError> {
+    if self.reader.is_null() {
+      return Err(XmlParseError::GotNullPointer);
+    }
+    unsafe { xmlResetLastError() };
+    match unsafe { xmlTextReaderNext(self.reader) } {
+      1 => Ok(true),
+      0 => Ok(false),
+      _ => Err(last_error()),
+    }
+  }
+
+  /// The kind of the current node
+  pub fn node_type(&self) -> ReaderNodeType {
+    if self.reader.is_null() {
+      return ReaderNodeType::None;
+    }
+    ReaderNodeType::from_raw(unsafe { xmlTextReaderNodeType(self.reader) })
+  }
+
+  /// The qualified name of the current node, e.g. `x:item`, or `#text` for text
+  pub fn name(&self) -> String {
+    self
+      .string(unsafe { xmlTextReaderConstName(self.reader) })
+      .unwrap_or_default()
+  }
+
+  /// The local name of the current node
+  pub fn local_name(&self) -> String {
+    self
+      .string(unsafe { xmlTextReaderConstLocalName(self.reader) })
+      .unwrap_or_default()
+  }
+
+  /// The namespace prefix of the current node, if any
+  pub fn prefix(&self) -> Option<String> {
+    self.string(unsafe { xmlTextReaderConstPrefix(self.reader) })
+  }
+
+  /// The namespace URI of the current node, if any
+  pub fn namespace_uri(&self) -> Option<String> {
+    self.string(unsafe { xmlTextReaderConstNamespaceUri(self.reader) })
+  }
+
+  /// The text of the current text, comment, processing instruction or attribute node
+  pub fn value(&self) -> Option<String> {
+    self.string(unsafe { xmlTextReaderConstValue(self.reader) })
+  }
+
+  /// The depth of the current node, 0 for the root element
+  pub fn depth(&self) -> usize {
+    unsafe { xmlTextReaderDepth(self.reader) }.max(0) as usize
+  }
+
+  /// Whether the current node is an element without content, like `<item/>`, which is
+  /// followed by no [`ReaderNodeType::EndElement`]
+  pub fn is_empty_element(&self) -> bool {
+    unsafe { xmlTextReaderIsEmptyElement(self.reader) == 1 }
+  }
+
+  /// The number of attributes of the current node, namespace declarations included
+  pub fn attribute_count(&self) -> usize {
+    unsafe { xmlTextReaderAttributeCount(self.reader) }.max(0) as usize
+  }
+
+  /// The value of the attribute `name` (qualified) of the current element
+  pub fn get_attribute(&self, name: &str) -> Option<String> {
+    let c_name = CString::new(name).ok()?;
+    let value = unsafe { xmlTextReaderGetAttribute(self.reader, c_name.as_ptr() as *const xmlChar) };
+    self.owned_string(value)
+  }
+
+  /// The value of the attribute `local_name` in the namespace `namespace_uri`
+  pub fn get_attribute_ns(&self, local_name: &str, namespace_uri: &str) -> Option<String> {
+    let c_name = CString::new(local_name).ok()?;
+    let c_ns = CString::new(namespace_uri).ok()?;
+    let value = unsafe {
+      xmlTextReaderGetAttributeNs(
+        self.reader,
+        c_name.as_ptr() as *const xmlChar,
+        c_ns.as_ptr() as *const xmlChar,
+      )
+    };
+    self.owned_string(value)
+  }
+
+  /// Moves to the next attribute of the current element, or the first one when positioned
+  /// on the element, returning `false` when there are no more
+  pub fn move_to_next_attribute(&mut self) -> bool {
+    unsafe { xmlTextReaderMoveToNextAttribute(self.reader) == 1 }
+  }
+
+  /// Moves back from an attribute to its element
+  pub fn move_to_element(&mut self) -> bool {
+    unsafe { xmlTextReaderMoveToElement(self.reader) == 1 }
+  }
+
+  /// Serializes the current node with its content
+  pub fn read_outer_xml(&mut self) -> String {
+    let xml = unsafe { xmlTextReaderReadOuterXml(self.reader) };
+    self.owned_string(xml).unwrap_or_default()
+  }
+
+  /// Serializes the content of the current node
+  pub fn read_inner_xml(&mut self) -> String {
+    let xml = unsafe { xmlTextReaderReadInnerXml(self.reader) };
+    self.owned_string(xml).unwrap_or_default()
+  }
+
+  /// Borrows the underlying source
+  pub fn source(&self) -> &R {
+    &self.source
+  }
+
+  fn string(&self, value: *const xmlChar) -> Option<String> {
+    if value.is_null() {
+      None
+    } else {
+      Some(
+        unsafe { CStr::from_ptr(value as *const c_char) }
+          .to_string_lossy()
+          .into_owned(),
+      )
+    }
+  }
+
+  /// Converts and frees a string allocated by libxml2
+  fn owned_string(&self, value: *mut xmlChar) -> Option<String> {
+    let string = self.string(value);
+    if !value.is_null() {
+      bindgenFree(value as *mut c_void);
+    }
+    string
+  }
+}
+
+impl<R: Read> Drop for TextReader<R> {
+  fn drop(&mut self) {
+    if !self.reader.is_null() {
+      unsafe { xmlFreeTextReader(self.reader) };
+    }
+  }
+}
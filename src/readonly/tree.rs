@@ -1,18 +1,20 @@
-use libc::{c_char, c_void};
 use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
 use std::ptr;
 use std::str;
 
 use crate::bindings::*;
 use crate::c_helpers::*;
-use crate::tree::namespace::Namespace;
+use crate::tree::namespace::{self, Namespace};
+use crate::tree::node;
 use crate::tree::nodetype::NodeType;
 use crate::tree::Document;
+use crate::tree::traversal;
 use crate::xpath::Context;
 
 /// Lightweight struct for read-only parallel processing
-#[derive(Debug, Copy, Clone)]
+#[derive(Copy, Clone)]
 pub struct RoNode(pub(crate) xmlNodePtr);
 
 // we claim Sync and Send, as we are in read-only mode over the owning document
@@ -166,6 +168,25 @@ impl RoNode {
       .collect::<Vec<RoNode>>()
   }
 
+  /// Returns the position of this node among the children of its parent, counting from 0,
+  /// or `None` for an attribute or a node without a parent
+  pub fn index_in_parent(self) -> Option<usize> {
+    traversal::index_in_parent(self.0)
+  }
+
+  /// Returns the child at `index`, counting from 0, if there are that many
+  pub fn nth_child(self, index: usize) -> Option<RoNode> {
+    let ptr = traversal::nth_child(self.0, index, false);
+    self.ptr_as_option(ptr)
+  }
+
+  /// Returns the child element at `index`, counting only elements from 0, if there are that
+  /// many
+  pub fn nth_element_child(self, index: usize) -> Option<RoNode> {
+    let ptr = traversal::nth_child(self.0, index, true);
+    self.ptr_as_option(ptr)
+  }
+
   /// Returns the parent if it exists
   pub fn get_parent(self) -> Option<RoNode> {
     let ptr = xmlGetParent(self.0);
@@ -182,6 +203,21 @@ impl RoNode {
     self.get_type() == Some(NodeType::TextNode)
   }
 
+  /// The target of a processing instruction, such as `xml-stylesheet`, `None` for other nodes
+  pub fn get_pi_target(self) -> Option<String> {
+    (self.get_type() == Some(NodeType::PiNode)).then(|| self.get_name())
+  }
+
+  /// The data of a processing instruction, what follows its target, `None` for other nodes
+  pub fn get_pi_data(self) -> Option<String> {
+    (self.get_type() == Some(NodeType::PiNode)).then(|| self.get_content())
+  }
+
+  /// Returns true if it is a CDATA section
+  pub fn is_cdata(self) -> bool {
+    self.get_type() == Some(NodeType::CDataSectionNode)
+  }
+
   /// Checks if the given node is an Element
   pub fn is_element_node(self) -> bool {
     self.get_type() == Some(NodeType::ElementNode)
@@ -192,6 +228,29 @@ impl RoNode {
     self.0.is_null()
   }
 
+  /// The line of the source document this node was parsed from, if known. libxml2 records
+  /// no columns for nodes, and lines past 65535 only with [`ParserOptions::big_lines`].
+  ///
+  /// [`ParserOptions::big_lines`]: crate::parser::ParserOptions::big_lines
+  pub fn get_line_number(self) -> Option<usize> {
+    let line = unsafe { xmlGetLineNo(self.0) };
+    (line > 0).then_some(line as usize)
+  }
+
+  /// The XPath-like location of this node in its document, e.g. `/root/items/item[3]`, for
+  /// reporting where a problem is. Elements in a default namespace, which XPath can only
+  /// name with a prefix, show as `*` steps.
+  pub fn get_path(self) -> Option<String> {
+    let path_ptr = unsafe { xmlGetNodePath(self.0) };
+    if path_ptr.is_null() {
+      return None;
+    }
+    let c_string = unsafe { CStr::from_ptr(path_ptr as *const c_char) };
+    let path = c_string.to_string_lossy().into_owned();
+    bindgenFree(path_ptr as *mut c_void);
+    Some(path)
+  }
+
   /// Returns the name of the node (empty string if name pointer is `NULL`)
   pub fn get_name(self) -> String {
     let name_ptr = xmlNodeGetName(self.0);
@@ -216,6 +275,19 @@ impl RoNode {
     rust_utf8
   }
 
+  /// Returns the base URI of this node, from the `xml:base` attributes of it and its
+  /// ancestors resolved against the URL of the document
+  pub fn get_base_uri(self) -> Option<String> {
+    node::base_uri(self.0)
+  }
+
+  /// Whether whitespace is significant in this node, as set by the nearest `xml:space`
+  /// attribute on it or its ancestors: `Some(true)` for `preserve`, `Some(false)` for
+  /// `default`, `None` if there is none
+  pub fn preserves_space(self) -> Option<bool> {
+    node::space_preserve(self.0)
+  }
+
   /// Returns the value of property `name`
   pub fn get_property(self, name: &str) -> Option<String> {
     let c_name = CString::new(name).unwrap();
@@ -478,14 +550,23 @@ impl RoNode {
     }
   }
 
-  /// Looks up the uri of a namespace from its prefix, basedo around a given `Node`
+  /// Looks up the prefix bound to the namespace `href` in the scope of this node, `None` if
+  /// there is none or the namespace is only the default one
+  pub fn lookup_prefix(self, href: &str) -> Option<String> {
+    namespace::lookup_prefix(self.0, href)
+  }
+
+  /// Looks up the uri of a namespace from its prefix, basedo around a given `Node`, the
+  /// default namespace for `""`
   pub fn lookup_namespace_uri(self, prefix: &str) -> Option<String> {
-    if prefix.is_empty() {
-      return None;
-    }
     let c_prefix = CString::new(prefix).unwrap();
+    let prefix_ptr = if prefix.is_empty() {
+      ptr::null()
+    } else {
+      c_prefix.as_bytes().as_ptr()
+    };
     unsafe {
-      let ns_ptr = xmlSearchNs(xmlGetDoc(self.0), self.0, c_prefix.as_bytes().as_ptr());
+      let ns_ptr = xmlSearchNs(xmlGetDoc(self.0), self.0, prefix_ptr);
       if !ns_ptr.is_null() {
         let ns = Namespace { ns_ptr };
         let ns_prefix = ns.get_href();
@@ -518,6 +599,29 @@ impl RoNode {
     Ok(evaluated.get_readonly_nodes_as_vec())
   }
 
+  /// Search this node for XPath `path` in the document `owner`, and return only the first
+  /// match
+  pub fn at_xpath(
+    self,
+    path: &str,
+    owner: &Document,
+    ns_bindings: &[(&str, &str)],
+  ) -> Result<Option<RoNode>, ()> {
+    let context = Context::new(owner)?;
+    for (prefix, href) in ns_bindings {
+      context.register_namespace(prefix, href)?;
+    }
+    let evaluated = context.node_evaluate_readonly(path, self)?;
+    Ok(evaluated.get_readonly_nodes_as_vec().first().copied())
+  }
+
+  /// find String values via xpath, at the specified node and a given document
+  pub fn findvalues(self, xpath: &str, owner: &Document) -> Result<Vec<String>, ()> {
+    let context = Context::new(owner)?;
+    let evaluated = context.node_evaluate_readonly(xpath, self)?;
+    Ok(evaluated.get_nodes_as_str())
+  }
+
   /// Read-only nodes are always linked
   pub fn is_unlinked(self) -> bool {
     false
@@ -0,0 +1,105 @@
+//! Wrapping of libxml2's regular expression engine (xmlRegexp)
+//!
+//! These are the regular expressions of XML Schema (Part 2, Appendix F), as used for
+//! `pattern` facets by the schema validator. Unlike most regex flavours, a pattern always
+//! has to match the whole value, there are no `^`/`$` anchors and `\i`, `\c` and `\p{..}`
+//! character classes are supported.
+//!
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+
+use crate::bindings::*;
+use crate::error::StructuredError;
+
+/// A compiled XML Schema regular expression
+pub struct Regexp {
+  inner: xmlRegexpPtr,
+  pattern: String,
+}
+
+/// Error raised when a pattern can not be compiled
+#[derive(Debug)]
+pub struct RegexpError {
+  /// The offending pattern
+  pub pattern: String,
+  /// The error reported by libxml2, if any
+  pub error: Option<StructuredError>,
+}
+
+impl Error for RegexpError {}
+
+impl fmt::Display for RegexpError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.error.as_ref().and_then(|e| e.message.as_deref()) {
+      Some(message) => write!(f, "invalid pattern {:?}: {}", self.pattern, message.trim_end()),
+      None => write!(f, "invalid pattern {:?}", self.pattern),
+    }
+  }
+}
+
+impl Regexp {
+  /// Compiles `pattern`
+  pub fn new(pattern: &str) -> Result<Self, RegexpError> {
+    let c_pattern = CString::new(pattern).map_err(|_| RegexpError {
+      pattern: pattern.to_string(),
+      error: None,
+    })?;
+    unsafe {
+      xmlResetLastError();
+      let inner = xmlRegexpCompile(c_pattern.as_bytes().as_ptr());
+      if inner.is_null() {
+        let last = xmlGetLastError();
+        return Err(RegexpError {
+          pattern: pattern.to_string(),
+          error: if last.is_null() {
+            None
+          } else {
+            Some(StructuredError::from_raw(last))
+          },
+        });
+      }
+      Ok(Regexp {
+        inner,
+        pattern: pattern.to_string(),
+      })
+    }
+  }
+
+  /// Checks whether the whole of `value` matches the expression.
+  /// Values containing NUL bytes never match.
+  pub fn is_match(&self, value: &str) -> bool {
+    let c_value = match CString::new(value) {
+      Ok(c_value) => c_value,
+      Err(_) => return false,
+    };
+    unsafe { xmlRegexpExec(self.inner, c_value.as_bytes().as_ptr()) == 1 }
+  }
+
+  /// Whether the compiled automaton is deterministic
+  pub fn is_deterministic(&self) -> bool {
+    unsafe { xmlRegexpIsDeterminist(self.inner) == 1 }
+  }
+
+  /// The source pattern
+  pub fn as_str(&self) -> &str {
+    &self.pattern
+  }
+
+  /// Return a raw pointer to the underlying xmlRegexp structure
+  pub fn as_ptr(&self) -> xmlRegexpPtr {
+    self.inner
+  }
+}
+
+impl fmt::Debug for Regexp {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_tuple("Regexp").field(&self.pattern).finish()
+  }
+}
+
+impl Drop for Regexp {
+  fn drop(&mut self) {
+    unsafe { xmlRegFreeRegexp(self.inner) }
+  }
+}
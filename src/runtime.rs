@@ -0,0 +1,239 @@
+//! Version and compiled-in features of the libxml2 library loaded at runtime
+//!
+use std::ffi::CStr;
+use std::fmt;
+
+use crate::bindings::*;
+
+/// The optional modules a libxml2 build may include, as reported by `xmlHasFeature`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+  /// Thread support
+  Thread,
+  /// The tree API
+  Tree,
+  /// Serialization
+  Output,
+  /// The push parser
+  Push,
+  /// The `xmlReader` pull parser
+  Reader,
+  /// Streamable patterns
+  Pattern,
+  /// The `xmlWriter` API
+  Writer,
+  /// The SAX1 interface
+  Sax1,
+  /// The FTP client
+  Ftp,
+  /// The HTTP client
+  Http,
+  /// DTD validation
+  Valid,
+  /// The HTML parser
+  Html,
+  /// Deprecated legacy APIs
+  Legacy,
+  /// Canonical XML
+  C14n,
+  /// XML Catalogs
+  Catalog,
+  /// XPath
+  XPath,
+  /// XPointer
+  XPointer,
+  /// XInclude
+  XInclude,
+  /// iconv based encoding conversions
+  Iconv,
+  /// The ISO-8859-X encodings without iconv
+  Iso8859x,
+  /// Unicode character classes
+  Unicode,
+  /// Regular expressions
+  Regexp,
+  /// Automata, needed by regular expressions
+  Automata,
+  /// Formal expressions
+  Expr,
+  /// XML Schema and RELAX NG validation
+  Schemas,
+  /// Schematron validation
+  Schematron,
+  /// Dynamically loaded modules
+  Modules,
+  /// Debugging helpers
+  Debug,
+  /// Memory debugging
+  DebugMem,
+  /// Runtime debugging
+  DebugRun,
+  /// gzip compressed documents
+  Zlib,
+  /// ICU based encoding conversions
+  Icu,
+  /// xz compressed documents
+  Lzma,
+}
+
+impl Feature {
+  /// All the features, in the order of libxml2's `xmlFeature` enumeration
+  pub const ALL: [Feature; 33] = [
+    Feature::Thread,
+    Feature::Tree,
+    Feature::Output,
+    Feature::Push,
+    Feature::Reader,
+    Feature::Pattern,
+    Feature::Writer,
+    Feature::Sax1,
+    Feature::Ftp,
+    Feature::Http,
+    Feature::Valid,
+    Feature::Html,
+    Feature::Legacy,
+    Feature::C14n,
+    Feature::Catalog,
+    Feature::XPath,
+    Feature::XPointer,
+    Feature::XInclude,
+    Feature::Iconv,
+    Feature::Iso8859x,
+    Feature::Unicode,
+    Feature::Regexp,
+    Feature::Automata,
+    Feature::Expr,
+    Feature::Schemas,
+    Feature::Schematron,
+    Feature::Modules,
+    Feature::Debug,
+    Feature::DebugMem,
+    Feature::DebugRun,
+    Feature::Zlib,
+    Feature::Icu,
+    Feature::Lzma,
+  ];
+
+  fn raw(self) -> xmlFeature {
+    match self {
+      Feature::Thread => xmlFeature_XML_WITH_THREAD,
+      Feature::Tree => xmlFeature_XML_WITH_TREE,
+      Feature::Output => xmlFeature_XML_WITH_OUTPUT,
+      Feature::Push => xmlFeature_XML_WITH_PUSH,
+      Feature::Reader => xmlFeature_XML_WITH_READER,
+      Feature::Pattern => xmlFeature_XML_WITH_PATTERN,
+      Feature::Writer => xmlFeature_XML_WITH_WRITER,
+      Feature::Sax1 => xmlFeature_XML_WITH_SAX1,
+      Feature::Ftp => xmlFeature_XML_WITH_FTP,
+      Feature::Http => xmlFeature_XML_WITH_HTTP,
+      Feature::Valid => xmlFeature_XML_WITH_VALID,
+      Feature::Html => xmlFeature_XML_WITH_HTML,
+      Feature::Legacy => xmlFeature_XML_WITH_LEGACY,
+      Feature::C14n => xmlFeature_XML_WITH_C14N,
+      Feature::Catalog => xmlFeature_XML_WITH_CATALOG,
+      Feature::XPath => xmlFeature_XML_WITH_XPATH,
+      Feature::XPointer => xmlFeature_XML_WITH_XPTR,
+      Feature::XInclude => xmlFeature_XML_WITH_XINCLUDE,
+      Feature::Iconv => xmlFeature_XML_WITH_ICONV,
+      Feature::Iso8859x => xmlFeature_XML_WITH_ISO8859X,
+      Feature::Unicode => xmlFeature_XML_WITH_UNICODE,
+      Feature::Regexp => xmlFeature_XML_WITH_REGEXP,
+      Feature::Automata => xmlFeature_XML_WITH_AUTOMATA,
+      Feature::Expr => xmlFeature_XML_WITH_EXPR,
+      Feature::Schemas => xmlFeature_XML_WITH_SCHEMAS,
+      Feature::Schematron => xmlFeature_XML_WITH_SCHEMATRON,
+      Feature::Modules => xmlFeature_XML_WITH_MODULES,
+      Feature::Debug => xmlFeature_XML_WITH_DEBUG,
+      Feature::DebugMem => xmlFeature_XML_WITH_DEBUG_MEM,
+      Feature::DebugRun => xmlFeature_XML_WITH_DEBUG_RUN,
+      Feature::Zlib => xmlFeature_XML_WITH_ZLIB,
+      Feature::Icu => xmlFeature_XML_WITH_ICU,
+      Feature::Lzma => xmlFeature_XML_WITH_LZMA,
+    }
+  }
+}
+
+/// What the libxml2 in use is and can do, see [`runtime_info`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeInfo {
+  /// The version of the loaded library, e.g. "2.9.14"
+  pub version: String,
+  /// The same version as a number, e.g. 20914
+  pub version_number: u32,
+  /// The libxml2 version the bindings were generated for at build time, which decides the
+  /// APIs the wrapper uses
+  pub bindings_version: &'static str,
+  features: Vec<Feature>,
+}
+
+impl RuntimeInfo {
+  /// Whether the loaded library was built with `feature`
+  pub fn has_feature(&self, feature: Feature) -> bool {
+    self.features.contains(&feature)
+  }
+
+  /// The features the loaded library was built with
+  pub fn features(&self) -> &[Feature] {
+    &self.features
+  }
+
+  /// Those of the `required` features the loaded library lacks, to check at startup that
+  /// it supports what the application relies on
+  pub fn missing_features(&self, required: &[Feature]) -> Vec<Feature> {
+    required
+      .iter()
+      .copied()
+      .filter(|feature| !self.has_feature(*feature))
+      .collect()
+  }
+}
+
+impl fmt::Display for RuntimeInfo {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "libxml2 {} (", self.version)?;
+    for (i, feature) in self.features.iter().enumerate() {
+      if i > 0 {
+        write!(f, ", ")?;
+      }
+      write!(f, "{:?}", feature)?;
+    }
+    write!(f, ")")
+  }
+}
+
+/// Reports the version and features of the libxml2 library loaded at runtime, which may
+/// differ from the one the crate was built against when it is linked dynamically
+pub fn runtime_info() -> RuntimeInfo {
+  // The version number as a string, e.g. "20914", possibly followed by a suffix
+  let raw_version = unsafe { CStr::from_ptr(parser_version()) }.to_string_lossy();
+  let digits: String = raw_version
+    .chars()
+    .take_while(|c| c.is_ascii_digit())
+    .collect();
+  let version_number = digits.parse().unwrap_or(0);
+  let features = Feature::ALL
+    .into_iter()
+    .filter(|feature| unsafe { xmlHasFeature(feature.raw()) } != 0)
+    .collect();
+  RuntimeInfo {
+    version: format!(
+      "{}.{}.{}",
+      version_number / 10000,
+      version_number / 100 % 100,
+      version_number % 100
+    ),
+    version_number,
+    bindings_version: env!("LIBXML2_BINDINGS_VERSION"),
+    features,
+  }
+}
+
+#[cfg(libxml_older_than_2_12)]
+fn parser_version() -> *const std::os::raw::c_char {
+  unsafe { *__xmlParserVersion() }
+}
+
+#[cfg(not(libxml_older_than_2_12))]
+fn parser_version() -> *const std::os::raw::c_char {
+  unsafe { xmlParserVersion }
+}
@@ -0,0 +1,329 @@
+//! Event based parsing with libxml2's SAX2 interface, without building a DOM
+//!
+use std::borrow::Cow;
+use std::ffi::{c_void, CStr};
+use std::io::{self, Read};
+use std::os::raw::{c_char, c_int};
+use std::{mem, ptr, slice};
+
+use crate::bindings::*;
+use crate::error::StructuredError;
+use crate::parser::push::{last_error, switch_encoding};
+use crate::parser::{ErrorPtr, ParseFormat, Parser, ParserOptions, XmlParseError};
+
+/// Bytes read from the reader and handed to libxml2 at a time
+const READ_SIZE: usize = 8192;
+
+/// The namespace-qualified name of an element or attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QName<'a> {
+  /// The local part of the name
+  pub local_name: &'a str,
+  /// The prefix, if the name has one
+  pub prefix: Option<&'a str>,
+  /// The namespace URI, if the name is in a namespace
+  pub namespace: Option<&'a str>,
+}
+
+/// An attribute of a started element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attribute<'a> {
+  /// The name of the attribute
+  pub name: QName<'a>,
+  /// Its normalized value
+  pub value: &'a str,
+}
+
+/// A namespace declared on a started element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamespaceDeclaration<'a> {
+  /// The declared prefix, `None` for the default namespace
+  pub prefix: Option<&'a str>,
+  /// The namespace URI
+  pub uri: &'a str,
+}
+
+/// Receives the events of [`parse`], each method doing nothing unless implemented.
+///
+/// Text may be reported in several `characters` calls. References to entities declared in
+/// a DTD are reported with `entity_reference` rather than expanded. Handlers must not panic.
+pub trait SaxHandler {
+  /// The document starts
+  fn start_document(&mut self) {}
+
+  /// The document is complete
+  fn end_document(&mut self) {}
+
+  /// An element starts, declaring `namespaces`
+  fn start_element(
+    &mut self,
+    _name: QName,
+    _attributes: &[Attribute],
+    _namespaces: &[NamespaceDeclaration],
+  ) {
+  }
+
+  /// An element ends
+  fn end_element(&mut self, _name: QName) {}
+
+  /// Some character data of the current element
+  fn characters(&mut self, _text: &str) {}
+
+  /// The content of a CDATA section, reported as `characters` unless implemented
+  fn cdata(&mut self, text: &str) {
+    self.characters(text);
+  }
+
+  /// A comment
+  fn comment(&mut self, _text: &str) {}
+
+  /// A processing instruction
+  fn processing_instruction(&mut self, _target: &str, _data: Option<&str>) {}
+
+  /// A reference to the entity `name` declared in the DTD
+  fn entity_reference(&mut self, _name: &str) {}
+
+  /// A warning or error reported by libxml2; without recovery, the first error also ends
+  /// the parse with [`XmlParseError::Malformed`]
+  fn error(&mut self, _error: StructuredError) {}
+}
+
+/// Parses the XML read from `reader` in a single pass, reporting it to `handler` without
+/// building a document, so that huge documents take constant memory
+pub fn parse<R: Read, H: SaxHandler>(reader: R, handler: &mut H) -> Result<(), XmlParseError> {
+  parse_with_options(reader, handler, ParserOptions::default())
+}
+
+/// Parses the XML read from `reader` with `options`, reporting it to `handler`
+pub fn parse_with_options<R: Read, H: SaxHandler>(
+  mut reader: R,
+  handler: &mut H,
+  options: ParserOptions,
+) -> Result<(), XmlParseError> {
+  // Initializes libxml2 like the other parsers
+  let _ = Parser::default();
+  let ctxt = unsafe {
+    // libxml2's SAX2 callbacks keep handling the DTD, so that entities are known
+    let mut sax: xmlSAXHandler = mem::zeroed();
+    xmlSAXVersion(&mut sax, 2);
+    sax.startDocument = Some(start_document::<H>);
+    sax.endDocument = Some(end_document::<H>);
+    sax.startElementNs = Some(start_element::<H>);
+    sax.endElementNs = Some(end_element::<H>);
+    sax.characters = Some(characters::<H>);
+    sax.ignorableWhitespace = Some(characters::<H>);
+    sax.cdataBlock = Some(cdata::<H>);
+    sax.comment = Some(comment::<H>);
+    sax.processingInstruction = Some(processing_instruction::<H>);
+    sax.reference = Some(reference::<H>);
+    sax.serror = Some(error::<H>);
+    // The context copies the callbacks, and is its own user data
+    xmlCreatePushParserCtxt(&mut sax, ptr::null_mut(), ptr::null(), 0, ptr::null())
+  };
+  if ctxt.is_null() {
+    return Err(XmlParseError::GotNullPointer);
+  }
+  unsafe {
+    (*ctxt)._private = handler as *mut H as *mut c_void;
+    xmlCtxtUseOptions(ctxt, options.to_flags(&ParseFormat::XML));
+  }
+
+  let result = options
+    .encoding
+    .map_or(Ok(()), |encoding| switch_encoding(ctxt, encoding))
+    .and_then(|()| feed(ctxt, &mut reader, options.recover));
+
+  unsafe {
+    let doc_ptr = (*ctxt).myDoc;
+    if !doc_ptr.is_null() {
+      xmlFreeDoc(doc_ptr);
+    }
+    xmlFreeParserCtxt(ctxt);
+  }
+  result
+}
+
+/// Hands the content of `reader` to the push parser `ctxt`
+fn feed<R: Read>(ctxt: xmlParserCtxtPtr, reader: &mut R, recover: bool) -> Result<(), XmlParseError> {
+  let mut buffer = vec![0u8; READ_SIZE];
+  loop {
+    let read = match reader.read(&mut buffer) {
+      Ok(read) => read,
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(XmlParseError::ReadError(e)),
+    };
+    let status = unsafe {
+      xmlParseChunk(
+        ctxt,
+        buffer.as_ptr() as *const c_char,
+        read as c_int,
+        (read == 0) as c_int,
+      )
+    };
+    if !recover && (status != 0 || unsafe { (*ctxt).wellFormed } == 0) {
+      return Err(last_error(ctxt));
+    }
+    if read == 0 {
+      return Ok(());
+    }
+  }
+}
+
+/// The handler of the parser context `ctx`
+unsafe fn handler<'a, H: SaxHandler>(ctx: *mut c_void) -> &'a mut H {
+  unsafe { &mut *((*(ctx as xmlParserCtxtPtr))._private as *mut H) }
+}
+
+/// The null terminated string at `ptr`, if any
+unsafe fn text<'a>(ptr: *const xmlChar) -> Option<Cow<'a, str>> {
+  (!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr as *const c_char) }.to_string_lossy())
+}
+
+/// The `len` bytes at `ptr`
+unsafe fn text_len<'a>(ptr: *const xmlChar, len: c_int) -> Cow<'a, str> {
+  if ptr.is_null() || len <= 0 {
+    return Cow::Borrowed("");
+  }
+  String::from_utf8_lossy(unsafe { slice::from_raw_parts(ptr, len as usize) })
+}
+
+unsafe extern "C" fn start_document<H: SaxHandler>(ctx: *mut c_void) {
+  unsafe {
+    // Creates the document the DTD is stored in
+    xmlSAX2StartDocument(ctx);
+    handler::<H>(ctx).start_document();
+  }
+}
+
+unsafe extern "C" fn end_document<H: SaxHandler>(ctx: *mut c_void) {
+  unsafe { handler::<H>(ctx).end_document() }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn start_element<H: SaxHandler>(
+  ctx: *mut c_void,
+  localname: *const xmlChar,
+  prefix: *const xmlChar,
+  uri: *const xmlChar,
+  nb_namespaces: c_int,
+  namespaces: *mut *const xmlChar,
+  nb_attributes: c_int,
+  _nb_defaulted: c_int,
+  attributes: *mut *const xmlChar,
+) {
+  unsafe {
+    let local_name = text(localname).unwrap_or_default();
+    let prefix = text(prefix);
+    let namespace = text(uri);
+
+    // Pairs of prefix and URI
+    let namespaces = if namespaces.is_null() {
+      &[][..]
+    } else {
+      slice::from_raw_parts(namespaces, 2 * nb_namespaces.max(0) as usize)
+    };
+    let declared: Vec<(Option<Cow<str>>, Cow<str>)> = namespaces
+      .chunks(2)
+      .map(|pair| (text(pair[0]), text(pair[1]).unwrap_or_default()))
+      .collect();
+
+    // Quintuplets of local name, prefix, URI, value and value end
+    let attributes = if attributes.is_null() {
+      &[][..]
+    } else {
+      slice::from_raw_parts(attributes, 5 * nb_attributes.max(0) as usize)
+    };
+    let values: Vec<[Option<Cow<str>>; 4]> = attributes
+      .chunks(5)
+      .map(|attr| {
+        let len = attr[4].offset_from(attr[3]) as c_int;
+        let value = text_len(attr[3], len);
+        // Without entity substitution, libxml2 keeps `&` escaped as a character reference
+        let value = if value.contains("&#38;") {
+          Cow::Owned(value.replace("&#38;", "&"))
+        } else {
+          value
+        };
+        [text(attr[0]), text(attr[1]), text(attr[2]), Some(value)]
+      })
+      .collect();
+
+    let namespaces: Vec<NamespaceDeclaration> = declared
+      .iter()
+      .map(|(prefix, uri)| NamespaceDeclaration {
+        prefix: prefix.as_deref(),
+        uri,
+      })
+      .collect();
+    let attributes: Vec<Attribute> = values
+      .iter()
+      .map(|[local_name, prefix, namespace, value]| Attribute {
+        name: QName {
+          local_name: local_name.as_deref().unwrap_or_default(),
+          prefix: prefix.as_deref(),
+          namespace: namespace.as_deref(),
+        },
+        value: value.as_deref().unwrap_or_default(),
+      })
+      .collect();
+    let name = QName {
+      local_name: &local_name,
+      prefix: prefix.as_deref(),
+      namespace: namespace.as_deref(),
+    };
+    handler::<H>(ctx).start_element(name, &attributes, &namespaces);
+  }
+}
+
+unsafe extern "C" fn end_element<H: SaxHandler>(
+  ctx: *mut c_void,
+  localname: *const xmlChar,
+  prefix: *const xmlChar,
+  uri: *const xmlChar,
+) {
+  unsafe {
+    let local_name = text(localname).unwrap_or_default();
+    let prefix = text(prefix);
+    let namespace = text(uri);
+    handler::<H>(ctx).end_element(QName {
+      local_name: &local_name,
+      prefix: prefix.as_deref(),
+      namespace: namespace.as_deref(),
+    });
+  }
+}
+
+unsafe extern "C" fn characters<H: SaxHandler>(ctx: *mut c_void, ch: *const xmlChar, len: c_int) {
+  unsafe { handler::<H>(ctx).characters(&text_len(ch, len)) }
+}
+
+unsafe extern "C" fn cdata<H: SaxHandler>(ctx: *mut c_void, value: *const xmlChar, len: c_int) {
+  unsafe { handler::<H>(ctx).cdata(&text_len(value, len)) }
+}
+
+unsafe extern "C" fn comment<H: SaxHandler>(ctx: *mut c_void, value: *const xmlChar) {
+  unsafe { handler::<H>(ctx).comment(&text(value).unwrap_or_default()) }
+}
+
+unsafe extern "C" fn processing_instruction<H: SaxHandler>(
+  ctx: *mut c_void,
+  target: *const xmlChar,
+  data: *const xmlChar,
+) {
+  unsafe {
+    let target = text(target).unwrap_or_default();
+    let data = text(data);
+    handler::<H>(ctx).processing_instruction(&target, data.as_deref());
+  }
+}
+
+unsafe extern "C" fn reference<H: SaxHandler>(ctx: *mut c_void, name: *const xmlChar) {
+  unsafe { handler::<H>(ctx).entity_reference(&text(name).unwrap_or_default()) }
+}
+
+unsafe extern "C" fn error<H: SaxHandler>(ctx: *mut c_void, error: ErrorPtr) {
+  if ctx.is_null() || error.is_null() {
+    return;
+  }
+  unsafe { handler::<H>(ctx).error(StructuredError::from_raw(error)) }
+}
@@ -0,0 +1,570 @@
+//! SOAP 1.1 / 1.2 envelopes
+//!
+//! Enabled with the `soap` cargo feature. An [`Envelope`] wraps a `Document` whose root is a
+//! SOAP `Envelope` element, giving access to the header blocks and body payload, reading and
+//! writing faults as [`Fault`] values, and checking `mustUnderstand` header blocks.
+//!
+use std::error::Error;
+use std::fmt;
+
+use crate::parser::{Parser, ParserOptions, XmlParseError};
+use crate::tree::{Document, Namespace, Node};
+
+/// Envelope namespace of SOAP 1.1
+pub const SOAP11_ENVELOPE_NS: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+/// Envelope namespace of SOAP 1.2
+pub const SOAP12_ENVELOPE_NS: &str = "http://www.w3.org/2003/05/soap-envelope";
+
+const SOAP11_NEXT_ACTOR: &str = "http://schemas.xmlsoap.org/soap/actor/next";
+const SOAP12_NEXT_ROLE: &str = "http://www.w3.org/2003/05/soap-envelope/role/next";
+const SOAP12_ULTIMATE_RECEIVER_ROLE: &str =
+  "http://www.w3.org/2003/05/soap-envelope/role/ultimateReceiver";
+
+/// The SOAP protocol version of an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoapVersion {
+  /// SOAP 1.1
+  Soap11,
+  /// SOAP 1.2
+  Soap12,
+}
+
+impl SoapVersion {
+  /// The envelope namespace of this version
+  pub fn namespace(self) -> &'static str {
+    match self {
+      SoapVersion::Soap11 => SOAP11_ENVELOPE_NS,
+      SoapVersion::Soap12 => SOAP12_ENVELOPE_NS,
+    }
+  }
+
+  /// The media type of messages of this version
+  pub fn content_type(self) -> &'static str {
+    match self {
+      SoapVersion::Soap11 => "text/xml",
+      SoapVersion::Soap12 => "application/soap+xml",
+    }
+  }
+
+  fn from_namespace(href: &str) -> Option<Self> {
+    match href {
+      SOAP11_ENVELOPE_NS => Some(SoapVersion::Soap11),
+      SOAP12_ENVELOPE_NS => Some(SoapVersion::Soap12),
+      _ => None,
+    }
+  }
+
+  /// Name of the header block attribute selecting the targeted node
+  fn role_attribute(self) -> &'static str {
+    match self {
+      SoapVersion::Soap11 => "actor",
+      SoapVersion::Soap12 => "role",
+    }
+  }
+}
+
+/// Errors raised while reading or building envelopes
+#[derive(Debug)]
+pub enum SoapError {
+  /// The message is not well-formed XML
+  Parse(XmlParseError),
+  /// The root element is not a SOAP 1.1 or 1.2 `Envelope`
+  NotAnEnvelope,
+  /// The envelope has no `Body` element
+  MissingBody,
+  /// The detail of a fault is not well-formed XML content
+  InvalidDetail(XmlParseError),
+  /// libxml2 refused to create or attach a node
+  Tree(String),
+}
+
+impl Error for SoapError {}
+
+impl fmt::Display for SoapError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SoapError::Parse(e) => write!(f, "failed to parse SOAP message: {e}"),
+      SoapError::NotAnEnvelope => write!(f, "root element is not a SOAP envelope"),
+      SoapError::MissingBody => write!(f, "SOAP envelope has no body"),
+      SoapError::InvalidDetail(e) => write!(f, "malformed SOAP fault detail: {e}"),
+      SoapError::Tree(e) => write!(f, "failed to build SOAP envelope: {e}"),
+    }
+  }
+}
+
+impl From<XmlParseError> for SoapError {
+  fn from(e: XmlParseError) -> Self {
+    SoapError::Parse(e)
+  }
+}
+
+/// The class of a fault, SOAP 1.1 names are mapped onto their SOAP 1.2 equivalents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FaultCode {
+  /// The envelope namespace was not recognized
+  VersionMismatch,
+  /// A mandatory header block was not understood
+  MustUnderstand,
+  /// A data encoding was not supported (SOAP 1.2 only)
+  DataEncodingUnknown,
+  /// The message was incorrect (`Client` in SOAP 1.1)
+  Sender,
+  /// The message could not be processed (`Server` in SOAP 1.1)
+  Receiver,
+  /// Any other code, as written in the message
+  Other(String),
+}
+
+impl FaultCode {
+  fn local_name(&self, version: SoapVersion) -> &str {
+    match (self, version) {
+      (FaultCode::VersionMismatch, _) => "VersionMismatch",
+      (FaultCode::MustUnderstand, _) => "MustUnderstand",
+      (FaultCode::DataEncodingUnknown, _) => "DataEncodingUnknown",
+      (FaultCode::Sender, SoapVersion::Soap11) => "Client",
+      (FaultCode::Sender, SoapVersion::Soap12) => "Sender",
+      (FaultCode::Receiver, SoapVersion::Soap11) => "Server",
+      (FaultCode::Receiver, SoapVersion::Soap12) => "Receiver",
+      (FaultCode::Other(code), _) => code,
+    }
+  }
+
+  fn from_local_name(name: &str) -> Option<Self> {
+    match name {
+      "VersionMismatch" => Some(FaultCode::VersionMismatch),
+      "MustUnderstand" => Some(FaultCode::MustUnderstand),
+      "DataEncodingUnknown" => Some(FaultCode::DataEncodingUnknown),
+      "Client" | "Sender" => Some(FaultCode::Sender),
+      "Server" | "Receiver" => Some(FaultCode::Receiver),
+      _ => None,
+    }
+  }
+}
+
+/// A SOAP fault
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+  /// The fault code
+  pub code: FaultCode,
+  /// Subcodes as written in the message (SOAP 1.2 `Subcode` values, or the dotted
+  /// suffixes of a SOAP 1.1 `faultcode`)
+  pub subcodes: Vec<String>,
+  /// Human-readable explanation (`faultstring` / the first `Reason/Text`)
+  pub reason: String,
+  /// The node that faulted (SOAP 1.2 `Node`)
+  pub node: Option<String>,
+  /// The role of the faulting node (`faultactor` / `Role`)
+  pub role: Option<String>,
+  /// Serialized content of the `detail` / `Detail` element
+  pub detail: Option<String>,
+}
+
+impl Fault {
+  /// Creates a fault with a code and reason only
+  pub fn new(code: FaultCode, reason: &str) -> Self {
+    Fault {
+      code,
+      subcodes: Vec::new(),
+      reason: reason.to_string(),
+      node: None,
+      role: None,
+      detail: None,
+    }
+  }
+}
+
+/// A SOAP envelope
+pub struct Envelope {
+  document: Document,
+  version: SoapVersion,
+}
+
+impl Envelope {
+  /// Creates an envelope with an empty body
+  pub fn new(version: SoapVersion) -> Result<Self, SoapError> {
+    let mut document =
+      Document::new().map_err(|_| SoapError::Tree("xmlNewDoc returned NULL".into()))?;
+    let mut root = Node::new("Envelope", None, &document)
+      .map_err(|_| SoapError::Tree("could not create Envelope".into()))?;
+    let ns = Namespace::new("soap", version.namespace(), &mut root).map_err(tree_error)?;
+    root.set_namespace(&ns).map_err(tree_error)?;
+    document.set_root_element(&root);
+    root.new_child(Some(ns), "Body").map_err(tree_error)?;
+    Ok(Envelope { document, version })
+  }
+
+  /// Parses a SOAP message
+  pub fn parse<Bytes: AsRef<[u8]>>(message: Bytes) -> Result<Self, SoapError> {
+    Envelope::from_document(Parser::default().parse_string(message)?)
+  }
+
+  /// Wraps a document whose root element is a SOAP envelope
+  pub fn from_document(document: Document) -> Result<Self, SoapError> {
+    let root = document
+      .get_root_element()
+      .ok_or(SoapError::NotAnEnvelope)?;
+    let version = root
+      .get_namespace()
+      .and_then(|ns| SoapVersion::from_namespace(&ns.get_href()))
+      .filter(|_| root.get_name() == "Envelope")
+      .ok_or(SoapError::NotAnEnvelope)?;
+    let envelope = Envelope { document, version };
+    if envelope.find("Body").is_none() {
+      return Err(SoapError::MissingBody);
+    }
+    Ok(envelope)
+  }
+
+  /// The SOAP version, as given by the envelope namespace
+  pub fn version(&self) -> SoapVersion {
+    self.version
+  }
+
+  /// The underlying document
+  pub fn document(&self) -> &Document {
+    &self.document
+  }
+
+  /// Unwraps the underlying document
+  pub fn into_document(self) -> Document {
+    self.document
+  }
+
+  /// The `Header` element, if present
+  pub fn header(&self) -> Option<Node> {
+    self.find("Header")
+  }
+
+  /// The `Body` element, which is only missing once removed through the document
+  pub fn body(&self) -> Result<Node, SoapError> {
+    self.find("Body").ok_or(SoapError::MissingBody)
+  }
+
+  /// The child elements of the `Header`
+  pub fn header_blocks(&self) -> Vec<Node> {
+    self
+      .header()
+      .map(|header| header.get_child_elements())
+      .unwrap_or_default()
+  }
+
+  /// The child elements of the `Body`
+  pub fn body_elements(&self) -> Vec<Node> {
+    self
+      .body()
+      .map(|body| body.get_child_elements())
+      .unwrap_or_default()
+  }
+
+  /// Appends a header block, creating the `Header` element if needed
+  pub fn add_header_block(&mut self, block: &mut Node) -> Result<(), SoapError> {
+    let mut header = match self.header() {
+      Some(header) => header,
+      None => {
+        let mut header = Node::new("Header", Some(self.namespace()?), &self.document)
+          .map_err(|_| SoapError::Tree("could not create Header".into()))?;
+        self
+          .body()?
+          .add_prev_sibling(&mut header)
+          .map_err(tree_error)?;
+        header
+      }
+    };
+    header.add_child(block).map_err(SoapError::Tree)
+  }
+
+  /// Appends an element to the `Body`
+  pub fn add_body_element(&mut self, element: &mut Node) -> Result<(), SoapError> {
+    self.body()?.add_child(element).map_err(SoapError::Tree)
+  }
+
+  /// Whether a header block carries `mustUnderstand="1"` (or `"true"`)
+  pub fn is_must_understand(&self, block: &Node) -> bool {
+    matches!(
+      block
+        .get_property_ns("mustUnderstand", self.version.namespace())
+        .as_deref()
+        .map(str::trim),
+      Some("1") | Some("true")
+    )
+  }
+
+  /// The role (`actor` in SOAP 1.1) a header block is targeted at, if any
+  pub fn role_of(&self, block: &Node) -> Option<String> {
+    block.get_property_ns(self.version.role_attribute(), self.version.namespace())
+  }
+
+  /// Mandatory header blocks targeted at a node acting in `roles`. Blocks without a role,
+  /// or targeted at the "next" node or the ultimate receiver, always apply.
+  pub fn must_understand_blocks(&self, roles: &[&str]) -> Vec<Node> {
+    self
+      .header_blocks()
+      .into_iter()
+      .filter(|block| self.is_must_understand(block))
+      .filter(|block| match self.role_of(block) {
+        None => true,
+        Some(role) => {
+          role.is_empty()
+            || role == SOAP11_NEXT_ACTOR
+            || role == SOAP12_NEXT_ROLE
+            || role == SOAP12_ULTIMATE_RECEIVER_ROLE
+            || roles.contains(&role.as_str())
+        }
+      })
+      .collect()
+  }
+
+  /// Checks that all mandatory header blocks targeted at `roles` are among the `understood`
+  /// (namespace, local name) pairs, returning a `MustUnderstand` fault for the first that is not
+  pub fn check_must_understand(
+    &self,
+    roles: &[&str],
+    understood: &[(&str, &str)],
+  ) -> Option<Fault> {
+    for block in self.must_understand_blocks(roles) {
+      let href = block
+        .get_namespace()
+        .map(|ns| ns.get_href())
+        .unwrap_or_default();
+      let name = block.get_name();
+      if !understood.contains(&(href.as_str(), name.as_str())) {
+        return Some(Fault::new(
+          FaultCode::MustUnderstand,
+          &format!("Header block {{{href}}}{name} was not understood"),
+        ));
+      }
+    }
+    None
+  }
+
+  /// The fault carried by the body, if any
+  pub fn fault(&self) -> Option<Fault> {
+    let fault = self.find_in(&self.find("Body")?, "Fault")?;
+    Some(match self.version {
+      SoapVersion::Soap11 => {
+        let code_text = child_text(&fault, None, "faultcode").unwrap_or_default();
+        let (local, in_envelope_ns) = self.resolve_code(&fault, &code_text);
+        let mut parts = local.split('.');
+        let (code, subcodes) = match parts.next().and_then(FaultCode::from_local_name) {
+          Some(code) if in_envelope_ns => (code, parts.map(str::to_string).collect()),
+          _ => (FaultCode::Other(code_text), Vec::new()),
+        };
+        Fault {
+          code,
+          subcodes,
+          reason: child_text(&fault, None, "faultstring").unwrap_or_default(),
+          node: None,
+          role: child_text(&fault, None, "faultactor"),
+          detail: find_child(&fault, None, "detail").map(|detail| self.inner_xml(&detail)),
+        }
+      }
+      SoapVersion::Soap12 => {
+        let ns = Some(SOAP12_ENVELOPE_NS);
+        let mut subcodes = Vec::new();
+        let mut code = FaultCode::Other(String::new());
+        if let Some(code_element) = find_child(&fault, ns, "Code") {
+          let value = child_text(&code_element, ns, "Value").unwrap_or_default();
+          code = match self.resolve_code(&code_element, &value) {
+            (local, true) => FaultCode::from_local_name(&local).unwrap_or(FaultCode::Other(value)),
+            (_, false) => FaultCode::Other(value),
+          };
+          let mut current = find_child(&code_element, ns, "Subcode");
+          while let Some(subcode) = current {
+            subcodes.push(child_text(&subcode, ns, "Value").unwrap_or_default());
+            current = find_child(&subcode, ns, "Subcode");
+          }
+        }
+        Fault {
+          code,
+          subcodes,
+          reason: find_child(&fault, ns, "Reason")
+            .and_then(|reason| child_text(&reason, ns, "Text"))
+            .unwrap_or_default(),
+          node: child_text(&fault, ns, "Node"),
+          role: child_text(&fault, ns, "Role"),
+          detail: find_child(&fault, ns, "Detail").map(|detail| self.inner_xml(&detail)),
+        }
+      }
+    })
+  }
+
+  /// Replaces the body content by `fault`. Subcodes must be qualified names whose prefixes
+  /// are in scope, and `detail` well-formed XML content; the body is left as it is when it
+  /// is not.
+  pub fn set_fault(&mut self, fault: &Fault) -> Result<(), SoapError> {
+    let detail = fault.detail.as_deref().map(parse_detail).transpose()?;
+    let mut body = self.body()?;
+    let ns = self.namespace()?;
+    for mut child in body.get_child_nodes() {
+      child.unlink();
+    }
+    let prefix = ns.get_prefix();
+    let qualify = |local: &str| {
+      if prefix.is_empty() {
+        local.to_string()
+      } else {
+        format!("{prefix}:{local}")
+      }
+    };
+    let mut fault_element = body
+      .new_child(Some(ns.clone()), "Fault")
+      .map_err(tree_error)?;
+
+    let detail_parent = match self.version {
+      SoapVersion::Soap11 => {
+        let code = match &fault.code {
+          FaultCode::Other(code) => code.clone(),
+          known => std::iter::once(qualify(known.local_name(self.version)))
+            .chain(fault.subcodes.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("."),
+        };
+        fault_element
+          .add_text_child(None, "faultcode", &code)
+          .map_err(tree_error)?;
+        fault_element
+          .add_text_child(None, "faultstring", &fault.reason)
+          .map_err(tree_error)?;
+        if let Some(role) = &fault.role {
+          fault_element
+            .add_text_child(None, "faultactor", role)
+            .map_err(tree_error)?;
+        }
+        detail
+          .as_ref()
+          .map(|_| fault_element.new_child(None, "detail"))
+          .transpose()
+          .map_err(tree_error)?
+      }
+      SoapVersion::Soap12 => {
+        let mut code = fault_element
+          .new_child(Some(ns.clone()), "Code")
+          .map_err(tree_error)?;
+        let value = match &fault.code {
+          FaultCode::Other(value) => value.clone(),
+          known => qualify(known.local_name(self.version)),
+        };
+        code
+          .add_text_child(Some(ns.clone()), "Value", &value)
+          .map_err(tree_error)?;
+        let mut parent = code;
+        for subcode in &fault.subcodes {
+          let mut subcode_element = parent
+            .new_child(Some(ns.clone()), "Subcode")
+            .map_err(tree_error)?;
+          subcode_element
+            .add_text_child(Some(ns.clone()), "Value", subcode)
+            .map_err(tree_error)?;
+          parent = subcode_element;
+        }
+        let mut reason = fault_element
+          .new_child(Some(ns.clone()), "Reason")
+          .map_err(tree_error)?;
+        let mut text = reason
+          .add_text_child(Some(ns.clone()), "Text", &fault.reason)
+          .map_err(tree_error)?;
+        let xml_ns = text
+          .search_namespace(Some("xml"))
+          .ok_or_else(|| SoapError::Tree("xml namespace not found".into()))?;
+        text
+          .set_property_ns("lang", "en", &xml_ns)
+          .map_err(tree_error)?;
+        if let Some(node) = &fault.node {
+          fault_element
+            .add_text_child(Some(ns.clone()), "Node", node)
+            .map_err(tree_error)?;
+        }
+        if let Some(role) = &fault.role {
+          fault_element
+            .add_text_child(Some(ns.clone()), "Role", role)
+            .map_err(tree_error)?;
+        }
+        detail
+          .as_ref()
+          .map(|_| fault_element.new_child(Some(ns.clone()), "Detail"))
+          .transpose()
+          .map_err(tree_error)?
+      }
+    };
+
+    if let (Some(mut detail), Some(fragment)) = (detail_parent, detail) {
+      let root = fragment
+        .get_root_element()
+        .ok_or(SoapError::InvalidDetail(XmlParseError::GotNullPointer))?;
+      for child in root.get_child_nodes() {
+        let mut imported = self
+          .document
+          .import_node(&child, true)
+          .map_err(|_| SoapError::Tree("could not import fault detail".into()))?;
+        detail.add_child(&mut imported).map_err(SoapError::Tree)?;
+      }
+    }
+    Ok(())
+  }
+
+  fn namespace(&self) -> Result<Namespace, SoapError> {
+    self
+      .document
+      .get_root_element()
+      .and_then(|root| root.get_namespace())
+      .ok_or(SoapError::NotAnEnvelope)
+  }
+
+  fn find(&self, name: &str) -> Option<Node> {
+    self.find_in(&self.document.get_root_element()?, name)
+  }
+
+  fn find_in(&self, parent: &Node, name: &str) -> Option<Node> {
+    find_child(parent, Some(self.version.namespace()), name)
+  }
+
+  /// Resolves a QName-valued code, returning its local name and whether it lives in the
+  /// envelope namespace
+  fn resolve_code(&self, context: &Node, qname: &str) -> (String, bool) {
+    let qname = qname.trim();
+    let (prefix, local) = match qname.split_once(':') {
+      Some((prefix, local)) => (Some(prefix), local),
+      None => (None, qname),
+    };
+    let href = context.search_namespace(prefix).map(|ns| ns.get_href());
+    let in_envelope_ns = href.as_deref() == Some(self.version.namespace())
+      // unqualified SOAP 1.1 codes are common in the wild
+      || (prefix.is_none() && self.version == SoapVersion::Soap11);
+    (local.to_string(), in_envelope_ns)
+  }
+
+  fn inner_xml(&self, node: &Node) -> String {
+    node
+      .get_child_nodes()
+      .iter()
+      .map(|child| self.document.node_to_string(child))
+      .collect()
+  }
+}
+
+/// Parses the content of a fault detail into a `detail` root element, without recovering
+/// from malformed markup
+fn parse_detail(xml: &str) -> Result<Document, SoapError> {
+  let options = ParserOptions {
+    recover: false,
+    no_net: true,
+    ..ParserOptions::default()
+  };
+  Parser::default()
+    .parse_string_with_options(format!("<detail>{xml}</detail>"), options)
+    .map_err(SoapError::InvalidDetail)
+}
+
+fn find_child(parent: &Node, ns: Option<&str>, name: &str) -> Option<Node> {
+  parent.get_child_elements().into_iter().find(|child| {
+    child.get_name() == name && child.get_namespace().map(|ns| ns.get_href()).as_deref() == ns
+  })
+}
+
+fn child_text(parent: &Node, ns: Option<&str>, name: &str) -> Option<String> {
+  find_child(parent, ns, name).map(|child| child.get_content().trim().to_string())
+}
+
+fn tree_error(e: Box<dyn Error + Send + Sync>) -> SoapError {
+  SoapError::Tree(e.to_string())
+}
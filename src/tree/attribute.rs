@@ -0,0 +1,115 @@
+//! Attributes with their namespaces
+//!
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::tree::{DocumentWeak, Node, NodeType};
+
+/// An attribute of an element, see [`Node::attributes`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Attribute {
+  /// The local part of the name
+  pub local_name: String,
+  /// The prefix of the name, if it has one
+  pub prefix: Option<String>,
+  /// The namespace URI, if the attribute is in a namespace
+  pub namespace: Option<String>,
+  /// The value, with entity references replaced
+  pub value: String,
+}
+
+impl Attribute {
+  /// The name as written, `prefix:local_name` or only the local name
+  pub fn qualified_name(&self) -> String {
+    match &self.prefix {
+      Some(prefix) => format!("{}:{}", prefix, self.local_name),
+      None => self.local_name.clone(),
+    }
+  }
+}
+
+/// The attributes of an element in document order, see [`Node::attributes`]
+pub struct Attributes {
+  next: xmlAttrPtr,
+  document: DocumentWeak,
+}
+
+impl Node {
+  /// Iterates over the attributes of this element in document order, with their
+  /// namespaces. Namespace declarations are not attributes, see
+  /// [`Node::get_namespace_declarations`].
+  pub fn attributes(&self) -> Attributes {
+    // Only elements have attributes, other nodes may not even have the field
+    let next = if self.get_type() == Some(NodeType::ElementNode) {
+      xmlGetFirstProperty(self.node_ptr())
+    } else {
+      std::ptr::null_mut()
+    };
+    Attributes {
+      next,
+      document: self.get_docref(),
+    }
+  }
+
+  /// The names and values of the attributes of this element in document order, unlike
+  /// [`Node::get_properties`]. Names are qualified with their prefix, if any.
+  pub fn attributes_ordered(&self) -> Vec<(String, String)> {
+    self
+      .attributes()
+      .map(|attribute| (attribute.qualified_name(), attribute.value))
+      .collect()
+  }
+
+  /// The names and values of the attributes of this element sorted by name, as in
+  /// [`Node::attributes_ordered`]
+  pub fn attributes_sorted(&self) -> BTreeMap<String, String> {
+    self.attributes_ordered().into_iter().collect()
+  }
+}
+
+impl Iterator for Attributes {
+  type Item = Attribute;
+
+  fn next(&mut self) -> Option<Attribute> {
+    if self.next.is_null() {
+      return None;
+    }
+    // The attributes are freed with the document
+    let _document = self.document.upgrade()?;
+    let attr = self.next;
+    self.next = xmlNextPropertySibling(attr);
+
+    let ns_ptr = xmlAttrNs(attr);
+    let (prefix, namespace) = if ns_ptr.is_null() {
+      (None, None)
+    } else {
+      (
+        owned_string(xmlNsPrefix(ns_ptr)),
+        owned_string(xmlNsHref(ns_ptr)),
+      )
+    };
+    let value_ptr = unsafe { xmlNodeGetContent(attr as xmlNodePtr) };
+    let value = owned_string(value_ptr as *const c_char).unwrap_or_default();
+    if !value_ptr.is_null() {
+      bindgenFree(value_ptr as *mut c_void);
+    }
+    Some(Attribute {
+      local_name: owned_string(xmlAttrName(attr)).unwrap_or_default(),
+      prefix,
+      namespace,
+      value,
+    })
+  }
+}
+
+/// A copy of the C string at `ptr`, if any
+fn owned_string(ptr: *const c_char) -> Option<String> {
+  if ptr.is_null() {
+    None
+  } else {
+    Some(unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+  }
+}
@@ -0,0 +1,304 @@
+//! Fluent construction of element trees
+//!
+//! An [`ElementBuilder`] describes an element with its namespace, attributes and content,
+//! and creates the whole subtree in a document at once:
+//!
+//! ```
+//! use libxml::tree::{Document, Node};
+//!
+//! let mut doc = Document::new().unwrap();
+//! Node::build("report")
+//!   .ns("", "urn:reports")
+//!   .attr("version", "2")
+//!   .child(Node::build("item").attr("id", "1").text("first"))
+//!   .child(Node::build("item").attr("id", "2").text("second"))
+//!   .into_root(&mut doc)
+//!   .unwrap();
+//! assert_eq!(
+//!   doc.node_to_string(&doc.get_root_element().unwrap()),
+//!   r#"<report xmlns="urn:reports" version="2"><item id="1">first</item><item id="2">second</item></report>"#
+//! );
+//! ```
+//!
+use std::error::Error;
+use std::ffi::CString;
+use std::ptr;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::fragment::link_last_child;
+use crate::tree::observer::{self, Mutation};
+use crate::tree::{Document, Namespace, Node, NodeType};
+
+/// Content of an element under construction
+#[derive(Debug, Clone)]
+enum Content {
+  Element(ElementBuilder),
+  Text(String),
+}
+
+/// Describes an element to create, see [`Node::build`]
+#[derive(Debug, Clone)]
+pub struct ElementBuilder {
+  name: String,
+  namespace: Option<(String, String)>,
+  attributes: Vec<(String, String)>,
+  content: Vec<Content>,
+}
+
+impl Node {
+  /// Starts describing an element with the local name `name`, to create with
+  /// [`ElementBuilder::create`]
+  pub fn build(name: &str) -> ElementBuilder {
+    ElementBuilder {
+      name: name.to_owned(),
+      namespace: None,
+      attributes: Vec::new(),
+      content: Vec::new(),
+    }
+  }
+}
+
+impl ElementBuilder {
+  /// Puts the element in the namespace `href`, declared on it with `prefix`, `""` for the
+  /// default namespace. Descendants without a namespace of their own inherit a default one.
+  pub fn ns(mut self, prefix: &str, href: &str) -> Self {
+    self.namespace = Some((prefix.to_owned(), href.to_owned()));
+    self
+  }
+
+  /// Sets the attribute `name` to `value`
+  pub fn attr(mut self, name: &str, value: &str) -> Self {
+    self.attributes.push((name.to_owned(), value.to_owned()));
+    self
+  }
+
+  /// Appends the element `child`
+  pub fn child(mut self, child: ElementBuilder) -> Self {
+    self.content.push(Content::Element(child));
+    self
+  }
+
+  /// Appends the elements `children`
+  pub fn children<I: IntoIterator<Item = ElementBuilder>>(mut self, children: I) -> Self {
+    self
+      .content
+      .extend(children.into_iter().map(Content::Element));
+    self
+  }
+
+  /// Appends the text `text`
+  pub fn text(mut self, text: &str) -> Self {
+    self.content.push(Content::Text(text.to_owned()));
+    self
+  }
+
+  /// Creates the element with its subtree in `doc`, not linked into its tree yet
+  pub fn create(&self, doc: &Document) -> Result<Node, Box<dyn Error + Send + Sync>> {
+    self.create_within(doc, None)
+  }
+
+  /// Creates the element with its subtree and appends it to the children of `parent`
+  pub fn append_to(&self, parent: &mut Node) -> Result<Node, Box<dyn Error + Send + Sync>> {
+    let doc = parent
+      .get_docref()
+      .upgrade()
+      .map(Document)
+      .ok_or("the parent node has no document")?;
+    let mut node = self.create(&doc)?;
+    parent.add_child(&mut node)?;
+    Ok(node)
+  }
+
+  /// Creates the element with its subtree as the root element of `doc`
+  pub fn into_root(self, doc: &mut Document) -> Result<Node, Box<dyn Error + Send + Sync>> {
+    let node = self.create(doc)?;
+    doc.set_root_element(&node);
+    Ok(node)
+  }
+
+  fn create_within(
+    &self,
+    doc: &Document,
+    default_ns: Option<&Namespace>,
+  ) -> Result<Node, Box<dyn Error + Send + Sync>> {
+    let mut node = Node::new(&self.name, None, doc).map_err(|()| "could not create the element")?;
+    let namespace = match &self.namespace {
+      Some((prefix, href)) => Some(Namespace::new(prefix, href, &mut node)?),
+      None => default_ns.cloned(),
+    };
+    if let Some(namespace) = &namespace {
+      node.set_namespace(namespace)?;
+    }
+    for (name, value) in &self.attributes {
+      node.set_attribute(name, value)?;
+    }
+    let default_ns = namespace
+      .as_ref()
+      .filter(|namespace| namespace.get_prefix().is_empty())
+      .or(default_ns);
+    for content in &self.content {
+      match content {
+        Content::Element(child) => {
+          let mut child = child.create_within(doc, default_ns)?;
+          node.add_child(&mut child)?;
+        }
+        Content::Text(text) => node.append_text(text)?,
+      }
+    }
+    Ok(node)
+  }
+}
+
+/// A step in building nodes from a stream of events, see [`Node::append_events`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildEvent<'a> {
+  /// Opens an element, to be closed by a matching [`BuildEvent::EndElement`]. Names are taken
+  /// as they are, without namespace processing.
+  StartElement {
+    /// The name of the element
+    name: &'a str,
+    /// Its attributes, as name and value
+    attributes: &'a [(&'a str, &'a str)],
+  },
+  /// Closes the element opened last
+  EndElement,
+  /// A text node, its content escaped when serialized
+  Text(&'a str),
+  /// A comment
+  Comment(&'a str),
+}
+
+impl Node {
+  /// Creates nodes from `events` and appends them to the children of this node, returning
+  /// how many nodes were appended at this level. The nodes are only wrapped in a `Node` when
+  /// asked for, so large batches cost no bookkeeping. Nothing is appended when the events are
+  /// unbalanced or a name or text can't be used.
+  pub fn append_events<'a>(
+    &mut self,
+    events: impl IntoIterator<Item = BuildEvent<'a>>,
+  ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    if !matches!(
+      self.get_type(),
+      Some(NodeType::ElementNode | NodeType::DocumentFragNode)
+    ) {
+      return Err(From::from("only elements and fragments can have events appended"));
+    }
+    let parent_ptr = self.node_ptr_mut()?;
+    let nodes = build_events(xmlGetDoc(parent_ptr), events)?;
+    let document = self.get_docref();
+    for &node_ptr in &nodes {
+      unsafe { link_last_child(parent_ptr, node_ptr) };
+      observer::notify(&document, || Mutation::Inserted {
+        node: RoNode(node_ptr),
+        parent: RoNode(parent_ptr),
+      });
+    }
+    Ok(nodes.len())
+  }
+}
+
+impl Document {
+  /// Creates the root element of the document from `events`, which must describe a single
+  /// element, in place of the current one, see [`Node::append_events`]
+  pub fn build_from_events<'a>(
+    &mut self,
+    events: impl IntoIterator<Item = BuildEvent<'a>>,
+  ) -> Result<Node, Box<dyn Error + Send + Sync>> {
+    let nodes = build_events(self.doc_ptr(), events)?;
+    let is_element =
+      |node_ptr: &xmlNodePtr| xmlGetNodeType(*node_ptr) == xmlElementType_XML_ELEMENT_NODE;
+    if nodes.len() != 1 || !is_element(&nodes[0]) {
+      for node_ptr in nodes {
+        unsafe { xmlFreeNode(node_ptr) };
+      }
+      return Err(From::from("the events must describe a single root element"));
+    }
+    let root = Node::wrap_new(nodes[0], &self.0);
+    self.set_root_element(&root);
+    Ok(root)
+  }
+}
+
+/// Creates the nodes `events` describe in `doc_ptr`, returning the unlinked top-level ones
+fn build_events<'a>(
+  doc_ptr: xmlDocPtr,
+  events: impl IntoIterator<Item = BuildEvent<'a>>,
+) -> Result<Vec<xmlNodePtr>, Box<dyn Error + Send + Sync>> {
+  let mut nodes = Vec::new();
+  let mut open: Vec<xmlNodePtr> = Vec::new();
+  let result = events.into_iter().try_for_each(|event| {
+    let node_ptr = match event {
+      BuildEvent::StartElement { name, attributes } => {
+        let c_name = CString::new(name)?;
+        let name_ptr = c_name.as_ptr() as *const xmlChar;
+        let element = unsafe { xmlNewDocNode(doc_ptr, ptr::null_mut(), name_ptr, ptr::null()) };
+        if element.is_null() {
+          return Err(From::from(format!("could not create the element {name}")));
+        }
+        attach(&mut nodes, &open, element);
+        for (name, value) in attributes {
+          let c_name = CString::new(*name)?;
+          let c_value = CString::new(*value)?;
+          let attr = unsafe {
+            xmlNewProp(
+              element,
+              c_name.as_ptr() as *const xmlChar,
+              c_value.as_ptr() as *const xmlChar,
+            )
+          };
+          if attr.is_null() {
+            return Err(From::from(format!("could not set the attribute {name}")));
+          }
+        }
+        open.push(element);
+        return Ok(());
+      }
+      BuildEvent::EndElement => {
+        return match open.pop() {
+          Some(_) => Ok(()),
+          None => Err(From::from("an element is closed which was not opened")),
+        };
+      }
+      BuildEvent::Text(text) => {
+        let c_text = CString::new(text)?;
+        unsafe { xmlNewDocText(doc_ptr, c_text.as_ptr() as *const xmlChar) }
+      }
+      BuildEvent::Comment(comment) => {
+        let c_comment = CString::new(comment)?;
+        unsafe { xmlNewDocComment(doc_ptr, c_comment.as_ptr() as *const xmlChar) }
+      }
+    };
+    if node_ptr.is_null() {
+      return Err(From::from("could not create a node"));
+    }
+    attach(&mut nodes, &open, node_ptr);
+    Ok(())
+  });
+  let result = result.and_then(|()| {
+    if open.is_empty() {
+      Ok(())
+    } else {
+      Err(From::from("an element is left open"))
+    }
+  });
+  match result {
+    Ok(()) => Ok(nodes),
+    Err(error) => {
+      // Descendants go with the top-level nodes
+      for node_ptr in nodes {
+        unsafe { xmlFreeNode(node_ptr) };
+      }
+      Err(error)
+    }
+  }
+}
+
+/// Links `node_ptr` into the element open last, or keeps it as a top-level node
+fn attach(nodes: &mut Vec<xmlNodePtr>, open: &[xmlNodePtr], node_ptr: xmlNodePtr) {
+  match open.last() {
+    Some(&parent_ptr) => unsafe { link_last_child(parent_ptr, node_ptr) },
+    None => nodes.push(node_ptr),
+  }
+}
@@ -1,20 +1,27 @@
 //! Document feature set
 //!
-use libc::{c_char, c_int};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
 use std::ffi::{CStr, CString};
 use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::rc::{Rc, Weak};
 use std::str;
 
 use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::parser::Compression;
 use crate::readonly::RoNode;
 use crate::tree::node::Node;
+use crate::tree::nodetype::NodeType;
+use crate::tree::noderef;
+use crate::tree::observer::{self, Mutation, Observers};
+use crate::tree::traversal::next_in_document_order;
 
-pub(crate) type DocumentRef = Rc<RefCell<_Document>>;
-pub(crate) type DocumentWeak = Weak<RefCell<_Document>>;
+pub(crate) type DocumentRef = Rc<_Document>;
+pub(crate) type DocumentWeak = Weak<_Document>;
 
 #[derive(Debug, Copy, Clone, Default)]
 /// Save Options for Document
@@ -37,26 +44,56 @@ pub struct SaveOptions {
   pub non_significant_whitespace: bool,
 }
 
+/// The state shared by the handles to a document.
+///
+/// Its cells are only borrowed within the methods here, which neither run callbacks nor drop
+/// nodes while a borrow is held, so that no use of the document from a callback, an iterator
+/// or a `Drop` can find it already borrowed.
 #[derive(Debug)]
 pub(crate) struct _Document {
   /// pointer to a libxml document
-  pub(crate) doc_ptr: xmlDocPtr,
+  doc_ptr: Cell<xmlDocPtr>,
   /// hashed pointer-to-Node bookkeeping table
-  nodes: HashMap<xmlNodePtr, Node>,
+  nodes: RefCell<HashMap<xmlNodePtr, Node>>,
+  /// callbacks notified of mutations
+  pub(crate) observers: Observers,
+  /// internal subsets replaced while a `Dtd` may still refer to them
+  replaced_dtds: RefCell<Vec<xmlDtdPtr>>,
 }
 
 impl _Document {
+  pub(crate) fn new(doc_ptr: xmlDocPtr) -> DocumentRef {
+    Rc::new(_Document {
+      doc_ptr: Cell::new(doc_ptr),
+      nodes: RefCell::new(HashMap::new()),
+      observers: Observers::default(),
+      replaced_dtds: RefCell::new(Vec::new()),
+    })
+  }
+  /// pointer to the libxml document
+  pub(crate) fn doc_ptr(&self) -> xmlDocPtr {
+    self.doc_ptr.get()
+  }
+  pub(crate) fn set_doc_ptr(&self, doc_ptr: xmlDocPtr) {
+    self.doc_ptr.set(doc_ptr);
+  }
   /// Internal bookkeeping function, so far only used by `Node::wrap`
-  pub(crate) fn insert_node(&mut self, node_ptr: xmlNodePtr, node: Node) {
-    self.nodes.insert(node_ptr, node);
+  pub(crate) fn insert_node(&self, node_ptr: xmlNodePtr, node: Node) {
+    let replaced = self.nodes.borrow_mut().insert(node_ptr, node);
+    // Dropped once the table is no longer borrowed, as freeing a node calls back
+    drop(replaced);
   }
   /// Internal bookkeeping function, so far only used by `Node::wrap`
-  pub(crate) fn get_node(&self, node_ptr: xmlNodePtr) -> Option<&Node> {
-    self.nodes.get(&node_ptr)
+  pub(crate) fn get_node(&self, node_ptr: xmlNodePtr) -> Option<Node> {
+    self.nodes.borrow().get(&node_ptr).cloned()
   }
   /// Internal bookkeeping function
-  pub(crate) fn forget_node(&mut self, node_ptr: xmlNodePtr) {
-    self.nodes.remove(&node_ptr);
+  pub(crate) fn forget_node(&self, node_ptr: xmlNodePtr) -> Option<Node> {
+    self.nodes.borrow_mut().remove(&node_ptr)
+  }
+  /// Keeps the unlinked `dtd_ptr` until the document is freed
+  pub(crate) fn keep_replaced_dtd(&self, dtd_ptr: xmlDtdPtr) {
+    self.replaced_dtds.borrow_mut().push(dtd_ptr);
   }
 }
 
@@ -68,8 +105,13 @@ impl Drop for _Document {
   ///Free document when it goes out of scope
   fn drop(&mut self) {
     unsafe {
-      if !self.doc_ptr.is_null() {
-        xmlFreeDoc(self.doc_ptr);
+      // Before the document, whose dictionary holds their strings
+      for dtd_ptr in self.replaced_dtds.get_mut().drain(..) {
+        xmlFreeDtd(dtd_ptr);
+      }
+      let doc_ptr = self.doc_ptr.get();
+      if !doc_ptr.is_null() {
+        xmlFreeDoc(doc_ptr);
       }
     }
   }
@@ -91,41 +133,42 @@ impl Document {
       if doc_ptr.is_null() {
         Err(())
       } else {
-        let doc = _Document {
-          doc_ptr,
-          nodes: HashMap::new(),
-        };
-        Ok(Document(Rc::new(RefCell::new(doc))))
+        Ok(Document(_Document::new(doc_ptr)))
       }
     }
   }
 
   /// Obtain the underlying libxml2 `xmlDocPtr` for this Document
   pub fn doc_ptr(&self) -> xmlDocPtr {
-    self.0.borrow().doc_ptr
+    self.0.doc_ptr()
   }
 
   /// Creates a new `Document` from an existing libxml2 pointer
   pub fn new_ptr(doc_ptr: xmlDocPtr) -> Self {
-    let doc = _Document {
-      doc_ptr,
-      nodes: HashMap::new(),
-    };
-    Document(Rc::new(RefCell::new(doc)))
+    Document(_Document::new(doc_ptr))
   }
 
   pub(crate) fn null_ref() -> DocumentRef {
-    Rc::new(RefCell::new(_Document {
-      doc_ptr: ptr::null_mut(),
-      nodes: HashMap::new(),
-    }))
+    _Document::new(ptr::null_mut())
   }
 
-  /// Write document to `filename`
+  /// Write document to `filename`.
+  ///
+  /// Names ending in `.gz` are gzip compressed, which fails unless
+  /// `Compression::Gzip.is_available()`; libxml2 can't write `.xz` files.
   pub fn save_file(&self, filename: &str) -> Result<c_int, ()> {
+    let compress = match Compression::from_path(filename) {
+      None => false,
+      Some(Compression::Gzip) if Compression::Gzip.is_available() => true,
+      Some(_) => return Err(()),
+    };
     let c_filename = CString::new(filename).unwrap();
     unsafe {
+      // Documents parsed from compressed files remember it, only the name decides here
+      let mode = xmlGetDocCompressMode(self.doc_ptr());
+      xmlSetDocCompressMode(self.doc_ptr(), if compress { 6 } else { 0 });
       let retval = xmlSaveFile(c_filename.as_ptr(), self.doc_ptr());
+      xmlSetDocCompressMode(self.doc_ptr(), mode);
       if retval < 0 {
         return Err(());
       }
@@ -161,39 +204,130 @@ impl Document {
     }
   }
 
+  /// Looks up the element with the ID `id`, given by an `xml:id` attribute or one the DTD
+  /// declares of type `ID`, without searching the tree. Elements unlinked from the tree are
+  /// not found.
+  pub fn get_element_by_id(&self, id: &str) -> Option<Node> {
+    self.element_by_id(id).map(|node_ptr| self.register_node(node_ptr))
+  }
+
+  /// Looks up the element with the ID `id` (read-only), see [`Document::get_element_by_id`]
+  pub fn get_element_by_id_readonly(&self, id: &str) -> Option<RoNode> {
+    self.element_by_id(id).map(RoNode)
+  }
+
+  fn element_by_id(&self, id: &str) -> Option<xmlNodePtr> {
+    let c_id = CString::new(id).ok()?;
+    let doc_ptr = self.doc_ptr();
+    let attr_ptr = unsafe { xmlGetID(doc_ptr, c_id.as_bytes().as_ptr()) };
+    if attr_ptr.is_null() {
+      return None;
+    }
+    let element = unsafe { (*attr_ptr).parent };
+    // IDs stay registered while their element is out of the tree
+    let mut ancestor = element;
+    while !ancestor.is_null() && ancestor != doc_ptr as xmlNodePtr {
+      ancestor = xmlGetParent(ancestor);
+    }
+    (!ancestor.is_null() && !element.is_null()).then_some(element)
+  }
+
   /// Sets the root element of the document
   pub fn set_root_element(&mut self, root: &Node) {
-    unsafe {
-      xmlDocSetRootElement(self.doc_ptr(), root.node_ptr());
-    }
+    let doc_node = RoNode(self.doc_ptr() as xmlNodePtr);
+    let old_root = unsafe { xmlDocSetRootElement(self.doc_ptr(), root.node_ptr()) };
     root.set_linked();
+    let document = Rc::downgrade(&self.0);
+    if !old_root.is_null() && old_root != root.node_ptr() {
+      observer::notify(&document, || Mutation::Removed {
+        node: RoNode(old_root),
+        parent: doc_node,
+      });
+    }
+    observer::notify(&document, || Mutation::Inserted {
+      node: RoNode(root.node_ptr()),
+      parent: doc_node,
+    });
   }
 
-  fn ptr_as_result(&mut self, node_ptr: xmlNodePtr) -> Result<Node, ()> {
+  /// Copies `node`, from any document, into this one, leaving the original untouched.
+  /// Without `deep` only the node itself and its attributes are copied. Namespaces the copy
+  /// uses which are declared outside of it are declared on the copy.
+  pub fn import_node(&self, node: &Node, deep: bool) -> Result<Node, ()> {
+    let recursive = if deep { 1 } else { 2 };
+    let node_ptr = unsafe { xmlDocCopyNode(node.node_ptr(), self.doc_ptr(), recursive) };
     if node_ptr.is_null() {
       Err(())
     } else {
-      let node = self.register_node(node_ptr);
-      Ok(node)
+      Ok(Node::wrap_new(node_ptr, &self.0))
     }
   }
 
-  /// Import a `Node` from another `Document`
-  pub fn import_node(&mut self, node: &mut Node) -> Result<Node, ()> {
-    if !node.is_unlinked() {
-      return Err(());
+  /// Moves `node` with its subtree from its document into this one, unlinking it from its
+  /// parent. Namespaces it uses which are declared outside of it are declared on it, and the
+  /// `Node`s of the subtree remain valid, now belonging to this document.
+  pub fn adopt_node(&mut self, node: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if matches!(
+      node.get_type(),
+      Some(
+        NodeType::DocumentNode
+          | NodeType::HtmlDocumentNode
+          | NodeType::DocumentFragNode
+          | NodeType::DTDNode
+      )
+    ) {
+      return Err(From::from("documents and DTDs can not be adopted"));
     }
-    // Also remove this node from the prior document hash
-    node
+    let node_ptr = node.node_ptr_mut()?;
+    let source = node
       .get_docref()
       .upgrade()
-      .unwrap()
-      .borrow_mut()
-      .forget_node(node.node_ptr());
+      .ok_or("the document of the node is gone")?;
+    node.unlink();
+    if Rc::ptr_eq(&source, &self.0) {
+      return Ok(());
+    }
+    let source_ptr = source.doc_ptr();
+    declare_outer_namespaces(node_ptr);
+    let status = unsafe {
+      xmlDOMWrapAdoptNode(
+        ptr::null_mut(),
+        source_ptr,
+        node_ptr,
+        self.doc_ptr(),
+        ptr::null_mut(),
+        0,
+      )
+    };
+    if status != 0 {
+      return Err(From::from("xmlDOMWrapAdoptNode failed"));
+    }
+    node.set_document(&self.0);
+    // The nodes of the subtree wrapped or referenced so far move over with it
+    let mut current = node_ptr;
+    while !current.is_null() {
+      self.take_wrapped(&source, current);
+      if xmlGetNodeType(current) == xmlElementType_XML_ELEMENT_NODE {
+        let mut attr = xmlGetFirstProperty(current);
+        while !attr.is_null() {
+          self.take_wrapped(&source, attr as xmlNodePtr);
+          attr = xmlNextPropertySibling(attr);
+        }
+      }
+      current = next_in_document_order(current, node_ptr);
+    }
+    Ok(())
+  }
 
-    let node_ptr = unsafe { xmlDocCopyNode(node.node_ptr(), self.doc_ptr(), 1) };
-    node.set_linked();
-    self.ptr_as_result(node_ptr)
+  /// Moves the `Node` wrapping `node_ptr` from the bookkeeping of `source` to this document,
+  /// along with the references to it
+  fn take_wrapped(&self, source: &DocumentRef, node_ptr: xmlNodePtr) {
+    noderef::adopted(node_ptr, &self.0);
+    let wrapped = source.forget_node(node_ptr);
+    if let Some(wrapped) = wrapped {
+      wrapped.set_document(&self.0);
+      self.0.insert_node(node_ptr, wrapped);
+    }
   }
 
   /// Serializes the `Document` with options
@@ -287,24 +421,90 @@ impl Document {
     }
   }
 
-  /// Creates a node for an XML processing instruction
-  pub fn create_processing_instruction(&mut self, name: &str, content: &str) -> Result<Node, ()> {
-    unsafe {
-      let c_name = CString::new(name).unwrap();
-      let c_name_bytes = c_name.as_bytes();
-      let c_content = CString::new(content).unwrap();
-      let c_content_bytes = c_content.as_bytes();
-
-      let node_ptr: xmlNodePtr = xmlNewDocPI(
+  /// Creates a processing instruction `<?target data?>`, e.g. an `xml-stylesheet` one to
+  /// insert with [`Document::add_to_prolog`]
+  pub fn create_processing_instruction(&mut self, target: &str, data: &str) -> Result<Node, ()> {
+    let c_target = CString::new(target).map_err(|_| ())?;
+    let c_data = CString::new(data).map_err(|_| ())?;
+    let node_ptr = unsafe {
+      xmlNewDocPI(
         self.doc_ptr(),
-        c_name_bytes.as_ptr(),
-        c_content_bytes.as_ptr(),
-      );
-      if node_ptr.is_null() {
-        Err(())
+        c_target.as_bytes().as_ptr(),
+        c_data.as_bytes().as_ptr(),
+      )
+    };
+    if node_ptr.is_null() {
+      Err(())
+    } else {
+      Ok(Node::wrap_new(node_ptr, &self.0))
+    }
+  }
+
+  /// Inserts the processing instruction or comment `node` before the root element, after
+  /// what the prolog already holds, or at the end of the document if it has no root element
+  pub fn add_to_prolog(&mut self, node: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !matches!(
+      node.get_type(),
+      Some(NodeType::PiNode) | Some(NodeType::CommentNode)
+    ) {
+      return Err(From::from(
+        "only processing instructions and comments belong to the prolog",
+      ));
+    }
+    let doc_ptr = self.doc_ptr();
+    let node_ptr = node.node_ptr_mut()?;
+    let added = unsafe {
+      let root = xmlDocGetRootElement(doc_ptr);
+      if root.is_null() {
+        xmlAddChild(doc_ptr as xmlNodePtr, node_ptr)
       } else {
-        Ok(self.register_node(node_ptr))
+        xmlAddPrevSibling(root, node_ptr)
       }
+    };
+    if added.is_null() {
+      return Err(From::from("could not add the node to the prolog"));
+    }
+    node.set_linked();
+    observer::notify(&Rc::downgrade(&self.0), || Mutation::Inserted {
+      node: RoNode(added),
+      parent: RoNode(doc_ptr as xmlNodePtr),
+    });
+    Ok(())
+  }
+
+  /// Creates a CDATA section holding `content` as is, which is serialized without escaping.
+  /// Occurrences of `]]>` are split over consecutive sections when serialized.
+  pub fn create_cdata_section(&self, content: &str) -> Result<Node, ()> {
+    let c_content = CString::new(content).map_err(|_| ())?;
+    let len = c_int::try_from(content.len()).map_err(|_| ())?;
+    let node_ptr = unsafe { xmlNewCDataBlock(self.doc_ptr(), c_content.as_bytes().as_ptr(), len) };
+    if node_ptr.is_null() {
+      Err(())
+    } else {
+      Ok(Node::wrap_new(node_ptr, &self.0))
+    }
+  }
+
+  /// Resolves `url` against the base URI of `node`
+  pub(crate) fn resolve_url(&self, node: RoNode, url: &str) -> String {
+    let Ok(c_url) = CString::new(url) else {
+      return url.to_string();
+    };
+    unsafe {
+      let base = xmlNodeGetBase(self.doc_ptr(), node.node_ptr());
+      if base.is_null() {
+        return url.to_string();
+      }
+      let resolved = xmlBuildURI(c_url.as_bytes().as_ptr(), base);
+      bindgenFree(base as *mut c_void);
+      if resolved.is_null() {
+        return url.to_string();
+      }
+      let resolved_string = CStr::from_ptr(resolved as *const c_char)
+        .to_string_lossy()
+        .into_owned();
+      bindgenFree(resolved as *mut c_void);
+      resolved_string
     }
   }
 
@@ -318,17 +518,103 @@ impl Document {
     self.register_node(self.doc_ptr() as xmlNodePtr)
   }
 
+  /// Sets the URL of the document, against which relative references
+  /// (such as XInclude `href`s) are resolved
+  pub fn set_url(&mut self, url: &str) {
+    let c_url = CString::new(url).unwrap();
+    unsafe { xmlNodeSetBase(self.doc_ptr() as xmlNodePtr, c_url.as_bytes().as_ptr()) }
+  }
+
+  /// Removes the whitespace-only text nodes from element content, the blanks `no_blanks`
+  /// drops at parse time, so that `get_child_nodes` only returns the data of data-oriented
+  /// documents. Mixed content, elements without child elements and `xml:space="preserve"`
+  /// subtrees keep their whitespace. Returns the number of text nodes removed.
+  pub fn strip_ignorable_whitespace(&mut self) -> usize {
+    let Some(root) = self.get_root_element() else {
+      return 0;
+    };
+    let mut blanks = Vec::new();
+    collect_ignorable_whitespace(&root, &mut blanks);
+    let count = blanks.len();
+    for mut blank in blanks {
+      blank.unlink_node();
+    }
+    count
+  }
+
+  /// Tidies the namespace declarations of the document after tree surgery: declares the
+  /// namespaces used out of their scope, then removes the declarations repeating one in
+  /// scope with the same prefix and URI, and those no element or attribute uses. Returns the
+  /// number of declarations removed.
+  ///
+  /// `Namespace` values of removed declarations must not be used afterwards.
+  pub fn cleanup_namespaces(&mut self) -> usize {
+    let doc_ptr = self.doc_ptr();
+    let root = unsafe { xmlDocGetRootElement(doc_ptr) };
+    if root.is_null() {
+      return 0;
+    }
+    unsafe { xmlReconciliateNs(doc_ptr, root) };
+
+    let mut removed = 0;
+    let mut node = root;
+    while !node.is_null() {
+      if xmlGetNodeType(node) == xmlElementType_XML_ELEMENT_NODE {
+        let parent = xmlGetParent(node);
+        removed += remove_namespace_declarations(node, |ns| {
+          let outer = unsafe { xmlSearchNs(doc_ptr, parent, (*ns).prefix) };
+          if outer.is_null() || !same_href(outer, ns) {
+            return false;
+          }
+          replace_namespace(node, ns, outer);
+          true
+        });
+      }
+      node = next_in_document_order(node, root);
+    }
+
+    let mut used = HashSet::new();
+    let mut node = root;
+    while !node.is_null() {
+      if xmlGetNodeType(node) == xmlElementType_XML_ELEMENT_NODE {
+        used.insert(xmlNodeNs(node));
+        let mut attr = xmlGetFirstProperty(node);
+        while !attr.is_null() {
+          used.insert(xmlAttrNs(attr));
+          attr = xmlNextPropertySibling(attr);
+        }
+      }
+      node = next_in_document_order(node, root);
+    }
+    let mut node = root;
+    while !node.is_null() {
+      if xmlGetNodeType(node) == xmlElementType_XML_ELEMENT_NODE {
+        removed += remove_namespace_declarations(node, |ns| !used.contains(&ns));
+      }
+      node = next_in_document_order(node, root);
+    }
+    removed
+  }
+
   /// Duplicates the libxml2 Document into a new instance
   pub fn dup(&self) -> Result<Self, ()> {
     let doc_ptr = unsafe { xmlCopyDoc(self.doc_ptr(), 1) };
     if doc_ptr.is_null() {
       Err(())
     } else {
-      let doc = _Document {
-        doc_ptr,
-        nodes: HashMap::new(),
-      };
-      Ok(Document(Rc::new(RefCell::new(doc))))
+      Ok(Document(_Document::new(doc_ptr)))
+    }
+  }
+
+  /// Copies the document with its nodes, DTDs and namespaces into a fully independent one.
+  /// Unlike [`Document::dup`], the copy does not share the string dictionary of the parser
+  /// with this document.
+  pub fn duplicate(&self) -> Result<Self, ()> {
+    let doc_ptr = copy_independent(self.doc_ptr());
+    if doc_ptr.is_null() {
+      Err(())
+    } else {
+      Ok(Document::new_ptr(doc_ptr))
     }
   }
 
@@ -342,9 +628,189 @@ impl Document {
     if doc_ptr.is_null() {
       return Err(());
     }
-    self.0.borrow_mut().doc_ptr = doc_ptr;
+    self.0.set_doc_ptr(doc_ptr);
     Ok(())
   }
 }
 
+/// Deeply copies `doc_ptr` into a document of its own, which owns all its strings.
+///
+/// The dictionary of `doc_ptr` is detached from it for the time of the copy, so the source
+/// is changed meanwhile and must not be used by anyone else, another thread included.
+pub(crate) fn copy_independent(doc_ptr: xmlDocPtr) -> xmlDocPtr {
+  if doc_ptr.is_null() {
+    return ptr::null_mut();
+  }
+  unsafe {
+    // Without a dictionary on the source the copy allocates its strings instead of interning
+    let dict = (*doc_ptr).dict;
+    (*doc_ptr).dict = ptr::null_mut();
+    let copy = xmlCopyDoc(doc_ptr, 1);
+    (*doc_ptr).dict = dict;
+    copy
+  }
+}
+
+/// Declares the namespaces the subtree of `root` uses from declarations outside of it on
+/// `root`, so that it no longer refers to the declarations of its old ancestors
+fn declare_outer_namespaces(root: xmlNodePtr) {
+  let mut node = root;
+  while !node.is_null() {
+    if xmlGetNodeType(node) == xmlElementType_XML_ELEMENT_NODE {
+      unsafe {
+        localize_namespace(root, node, &mut (*node).ns);
+        let mut attr = (*node).properties;
+        while !attr.is_null() {
+          localize_namespace(root, node, &mut (*attr).ns);
+          attr = (*attr).next;
+        }
+      }
+    }
+    node = next_in_document_order(node, root);
+  }
+}
+
+/// Points `ns`, used on `element` below `root`, to a declaration on `root` if it is declared
+/// outside of the subtree of `root`
+fn localize_namespace(root: xmlNodePtr, element: xmlNodePtr, ns: &mut xmlNsPtr) {
+  if ns.is_null() {
+    return;
+  }
+  let prefix = xmlNsPrefix(*ns);
+  // The xml namespace is never declared
+  if !prefix.is_null() && unsafe { CStr::from_ptr(prefix) } == c"xml" {
+    return;
+  }
+  let mut scope = element;
+  loop {
+    if declares(scope, *ns) {
+      return;
+    }
+    if scope == root {
+      break;
+    }
+    scope = xmlGetParent(scope);
+  }
+  unsafe {
+    // Another declaration of the namespace on root, made for an earlier use
+    let mut declared = (*root).nsDef;
+    while !declared.is_null() {
+      if same_prefix(declared, *ns) {
+        if same_href(declared, *ns) {
+          *ns = declared;
+          return;
+        }
+        break;
+      }
+      declared = (*declared).next;
+    }
+    let mut declared = xmlNewNs(root, (**ns).href, (**ns).prefix);
+    // The prefix is taken on root by another namespace
+    let mut counter = 0;
+    while declared.is_null() {
+      counter += 1;
+      let c_prefix = CString::new(format!("ns{counter}")).unwrap();
+      declared = xmlNewNs(root, (**ns).href, c_prefix.as_bytes().as_ptr());
+    }
+    *ns = declared;
+  }
+}
+
+/// Whether `ns` is one of the declarations of `element`
+fn declares(element: xmlNodePtr, ns: xmlNsPtr) -> bool {
+  let mut declared = xmlNodeNsDeclarations(element);
+  while !declared.is_null() {
+    if declared == ns {
+      return true;
+    }
+    declared = xmlNextNsSibling(declared);
+  }
+  false
+}
+
+/// Whether two namespaces have the same prefix
+fn same_prefix(ns: xmlNsPtr, other: xmlNsPtr) -> bool {
+  let (prefix, other_prefix) = (xmlNsPrefix(ns), xmlNsPrefix(other));
+  match (prefix.is_null(), other_prefix.is_null()) {
+    (true, true) => true,
+    (false, false) => unsafe { CStr::from_ptr(prefix) == CStr::from_ptr(other_prefix) },
+    _ => false,
+  }
+}
+
+/// Removes the namespace declarations of `element` for which `remove` returns `true`,
+/// returning how many
+fn remove_namespace_declarations(element: xmlNodePtr, mut remove: impl FnMut(xmlNsPtr) -> bool) -> usize {
+  let mut removed = 0;
+  unsafe {
+    let mut link: *mut xmlNsPtr = &mut (*element).nsDef;
+    while !(*link).is_null() {
+      let ns = *link;
+      if remove(ns) {
+        *link = (*ns).next;
+        (*ns).next = ptr::null_mut();
+        xmlFreeNs(ns);
+        removed += 1;
+      } else {
+        link = &mut (*ns).next;
+      }
+    }
+  }
+  removed
+}
+
+/// Whether two namespaces have the same URI
+fn same_href(ns: xmlNsPtr, other: xmlNsPtr) -> bool {
+  let (href, other_href) = (xmlNsHref(ns), xmlNsHref(other));
+  !href.is_null() && !other_href.is_null() && unsafe { CStr::from_ptr(href) == CStr::from_ptr(other_href) }
+}
+
+/// Makes the elements and attributes of the subtree of `root` in the namespace `old` use `new`
+fn replace_namespace(root: xmlNodePtr, old: xmlNsPtr, new: xmlNsPtr) {
+  let mut node = root;
+  while !node.is_null() {
+    if xmlGetNodeType(node) == xmlElementType_XML_ELEMENT_NODE {
+      unsafe {
+        if (*node).ns == old {
+          (*node).ns = new;
+        }
+        let mut attr = (*node).properties;
+        while !attr.is_null() {
+          if (*attr).ns == old {
+            (*attr).ns = new;
+          }
+          attr = (*attr).next;
+        }
+      }
+    }
+    node = next_in_document_order(node, root);
+  }
+}
+
+/// Gathers the whitespace-only text nodes in the element content below `element`
+fn collect_ignorable_whitespace(element: &Node, blanks: &mut Vec<Node>) {
+  if unsafe { xmlNodeGetSpacePreserve(element.node_ptr()) } == 1 {
+    return;
+  }
+  let children = element.get_child_nodes();
+  let is_blank = |node: &Node| {
+    node.is_text_node()
+      && node
+        .get_content()
+        .bytes()
+        .all(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+  };
+  let element_content = children.iter().any(Node::is_element_node)
+    && children
+      .iter()
+      .all(|child| !child.is_text_node() || is_blank(child));
+  for child in children {
+    if child.is_element_node() {
+      collect_ignorable_whitespace(&child, blanks);
+    } else if element_content && is_blank(&child) {
+      blanks.push(child);
+    }
+  }
+}
+
 mod c14n;
@@ -18,7 +18,7 @@ impl Document {
     options: CanonicalizationOptions,
     callback: Option<(xmlNodePtr, xmlC14NIsVisibleCallback)>,
   ) -> Result<String, ()> {
-    let document = (*self.0).borrow().doc_ptr;
+    let document = self.doc_ptr();
 
     let mut ns_list_c = to_xml_string_vec(options.inclusive_ns_prefixes);
     let inclusive_ns_prefixes = ns_list_c.as_mut_ptr();
@@ -0,0 +1,144 @@
+//! GraphViz DOT export of the document structure
+//!
+use std::fmt::Write;
+
+use crate::bindings::xmlNodePtr;
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::{Document, NodeType};
+
+/// What goes into the graph produced by [`Document::to_dot`]
+#[derive(Debug, Clone, Copy)]
+pub struct DotOptions {
+  /// List the attributes of each element in its label
+  pub attributes: bool,
+  /// Add text and CDATA nodes (whitespace-only text is always left out)
+  pub text: bool,
+  /// Add comments and processing instructions
+  pub comments: bool,
+  /// Stop descending below this depth, the root element being at depth 0
+  pub max_depth: Option<usize>,
+  /// Truncate text and attribute values to this many characters
+  pub text_width: usize,
+}
+
+impl Default for DotOptions {
+  fn default() -> Self {
+    DotOptions {
+      attributes: false,
+      text: false,
+      comments: false,
+      max_depth: None,
+      text_width: 30,
+    }
+  }
+}
+
+impl Document {
+  /// Renders the element tree as a GraphViz `digraph`, one vertex per node with edges from
+  /// parents to children, e.g. for `dot -Tsvg`
+  pub fn to_dot(&self, options: &DotOptions) -> String {
+    let mut dot = DotWriter {
+      options,
+      out: String::new(),
+      next_id: 0,
+    };
+    dot.out.push_str("digraph document {\n");
+    dot.out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+    if let Some(root) = self.get_root_readonly() {
+      dot.write_node(root, None, 0);
+    }
+    dot.out.push_str("}\n");
+    dot.out
+  }
+}
+
+struct DotWriter<'a> {
+  options: &'a DotOptions,
+  out: String,
+  next_id: usize,
+}
+
+impl DotWriter<'_> {
+  fn write_node(&mut self, node: RoNode, parent: Option<usize>, depth: usize) {
+    let (label, shape) = match node.get_type() {
+      Some(NodeType::ElementNode) => (self.element_label(node), None),
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) if self.options.text => {
+        let content = node.get_content();
+        if content.trim().is_empty() {
+          return;
+        }
+        (format!("\"{}\"", self.truncate(&content)), Some("plaintext"))
+      }
+      Some(NodeType::CommentNode) if self.options.comments => (
+        format!("<!-- {} -->", self.truncate(&node.get_content())),
+        Some("note"),
+      ),
+      Some(NodeType::PiNode) if self.options.comments => (
+        format!("<?{} {}?>", node.get_name(), self.truncate(&node.get_content())),
+        Some("note"),
+      ),
+      _ => return,
+    };
+
+    let id = self.next_id;
+    self.next_id += 1;
+    let _ = write!(self.out, "  n{} [label=\"{}\"", id, escape(&label));
+    if let Some(shape) = shape {
+      let _ = write!(self.out, ", shape={shape}");
+    }
+    self.out.push_str("];\n");
+    if let Some(parent) = parent {
+      let _ = writeln!(self.out, "  n{parent} -> n{id};");
+    }
+
+    if self.options.max_depth.is_some_and(|max| depth >= max) {
+      return;
+    }
+    for child in node.get_child_nodes() {
+      self.write_node(child, Some(id), depth + 1);
+    }
+  }
+
+  fn element_label(&self, node: RoNode) -> String {
+    let mut label = qualified_name(node.node_ptr());
+    if self.options.attributes {
+      let mut attr_ptr = xmlGetFirstProperty(node.node_ptr());
+      while !attr_ptr.is_null() {
+        let attr = RoNode(attr_ptr as xmlNodePtr);
+        label.push('\n');
+        label.push_str(&format!(
+          "{}={}",
+          qualified_name(attr.node_ptr()),
+          self.truncate(&attr.get_content())
+        ));
+        attr_ptr = xmlNextPropertySibling(attr_ptr);
+      }
+    }
+    label
+  }
+
+  fn truncate(&self, text: &str) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() > self.options.text_width {
+      let cut: String = collapsed.chars().take(self.options.text_width).collect();
+      format!("{}…", cut.trim_end())
+    } else {
+      collapsed
+    }
+  }
+}
+
+/// Escapes `label` for a double-quoted DOT string, line breaks becoming centered `\n`
+fn escape(label: &str) -> String {
+  let mut escaped = String::with_capacity(label.len());
+  for c in label.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      _ => escaped.push(c),
+    }
+  }
+  escaped
+}
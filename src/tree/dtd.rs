@@ -0,0 +1,334 @@
+//! Document type definitions
+//!
+//! The internal subset of a document is the part of its DTD written in the `<!DOCTYPE>`
+//! declaration; the external subset is the one its system identifier points to, only loaded
+//! with [`ParserOptions::dtd_load`](crate::parser::ParserOptions::dtd_load) or validation:
+//!
+//! ```
+//! use libxml::parser::Parser;
+//! use libxml::tree::EntityKind;
+//!
+//! let doc = Parser::default()
+//!   .parse_string(r#"<!DOCTYPE note SYSTEM "note.dtd" [<!ENTITY sig "Jani">]><note/>"#)
+//!   .unwrap();
+//! let dtd = doc.get_internal_subset().unwrap();
+//! assert_eq!(dtd.name(), "note");
+//! assert_eq!(dtd.system_id().as_deref(), Some("note.dtd"));
+//! let entity = dtd.entities().next().unwrap();
+//! assert_eq!((entity.name.as_str(), entity.kind), ("sig", EntityKind::InternalGeneral));
+//! assert_eq!(entity.value.as_deref(), Some("Jani"));
+//! ```
+//!
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::tree::snapshot::expand_entity;
+use crate::tree::{Document, DocumentRef, Node, NodeType};
+
+/// The internal or external subset of the DTD of a document
+pub struct Dtd {
+  dtd_ptr: xmlDtdPtr,
+  // The DTD is freed with the document
+  _document: DocumentRef,
+}
+
+/// The kinds of entities a DTD declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityKind {
+  /// A general entity with a literal value
+  InternalGeneral,
+  /// A general entity whose XML content is in another resource
+  ExternalParsed,
+  /// A general entity naming a non-XML resource with a notation (`NDATA`)
+  ExternalUnparsed,
+  /// A parameter entity with a literal value
+  InternalParameter,
+  /// A parameter entity whose declarations are in another resource
+  ExternalParameter,
+  /// One of the entities predefined by XML, such as `amp`
+  Predefined,
+}
+
+/// An entity declared in a DTD, see [`Dtd::entities`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entity {
+  /// The name of the entity
+  pub name: String,
+  /// What kind of entity it is
+  pub kind: EntityKind,
+  /// The literal value of an internal entity, as declared
+  pub value: Option<String>,
+  /// The public identifier of an external entity, if it has one
+  pub public_id: Option<String>,
+  /// The system identifier of an external entity
+  pub system_id: Option<String>,
+}
+
+/// The entities declared in a DTD in declaration order, see [`Dtd::entities`]
+pub struct Entities<'a> {
+  next: xmlNodePtr,
+  _dtd: &'a Dtd,
+}
+
+impl Document {
+  /// Returns the internal subset of the DTD, declared in the `<!DOCTYPE>` of the document
+  pub fn get_internal_subset(&self) -> Option<Dtd> {
+    let dtd_ptr = unsafe { xmlGetIntSubset(self.doc_ptr()) };
+    self.wrap_dtd(dtd_ptr)
+  }
+
+  /// Returns the `<!DOCTYPE>` declaration of the document, held by its internal subset
+  pub fn get_doctype(&self) -> Option<Dtd> {
+    self.get_internal_subset()
+  }
+
+  /// Declares the document type, as `<!DOCTYPE name PUBLIC "public_id" "system_id">`, in
+  /// place of the one the document had; for `<!DOCTYPE html>` leave out both identifiers.
+  /// Entities declared by a former internal subset are dropped with it.
+  pub fn set_internal_subset(
+    &mut self,
+    name: &str,
+    public_id: Option<&str>,
+    system_id: Option<&str>,
+  ) -> Result<Dtd, Box<dyn Error + Send + Sync>> {
+    if public_id.is_some() && system_id.is_none() {
+      return Err(From::from("a public identifier requires a system identifier"));
+    }
+    let c_name = CString::new(name)?;
+    let c_public_id = public_id.map(CString::new).transpose()?;
+    let c_system_id = system_id.map(CString::new).transpose()?;
+    let doc_ptr = self.doc_ptr();
+    let old_ptr = unsafe { xmlGetIntSubset(doc_ptr) };
+    if !old_ptr.is_null() {
+      // `Dtd`s of the old subset may still be around, it is freed with the document
+      unsafe { xmlUnlinkNode(old_ptr as xmlNodePtr) };
+      self.0.keep_replaced_dtd(old_ptr);
+    }
+    let as_ptr = |c_string: &Option<CString>| {
+      c_string
+        .as_ref()
+        .map_or(ptr::null(), |c_string| c_string.as_ptr() as *const xmlChar)
+    };
+    let dtd_ptr = unsafe {
+      xmlCreateIntSubset(
+        doc_ptr,
+        c_name.as_ptr() as *const xmlChar,
+        as_ptr(&c_public_id),
+        as_ptr(&c_system_id),
+      )
+    };
+    self
+      .wrap_dtd(dtd_ptr)
+      .ok_or_else(|| From::from("xmlCreateIntSubset failed"))
+  }
+
+  /// Declares the internal general entity `name` with the replacement text `value`, in the
+  /// internal subset, which is created for the root element if the document has none. The
+  /// value is XML, references it contains are expanded where the entity is.
+  pub fn add_entity(
+    &mut self,
+    name: &str,
+    value: &str,
+  ) -> Result<Entity, Box<dyn Error + Send + Sync>> {
+    let doc_ptr = self.doc_ptr();
+    if unsafe { xmlGetIntSubset(doc_ptr) }.is_null() {
+      let root = self
+        .get_root_element()
+        .ok_or("a document without root element has no doctype to declare entities in")?;
+      self.set_internal_subset(&root.get_name(), None, None)?;
+    }
+    let c_name = CString::new(name)?;
+    let c_value = CString::new(value)?;
+    let value_ptr = c_value.as_ptr() as *const xmlChar;
+    let mut list = ptr::null_mut();
+    let status = unsafe {
+      xmlParseBalancedChunkMemory(doc_ptr, ptr::null_mut(), ptr::null_mut(), 0, value_ptr, &mut list)
+    };
+    unsafe { xmlFreeNodeList(list) };
+    if status != 0 {
+      return Err(From::from(format!("the value of the entity {name} is not well-formed")));
+    }
+    let entity = unsafe {
+      xmlAddDocEntity(
+        doc_ptr,
+        c_name.as_ptr() as *const xmlChar,
+        xmlEntityType_XML_INTERNAL_GENERAL_ENTITY as c_int,
+        ptr::null(),
+        ptr::null(),
+        value_ptr,
+      )
+    };
+    unsafe { expand_entity(entity) };
+    Entity::from_ptr(entity)
+      .ok_or_else(|| From::from(format!("the entity {name} can't be declared")))
+  }
+
+  /// Looks up the general entity `name`, in the internal subset, then the external one, then
+  /// among the predefined entities
+  pub fn get_entity(&self, name: &str) -> Option<Entity> {
+    let c_name = CString::new(name).ok()?;
+    let entity = unsafe { xmlGetDocEntity(self.doc_ptr(), c_name.as_ptr() as *const xmlChar) };
+    Entity::from_ptr(entity)
+  }
+
+  /// Returns the external subset of the DTD, if it was loaded while parsing
+  pub fn get_external_subset(&self) -> Option<Dtd> {
+    let dtd_ptr = unsafe { (*self.doc_ptr()).extSubset };
+    self.wrap_dtd(dtd_ptr)
+  }
+
+  fn wrap_dtd(&self, dtd_ptr: xmlDtdPtr) -> Option<Dtd> {
+    if dtd_ptr.is_null() {
+      None
+    } else {
+      Some(Dtd {
+        dtd_ptr,
+        _document: self.0.clone(),
+      })
+    }
+  }
+}
+
+impl Node {
+  /// Creates a reference to the entity `name`, `&name;`, bound to a given document. It
+  /// resolves to the entity the document declares by that name.
+  pub fn new_entity_ref(name: &str, doc: &Document) -> Result<Node, ()> {
+    let c_name = CString::new(name).map_err(|_| ())?;
+    let node_ptr = unsafe { xmlNewReference(doc.doc_ptr(), c_name.as_ptr() as *const xmlChar) };
+    if node_ptr.is_null() {
+      return Err(());
+    }
+    // A declaration from the parser only gets its content when first referenced
+    unsafe { expand_entity((*node_ptr).children as xmlEntityPtr) };
+    Ok(Node::wrap_new(node_ptr, &doc.0))
+  }
+
+  /// Returns the declaration of the entity this entity reference refers to, if the document
+  /// has one
+  pub fn get_referenced_entity(&self) -> Option<Entity> {
+    if self.get_type() != Some(NodeType::EntityRefNode) {
+      return None;
+    }
+    let c_name = CString::new(self.get_name()).ok()?;
+    let doc_ptr = xmlGetDoc(self.node_ptr());
+    Entity::from_ptr(unsafe { xmlGetDocEntity(doc_ptr, c_name.as_ptr() as *const xmlChar) })
+  }
+}
+
+impl Dtd {
+  /// The name of the DTD, which the root element should have
+  pub fn name(&self) -> String {
+    xml_string(unsafe { (*self.dtd_ptr).name }).unwrap_or_default()
+  }
+
+  /// The public identifier of the external subset, if the doctype gives one
+  pub fn public_id(&self) -> Option<String> {
+    xml_string(unsafe { (*self.dtd_ptr).ExternalID })
+  }
+
+  /// The system identifier of the external subset, if the doctype gives one
+  pub fn system_id(&self) -> Option<String> {
+    xml_string(unsafe { (*self.dtd_ptr).SystemID })
+  }
+
+  /// Iterates over the general and parameter entities this subset declares, in declaration
+  /// order
+  pub fn entities(&self) -> Entities<'_> {
+    Entities {
+      next: unsafe { (*self.dtd_ptr).children },
+      _dtd: self,
+    }
+  }
+
+  /// Looks up the general entity `name` this subset declares
+  pub fn get_entity(&self, name: &str) -> Option<Entity> {
+    self
+      .entities()
+      .find(|entity| entity.name == name && entity.kind.is_general())
+  }
+}
+
+impl EntityKind {
+  /// Converts an integer from libxml's `xmlEntityType`
+  fn from_int(etype: xmlEntityType) -> Option<EntityKind> {
+    match etype {
+      1 => Some(EntityKind::InternalGeneral),
+      2 => Some(EntityKind::ExternalParsed),
+      3 => Some(EntityKind::ExternalUnparsed),
+      4 => Some(EntityKind::InternalParameter),
+      5 => Some(EntityKind::ExternalParameter),
+      6 => Some(EntityKind::Predefined),
+      _ => None,
+    }
+  }
+
+  /// Whether entities of this kind are referenced from the document, rather than from the DTD
+  pub fn is_general(self) -> bool {
+    !matches!(
+      self,
+      EntityKind::InternalParameter | EntityKind::ExternalParameter
+    )
+  }
+}
+
+impl Iterator for Entities<'_> {
+  type Item = Entity;
+
+  fn next(&mut self) -> Option<Entity> {
+    while !self.next.is_null() {
+      let node = self.next;
+      self.next = xmlNextSibling(node);
+      if NodeType::from_int(xmlGetNodeType(node)) != Some(NodeType::EntityDecl) {
+        continue;
+      }
+      if let Some(entity) = Entity::from_ptr(node as xmlEntityPtr) {
+        return Some(entity);
+      }
+    }
+    None
+  }
+}
+
+impl Entity {
+  /// A copy of the declaration at `entity`
+  fn from_ptr(entity: xmlEntityPtr) -> Option<Entity> {
+    if entity.is_null() {
+      return None;
+    }
+    let kind = EntityKind::from_int(unsafe { (*entity).etype })?;
+    let internal = matches!(
+      kind,
+      EntityKind::InternalGeneral | EntityKind::InternalParameter | EntityKind::Predefined
+    );
+    Some(unsafe {
+      Entity {
+        name: xml_string((*entity).name).unwrap_or_default(),
+        kind,
+        value: if internal {
+          xml_string((*entity).content).or_else(|| Some(String::new()))
+        } else {
+          None
+        },
+        public_id: xml_string((*entity).ExternalID),
+        system_id: xml_string((*entity).SystemID),
+      }
+    })
+  }
+}
+
+/// A copy of the string at `ptr`, if any
+fn xml_string(ptr: *const xmlChar) -> Option<String> {
+  if ptr.is_null() {
+    None
+  } else {
+    Some(
+      unsafe { CStr::from_ptr(ptr as *const c_char) }
+        .to_string_lossy()
+        .into_owned(),
+    )
+  }
+}
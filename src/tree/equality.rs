@@ -0,0 +1,159 @@
+//! Structural equality of subtrees
+//!
+//! Two subtrees are equal when they hold the same content for a reader of the XML, however
+//! it is written: elements and attributes are compared by local name and namespace URI
+//! whatever their prefixes, attributes in any order, and text whether it is in CDATA
+//! sections or split over several nodes.
+//!
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::{Document, Node, NodeType};
+
+/// Options controlling which differences [`Node::deep_equals`] ignores
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EqualityOptions {
+  /// Drop whitespace-only text and compare text with normalized whitespace
+  pub ignore_whitespace: bool,
+  /// Do not compare comments
+  pub ignore_comments: bool,
+}
+
+/// A child as compared, with adjacent text merged
+enum Item {
+  Node(RoNode),
+  Text(String),
+}
+
+impl Node {
+  /// Whether this node and `other`, possibly from another document, have equal subtrees:
+  /// names, namespaces, attributes and text, up to the differences `options` ignores
+  pub fn deep_equals(&self, other: &Node, options: &EqualityOptions) -> bool {
+    let (node, other) = (RoNode(self.node_ptr()), RoNode(other.node_ptr()));
+    match (text_of(node), text_of(other)) {
+      (Some(text), Some(other_text)) => normalize(text, options) == normalize(other_text, options),
+      (None, None) => nodes_equal(node, other, options),
+      _ => false,
+    }
+  }
+}
+
+impl Document {
+  /// Whether both documents hold equal content, comparing comments and whitespace, see
+  /// [`Node::deep_equals`]. The DTDs are not compared.
+  pub fn equivalent_to(&self, other: &Document) -> bool {
+    let options = EqualityOptions::default();
+    let doc_node = RoNode(self.doc_ptr() as _);
+    let other_node = RoNode(other.doc_ptr() as _);
+    children_equal(doc_node, other_node, &options)
+  }
+}
+
+/// Compares two nodes other than text
+fn nodes_equal(node: RoNode, other: RoNode, options: &EqualityOptions) -> bool {
+  if node.get_type() != other.get_type() {
+    return false;
+  }
+  match node.get_type() {
+    Some(NodeType::ElementNode) => {
+      node.get_name() == other.get_name()
+        && namespace_href(node) == namespace_href(other)
+        && attributes_equal(node, other)
+        && children_equal(node, other, options)
+    }
+    Some(NodeType::CommentNode) | Some(NodeType::PiNode) => {
+      node.get_name() == other.get_name() && node.get_content() == other.get_content()
+    }
+    Some(NodeType::EntityRefNode) => node.get_name() == other.get_name(),
+    Some(NodeType::DocumentNode) | Some(NodeType::HtmlDocumentNode) => {
+      children_equal(node, other, options)
+    }
+    _ => node.get_content() == other.get_content(),
+  }
+}
+
+fn children_equal(node: RoNode, other: RoNode, options: &EqualityOptions) -> bool {
+  let (items, other_items) = (items(node, options), items(other, options));
+  items.len() == other_items.len()
+    && items
+      .iter()
+      .zip(&other_items)
+      .all(|pair| match pair {
+        (Item::Text(text), Item::Text(other_text)) => text == other_text,
+        (Item::Node(child), Item::Node(other_child)) => nodes_equal(*child, *other_child, options),
+        _ => false,
+      })
+}
+
+/// The children of `node` which are compared
+fn items(node: RoNode, options: &EqualityOptions) -> Vec<Item> {
+  let mut items = Vec::new();
+  let mut text: Option<String> = None;
+  for child in node.get_child_nodes() {
+    if let Some(content) = text_of(child) {
+      text.get_or_insert_with(String::new).push_str(&content);
+      continue;
+    }
+    let skipped = match child.get_type() {
+      Some(NodeType::CommentNode) => options.ignore_comments,
+      Some(NodeType::DocumentTypeNode) | Some(NodeType::DTDNode) => true,
+      _ => false,
+    };
+    if skipped {
+      continue;
+    }
+    if let Some(text) = text.take() {
+      push_text(&mut items, text, options);
+    }
+    items.push(Item::Node(child));
+  }
+  if let Some(text) = text {
+    push_text(&mut items, text, options);
+  }
+  items
+}
+
+fn push_text(items: &mut Vec<Item>, text: String, options: &EqualityOptions) {
+  let text = normalize(text, options);
+  if !(options.ignore_whitespace && text.is_empty()) {
+    items.push(Item::Text(text));
+  }
+}
+
+/// The content of text and CDATA nodes
+fn text_of(node: RoNode) -> Option<String> {
+  match node.get_type() {
+    Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => Some(node.get_content()),
+    _ => None,
+  }
+}
+
+fn normalize(text: String, options: &EqualityOptions) -> String {
+  if options.ignore_whitespace {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+  } else {
+    text
+  }
+}
+
+/// Compares the attributes of two elements by namespace URI, local name and value
+fn attributes_equal(node: RoNode, other: RoNode) -> bool {
+  let (mut attributes, mut other_attributes) = (attributes(node), attributes(other));
+  attributes.sort();
+  other_attributes.sort();
+  attributes == other_attributes
+}
+
+fn attributes(node: RoNode) -> Vec<(Option<String>, String, String)> {
+  let mut attributes = Vec::new();
+  let mut attr_ptr = xmlGetFirstProperty(node.node_ptr());
+  while !attr_ptr.is_null() {
+    let attr = RoNode(attr_ptr as _);
+    attributes.push((namespace_href(attr), attr.get_name(), attr.get_content()));
+    attr_ptr = xmlNextPropertySibling(attr_ptr);
+  }
+  attributes
+}
+
+fn namespace_href(node: RoNode) -> Option<String> {
+  node.get_namespace().map(|ns| ns.get_href())
+}
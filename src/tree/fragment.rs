@@ -0,0 +1,171 @@
+//! Document fragments
+//!
+//! A [`DocumentFragment`] holds a sequence of sibling nodes of a document outside of its
+//! tree, to be built up and then spliced into an element in one go:
+//!
+//! ```
+//! use libxml::parser::Parser;
+//! use libxml::tree::{DocumentFragment, Node};
+//!
+//! let doc = Parser::default().parse_string("<list><item>0</item></list>").unwrap();
+//! let mut fragment = DocumentFragment::new(&doc).unwrap();
+//! for i in 1..3 {
+//!   let mut item = Node::new("item", None, &doc).unwrap();
+//!   item.append_text(&i.to_string()).unwrap();
+//!   fragment.append(&mut item).unwrap();
+//! }
+//! let mut list = doc.get_root_element().unwrap();
+//! fragment.append_to(&mut list).unwrap();
+//! assert_eq!(
+//!   doc.node_to_string(&list),
+//!   "<list><item>0</item><item>1</item><item>2</item></list>"
+//! );
+//! ```
+//!
+use std::error::Error;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::node::is_ancestor_or_self;
+use crate::tree::observer::{self, Mutation};
+use crate::tree::{Document, Node, NodeType};
+
+/// Sibling nodes of a document not attached to any parent
+pub struct DocumentFragment {
+  node: Node,
+}
+
+impl DocumentFragment {
+  /// Creates an empty fragment for nodes of `doc`
+  pub fn new(doc: &Document) -> Result<Self, ()> {
+    let node_ptr = unsafe { xmlNewDocFragment(doc.doc_ptr()) };
+    if node_ptr.is_null() {
+      return Err(());
+    }
+    Ok(DocumentFragment {
+      node: Node::wrap_new(node_ptr, &doc.0),
+    })
+  }
+
+  /// Moves `node` to the end of the fragment, unlinking it from where it was
+  pub fn append(&mut self, node: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !matches!(
+      node.get_type(),
+      Some(
+        NodeType::ElementNode
+          | NodeType::TextNode
+          | NodeType::CDataSectionNode
+          | NodeType::EntityRefNode
+          | NodeType::PiNode
+          | NodeType::CommentNode
+      )
+    ) {
+      return Err(From::from("only content nodes can be part of a fragment"));
+    }
+    let fragment_ptr = self.node.node_ptr_mut()?;
+    let node_ptr = node.node_ptr_mut()?;
+    if xmlGetDoc(node_ptr) != xmlGetDoc(fragment_ptr) {
+      return Err(From::from("the node belongs to another document"));
+    }
+    node.unlink();
+    unsafe { link_last_child(fragment_ptr, node_ptr) };
+    node.set_linked();
+    Ok(())
+  }
+
+  /// The nodes of the fragment, in order
+  pub fn children(&self) -> Vec<Node> {
+    self.node.get_child_nodes()
+  }
+
+  /// The number of nodes in the fragment
+  pub fn len(&self) -> usize {
+    let mut count = 0;
+    let mut child = xmlGetFirstChild(self.node.node_ptr());
+    while !child.is_null() {
+      count += 1;
+      child = xmlNextSibling(child);
+    }
+    count
+  }
+
+  /// Whether the fragment holds no nodes
+  pub fn is_empty(&self) -> bool {
+    xmlGetFirstChild(self.node.node_ptr()).is_null()
+  }
+
+  /// Moves the nodes of the fragment, in order, to the end of the children of `parent`,
+  /// returning them. The document node can't take them, as it holds no text and a single
+  /// element.
+  pub fn append_to(self, parent: &mut Node) -> Result<Vec<Node>, Box<dyn Error + Send + Sync>> {
+    if !matches!(
+      parent.get_type(),
+      Some(NodeType::ElementNode | NodeType::DocumentFragNode)
+    ) {
+      return Err(From::from(
+        "only elements and fragments can take the nodes of a fragment",
+      ));
+    }
+    let parent_ptr = parent.node_ptr_mut()?;
+    if xmlGetDoc(parent_ptr) != xmlGetDoc(self.node.node_ptr()) {
+      return Err(From::from("the parent belongs to another document"));
+    }
+    let children = self.children();
+    if children
+      .iter()
+      .any(|child| is_ancestor_or_self(child.node_ptr(), parent_ptr))
+    {
+      return Err(From::from("a fragment can't go into its own subtree"));
+    }
+    let document = parent.get_docref();
+    for child in &children {
+      let child_ptr = child.node_ptr();
+      unsafe {
+        xmlUnlinkNode(child_ptr);
+        link_last_child(parent_ptr, child_ptr);
+      }
+      observer::notify(&document, || Mutation::Inserted {
+        node: RoNode(child_ptr),
+        parent: RoNode(parent_ptr),
+      });
+    }
+    Ok(children)
+  }
+}
+
+/// Links the unlinked `child` as the last child of `parent`. Unlike `xmlAddChild`, this
+/// never merges text nodes, which would free the ones already wrapped in a `Node`.
+pub(crate) unsafe fn link_last_child(parent: xmlNodePtr, child: xmlNodePtr) {
+  unsafe {
+    let last = (*parent).last;
+    (*child).parent = parent;
+    (*child).prev = last;
+    (*child).next = std::ptr::null_mut();
+    if last.is_null() {
+      (*parent).children = child;
+    } else {
+      (*last).next = child;
+    }
+    (*parent).last = child;
+  }
+}
+
+/// Links the unlinked `node` as the previous sibling of `sibling`, never merging text nodes
+pub(crate) unsafe fn link_before(sibling: xmlNodePtr, node: xmlNodePtr) {
+  unsafe {
+    let parent = (*sibling).parent;
+    let prev = (*sibling).prev;
+    (*node).parent = parent;
+    (*node).prev = prev;
+    (*node).next = sibling;
+    (*sibling).prev = node;
+    if prev.is_null() {
+      if !parent.is_null() {
+        (*parent).children = node;
+      }
+    } else {
+      (*prev).next = node;
+    }
+  }
+}
@@ -1,12 +1,44 @@
+pub mod attribute;
+pub mod builder;
 pub mod c14n;
 pub mod document;
+pub mod dot;
+pub mod dtd;
+pub mod equality;
+pub mod fragment;
 pub mod namespace;
 pub mod node;
+pub mod noderef;
 pub mod nodetype;
+pub mod observer;
+pub mod outline;
+pub mod shared;
+pub mod snapshot;
+pub mod template;
+pub mod traversal;
+pub mod typed;
+pub mod userdata;
 
+pub use self::attribute::{Attribute, Attributes};
+pub use self::builder::{BuildEvent, ElementBuilder};
 pub use self::document::{Document, SaveOptions};
+pub use self::dot::DotOptions;
+pub use self::dtd::{Dtd, Entities, Entity, EntityKind};
+pub use self::equality::EqualityOptions;
+pub use self::fragment::DocumentFragment;
 pub(crate) use self::document::{DocumentRef, DocumentWeak};
 pub use self::namespace::Namespace;
 pub use self::node::set_node_rc_guard;
 pub use self::node::{Node, NODE_RC_MAX_GUARD};
+pub use self::noderef::{NodePath, NodeRef};
 pub use self::nodetype::NodeType;
+pub use self::observer::{Mutation, ObserverId};
+pub use self::outline::Outline;
+pub use self::shared::{SharedDocument, SharedRead};
+pub use self::snapshot::SnapshotError;
+pub use self::template::DocumentTemplate;
+pub use self::typed::AttributeError;
+pub use self::traversal::{
+  Ancestors, Children, Descendants, ElementChildren, Following, FollowingSiblings, Preceding,
+  PrecedingSiblings, RoAxis,
+};
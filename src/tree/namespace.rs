@@ -3,6 +3,7 @@
 use std::error::Error;
 use std::ffi::{CStr, CString};
 use std::hash::{Hash, Hasher};
+use std::os::raw::c_void;
 use std::ptr;
 use std::str;
 
@@ -101,3 +102,26 @@ impl Namespace {
     unsafe { xmlFreeNs(self.ns_ptr()) }
   }
 }
+
+/// The prefix bound to `href` in the scope of `node`, leaving out the default namespace
+pub(crate) fn lookup_prefix(node: xmlNodePtr, href: &str) -> Option<String> {
+  let list = unsafe { xmlGetNsList(xmlGetDoc(node), node) };
+  if list.is_null() {
+    return None;
+  }
+  let mut prefix = None;
+  unsafe {
+    // The namespaces in scope, nearest first, without those shadowed by a nearer prefix
+    let mut ns_iter = list;
+    while prefix.is_none() && !(*ns_iter).is_null() {
+      let ns = Namespace { ns_ptr: *ns_iter };
+      if !xmlNsPrefix(ns.ns_ptr).is_null() && ns.get_href() == href {
+        prefix = Some(ns.get_prefix());
+      }
+      ns_iter = ns_iter.add(1);
+    }
+    // Only the array is allocated for the list
+    bindgenFree(list as *mut c_void);
+  }
+  prefix
+}
@@ -1,19 +1,24 @@
 //! Node, and related, feature set
 //!
-use libc::{c_char, c_void};
-use std::cell::RefCell;
+use std::cell::Cell;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::ffi::{CStr, CString};
 use std::hash::{Hash, Hasher};
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::rc::Rc;
 use std::str;
 
 use crate::bindings::*;
 use crate::c_helpers::*;
-use crate::tree::namespace::Namespace;
+use crate::parser::try_usize_to_i32;
+use crate::tree::fragment;
+use crate::tree::namespace::{self, Namespace};
+use crate::readonly::RoNode;
 use crate::tree::nodetype::NodeType;
+use crate::tree::observer::{self, Mutation};
+use crate::tree::traversal;
 use crate::tree::{Document, DocumentRef, DocumentWeak};
 use crate::xpath::Context;
 
@@ -28,20 +33,23 @@ pub fn set_node_rc_guard(value: usize) {
   }
 }
 
-type NodeRef = Rc<RefCell<_Node>>;
+type NodeRef = Rc<_Node>;
 
-#[derive(Debug)]
+/// The state shared by the handles to a node, in cells which are never borrowed, so that
+/// handles can be used from anywhere, including while iterating or from observer callbacks
 struct _Node {
   /// libxml's xmlNodePtr
   node_ptr: xmlNodePtr,
   /// Reference to parent `Document`
-  document: DocumentWeak,
+  document: Cell<DocumentWeak>,
   /// Bookkeep removal from a parent
-  unlinked: bool,
+  unlinked: Cell<bool>,
+  /// Handles kept by traversals, which do not count as sharing the node
+  held: Cell<usize>,
 }
 
 /// An xml node
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Node(NodeRef);
 
 impl Hash for Node {
@@ -64,17 +72,36 @@ impl Drop for _Node {
   /// Free node if it isn't bound in some document
   /// Warning: xmlFreeNode is RECURSIVE into the node's children, so this may lead to segfaults if used carelessly
   fn drop(&mut self) {
-    if self.unlinked {
+    if self.unlinked.get() {
       let node_ptr = self.node_ptr;
       if !node_ptr.is_null() {
-        unsafe {
-          xmlFreeNode(node_ptr);
-        }
+        free_removed(node_ptr, &self.document.take());
       }
     }
   }
 }
 
+/// A handle a traversal keeps on the node it is at, so that removing the node meanwhile does
+/// not free it, while still letting the node be mutably referenced
+pub(crate) struct HeldNode(Node);
+
+impl HeldNode {
+  pub(crate) fn new(node: Node) -> Self {
+    node.0.held.set(node.0.held.get() + 1);
+    HeldNode(node)
+  }
+
+  pub(crate) fn node(&self) -> &Node {
+    &self.0
+  }
+}
+
+impl Drop for HeldNode {
+  fn drop(&mut self) {
+    self.0.0.held.set(self.0.0.held.get() - 1);
+  }
+}
+
 impl Node {
   /// Create a new node, bound to a given document.
   pub fn new(name: &str, ns: Option<Namespace>, doc: &Document) -> Result<Self, ()> {
@@ -102,14 +129,14 @@ impl Node {
 
   /// Immutably borrows the underlying libxml2 `xmlNodePtr` pointer
   pub fn node_ptr(&self) -> xmlNodePtr {
-    self.0.borrow().node_ptr
+    self.0.node_ptr
   }
 
   /// Mutably borrows the underlying libxml2 `xmlNodePtr` pointer
   /// Also protects against mutability conflicts at runtime.
   pub fn node_ptr_mut(&mut self) -> Result<xmlNodePtr, String> {
     let weak_count = Rc::weak_count(&self.0);
-    let strong_count = Rc::strong_count(&self.0);
+    let strong_count = Rc::strong_count(&self.0) - self.0.held.get();
 
     // The basic idea would be to use `Rc::get_mut` to guard against multiple borrows.
     // However, our approach to bookkeeping nodes implies there is *always* a second Rc reference
@@ -117,7 +144,7 @@ impl Node {
     // correct check would be to have a weak count of 0 and a strong count <=2 (one for self, one for .nodes)
     let guard_ok = unsafe { weak_count == 0 && strong_count <= NODE_RC_MAX_GUARD };
     if guard_ok {
-      Ok(self.0.borrow_mut().node_ptr)
+      Ok(self.0.node_ptr)
     } else {
       Err(format!(
         "Can not mutably reference a shared Node {:?}! Rc: weak count: {:?}; strong count: {:?}",
@@ -131,19 +158,18 @@ impl Node {
   /// Wrap a libxml node ptr with a Node
   fn _wrap(node_ptr: xmlNodePtr, unlinked: bool, document: &DocumentRef) -> Node {
     // If already seen, return saved Node
-    if let Some(node) = document.borrow().get_node(node_ptr) {
-      return node.clone();
+    if let Some(node) = document.get_node(node_ptr) {
+      return node;
     }
     // If newly encountered pointer, wrap
     let node = _Node {
       node_ptr,
-      document: Rc::downgrade(document),
-      unlinked,
+      document: Cell::new(Rc::downgrade(document)),
+      unlinked: Cell::new(unlinked),
+      held: Cell::new(0),
     };
-    let wrapped_node = Node(Rc::new(RefCell::new(node)));
-    document
-      .borrow_mut()
-      .insert_node(node_ptr, wrapped_node.clone());
+    let wrapped_node = Node(Rc::new(node));
+    document.insert_node(node_ptr, wrapped_node.clone());
     wrapped_node
   }
   /// Wrap a node already linked to a `document` tree
@@ -175,11 +201,12 @@ impl Node {
 
   /// Create a mock node, used for a placeholder argument
   pub fn null() -> Self {
-    Node(Rc::new(RefCell::new(_Node {
+    Node(Rc::new(_Node {
       node_ptr: ptr::null_mut(),
-      document: Rc::downgrade(&Document::null_ref()),
-      unlinked: true,
-    })))
+      document: Cell::new(Rc::downgrade(&Document::null_ref())),
+      unlinked: Cell::new(true),
+      held: Cell::new(0),
+    }))
   }
 
   /// `libc::c_void` isn't hashable and cannot be made hashable
@@ -188,7 +215,9 @@ impl Node {
   }
 
   pub(crate) fn get_docref(&self) -> DocumentWeak {
-    self.0.borrow().document.clone()
+    let document = self.0.document.take();
+    self.0.document.set(document.clone());
+    document
   }
 
   /// Returns the next sibling if it exists
@@ -323,6 +352,49 @@ impl Node {
       .collect::<Vec<Node>>()
   }
 
+  /// Returns the position of this node among the children of its parent, counting from 0,
+  /// or `None` for an attribute or a node without a parent
+  pub fn index_in_parent(&self) -> Option<usize> {
+    traversal::index_in_parent(self.node_ptr())
+  }
+
+  /// Returns the child at `index`, counting from 0, if there are that many
+  pub fn nth_child(&self, index: usize) -> Option<Node> {
+    let ptr = traversal::nth_child(self.node_ptr(), index, false);
+    self.ptr_as_option(ptr)
+  }
+
+  /// Returns the child element at `index`, counting only elements from 0, if there are that
+  /// many
+  pub fn nth_element_child(&self, index: usize) -> Option<Node> {
+    let ptr = traversal::nth_child(self.node_ptr(), index, true);
+    self.ptr_as_option(ptr)
+  }
+
+  /// Returns the first child element named `local_name` in the namespace `ns_href`, or in no
+  /// namespace for `None`, whatever its prefix
+  pub fn find_child(&self, ns_href: Option<&str>, local_name: &str) -> Option<Node> {
+    let mut child = xmlGetFirstChild(self.node_ptr());
+    while !child.is_null() && !is_named(child, ns_href, local_name) {
+      child = xmlNextSibling(child);
+    }
+    self.ptr_as_option(child)
+  }
+
+  /// Returns the child elements named `local_name` in the namespace `ns_href`, see
+  /// [`Node::find_child`]
+  pub fn find_children(&self, ns_href: Option<&str>, local_name: &str) -> Vec<Node> {
+    let mut children = Vec::new();
+    let mut child = xmlGetFirstChild(self.node_ptr());
+    while !child.is_null() {
+      if is_named(child, ns_href, local_name) {
+        children.extend(self.ptr_as_option(child));
+      }
+      child = xmlNextSibling(child);
+    }
+    children
+  }
+
   /// Returns the parent if it exists
   pub fn get_parent(&self) -> Option<Node> {
     let ptr = xmlGetParent(self.node_ptr());
@@ -340,12 +412,12 @@ impl Node {
     new_sibling: &mut Node,
   ) -> Result<(), Box<dyn Error + Send + Sync>> {
     new_sibling.set_linked();
-    unsafe {
-      if xmlAddPrevSibling(self.node_ptr_mut()?, new_sibling.node_ptr_mut()?).is_null() {
-        Err(From::from("add_prev_sibling returned NULL"))
-      } else {
-        Ok(())
-      }
+    let added = unsafe { xmlAddPrevSibling(self.node_ptr_mut()?, new_sibling.node_ptr_mut()?) };
+    if added.is_null() {
+      Err(From::from("add_prev_sibling returned NULL"))
+    } else {
+      self.notify_inserted(added);
+      Ok(())
     }
   }
 
@@ -355,12 +427,12 @@ impl Node {
     new_sibling: &mut Node,
   ) -> Result<(), Box<dyn Error + Send + Sync>> {
     new_sibling.set_linked();
-    unsafe {
-      if xmlAddNextSibling(self.node_ptr_mut()?, new_sibling.node_ptr_mut()?).is_null() {
-        Err(From::from("add_next_sibling returned NULL"))
-      } else {
-        Ok(())
-      }
+    let added = unsafe { xmlAddNextSibling(self.node_ptr_mut()?, new_sibling.node_ptr_mut()?) };
+    if added.is_null() {
+      Err(From::from("add_next_sibling returned NULL"))
+    } else {
+      self.notify_inserted(added);
+      Ok(())
     }
   }
 
@@ -369,6 +441,21 @@ impl Node {
     self.get_type() == Some(NodeType::TextNode)
   }
 
+  /// Checks if the given node is a CDATA section
+  pub fn is_cdata(&self) -> bool {
+    self.get_type() == Some(NodeType::CDataSectionNode)
+  }
+
+  /// The target of a processing instruction, such as `xml-stylesheet`, `None` for other nodes
+  pub fn get_pi_target(&self) -> Option<String> {
+    (self.get_type() == Some(NodeType::PiNode)).then(|| self.get_name())
+  }
+
+  /// The data of a processing instruction, what follows its target, `None` for other nodes
+  pub fn get_pi_data(&self) -> Option<String> {
+    (self.get_type() == Some(NodeType::PiNode)).then(|| self.get_content())
+  }
+
   /// Checks if the given node is an Element
   pub fn is_element_node(&self) -> bool {
     self.get_type() == Some(NodeType::ElementNode)
@@ -379,6 +466,29 @@ impl Node {
     self.node_ptr().is_null()
   }
 
+  /// The line of the source document this node was parsed from, if known. libxml2 records
+  /// no columns for nodes, and lines past 65535 only with [`ParserOptions::big_lines`].
+  ///
+  /// [`ParserOptions::big_lines`]: crate::parser::ParserOptions::big_lines
+  pub fn get_line_number(&self) -> Option<usize> {
+    let line = unsafe { xmlGetLineNo(self.node_ptr()) };
+    (line > 0).then_some(line as usize)
+  }
+
+  /// The XPath-like location of this node in its document, e.g. `/root/items/item[3]`, for
+  /// reporting where a problem is. Elements in a default namespace, which XPath can only
+  /// name with a prefix, show as `*` steps.
+  pub fn get_path(&self) -> Option<String> {
+    let path_ptr = unsafe { xmlGetNodePath(self.node_ptr()) };
+    if path_ptr.is_null() {
+      return None;
+    }
+    let c_string = unsafe { CStr::from_ptr(path_ptr as *const c_char) };
+    let path = c_string.to_string_lossy().into_owned();
+    bindgenFree(path_ptr as *mut c_void);
+    Some(path)
+  }
+
   /// Returns the name of the node (empty string if name pointer is `NULL`)
   pub fn get_name(&self) -> String {
     let name_ptr = xmlNodeGetName(self.node_ptr());
@@ -410,13 +520,148 @@ impl Node {
     rust_utf8
   }
 
-  /// Sets the text content of this `Node`
+  /// Returns the text of this node and all its descendants, concatenated in document order
+  /// by libxml2 in a single pass: the DOM `textContent`. Entity references contribute their
+  /// replacement text.
+  pub fn text_content(&self) -> String {
+    let mut text = String::new();
+    self.text_content_into(&mut text);
+    text
+  }
+
+  /// Appends the text of [`Node::text_content`] to `text`, to reuse one buffer across nodes
+  pub fn text_content_into(&self, text: &mut String) {
+    unsafe {
+      let buf = xmlBufferCreate();
+      if buf.is_null() {
+        return;
+      }
+      if xmlNodeBufGetContent(buf, self.node_ptr()) == 0 {
+        let content = xmlBufferContent(buf);
+        if !content.is_null() {
+          text.push_str(&CStr::from_ptr(content as *const c_char).to_string_lossy());
+        }
+      }
+      xmlBufferFree(buf);
+    }
+  }
+
+  /// Sets the text content of this `Node`. For elements and attributes, entity and
+  /// character references in `content` are parsed, see [`Node::escapes_content`].
   pub fn set_content(&mut self, content: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
     let c_content = CString::new(content).unwrap();
+    let old_value = observer::observed(&self.get_docref()).then(|| self.get_content());
     unsafe { xmlNodeSetContent(self.node_ptr_mut()?, c_content.as_bytes().as_ptr()); }
+    if let Some(old_value) = old_value {
+      self.notify_text_changed(old_value);
+    }
+    Ok(())
+  }
+
+  /// Whether [`Node::set_content`] takes its argument as plain text, escaping `&` and `<`
+  /// when serializing, as it does for text, CDATA, comments and processing instructions.
+  /// For elements and attributes it parses entity and character references instead, so
+  /// `&amp;` stands for `&` and a lone `&` is an error; [`Node::set_text`] never does.
+  pub fn escapes_content(&self) -> bool {
+    !matches!(
+      self.get_type(),
+      Some(NodeType::ElementNode | NodeType::AttributeNode | NodeType::DocumentFragNode)
+    )
+  }
+
+  /// Sets the text content of this `Node` to `text` as it is, escaping it as needed when
+  /// serializing. The children of an element are replaced by a single text node.
+  pub fn set_text(&mut self, text: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if self.escapes_content() {
+      return self.set_content(text);
+    }
+    let c_text = CString::new(text)?;
+    let escaped = unsafe { xmlEncodeSpecialChars(ptr::null(), c_text.as_bytes().as_ptr()) };
+    if escaped.is_null() {
+      return Err(From::from("failed to escape the text"));
+    }
+    let old_value = observer::observed(&self.get_docref()).then(|| self.get_content());
+    let result = self.node_ptr_mut();
+    if let Ok(node_ptr) = result {
+      unsafe { xmlNodeSetContent(node_ptr, escaped) };
+    }
+    bindgenFree(escaped as *mut c_void);
+    result?;
+    if let Some(old_value) = old_value {
+      self.notify_text_changed(old_value);
+    }
+    Ok(())
+  }
+
+  /// Parses `xml`, a well-formed sequence of content such as `<b>bold</b> text`, in the
+  /// context of this element, whose namespaces are in scope, and appends the resulting
+  /// nodes to its children, returning them
+  pub fn append_raw_xml(&mut self, xml: &str) -> Result<Vec<Node>, Box<dyn Error + Send + Sync>> {
+    if self.get_type() != Some(NodeType::ElementNode) {
+      return Err(From::from("XML can only be parsed into an element"));
+    }
+    let node_ptr = self.node_ptr_mut()?;
+    let len = try_usize_to_i32(xml.len())?;
+    let document = self
+      .get_docref()
+      .upgrade()
+      .ok_or("the document of the node is gone")?;
+    let options = xmlParserOption_XML_PARSE_NONET
+      | xmlParserOption_XML_PARSE_NOERROR
+      | xmlParserOption_XML_PARSE_NOWARNING;
+    let mut list = ptr::null_mut();
+    let status = unsafe {
+      xmlParseInNodeContext(
+        node_ptr,
+        xml.as_ptr() as *const c_char,
+        len,
+        options as c_int,
+        &mut list,
+      )
+    };
+    if status != xmlParserErrors_XML_ERR_OK {
+      unsafe { xmlFreeNodeList(list) };
+      return Err(From::from(format!("malformed XML content (error {status})")));
+    }
+    let mut nodes = Vec::new();
+    while !list.is_null() {
+      let child_ptr = list;
+      list = xmlNextSibling(child_ptr);
+      // Linked as is, as xmlAddChild would merge text nodes into the existing children
+      unsafe { fragment::link_last_child(node_ptr, child_ptr) };
+      self.notify_inserted(child_ptr);
+      nodes.push(Node::wrap(child_ptr, &document));
+    }
+    Ok(nodes)
+  }
+
+  /// Returns the base URI of this node, from the `xml:base` attributes of it and its
+  /// ancestors resolved against the URL of the document
+  pub fn get_base_uri(&self) -> Option<String> {
+    base_uri(self.node_ptr())
+  }
+
+  /// Sets the base URI of this element with an `xml:base` attribute, or the URL of the
+  /// document for a document node
+  pub fn set_base_uri(&mut self, uri: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !matches!(
+      self.get_type(),
+      Some(NodeType::ElementNode | NodeType::DocumentNode | NodeType::HtmlDocumentNode)
+    ) {
+      return Err(From::from("only elements and documents have a base URI"));
+    }
+    let c_uri = CString::new(uri)?;
+    unsafe { xmlNodeSetBase(self.node_ptr_mut()?, c_uri.as_bytes().as_ptr()) };
     Ok(())
   }
 
+  /// Whether whitespace is significant in this node, as set by the nearest `xml:space`
+  /// attribute on it or its ancestors: `Some(true)` for `preserve`, `Some(false)` for
+  /// `default`, `None` if there is none
+  pub fn preserves_space(&self) -> Option<bool> {
+    space_preserve(self.node_ptr())
+  }
+
   /// Returns the value of property `name`
   pub fn get_property(&self, name: &str) -> Option<String> {
     let c_name = CString::new(name).unwrap();
@@ -545,13 +790,15 @@ impl Node {
   ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let c_name = CString::new(name).unwrap();
     let c_value = CString::new(value).unwrap();
-    unsafe {
+    let old_value = self.observed_property(|node| node.get_property_no_ns(name));
+    let attr = unsafe {
       xmlSetProp(
         self.node_ptr_mut()?,
         c_name.as_bytes().as_ptr(),
         c_value.as_bytes().as_ptr(),
       )
     };
+    self.notify_attribute_set(attr, old_value, value);
     Ok(())
   }
   /// Sets a namespaced attribute
@@ -563,7 +810,8 @@ impl Node {
   ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let c_name = CString::new(name).unwrap();
     let c_value = CString::new(value).unwrap();
-    unsafe {
+    let old_value = self.observed_property(|node| node.get_property_ns(name, &ns.get_href()));
+    let attr = unsafe {
       xmlSetNsProp(
         self.node_ptr_mut()?,
         ns.ns_ptr(),
@@ -571,17 +819,64 @@ impl Node {
         c_value.as_bytes().as_ptr(),
       )
     };
+    self.notify_attribute_set(attr, old_value, value);
     Ok(())
   }
 
+  /// Sets the attribute `qualified_name`, such as `xlink:href`, in the namespace `href`,
+  /// reusing a prefix bound to it in scope or declaring it on this element. A prefix of
+  /// `qualified_name` bound to another namespace here is replaced by a new one.
+  pub fn set_qualified_attribute(
+    &mut self,
+    href: &str,
+    qualified_name: &str,
+    value: &str,
+  ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if href.is_empty() {
+      return Err(From::from("an attribute namespace needs a URI"));
+    }
+    let (prefix, local_name) = match qualified_name.split_once(':') {
+      Some((prefix, local_name)) => (Some(prefix), local_name),
+      None => (None, qualified_name),
+    };
+    let bound = |node: &Node, prefix: &str| {
+      node
+        .search_namespace(Some(prefix))
+        .filter(|ns| ns.get_href() == href)
+    };
+    let in_scope = prefix
+      .and_then(|prefix| bound(self, prefix))
+      .or_else(|| {
+        self
+          .lookup_prefix(href)
+          .and_then(|prefix| bound(self, &prefix))
+      });
+    let ns = match in_scope {
+      Some(ns) => ns,
+      None => {
+        let prefix = match prefix.filter(|prefix| self.search_namespace(Some(prefix)).is_none()) {
+          Some(prefix) => prefix.to_owned(),
+          None => (0..)
+            .map(|i| format!("ns{i}"))
+            .find(|prefix| self.search_namespace(Some(prefix)).is_none())
+            .unwrap(),
+        };
+        Namespace::new(&prefix, href, self)?
+      }
+    };
+    self.set_property_ns(local_name, value, &ns)
+  }
+
   /// Removes the property of given `name`
   pub fn remove_property(&mut self, name: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
     let c_name = CString::new(name).unwrap();
     unsafe {
       let attr_node = xmlHasProp(self.node_ptr_mut()?, c_name.as_bytes().as_ptr());
       if !attr_node.is_null() {
+        let mutation = self.attribute_removal(attr_node);
         let remove_prop_status = xmlRemoveProp(attr_node);
         if remove_prop_status == 0 {
+          self.notify(mutation);
           Ok(())
         } else {
           // Propagate libxml2 failure to remove
@@ -595,13 +890,15 @@ impl Node {
     }
   }
 
-  /// Removes the property of given `name` and namespace (`ns`)
+  /// Removes the property of given `name` and namespace (`ns`). A prefix in `name`, as
+  /// in `xlink:href`, is ignored.
   pub fn remove_property_ns(
     &mut self,
     name: &str,
     ns: &str,
   ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let c_name = CString::new(name).unwrap();
+    let local_name = name.split_once(':').map_or(name, |(_, local_name)| local_name);
+    let c_name = CString::new(local_name).unwrap();
     let c_ns = CString::new(ns).unwrap();
     unsafe {
       let attr_node = xmlHasNsProp(
@@ -610,8 +907,10 @@ impl Node {
         c_ns.as_bytes().as_ptr(),
       );
       if !attr_node.is_null() {
+        let mutation = self.attribute_removal(attr_node);
         let remove_prop_status = xmlRemoveProp(attr_node);
         if remove_prop_status == 0 {
+          self.notify(mutation);
           Ok(())
         } else {
           // Propagate libxml2 failure to remove
@@ -636,8 +935,10 @@ impl Node {
       )
     };
     if !attr_node.is_null() {
+      let mutation = self.attribute_removal(attr_node);
       let remove_prop_status = unsafe { xmlRemoveProp(attr_node) };
       if remove_prop_status == 0 {
+        self.notify(mutation);
         Ok(())
       } else {
         // Propagate libxml2 failure to remove
@@ -838,6 +1139,17 @@ impl Node {
     Ok(())
   }
 
+  /// Declares the namespaces used in the subtree of this node which are not in scope where
+  /// they are used, as happens after moving nodes between documents or subtrees, reusing
+  /// declarations in scope for the same URI. Missing declarations are added to this node.
+  pub fn reconcile_namespaces(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let node_ptr = self.node_ptr_mut()?;
+    if unsafe { xmlReconciliateNs(xmlGetDoc(node_ptr), node_ptr) } < 0 {
+      return Err(From::from("xmlReconciliateNs failed"));
+    }
+    Ok(())
+  }
+
   /// Looks up the prefix of a namespace from its URI, basedo around a given `Node`
   pub fn lookup_namespace_prefix(&self, href: &str) -> Option<String> {
     if href.is_empty() {
@@ -857,29 +1169,33 @@ impl Node {
     }
   }
 
-  /// Looks up the uri of a namespace from its prefix, basedo around a given `Node`
+  /// Looks up the prefix bound to the namespace `href` in the scope of this node, `None` if
+  /// there is none or the namespace is only the default one
+  pub fn lookup_prefix(&self, href: &str) -> Option<String> {
+    namespace::lookup_prefix(self.node_ptr(), href)
+  }
+
+  /// Looks up the uri of a namespace from its prefix, basedo around a given `Node`, the
+  /// default namespace for `""`
   pub fn lookup_namespace_uri(&self, prefix: &str) -> Option<String> {
-    if prefix.is_empty() {
-      return None;
-    }
-    let c_prefix = CString::new(prefix).unwrap();
-    unsafe {
-      let ns_ptr = xmlSearchNs(
-        xmlGetDoc(self.node_ptr()),
-        self.node_ptr(),
-        c_prefix.as_bytes().as_ptr(),
-      );
-      if !ns_ptr.is_null() {
-        let ns = Namespace { ns_ptr };
-        let ns_prefix = ns.get_href();
-        if !ns_prefix.is_empty() {
-          Some(ns_prefix)
-        } else {
-          None
-        }
-      } else {
-        None
-      }
+    self
+      .search_namespace(Some(prefix).filter(|prefix| !prefix.is_empty()))
+      .map(|ns| ns.get_href())
+      .filter(|href| !href.is_empty())
+  }
+
+  /// Searches the in-scope `Namespace` bound to `prefix` (`None` for the default namespace)
+  pub(crate) fn search_namespace(&self, prefix: Option<&str>) -> Option<Namespace> {
+    let c_prefix = prefix.map(|p| CString::new(p).unwrap());
+    let prefix_ptr = c_prefix
+      .as_ref()
+      .map_or(ptr::null(), |p| p.as_bytes().as_ptr());
+    let node_ptr = self.node_ptr();
+    let ns_ptr = unsafe { xmlSearchNs(xmlGetDoc(node_ptr), node_ptr, prefix_ptr) };
+    if ns_ptr.is_null() {
+      None
+    } else {
+      Some(Namespace { ns_ptr })
     }
   }
 
@@ -909,11 +1225,69 @@ impl Node {
       if new_child_ptr.is_null() {
         Err("add_child encountered NULL pointer".to_string())
       } else {
+        self.notify_inserted(new_child_ptr);
         Ok(())
       }
     }
   }
 
+  /// Appends `children`, in order, to the children of this node, moving the ones linked
+  /// elsewhere in the document, and returns how many were appended. Unlike repeated calls to
+  /// [`Node::add_child`] this never merges adjacent text nodes, and looks up the document
+  /// and its observers once for the whole batch. On error, the children before the failing
+  /// one stay appended.
+  pub fn append_children(
+    &mut self,
+    children: impl IntoIterator<Item = Node>,
+  ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    if !matches!(
+      self.get_type(),
+      Some(NodeType::ElementNode | NodeType::DocumentNode | NodeType::DocumentFragNode)
+    ) {
+      return Err(From::from("only elements and documents can have children appended"));
+    }
+    let parent_ptr = self.node_ptr_mut()?;
+    let doc_ptr = xmlGetDoc(parent_ptr);
+    let document = self.get_docref();
+    let observed = observer::observed(&document);
+    let mut count = 0;
+    for mut child in children {
+      let child_ptr = child.node_ptr();
+      if !matches!(
+        child.get_type(),
+        Some(
+          NodeType::ElementNode
+            | NodeType::TextNode
+            | NodeType::CDataSectionNode
+            | NodeType::EntityRefNode
+            | NodeType::PiNode
+            | NodeType::CommentNode
+        )
+      ) {
+        return Err(From::from("only content nodes can be appended"));
+      }
+      if xmlGetDoc(child_ptr) != doc_ptr {
+        return Err(From::from("the node belongs to another document, adopt it first"));
+      }
+      if is_ancestor_or_self(child_ptr, parent_ptr) {
+        return Err(From::from("a node can't be appended to its own subtree"));
+      }
+      if !xmlGetParent(child_ptr).is_null() {
+        child.unlink();
+      }
+      unsafe { fragment::link_last_child(parent_ptr, child_ptr) };
+      child.set_linked();
+      if observed {
+        observer::notify(&document, || Mutation::Inserted {
+          node: RoNode(child_ptr),
+          parent: RoNode(parent_ptr),
+        });
+      }
+      count += 1;
+    }
+    Ok(count)
+  }
+
   /// Creates a new `Node` as child to the self `Node`
   pub fn new_child(
     &mut self,
@@ -932,6 +1306,7 @@ impl Node {
         c_name.as_bytes().as_ptr(),
         ptr::null(),
       );
+      self.notify_inserted(new_ptr);
       Ok(Node::wrap(new_ptr, &self.get_docref().upgrade().unwrap()))
     }
   }
@@ -956,6 +1331,7 @@ impl Node {
         c_name.as_bytes().as_ptr(),
         c_content.as_bytes().as_ptr(),
       );
+      self.notify_inserted(new_ptr);
       Ok(Node::wrap(new_ptr, &self.get_docref().upgrade().unwrap()))
     }
   }
@@ -965,9 +1341,13 @@ impl Node {
     let c_len = content.len() as i32;
     if c_len > 0 {
       let c_content = CString::new(content).unwrap();
+      let old_value = observer::observed(&self.get_docref()).then(|| self.get_content());
       unsafe {
         xmlNodeAddContentLen(self.node_ptr_mut()?, c_content.as_bytes().as_ptr(), c_len);
       }
+      if let Some(old_value) = old_value {
+        self.notify_text_changed(old_value);
+      }
     }
     Ok(())
   }
@@ -984,9 +1364,16 @@ impl Node {
     {
       // only unlink nodes that are currently marked as linked
       self.set_unlinked();
+      let parent = xmlGetParent(self.node_ptr());
       unsafe {
         xmlUnlinkNode(self.node_ptr());
       }
+      if !parent.is_null() {
+        observer::notify(&self.get_docref(), || Mutation::Removed {
+          node: RoNode(self.node_ptr()),
+          parent: RoNode(parent),
+        });
+      }
     }
   }
   /// Alias for `unlink_node`
@@ -1002,9 +1389,89 @@ impl Node {
     self.unlink_node()
   }
 
+  /// Removes all children of this node at once, freeing them. Removed nodes still held
+  /// elsewhere, children or deeper descendants, become unlinked instead.
+  pub fn remove_children(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let node_ptr = self.node_ptr_mut()?;
+    if self.get_type() == Some(NodeType::EntityRefNode) {
+      return Err(From::from("the children of an entity reference belong to the entity"));
+    }
+    let document = self.get_docref();
+    loop {
+      let child = xmlGetFirstChild(node_ptr);
+      if child.is_null() {
+        return Ok(());
+      }
+      remove_child(child, &document);
+    }
+  }
+
+  /// Removes the children of this node for which `keep` returns false, in a single pass,
+  /// and returns how many were removed. Removed children are freed as by
+  /// [`Node::remove_children`].
+  pub fn retain_children(
+    &mut self,
+    mut keep: impl FnMut(RoNode) -> bool,
+  ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let node_ptr = self.node_ptr_mut()?;
+    if self.get_type() == Some(NodeType::EntityRefNode) {
+      return Err(From::from("the children of an entity reference belong to the entity"));
+    }
+    let mut removed = Vec::new();
+    let mut child = xmlGetFirstChild(node_ptr);
+    while !child.is_null() {
+      let next = xmlNextSibling(child);
+      if !keep(RoNode(child)) {
+        unsafe { xmlUnlinkNode(child) };
+        removed.push(child);
+      }
+      child = next;
+    }
+    // Observers may change the document, only once the walk over it is done
+    let document = self.get_docref();
+    for &child in &removed {
+      observer::notify(&document, || Mutation::Removed {
+        node: RoNode(child),
+        parent: RoNode(node_ptr),
+      });
+    }
+    for &child in &removed {
+      release_removed(child, &document);
+    }
+    Ok(removed.len())
+  }
+
+  /// Merges adjacent text nodes and removes empty ones throughout the subtree of this node,
+  /// like the DOM `Node.normalize()`. CDATA sections are left as they are. Removed text
+  /// nodes still held elsewhere become unlinked.
+  pub fn normalize(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let root = self.node_ptr_mut()?;
+    let document = self.get_docref();
+    let observed = observer::observed(&document);
+    let (mut mutations, mut removed) = (Vec::new(), Vec::new());
+    let mut node = root;
+    while !node.is_null() {
+      if matches!(
+        NodeType::from_int(xmlGetNodeType(node)),
+        Some(NodeType::ElementNode | NodeType::DocumentNode | NodeType::DocumentFragNode)
+      ) {
+        normalize_children(node, observed, &mut mutations, &mut removed);
+      }
+      node = traversal::next_in_document_order(node, root);
+    }
+    // Observers may change the document, only once the walk over it is done
+    for mutation in mutations {
+      observer::notify(&document, || mutation);
+    }
+    for node in removed {
+      release_removed(node, &document);
+    }
+    Ok(())
+  }
+
   /// Checks if node is marked as unlinked
   pub fn is_unlinked(&self) -> bool {
-    self.0.borrow().unlinked
+    self.0.unlinked.get()
   }
 
   fn ptr_as_option(&self, node_ptr: xmlNodePtr) -> Option<Node> {
@@ -1017,19 +1484,74 @@ impl Node {
     }
   }
 
+  /// Reports the insertion of `child_ptr`, as returned by libxml2, to the document observers
+  fn notify_inserted(&self, child_ptr: xmlNodePtr) {
+    if child_ptr.is_null() {
+      return;
+    }
+    observer::notify(&self.get_docref(), || Mutation::Inserted {
+      node: RoNode(child_ptr),
+      parent: RoNode(xmlGetParent(child_ptr)),
+    });
+  }
+
+  fn notify_text_changed(&self, old_value: String) {
+    observer::notify(&self.get_docref(), || Mutation::TextChanged {
+      node: RoNode(self.node_ptr()),
+      old_value,
+      new_value: self.get_content(),
+    });
+  }
+
+  /// The value an attribute had before a change, only looked up when observed
+  fn observed_property(&self, get: impl FnOnce(&Self) -> Option<String>) -> Option<String> {
+    if observer::observed(&self.get_docref()) {
+      get(self)
+    } else {
+      None
+    }
+  }
+
+  fn notify_attribute_set(&self, attr: xmlAttrPtr, old_value: Option<String>, value: &str) {
+    if attr.is_null() || old_value.as_deref() == Some(value) {
+      return;
+    }
+    observer::notify(&self.get_docref(), || {
+      observer::attribute_changed(self.node_ptr(), attr, old_value, Some(value.to_owned()))
+    });
+  }
+
+  /// Describes the removal of `attr`, which must happen before it is freed
+  fn attribute_removal(&self, attr: xmlAttrPtr) -> Option<Mutation> {
+    observer::observed(&self.get_docref()).then(|| {
+      let old_value = RoNode(attr as xmlNodePtr).get_content();
+      observer::attribute_changed(self.node_ptr(), attr, Some(old_value), None)
+    })
+  }
+
+  fn notify(&self, mutation: Option<Mutation>) {
+    if let Some(mutation) = mutation {
+      observer::notify(&self.get_docref(), || mutation);
+    }
+  }
+
   /// internal helper to ensure the node is marked as linked/imported/adopted in the main document tree
   pub(crate) fn set_linked(&self) {
-    self.0.borrow_mut().unlinked = false;
+    self.0.unlinked.set(false);
+  }
+
+  /// internal helper to move the node to the bookkeeping of another document
+  pub(crate) fn set_document(&self, document: &DocumentRef) {
+    self.0.document.set(Rc::downgrade(document));
   }
 
   /// internal helper to ensure the node is marked as unlinked/removed from the main document tree
   pub(crate) fn set_unlinked(&self) {
-    self.0.borrow_mut().unlinked = true;
+    self.0.unlinked.set(true);
     self
       .get_docref()
       .upgrade()
       .unwrap()
-      .borrow_mut()
       .forget_node(self.node_ptr());
   }
 
@@ -1050,29 +1572,144 @@ impl Node {
     Ok(nodes.first().cloned())
   }
 
-  /// Get a list of ancestor Node for this Node.
-  pub fn ancestors(&self) -> Vec<Node> {
-    let node_ptr = self.node_ptr();
-
-    let mut res = Vec::new();
-
-    let ancestor_ptrs = node_ancestors(node_ptr);
-
-    for ptr in ancestor_ptrs {
-      if let Some(node) = self.ptr_as_option(ptr) {
-        res.push(node)
-      }
-    }
-
-    res
-  }
-
   /// find String values via xpath, at a specified node or the document root
   pub fn findvalues(&self, xpath: &str) -> Result<Vec<String>, ()> {
     let mut context = Context::from_node(self)?;
     context.findvalues(xpath, Some(self))
   }
 
+  /// Puts `new` in the place of this node, which is unlinked. `new` is moved from wherever
+  /// it was in the same document, and namespaces it uses which are not in scope at its new
+  /// place are declared on it.
+  pub fn replace_with(&mut self, new: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let node_ptr = self.node_ptr_mut()?;
+    let new_ptr = new.node_ptr_mut()?;
+    let parent = xmlGetParent(node_ptr);
+    if parent.is_null() || self.get_type() == Some(NodeType::AttributeNode) {
+      return Err(From::from("only a node with a parent can be replaced"));
+    }
+    self.check_movable(new_ptr)?;
+    if is_ancestor_or_self(new_ptr, node_ptr) {
+      return Err(From::from("a node can't be replaced by one of its ancestors"));
+    }
+    new.unlink();
+    unsafe { xmlReplaceNode(node_ptr, new_ptr) };
+    self.set_unlinked();
+    observer::notify(&self.get_docref(), || Mutation::Removed {
+      node: RoNode(node_ptr),
+      parent: RoNode(parent),
+    });
+    new.set_linked();
+    new.notify_inserted(new_ptr);
+    reconcile(new_ptr)
+  }
+
+  /// Puts the element `wrapper` in the place of this node, and this node at the end of its
+  /// children, keeping the namespaces of both in scope
+  pub fn wrap_in(&mut self, wrapper: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if wrapper.get_type() != Some(NodeType::ElementNode) {
+      return Err(From::from("only an element can wrap a node"));
+    }
+    self.replace_with(wrapper)?;
+    let node_ptr = self.node_ptr_mut()?;
+    let wrapper_ptr = wrapper.node_ptr();
+    unsafe { fragment::link_last_child(wrapper_ptr, node_ptr) };
+    self.set_linked();
+    self.notify_inserted(node_ptr);
+    reconcile(node_ptr)
+  }
+
+  /// Moves the children of this element in its place and unlinks it, returning them.
+  /// Namespaces the children use from declarations on this element are declared on them.
+  pub fn unwrap(&mut self) -> Result<Vec<Node>, Box<dyn Error + Send + Sync>> {
+    if self.get_type() != Some(NodeType::ElementNode) {
+      return Err(From::from("only an element can be unwrapped"));
+    }
+    let node_ptr = self.node_ptr_mut()?;
+    if xmlGetParent(node_ptr).is_null() {
+      return Err(From::from("only an element with a parent can be unwrapped"));
+    }
+    let children = self.get_child_nodes();
+    for child in &children {
+      let child_ptr = child.node_ptr();
+      unsafe {
+        xmlUnlinkNode(child_ptr);
+        fragment::link_before(node_ptr, child_ptr);
+      }
+      self.notify_inserted(child_ptr);
+      // Before this element and its declarations go away
+      reconcile(child_ptr)?;
+    }
+    self.unlink_node();
+    Ok(children)
+  }
+
+  /// Moves this node with its subtree to the end of the children of `target_parent`, which
+  /// may belong to another document. The subtree is then adopted by that document, see
+  /// [`Document::adopt_node`], and no longer refers to anything of the source document, which
+  /// can be dropped afterwards.
+  pub fn move_to(&mut self, target_parent: &mut Node) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !matches!(
+      target_parent.get_type(),
+      Some(NodeType::ElementNode | NodeType::DocumentNode | NodeType::DocumentFragNode)
+    ) {
+      return Err(From::from("only elements and documents can have children moved to them"));
+    }
+    if !matches!(
+      self.get_type(),
+      Some(
+        NodeType::ElementNode
+          | NodeType::TextNode
+          | NodeType::CDataSectionNode
+          | NodeType::EntityRefNode
+          | NodeType::PiNode
+          | NodeType::CommentNode
+      )
+    ) {
+      return Err(From::from("only content nodes can be moved into the tree"));
+    }
+    let parent_ptr = target_parent.node_ptr_mut()?;
+    let node_ptr = self.node_ptr_mut()?;
+    if is_ancestor_or_self(node_ptr, parent_ptr) {
+      return Err(From::from("a node can't be moved into its own subtree"));
+    }
+    let target_document = target_parent
+      .get_docref()
+      .upgrade()
+      .ok_or("the document of the target is gone")?;
+    Document(target_document).adopt_node(self)?;
+    unsafe { fragment::link_last_child(parent_ptr, node_ptr) };
+    self.set_linked();
+    target_parent.notify_inserted(node_ptr);
+    reconcile(node_ptr)
+  }
+
+  /// Checks that `node_ptr` may be moved to the place of this node
+  fn check_movable(&self, node_ptr: xmlNodePtr) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if node_ptr == self.node_ptr() {
+      return Err(From::from("a node can't take its own place"));
+    }
+    if xmlGetDoc(node_ptr) != xmlGetDoc(self.node_ptr()) {
+      return Err(From::from(
+        "the node belongs to another document, adopt it first",
+      ));
+    }
+    if !matches!(
+      NodeType::from_int(xmlGetNodeType(node_ptr)),
+      Some(
+        NodeType::ElementNode
+          | NodeType::TextNode
+          | NodeType::CDataSectionNode
+          | NodeType::EntityRefNode
+          | NodeType::PiNode
+          | NodeType::CommentNode
+      )
+    ) {
+      return Err(From::from("only content nodes can be moved into the tree"));
+    }
+    Ok(())
+  }
+
   /// replace a `self`'s `old` child node with a `new` node in the same position
   /// borrowed from Perl's XML::LibXML
   pub fn replace_child_node(
@@ -1114,6 +1751,27 @@ impl Node {
   }
 }
 
+/// Whether `ancestor` is `node` or one of its ancestors
+pub(crate) fn is_ancestor_or_self(ancestor: xmlNodePtr, mut node: xmlNodePtr) -> bool {
+  while !node.is_null() {
+    if node == ancestor {
+      return true;
+    }
+    node = xmlGetParent(node);
+  }
+  false
+}
+
+/// Declares the namespaces used in the subtree of the moved `node` that are out of scope
+fn reconcile(node: xmlNodePtr) -> Result<(), Box<dyn Error + Send + Sync>> {
+  if NodeType::from_int(xmlGetNodeType(node)) == Some(NodeType::ElementNode)
+    && unsafe { xmlReconciliateNs(xmlGetDoc(node), node) } < 0
+  {
+    return Err(From::from("xmlReconciliateNs failed"));
+  }
+  Ok(())
+}
+
 fn node_ancestors(node_ptr: xmlNodePtr) -> Vec<xmlNodePtr> {
   if node_ptr.is_null() {
     return Vec::new();
@@ -1135,4 +1793,147 @@ fn node_ancestors(node_ptr: xmlNodePtr) -> Vec<xmlNodePtr> {
   }
 }
 
+/// The base URI of `node`, see [`Node::get_base_uri`]
+pub(crate) fn base_uri(node: xmlNodePtr) -> Option<String> {
+  let uri_ptr = unsafe { xmlNodeGetBase(xmlGetDoc(node), node) };
+  if uri_ptr.is_null() {
+    return None;
+  }
+  let uri = unsafe { CStr::from_ptr(uri_ptr as *const c_char) }
+    .to_string_lossy()
+    .into_owned();
+  bindgenFree(uri_ptr as *mut c_void);
+  Some(uri)
+}
+
+/// The inherited `xml:space` of `node`, see [`Node::preserves_space`]
+pub(crate) fn space_preserve(node: xmlNodePtr) -> Option<bool> {
+  match unsafe { xmlNodeGetSpacePreserve(node) } {
+    1 => Some(true),
+    0 => Some(false),
+    _ => None,
+  }
+}
+
+/// Merges the adjacent text children of `parent` and unlinks the empty and merged ones into
+/// `removed`, recording the changes for observers in `mutations`
+fn normalize_children(
+  parent: xmlNodePtr,
+  observed: bool,
+  mutations: &mut Vec<Mutation>,
+  removed: &mut Vec<xmlNodePtr>,
+) {
+  let mut text: xmlNodePtr = ptr::null_mut();
+  let mut child = xmlGetFirstChild(parent);
+  while !child.is_null() {
+    let next = xmlNextSibling(child);
+    if NodeType::from_int(xmlGetNodeType(child)) != Some(NodeType::TextNode) {
+      text = ptr::null_mut();
+    } else {
+      let content = unsafe { (*child).content };
+      if content.is_null() || unsafe { *content } == 0 {
+        unsafe { xmlUnlinkNode(child) };
+        removed.push(child);
+        if observed {
+          mutations.push(Mutation::Removed {
+            node: RoNode(child),
+            parent: RoNode(parent),
+          });
+        }
+      } else if text.is_null() {
+        text = child;
+      } else {
+        let old_value = observed.then(|| RoNode(text).get_content());
+        unsafe {
+          xmlNodeAddContent(text, content);
+          xmlUnlinkNode(child);
+        }
+        removed.push(child);
+        if let Some(old_value) = old_value {
+          mutations.push(Mutation::Removed {
+            node: RoNode(child),
+            parent: RoNode(parent),
+          });
+          mutations.push(Mutation::TextChanged {
+            node: RoNode(text),
+            old_value,
+            new_value: RoNode(text).get_content(),
+          });
+        }
+      }
+    }
+    child = next;
+  }
+}
+
+/// Whether `node` is an element named `local_name` in the namespace `ns_href`
+fn is_named(node: xmlNodePtr, ns_href: Option<&str>, local_name: &str) -> bool {
+  let node = RoNode(node);
+  node.get_type() == Some(NodeType::ElementNode)
+    && node.get_name() == local_name
+    && node.get_namespace().map(|ns| ns.get_href()).as_deref() == ns_href
+}
+
+/// Unlinks `node` from its parent, freeing it unless a `Node` still holds it
+fn remove_child(node: xmlNodePtr, document: &DocumentWeak) {
+  let parent = xmlGetParent(node);
+  unsafe { xmlUnlinkNode(node) };
+  observer::notify(document, || Mutation::Removed {
+    node: RoNode(node),
+    parent: RoNode(parent),
+  });
+  release_removed(node, document);
+}
+
+/// Frees the unlinked `node`, or leaves it to the `Node` which holds it
+fn release_removed(node: xmlNodePtr, document: &DocumentWeak) {
+  let wrapped = document
+    .upgrade()
+    .and_then(|document| document.forget_node(node));
+  match wrapped {
+    Some(wrapped) => wrapped.set_unlinked(),
+    None => free_removed(node, document),
+  }
+}
+
+/// Frees the unlinked subtree of `node`, which no `Node` holds. The nodes of the subtree
+/// wrapped in a `Node` are detached from it first, with their own subtrees, and become
+/// unlinked nodes of their own, so that no `Node` is left pointing into freed memory.
+fn free_removed(node: xmlNodePtr, document: &DocumentWeak) {
+  let Some(document) = document.upgrade() else {
+    unsafe { xmlFreeNode(node) };
+    return;
+  };
+  let mut held = Vec::new();
+  let mut current = node;
+  while !current.is_null() {
+    if current != node
+      && let Some(wrapped) = document.forget_node(current)
+    {
+      let next = traversal::next_outside(current, node);
+      unsafe { xmlUnlinkNode(current) };
+      wrapped.set_unlinked();
+      held.push(wrapped);
+      current = next;
+      continue;
+    }
+    if xmlGetNodeType(current) == xmlElementType_XML_ELEMENT_NODE {
+      let mut attr = xmlGetFirstProperty(current);
+      while !attr.is_null() {
+        let next = xmlNextPropertySibling(attr);
+        if let Some(wrapped) = document.forget_node(attr as xmlNodePtr) {
+          unsafe { xmlUnlinkNode(attr as xmlNodePtr) };
+          wrapped.set_unlinked();
+          held.push(wrapped);
+        }
+        attr = next;
+      }
+    }
+    current = traversal::next_in_document_order(current, node);
+  }
+  unsafe { xmlFreeNode(node) };
+  // The detached nodes no longer held elsewhere are freed in turn
+  drop(held);
+}
+
 mod c14n;
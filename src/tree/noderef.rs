@@ -0,0 +1,530 @@
+//! Stable node references and structural node paths
+//!
+//! A [`NodeRef`] identifies a node without keeping it alive. It stays valid while the node is
+//! unlinked, moved and reinserted, notices when libxml2 frees the node, and carries the
+//! [`NodePath`] of the node so that it can be found again in a reparsed copy of the document.
+//!
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::{Rc, Weak};
+use std::str::FromStr;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::userdata;
+use crate::tree::{Document, DocumentRef, DocumentWeak, Node, NodeType};
+
+/// A referenced node, shared by all the references to it
+struct Tracked {
+  id: u64,
+  /// The document the node is in, updated when another document adopts it
+  document: RefCell<DocumentWeak>,
+}
+
+thread_local! {
+  /// The referenced nodes, dropped when libxml2 frees the node so that the references to it
+  /// can no longer upgrade
+  static TRACKED: RefCell<HashMap<usize, Rc<Tracked>>> = RefCell::new(HashMap::new());
+  static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+  static PREVIOUS_DEREGISTER: Cell<Option<xmlDeregisterNodeFunc>> = const { Cell::new(None) };
+}
+
+/// Called by libxml2 for every node it frees
+unsafe extern "C" fn deregister_node(node: xmlNodePtr) {
+  let _ = TRACKED.try_with(|tracked| {
+    if let Ok(mut tracked) = tracked.try_borrow_mut() {
+      tracked.remove(&(node as usize));
+    }
+  });
+  userdata::release(node);
+  if let Ok(Some(Some(previous))) = PREVIOUS_DEREGISTER.try_with(Cell::get) {
+    unsafe { previous(node) };
+  }
+}
+
+/// Has libxml2 report the nodes it frees on this thread, for node references and user data
+pub(crate) fn hook_node_deregistration() {
+  PREVIOUS_DEREGISTER.with(|previous| {
+    if previous.get().is_none() {
+      // Depending on how libxml2 was built the hook is global or per thread, another thread
+      // may already have installed it
+      let chained = unsafe { xmlDeregisterNodeDefault(Some(deregister_node)) }.filter(|hook| {
+        !std::ptr::fn_addr_eq(*hook, deregister_node as unsafe extern "C" fn(xmlNodePtr))
+      });
+      previous.set(Some(chained));
+    }
+  });
+}
+
+/// Returns the identifier of `node_ptr` in `document`, creating it (and hooking into libxml2)
+/// on first use
+fn track(node_ptr: xmlNodePtr, document: DocumentWeak) -> (u64, Weak<Tracked>) {
+  hook_node_deregistration();
+  TRACKED.with(|tracked| {
+    let mut tracked = tracked.borrow_mut();
+    let entry = tracked.entry(node_ptr as usize).or_insert_with(|| {
+      NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        Rc::new(Tracked {
+          id,
+          document: RefCell::new(Weak::new()),
+        })
+      })
+    });
+    *entry.document.borrow_mut() = document;
+    (entry.id, Rc::downgrade(entry))
+  })
+}
+
+/// Points the references to `node_ptr`, if any, to `document`, which adopted the node
+pub(crate) fn adopted(node_ptr: xmlNodePtr, document: &DocumentRef) {
+  let _ = TRACKED.try_with(|tracked| {
+    if let Some(entry) = tracked.borrow().get(&(node_ptr as usize)) {
+      *entry.document.borrow_mut() = Rc::downgrade(document);
+    }
+  });
+}
+
+/// A weak, stable reference to a node.
+///
+/// References to the same node compare equal and hash alike, whichever way they were obtained,
+/// so they can key external stores of annotations. Unlike a [`Node`] a `NodeRef` does not keep
+/// the node from being freed: once libxml2 frees it, for instance along with an unlinked parent
+/// or when its document is dropped, the reference becomes invalid and [`NodeRef::resolve`]
+/// returns `None`.
+///
+/// The reference also records the structural [`NodePath`] of the node, which locates it again
+/// in another document with the same structure, e.g. after saving and reparsing the document.
+#[derive(Clone)]
+pub struct NodeRef {
+  node_ptr: xmlNodePtr,
+  id: u64,
+  tracked: Weak<Tracked>,
+  path: NodePath,
+}
+
+impl NodeRef {
+  /// A reference to `node`
+  pub fn new(node: &Node) -> Self {
+    let (id, tracked) = track(node.node_ptr(), node.get_docref());
+    NodeRef {
+      node_ptr: node.node_ptr(),
+      id,
+      tracked,
+      path: NodePath::of(RoNode(node.node_ptr())),
+    }
+  }
+
+  /// A reference to the read-only node `node` of `document`, or `None` if `node` is not a
+  /// node of `document`
+  pub fn from_ronode(node: RoNode, document: &Document) -> Option<Self> {
+    if xmlGetDoc(node.node_ptr()) != document.doc_ptr() {
+      return None;
+    }
+    let (id, tracked) = track(node.node_ptr(), Rc::downgrade(&document.0));
+    Some(NodeRef {
+      node_ptr: node.node_ptr(),
+      id,
+      tracked,
+      path: NodePath::of(node),
+    })
+  }
+
+  /// Whether the node still exists
+  pub fn is_valid(&self) -> bool {
+    self.document().is_some()
+  }
+
+  /// The node, wherever it is now, or `None` once it has been freed
+  pub fn resolve(&self) -> Option<Node> {
+    let document = self.document()?;
+    Some(Node::wrap(self.node_ptr, &document))
+  }
+
+  /// The document the node is in now, `None` once the node has been freed
+  fn document(&self) -> Option<DocumentRef> {
+    self.tracked.upgrade()?.document.borrow().upgrade()
+  }
+
+  /// The node as a read-only handle, or `None` once it has been freed
+  pub fn resolve_readonly(&self) -> Option<RoNode> {
+    self.is_valid().then_some(RoNode(self.node_ptr))
+  }
+
+  /// The path of the node when the reference was created or last updated
+  pub fn path(&self) -> &NodePath {
+    &self.path
+  }
+
+  /// Records the current path of the node, after it was moved. Returns false, keeping the old
+  /// path, when the node no longer exists.
+  pub fn update_path(&mut self) -> bool {
+    match self.resolve_readonly() {
+      Some(node) => {
+        self.path = NodePath::of(node);
+        true
+      }
+      None => false,
+    }
+  }
+
+  /// Finds the node at the recorded path in `document`, typically a reloaded copy of the
+  /// document the reference was made in
+  pub fn resolve_in(&self, document: &Document) -> Option<Node> {
+    let node = self.path.resolve(document)?;
+    Some(Node::wrap(node.node_ptr(), &document.0))
+  }
+}
+
+impl Drop for NodeRef {
+  fn drop(&mut self) {
+    // The last reference to a node stops tracking it
+    if self.tracked.strong_count() > 0 && self.tracked.weak_count() == 1 {
+      let _ = TRACKED.try_with(|tracked| {
+        if let Ok(mut tracked) = tracked.try_borrow_mut() {
+          tracked.remove(&(self.node_ptr as usize));
+        }
+      });
+    }
+  }
+}
+
+impl PartialEq for NodeRef {
+  fn eq(&self, other: &NodeRef) -> bool {
+    self.id == other.id
+  }
+}
+
+impl Eq for NodeRef {}
+
+impl Hash for NodeRef {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.id.hash(state);
+  }
+}
+
+impl fmt::Debug for NodeRef {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("NodeRef")
+      .field("path", &self.path.to_string())
+      .field("valid", &self.is_valid())
+      .finish()
+  }
+}
+
+impl Node {
+  /// A stable reference to this node, see [`NodeRef`]
+  pub fn node_ref(&self) -> NodeRef {
+    NodeRef::new(self)
+  }
+}
+
+/// One step of a [`NodePath`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Step {
+  /// The `index`-th (from 1) element child with this local name and namespace
+  Element {
+    name: String,
+    ns: Option<String>,
+    index: usize,
+  },
+  /// The `index`-th text or CDATA child
+  Text(usize),
+  /// The `index`-th comment child
+  Comment(usize),
+  /// The `index`-th processing instruction child with this target
+  Pi { target: String, index: usize },
+  /// The attribute with this local name and namespace
+  Attribute { name: String, ns: Option<String> },
+}
+
+/// The location of a node as the sequence of steps leading to it from the document node,
+/// each step naming a child by kind, name and position among the siblings of the same kind
+/// and name.
+///
+/// Paths display in an XPath-like syntax with namespaces in Clark notation, e.g.
+/// `/{urn:catalog}catalog[1]/book[2]/text()[1]` or `/root[1]/@id`, and parse back from it.
+/// Nodes outside of a document tree have the empty path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct NodePath {
+  steps: Vec<Step>,
+}
+
+impl NodePath {
+  /// The path of `node` in its current tree
+  pub fn of(node: RoNode) -> NodePath {
+    let mut steps = Vec::new();
+    let mut current = node.node_ptr();
+    while !current.is_null() {
+      let parent = xmlGetParent(current);
+      let node_type = NodeType::from_int(xmlGetNodeType(current));
+      if matches!(
+        node_type,
+        Some(NodeType::DocumentNode) | Some(NodeType::HtmlDocumentNode)
+      ) {
+        steps.reverse();
+        return NodePath { steps };
+      }
+      if parent.is_null() {
+        // Unlinked
+        break;
+      }
+      let step = match node_type {
+        Some(NodeType::AttributeNode) => Step::Attribute {
+          name: RoNode(current).get_name(),
+          ns: namespace_href(xmlAttrNs(current as xmlAttrPtr)),
+        },
+        _ => {
+          let index = preceding_matches(current) + 1;
+          match node_type {
+            Some(NodeType::ElementNode) => Step::Element {
+              name: RoNode(current).get_name(),
+              ns: namespace_href(xmlNodeNs(current)),
+              index,
+            },
+            Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode) => Step::Text(index),
+            Some(NodeType::CommentNode) => Step::Comment(index),
+            Some(NodeType::PiNode) => Step::Pi {
+              target: RoNode(current).get_name(),
+              index,
+            },
+            _ => break,
+          }
+        }
+      };
+      steps.push(step);
+      current = parent;
+    }
+    NodePath::default()
+  }
+
+  /// Whether the path has no steps, the path of a document or of a detached node
+  pub fn is_empty(&self) -> bool {
+    self.steps.is_empty()
+  }
+
+  /// The node at this path in `document`, if any
+  pub fn resolve(&self, document: &Document) -> Option<RoNode> {
+    if self.steps.is_empty() {
+      return None;
+    }
+    let mut current = RoNode(document.doc_ptr() as xmlNodePtr);
+    for step in &self.steps {
+      current = match step {
+        Step::Attribute { name, ns } => {
+          let mut attr = xmlGetFirstProperty(current.node_ptr());
+          loop {
+            if attr.is_null() {
+              return None;
+            }
+            let candidate = RoNode(attr as xmlNodePtr);
+            if candidate.get_name() == *name && namespace_href(xmlAttrNs(attr)) == *ns {
+              break candidate;
+            }
+            attr = xmlNextPropertySibling(attr);
+          }
+        }
+        _ => {
+          let mut seen = 0;
+          let mut child = xmlGetFirstChild(current.node_ptr());
+          loop {
+            if child.is_null() {
+              return None;
+            }
+            if step_matches(step, child) {
+              seen += 1;
+              if seen == step_index(step) {
+                break RoNode(child);
+              }
+            }
+            child = xmlNextSibling(child);
+          }
+        }
+      };
+    }
+    Some(current)
+  }
+}
+
+fn namespace_href(ns: xmlNsPtr) -> Option<String> {
+  if ns.is_null() {
+    return None;
+  }
+  let href = xmlNsHref(ns);
+  if href.is_null() {
+    return None;
+  }
+  Some(
+    unsafe { std::ffi::CStr::from_ptr(href) }
+      .to_string_lossy()
+      .into_owned(),
+  )
+}
+
+/// The number of preceding siblings of `node` of the same kind and name
+fn preceding_matches(node: xmlNodePtr) -> usize {
+  let step = match NodeType::from_int(xmlGetNodeType(node)) {
+    Some(NodeType::ElementNode) => Step::Element {
+      name: RoNode(node).get_name(),
+      ns: namespace_href(xmlNodeNs(node)),
+      index: 0,
+    },
+    Some(NodeType::PiNode) => Step::Pi {
+      target: RoNode(node).get_name(),
+      index: 0,
+    },
+    Some(NodeType::CommentNode) => Step::Comment(0),
+    _ => Step::Text(0),
+  };
+  let mut count = 0;
+  let mut sibling = xmlPrevSibling(node);
+  while !sibling.is_null() {
+    if step_matches(&step, sibling) {
+      count += 1;
+    }
+    sibling = xmlPrevSibling(sibling);
+  }
+  count
+}
+
+fn step_matches(step: &Step, node: xmlNodePtr) -> bool {
+  let node_type = NodeType::from_int(xmlGetNodeType(node));
+  match step {
+    Step::Element { name, ns, .. } => {
+      node_type == Some(NodeType::ElementNode)
+        && RoNode(node).get_name() == *name
+        && namespace_href(xmlNodeNs(node)) == *ns
+    }
+    Step::Text(_) => matches!(
+      node_type,
+      Some(NodeType::TextNode) | Some(NodeType::CDataSectionNode)
+    ),
+    Step::Comment(_) => node_type == Some(NodeType::CommentNode),
+    Step::Pi { target, .. } => {
+      node_type == Some(NodeType::PiNode) && RoNode(node).get_name() == *target
+    }
+    Step::Attribute { .. } => false,
+  }
+}
+
+fn step_index(step: &Step) -> usize {
+  match step {
+    Step::Element { index, .. } | Step::Pi { index, .. } => *index,
+    Step::Text(index) | Step::Comment(index) => *index,
+    Step::Attribute { .. } => 1,
+  }
+}
+
+impl fmt::Display for NodePath {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.steps.is_empty() {
+      return f.write_str("/");
+    }
+    for step in &self.steps {
+      f.write_str("/")?;
+      match step {
+        Step::Element { name, ns, index } => {
+          if let Some(ns) = ns {
+            write!(f, "{{{ns}}}")?;
+          }
+          write!(f, "{name}[{index}]")?;
+        }
+        Step::Text(index) => write!(f, "text()[{index}]")?,
+        Step::Comment(index) => write!(f, "comment()[{index}]")?,
+        Step::Pi { target, index } => write!(f, "processing-instruction({target})[{index}]")?,
+        Step::Attribute { name, ns } => {
+          f.write_str("@")?;
+          if let Some(ns) = ns {
+            write!(f, "{{{ns}}}")?;
+          }
+          f.write_str(name)?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl FromStr for NodePath {
+  type Err = ();
+
+  /// Parses the syntax produced by the `Display` implementation
+  fn from_str(path: &str) -> Result<NodePath, ()> {
+    if path == "/" {
+      return Ok(NodePath::default());
+    }
+    let mut rest = path.strip_prefix('/').ok_or(())?;
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+      // Namespace URIs may contain slashes, the step ends at the first one after them
+      let name_start = if rest.starts_with('{') || rest.starts_with("@{") {
+        rest.find('}').ok_or(())? + 1
+      } else {
+        0
+      };
+      let end = rest[name_start..]
+        .find('/')
+        .map_or(rest.len(), |i| i + name_start);
+      steps.push(parse_step(&rest[..end])?);
+      rest = match rest[end..].strip_prefix('/') {
+        Some(next) if !next.is_empty() => next,
+        Some(_) => return Err(()),
+        None => "",
+      };
+    }
+    Ok(NodePath { steps })
+  }
+}
+
+fn parse_step(step: &str) -> Result<Step, ()> {
+  if let Some(attribute) = step.strip_prefix('@') {
+    let (name, ns) = split_clark(attribute)?;
+    return Ok(Step::Attribute { name, ns });
+  }
+  let open = step.rfind('[').ok_or(())?;
+  let index: usize = step[open + 1..]
+    .strip_suffix(']')
+    .ok_or(())?
+    .parse()
+    .map_err(|_| ())?;
+  if index == 0 {
+    return Err(());
+  }
+  let test = &step[..open];
+  Ok(match test {
+    "text()" => Step::Text(index),
+    "comment()" => Step::Comment(index),
+    _ => match test
+      .strip_prefix("processing-instruction(")
+      .and_then(|target| target.strip_suffix(')'))
+    {
+      Some(target) => Step::Pi {
+        target: target.to_string(),
+        index,
+      },
+      None => {
+        let (name, ns) = split_clark(test)?;
+        Step::Element { name, ns, index }
+      }
+    },
+  })
+}
+
+/// Splits `{ns}name` into the name and namespace
+fn split_clark(name: &str) -> Result<(String, Option<String>), ()> {
+  let (name, ns) = match name.strip_prefix('{') {
+    Some(qualified) => {
+      let (ns, name) = qualified.split_once('}').ok_or(())?;
+      (name, Some(ns.to_string()))
+    }
+    None => (name, None),
+  };
+  if name.is_empty() {
+    return Err(());
+  }
+  Ok((name.to_string(), ns))
+}
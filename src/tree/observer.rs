@@ -0,0 +1,156 @@
+//! Mutation observers, notified of the changes made to a document through `Node`
+//!
+use std::cell::{Cell, RefCell};
+use std::ffi::CStr;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::bindings::{xmlAttrPtr, xmlNodePtr};
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::{Document, DocumentWeak};
+
+/// A change made to a document, as reported to the callbacks registered with
+/// [`Document::observe`]
+#[derive(Clone)]
+pub enum Mutation {
+  /// `node` was added to the children of `parent`
+  Inserted {
+    /// The inserted node
+    node: RoNode,
+    /// Its new parent, the document node for the root element
+    parent: RoNode,
+  },
+  /// `node` was unlinked from `parent`; it stays valid until its owner drops it
+  Removed {
+    /// The unlinked node
+    node: RoNode,
+    /// The parent it was removed from
+    parent: RoNode,
+  },
+  /// An attribute of `element` was added, changed or removed
+  AttributeChanged {
+    /// The element carrying the attribute
+    element: RoNode,
+    /// The local name of the attribute
+    name: String,
+    /// The namespace URI of the attribute, if any
+    namespace: Option<String>,
+    /// The value before the change, `None` when the attribute was added
+    old_value: Option<String>,
+    /// The value after the change, `None` when the attribute was removed
+    new_value: Option<String>,
+  },
+  /// The text content of `node` was replaced or appended to; for an element, this means its
+  /// children were replaced by a single text node
+  TextChanged {
+    /// The node whose content changed
+    node: RoNode,
+    /// The content before the change
+    old_value: String,
+    /// The content after the change
+    new_value: String,
+  },
+}
+
+/// Identifies a callback registered with [`Document::observe`], to remove it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(u64);
+
+type Callback = Rc<dyn Fn(&Mutation)>;
+
+/// The callbacks registered on a document
+#[derive(Default)]
+pub(crate) struct Observers {
+  next_id: Cell<u64>,
+  callbacks: RefCell<Vec<(u64, Callback)>>,
+}
+
+impl fmt::Debug for Observers {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Observers({})", self.callbacks.borrow().len())
+  }
+}
+
+impl Document {
+  /// Registers `callback` to be called after each change made to this document through the
+  /// `Node` and `Document` mutation methods, so that indexes and dirty-tracking layers can
+  /// follow along without diffing.
+  ///
+  /// Callbacks run in registration order and may read or modify the document; the changes
+  /// they make are reported as well. Changes made by libxml2 itself (parsing, XInclude, XSLT)
+  /// or through raw pointers are not observed. A callback holding a clone of the document
+  /// keeps it alive until removed with [`Document::unobserve`].
+  pub fn observe<F: Fn(&Mutation) + 'static>(&self, callback: F) -> ObserverId {
+    let observers = &self.0.observers;
+    let id = observers.next_id.get();
+    observers.next_id.set(id + 1);
+    observers.callbacks.borrow_mut().push((id, Rc::new(callback)));
+    ObserverId(id)
+  }
+
+  /// Removes a callback registered with [`Document::observe`], returning whether it was
+  /// still registered
+  pub fn unobserve(&self, id: ObserverId) -> bool {
+    let mut callbacks = self.0.observers.callbacks.borrow_mut();
+    let position = callbacks.iter().position(|(callback_id, _)| *callback_id == id.0);
+    let removed = position.map(|position| callbacks.remove(position));
+    // The callback may hold nodes, which are dropped once the callbacks are no longer borrowed
+    drop(callbacks);
+    removed.is_some()
+  }
+}
+
+/// Whether anybody listens to the changes of `document`, to skip gathering old values
+pub(crate) fn observed(document: &DocumentWeak) -> bool {
+  document
+    .upgrade()
+    .is_some_and(|doc| !doc.observers.callbacks.borrow().is_empty())
+}
+
+/// Reports `mutation` to the observers of `document`
+pub(crate) fn notify(document: &DocumentWeak, mutation: impl FnOnce() -> Mutation) {
+  let Some(doc) = document.upgrade() else {
+    return;
+  };
+  // Callbacks are free to use the document, don't keep it borrowed while they run
+  let callbacks: Vec<Callback> = doc
+    .observers
+    .callbacks
+    .borrow()
+    .iter()
+    .map(|(_, callback)| callback.clone())
+    .collect();
+  if callbacks.is_empty() {
+    return;
+  }
+  let mutation = mutation();
+  for callback in callbacks {
+    callback(&mutation);
+  }
+}
+
+/// Describes a change of the attribute `attr` of `element`
+pub(crate) fn attribute_changed(
+  element: xmlNodePtr,
+  attr: xmlAttrPtr,
+  old_value: Option<String>,
+  new_value: Option<String>,
+) -> Mutation {
+  let name = unsafe { CStr::from_ptr(xmlAttrName(attr)) }
+    .to_string_lossy()
+    .into_owned();
+  let ns = xmlAttrNs(attr);
+  let namespace = (!ns.is_null()).then(|| {
+    unsafe { CStr::from_ptr(xmlNsHref(ns)) }
+      .to_string_lossy()
+      .into_owned()
+  });
+  Mutation::AttributeChanged {
+    element: RoNode(element),
+    name,
+    namespace,
+    old_value,
+    new_value,
+  }
+}
@@ -0,0 +1,184 @@
+//! Indented tree outlines of documents and nodes, for diagnostics
+//!
+use std::fmt;
+
+use crate::bindings::xmlNodePtr;
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::{Document, Node, NodeType};
+
+/// Attributes shown per element before the rest is elided
+const MAX_ATTRIBUTES: usize = 4;
+
+/// An indented outline of a subtree: element names with their attributes, text, comments and
+/// processing instructions, one node per line. Whitespace-only text is left out and long
+/// values are truncated.
+///
+/// This is what the `Debug` implementations of [`Node`] and [`Document`] print.
+#[derive(Clone, Copy)]
+pub struct Outline {
+  root: RoNode,
+  max_depth: Option<usize>,
+  text_width: usize,
+}
+
+impl Outline {
+  /// Outline of the subtree rooted at `root`
+  pub fn new(root: RoNode) -> Self {
+    Outline {
+      root,
+      max_depth: None,
+      text_width: 40,
+    }
+  }
+
+  /// Stops descending below `depth` levels, the root being at depth 0
+  pub fn max_depth(mut self, depth: usize) -> Self {
+    self.max_depth = Some(depth);
+    self
+  }
+
+  /// Truncates text and attribute values to `width` characters
+  pub fn text_width(mut self, width: usize) -> Self {
+    self.text_width = width;
+    self
+  }
+
+  fn push_lines(&self, lines: &mut Vec<String>, node: RoNode, depth: usize) {
+    let label = match self.label(node) {
+      Some(label) => label,
+      None => return,
+    };
+    lines.push(format!("{:indent$}{}", "", label, indent = depth * 2));
+
+    let children = node.get_child_nodes();
+    if self.max_depth.is_some_and(|max| depth >= max) {
+      if !children.is_empty() {
+        lines.push(format!(
+          "{:indent$}… ({} children)",
+          "",
+          children.len(),
+          indent = (depth + 1) * 2
+        ));
+      }
+      return;
+    }
+    for child in children {
+      self.push_lines(lines, child, depth + 1);
+    }
+  }
+
+  /// The line describing `node`, `None` for nodes left out of outlines
+  fn label(&self, node: RoNode) -> Option<String> {
+    let label = match node.get_type() {
+      Some(NodeType::DocumentNode) => "#document".to_string(),
+      Some(NodeType::HtmlDocumentNode) => "#document (html)".to_string(),
+      Some(NodeType::DocumentFragNode) => "#document-fragment".to_string(),
+      Some(NodeType::DTDNode) | Some(NodeType::DocumentTypeNode) => {
+        format!("<!DOCTYPE {}>", node.get_name())
+      }
+      Some(NodeType::ElementNode) => self.element_label(node),
+      Some(NodeType::TextNode) => {
+        let content = node.get_content();
+        if content.trim().is_empty() {
+          return None;
+        }
+        format!("{:?}", self.truncate(&content))
+      }
+      Some(NodeType::CDataSectionNode) => {
+        format!("<![CDATA[{}]]>", self.truncate(&node.get_content()))
+      }
+      Some(NodeType::CommentNode) => format!("<!-- {} -->", self.truncate(&node.get_content())),
+      Some(NodeType::PiNode) => {
+        format!("<?{} {}?>", node.get_name(), self.truncate(&node.get_content()))
+      }
+      Some(NodeType::EntityRefNode) => format!("&{};", node.get_name()),
+      Some(other) => format!("#{other:?}"),
+      None => "#unknown".to_string(),
+    };
+    Some(label)
+  }
+
+  fn element_label(&self, node: RoNode) -> String {
+    let mut label = format!("<{}", qualified_name(node.node_ptr()));
+    let mut attr_ptr = xmlGetFirstProperty(node.node_ptr());
+    let mut shown = 0;
+    while !attr_ptr.is_null() {
+      if shown == MAX_ATTRIBUTES {
+        label.push_str(" …");
+        break;
+      }
+      let attr = RoNode(attr_ptr as xmlNodePtr);
+      label.push_str(&format!(
+        " {}={:?}",
+        qualified_name(attr.node_ptr()),
+        self.truncate(&attr.get_content())
+      ));
+      shown += 1;
+      attr_ptr = xmlNextPropertySibling(attr_ptr);
+    }
+    label.push('>');
+    label
+  }
+
+  /// Collapses whitespace runs and cuts `text` down to the configured width
+  fn truncate(&self, text: &str) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() > self.text_width {
+      let cut: String = collapsed.chars().take(self.text_width).collect();
+      format!("{}…", cut.trim_end())
+    } else {
+      collapsed
+    }
+  }
+}
+
+impl fmt::Display for Outline {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.root.is_null() {
+      return f.write_str("#null");
+    }
+    let mut lines = Vec::new();
+    self.push_lines(&mut lines, self.root, 0);
+    f.write_str(&lines.join("\n"))
+  }
+}
+
+impl fmt::Debug for Node {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Outline::new(RoNode(self.node_ptr())))
+  }
+}
+
+impl fmt::Debug for RoNode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", Outline::new(*self))
+  }
+}
+
+impl fmt::Debug for Document {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.outline())
+  }
+}
+
+impl Node {
+  /// An indented outline of this node and its descendants
+  pub fn outline(&self) -> Outline {
+    Outline::new(RoNode(self.node_ptr()))
+  }
+}
+
+impl RoNode {
+  /// An indented outline of this node and its descendants
+  pub fn outline(self) -> Outline {
+    Outline::new(self)
+  }
+}
+
+impl Document {
+  /// An indented outline of the whole document
+  pub fn outline(&self) -> Outline {
+    Outline::new(RoNode(self.doc_ptr() as xmlNodePtr))
+  }
+}
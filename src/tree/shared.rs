@@ -0,0 +1,165 @@
+//! Documents shared between threads
+//!
+//! A [`Document`] and its nodes are bound to the thread that made them. A [`SharedDocument`]
+//! holds a document any number of threads can read at once and change in turn.
+//!
+//! Readers go through [`SharedDocument::read`], which hands out read-only access to the
+//! shared document itself, without copying it: [`RoNode`]s and XPath queries over them.
+//! Writers never get at the shared document: [`SharedDocument::with`] works on a copy of
+//! its own, which then replaces the shared one, so that nothing it keeps of the copy,
+//! `Node`s or `RoNode`s, can reach another thread:
+//!
+//! ```
+//! use std::thread;
+//! use libxml::parser::Parser;
+//! use libxml::tree::SharedDocument;
+//!
+//! let doc = Parser::default().parse_string(r#"<config workers="4"/>"#).unwrap();
+//! let shared = SharedDocument::new(&doc).unwrap();
+//! thread::scope(|scope| {
+//!   for _ in 0..2 {
+//!     scope.spawn(|| {
+//!       let read = shared.read();
+//!       let workers = read.get_root_readonly().unwrap().get_attribute("workers");
+//!       assert_eq!(workers.as_deref(), Some("4"));
+//!     });
+//!   }
+//! });
+//! shared
+//!   .with(|doc| doc.get_root_element().unwrap().set_attribute("workers", "8"))
+//!   .unwrap()
+//!   .unwrap();
+//! assert_eq!(shared.read().findvalues("/config/@workers", None), Ok(vec!["8".to_string()]));
+//! ```
+//!
+//! Threads that need a document of their own can copy the shared one with
+//! [`SharedDocument::to_document`] or a [`DocumentTemplate`](crate::tree::DocumentTemplate).
+//!
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+
+use crate::bindings::*;
+use crate::readonly::RoNode;
+use crate::tree::document::copy_independent;
+use crate::tree::Document;
+use crate::xpath::{Context, Object};
+
+/// A document which threads read at once and change one at a time
+#[derive(Clone)]
+pub struct SharedDocument {
+  inner: Arc<RwLock<Owned>>,
+}
+
+/// The document, only changed by the thread holding the write lock
+struct Owned {
+  doc_ptr: xmlDocPtr,
+}
+
+// The document shares no dictionary with other documents, and is never wrapped in a
+// `Document`: it is only ever read, copied or replaced. Reads under the read lock go through
+// `RoNode`s and XPath, which leave the document as it is.
+unsafe impl Send for Owned {}
+unsafe impl Sync for Owned {}
+
+impl Drop for Owned {
+  fn drop(&mut self) {
+    unsafe { xmlFreeDoc(self.doc_ptr) }
+  }
+}
+
+impl SharedDocument {
+  /// Shares a copy of `doc`
+  pub fn new(doc: &Document) -> Result<Self, ()> {
+    let doc_ptr = copy_independent(doc.doc_ptr());
+    if doc_ptr.is_null() {
+      return Err(());
+    }
+    Ok(SharedDocument {
+      inner: Arc::new(RwLock::new(Owned { doc_ptr })),
+    })
+  }
+
+  /// Read access to the document, shared with the other threads reading it. Changes wait
+  /// until the returned guard is dropped.
+  pub fn read(&self) -> SharedRead<'_> {
+    SharedRead {
+      owned: self.inner.read().unwrap_or_else(PoisonError::into_inner),
+    }
+  }
+
+  /// Calls `f` with a copy of the document, waiting for other threads using it to finish,
+  /// then shares the copy as changed by `f` instead. Changes are thus seen by the threads
+  /// using the document after it, unless `f` panics. Anything `f` keeps of the copy stays
+  /// with the current thread, which is why `f` never gets the shared document itself.
+  pub fn with<R>(&self, f: impl FnOnce(&mut Document) -> R) -> Result<R, ()> {
+    let mut owned = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+    // The shared document has no dictionary, the copy owns all its strings
+    let doc_ptr = unsafe { xmlCopyDoc(owned.doc_ptr, 1) };
+    if doc_ptr.is_null() {
+      return Err(());
+    }
+    let mut doc = Document::new_ptr(doc_ptr);
+    let result = f(&mut doc);
+    let doc_ptr = copy_independent(doc.doc_ptr());
+    if doc_ptr.is_null() {
+      return Err(());
+    }
+    unsafe { xmlFreeDoc(owned.doc_ptr) };
+    owned.doc_ptr = doc_ptr;
+    Ok(result)
+  }
+
+  /// A copy of the document as it is now, for the current thread alone
+  pub fn to_document(&self) -> Result<Document, ()> {
+    self.read().to_document()
+  }
+}
+
+/// Read-only access to a [`SharedDocument`], see [`SharedDocument::read`].
+///
+/// The `RoNode`s it hands out point into the shared document: they must not be used once the
+/// guard is dropped, after which another thread may replace the document.
+pub struct SharedRead<'a> {
+  owned: RwLockReadGuard<'a, Owned>,
+}
+
+impl SharedRead<'_> {
+  /// The root element of the document
+  pub fn get_root_readonly(&self) -> Option<RoNode> {
+    let node_ptr = unsafe { xmlDocGetRootElement(self.owned.doc_ptr) };
+    if node_ptr.is_null() {
+      None
+    } else {
+      Some(RoNode(node_ptr))
+    }
+  }
+
+  /// Finds nodes via XPath, at `node` or the document root
+  pub fn findnodes(&self, xpath: &str, node: Option<RoNode>) -> Result<Vec<RoNode>, ()> {
+    Ok(self.evaluate(xpath, node)?.get_readonly_nodes_as_vec())
+  }
+
+  /// Finds literal values via XPath, at `node` or the document root
+  pub fn findvalues(&self, xpath: &str, node: Option<RoNode>) -> Result<Vec<String>, ()> {
+    Ok(self.evaluate(xpath, node)?.get_nodes_as_str())
+  }
+
+  /// A copy of the document, for the current thread alone
+  pub fn to_document(&self) -> Result<Document, ()> {
+    // The shared document has no dictionary, the copy owns all its strings
+    let doc_ptr = unsafe { xmlCopyDoc(self.owned.doc_ptr, 1) };
+    if doc_ptr.is_null() {
+      Err(())
+    } else {
+      Ok(Document::new_ptr(doc_ptr))
+    }
+  }
+
+  fn evaluate(&self, xpath: &str, node: Option<RoNode>) -> Result<Object, ()> {
+    // Each reader evaluates in a context of its own
+    let context = Context::new_detached(self.owned.doc_ptr)?;
+    match node {
+      Some(node) => context.node_evaluate_readonly(xpath, node),
+      None => context.evaluate(xpath),
+    }
+  }
+}
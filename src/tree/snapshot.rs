@@ -0,0 +1,553 @@
+//! Binary snapshots of documents
+//!
+//! A snapshot is the document tree in a compact binary form: a table of the distinct strings
+//! of the document (names, namespaces, text) followed by the nodes in document order, each
+//! referring to its strings by index. Loading one builds the tree directly: there is no markup
+//! to tokenize, no character references or encodings to decode and no well-formedness to
+//! check, and the snapshot of a document with repetitive content is much smaller than its
+//! markup. Building the nodes still dominates, so loading is only somewhat faster than
+//! reparsing, see the `snapshots` group in `benches/parsing_benchmarks.rs`.
+//!
+//! The internal DTD subset is stored as markup and parsed again on load, so that entity
+//! references keep pointing at their declarations. Snapshots are meant for caches written and
+//! read by the same version of this crate, [`Document::load_snapshot`] rejects other formats.
+//!
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::parser::try_usize_to_i32;
+use crate::tree::{Document, NodeType};
+
+const MAGIC: &[u8] = b"LXSNAP\0";
+const FORMAT_VERSION: u8 = 1;
+
+const TAG_END: u8 = 0;
+const TAG_ELEMENT: u8 = 1;
+const TAG_TEXT: u8 = 2;
+const TAG_CDATA: u8 = 3;
+const TAG_COMMENT: u8 = 4;
+const TAG_PI: u8 = 5;
+const TAG_ENTITY_REF: u8 = 6;
+
+/// Errors raised while loading a snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+  /// The data does not start with the snapshot header
+  NotASnapshot,
+  /// The snapshot was written in another version of the format
+  UnsupportedVersion(u8),
+  /// The snapshot is truncated or otherwise damaged
+  Corrupt,
+}
+
+impl Error for SnapshotError {}
+
+impl fmt::Display for SnapshotError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      SnapshotError::NotASnapshot => write!(f, "data is not a document snapshot"),
+      SnapshotError::UnsupportedVersion(version) => {
+        write!(f, "unsupported snapshot format version {version}")
+      }
+      SnapshotError::Corrupt => write!(f, "document snapshot is corrupt"),
+    }
+  }
+}
+
+impl Document {
+  /// Serializes the document into a binary snapshot, to be restored with
+  /// [`Document::load_snapshot`]
+  pub fn dump_snapshot(&self) -> Vec<u8> {
+    let doc_ptr = self.doc_ptr();
+    let mut writer = SnapshotWriter::default();
+    unsafe {
+      writer.optional_string(raw_bytes((*doc_ptr).version));
+      writer.optional_string(raw_bytes((*doc_ptr).encoding));
+      writer.optional_string(raw_bytes((*doc_ptr).URL));
+      writer.varint(((*doc_ptr).standalone + 2) as u64);
+      let subset = (*doc_ptr).intSubset;
+      if subset.is_null() {
+        writer.optional_string(None);
+      } else {
+        let buffer = xmlBufferCreate();
+        xmlNodeDump(buffer, doc_ptr, subset as xmlNodePtr, 0, 0);
+        let markup = raw_bytes(xmlBufferContent(buffer)).map(<[u8]>::to_vec);
+        xmlBufferFree(buffer);
+        writer.optional_string(markup.as_deref());
+      }
+      let mut child = (*doc_ptr).children;
+      while !child.is_null() {
+        writer.node(child);
+        child = xmlNextSibling(child);
+      }
+    }
+    writer.body.push(TAG_END);
+    writer.finish()
+  }
+
+  /// Rebuilds a document from a snapshot made by [`Document::dump_snapshot`]
+  pub fn load_snapshot(snapshot: &[u8]) -> Result<Document, SnapshotError> {
+    let mut reader = SnapshotReader::new(snapshot)?;
+    let version = reader.optional_string()?;
+    let encoding = reader.optional_string()?;
+    let url = reader.optional_string()?;
+    let standalone = reader.varint()? as c_int - 2;
+    let subset = reader.optional_string()?;
+
+    let doc_ptr = match subset {
+      Some(subset) => parse_subset(subset)?,
+      None => unsafe { xmlNewDoc(c"1.0".as_ptr() as *const xmlChar) },
+    };
+    if doc_ptr.is_null() {
+      return Err(SnapshotError::Corrupt);
+    }
+    // From here on the document is freed on errors
+    let doc = Document::new_ptr(doc_ptr);
+    unsafe {
+      replace_string(&mut (*doc_ptr).version, version);
+      replace_string(&mut (*doc_ptr).encoding, encoding);
+      replace_string(&mut (*doc_ptr).URL, url);
+      (*doc_ptr).standalone = standalone;
+      if (*doc_ptr).dict.is_null() {
+        (*doc_ptr).dict = xmlDictCreate();
+      }
+    }
+    reader.nodes(doc_ptr)?;
+    if reader.position != snapshot.len() {
+      return Err(SnapshotError::Corrupt);
+    }
+    Ok(doc)
+  }
+}
+
+/// The bytes of a C string, `None` for a null pointer
+fn raw_bytes<'a>(ptr: *const xmlChar) -> Option<&'a [u8]> {
+  if ptr.is_null() {
+    None
+  } else {
+    Some(unsafe { CStr::from_ptr(ptr as *const c_char) }.to_bytes())
+  }
+}
+
+/// Parses the internal subset into a new document, without any element
+fn parse_subset(subset: &CStr) -> Result<xmlDocPtr, SnapshotError> {
+  let mut markup = subset.to_bytes().to_vec();
+  markup.extend_from_slice(b"<_/>");
+  let len = try_usize_to_i32(markup.len()).map_err(|_| SnapshotError::Corrupt)?;
+  let flags = xmlParserOption_XML_PARSE_NONET
+    | xmlParserOption_XML_PARSE_NOERROR
+    | xmlParserOption_XML_PARSE_NOWARNING;
+  unsafe {
+    let doc_ptr = xmlReadMemory(
+      markup.as_ptr() as *const c_char,
+      len,
+      ptr::null(),
+      c"UTF-8".as_ptr(),
+      flags as i32,
+    );
+    if doc_ptr.is_null() {
+      return Err(SnapshotError::Corrupt);
+    }
+    let placeholder = xmlDocGetRootElement(doc_ptr);
+    if !placeholder.is_null() {
+      xmlUnlinkNode(placeholder);
+      xmlFreeNode(placeholder);
+    }
+    Ok(doc_ptr)
+  }
+}
+
+/// Replaces a string owned by the document
+unsafe fn replace_string(field: &mut *const xmlChar, value: Option<&CStr>) {
+  unsafe {
+    if !field.is_null() {
+      bindgenFree(*field as *mut c_void);
+    }
+    *field = match value {
+      Some(value) => xmlStrdup(value.as_ptr() as *const xmlChar),
+      None => ptr::null(),
+    };
+  }
+}
+
+#[derive(Default)]
+struct SnapshotWriter {
+  strings: Vec<Vec<u8>>,
+  indices: HashMap<Vec<u8>, u64>,
+  body: Vec<u8>,
+}
+
+impl SnapshotWriter {
+  fn varint(&mut self, mut value: u64) {
+    write_varint(&mut self.body, &mut value);
+  }
+
+  /// Refers to `string` by its index in the string table, plus one to leave 0 for `None`
+  fn optional_string(&mut self, string: Option<&[u8]>) {
+    let reference = match string {
+      Some(string) => self.intern(string) + 1,
+      None => 0,
+    };
+    self.varint(reference);
+  }
+
+  fn string(&mut self, string: &[u8]) {
+    let index = self.intern(string);
+    self.varint(index);
+  }
+
+  fn intern(&mut self, string: &[u8]) -> u64 {
+    if let Some(&index) = self.indices.get(string) {
+      return index;
+    }
+    let index = self.strings.len() as u64;
+    self.strings.push(string.to_vec());
+    self.indices.insert(string.to_vec(), index);
+    index
+  }
+
+  fn namespace(&mut self, ns: xmlNsPtr) {
+    if ns.is_null() {
+      self.varint(0);
+    } else {
+      self.varint(1);
+      self.optional_string(raw_bytes(xmlNsPrefix(ns) as *const xmlChar));
+      self.optional_string(raw_bytes(xmlNsHref(ns) as *const xmlChar).or(Some(b"")));
+    }
+  }
+
+  fn node(&mut self, node: xmlNodePtr) {
+    let name = || raw_bytes(xmlNodeGetName(node) as *const xmlChar).unwrap_or_default();
+    let content = || unsafe { raw_bytes((*node).content).unwrap_or_default() };
+    match NodeType::from_int(xmlGetNodeType(node)) {
+      Some(NodeType::ElementNode) => {
+        self.body.push(TAG_ELEMENT);
+        self.string(name());
+
+        let mut declarations = Vec::new();
+        let mut ns = xmlNodeNsDeclarations(node);
+        while !ns.is_null() {
+          declarations.push(ns);
+          ns = xmlNextNsSibling(ns);
+        }
+        self.varint(declarations.len() as u64);
+        for ns in declarations {
+          self.optional_string(raw_bytes(xmlNsPrefix(ns) as *const xmlChar));
+          self.string(raw_bytes(xmlNsHref(ns) as *const xmlChar).unwrap_or_default());
+        }
+        self.namespace(xmlNodeNs(node));
+
+        let mut attributes = Vec::new();
+        let mut attr = xmlGetFirstProperty(node);
+        while !attr.is_null() {
+          attributes.push(attr);
+          attr = xmlNextPropertySibling(attr);
+        }
+        self.varint(attributes.len() as u64);
+        for attr in attributes {
+          self.string(raw_bytes(xmlAttrName(attr) as *const xmlChar).unwrap_or_default());
+          self.namespace(xmlAttrNs(attr));
+          self.attribute_value(attr);
+        }
+
+        let mut child = xmlGetFirstChild(node);
+        while !child.is_null() {
+          self.node(child);
+          child = xmlNextSibling(child);
+        }
+        self.body.push(TAG_END);
+      }
+      Some(NodeType::TextNode) => {
+        self.body.push(TAG_TEXT);
+        self.string(content());
+      }
+      Some(NodeType::CDataSectionNode) => {
+        self.body.push(TAG_CDATA);
+        self.string(content());
+      }
+      Some(NodeType::CommentNode) => {
+        self.body.push(TAG_COMMENT);
+        self.string(content());
+      }
+      Some(NodeType::PiNode) => {
+        self.body.push(TAG_PI);
+        self.string(name());
+        self.optional_string(unsafe { raw_bytes((*node).content) });
+      }
+      Some(NodeType::EntityRefNode) => {
+        self.body.push(TAG_ENTITY_REF);
+        self.string(name());
+      }
+      // The DTD is stored in the header, XInclude markers and the like are dropped
+      _ => {}
+    }
+  }
+
+  fn attribute_value(&mut self, attr: xmlAttrPtr) {
+    unsafe {
+      let child = (*attr).children;
+      // The common case of a single text child avoids a copy
+      if !child.is_null()
+        && (*child).next.is_null()
+        && NodeType::from_int(xmlGetNodeType(child)) == Some(NodeType::TextNode)
+      {
+        self.string(raw_bytes((*child).content).unwrap_or_default());
+        return;
+      }
+      let value = xmlNodeGetContent(attr as xmlNodePtr);
+      self.string(raw_bytes(value).unwrap_or_default());
+      if !value.is_null() {
+        bindgenFree(value as *mut c_void);
+      }
+    }
+  }
+
+  fn finish(self) -> Vec<u8> {
+    let size = self.strings.iter().map(|s| s.len() + 3).sum::<usize>();
+    let mut out = Vec::with_capacity(MAGIC.len() + 1 + size + self.body.len() + 10);
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    write_varint(&mut out, &mut (self.strings.len() as u64));
+    for string in &self.strings {
+      write_varint(&mut out, &mut (string.len() as u64));
+      out.extend_from_slice(string);
+      // Strings are NUL terminated so that loading can hand them to libxml2 in place
+      out.push(0);
+    }
+    out.extend_from_slice(&self.body);
+    out
+  }
+}
+
+/// Appends `value` as a LEB128 varint
+fn write_varint(out: &mut Vec<u8>, value: &mut u64) {
+  while *value >= 0x80 {
+    out.push((*value as u8) | 0x80);
+    *value >>= 7;
+  }
+  out.push(*value as u8);
+}
+
+struct SnapshotReader<'a> {
+  data: &'a [u8],
+  position: usize,
+  strings: Vec<&'a CStr>,
+  /// The strings used as names, interned in the document dictionary on first use
+  names: Vec<*const xmlChar>,
+}
+
+impl<'a> SnapshotReader<'a> {
+  fn new(data: &'a [u8]) -> Result<Self, SnapshotError> {
+    if !data.starts_with(MAGIC) {
+      return Err(SnapshotError::NotASnapshot);
+    }
+    let mut reader = SnapshotReader {
+      data,
+      position: MAGIC.len(),
+      strings: Vec::new(),
+      names: Vec::new(),
+    };
+    let version = reader.byte()?;
+    if version != FORMAT_VERSION {
+      return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let count = reader.varint()? as usize;
+    // Each string takes at least two bytes, do not trust larger counts
+    reader.strings.reserve(count.min(data.len() / 2));
+    for _ in 0..count {
+      let len = reader.varint()? as usize;
+      let end = reader
+        .position
+        .checked_add(len)
+        .filter(|&end| end < data.len())
+        .ok_or(SnapshotError::Corrupt)?;
+      let string = CStr::from_bytes_with_nul(&data[reader.position..=end])
+        .map_err(|_| SnapshotError::Corrupt)?;
+      reader.strings.push(string);
+      reader.position = end + 1;
+    }
+    reader.names = vec![ptr::null(); reader.strings.len()];
+    Ok(reader)
+  }
+
+  fn byte(&mut self) -> Result<u8, SnapshotError> {
+    let byte = *self.data.get(self.position).ok_or(SnapshotError::Corrupt)?;
+    self.position += 1;
+    Ok(byte)
+  }
+
+  fn varint(&mut self) -> Result<u64, SnapshotError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+      let byte = self.byte()?;
+      value |= u64::from(byte & 0x7f) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+    }
+    Err(SnapshotError::Corrupt)
+  }
+
+  fn string(&mut self) -> Result<&'a CStr, SnapshotError> {
+    let index = self.varint()? as usize;
+    self.strings.get(index).copied().ok_or(SnapshotError::Corrupt)
+  }
+
+  fn optional_string(&mut self) -> Result<Option<&'a CStr>, SnapshotError> {
+    match self.varint()? as usize {
+      0 => Ok(None),
+      reference => self
+        .strings
+        .get(reference - 1)
+        .copied()
+        .map(Some)
+        .ok_or(SnapshotError::Corrupt),
+    }
+  }
+
+  /// Reads a string reference as a name owned by the dictionary of `doc`, which nodes do not
+  /// copy nor free
+  fn name(&mut self, doc: xmlDocPtr) -> Result<*mut xmlChar, SnapshotError> {
+    let index = self.varint()? as usize;
+    let string = *self.strings.get(index).ok_or(SnapshotError::Corrupt)?;
+    if self.names[index].is_null() {
+      self.names[index] = unsafe { xmlDictLookup((*doc).dict, as_xml(string), -1) };
+      if self.names[index].is_null() {
+        return Err(SnapshotError::Corrupt);
+      }
+    }
+    Ok(self.names[index] as *mut xmlChar)
+  }
+
+  /// Reads a namespace reference as its prefix and href
+  #[allow(clippy::type_complexity)]
+  fn namespace(&mut self) -> Result<Option<(Option<&'a CStr>, &'a CStr)>, SnapshotError> {
+    match self.byte()? {
+      0 => Ok(None),
+      1 => {
+        let prefix = self.optional_string()?;
+        let href = self.optional_string()?.ok_or(SnapshotError::Corrupt)?;
+        Ok(Some((prefix, href)))
+      }
+      _ => Err(SnapshotError::Corrupt),
+    }
+  }
+
+  /// Builds the nodes of the snapshot body below the document node
+  fn nodes(&mut self, doc: xmlDocPtr) -> Result<(), SnapshotError> {
+    let mut parents = vec![doc as xmlNodePtr];
+    while let Some(&parent) = parents.last() {
+      let tag = self.byte()?;
+      let node = unsafe {
+        match tag {
+          TAG_END => {
+            parents.pop();
+            continue;
+          }
+          TAG_ELEMENT => {
+            let name = self.name(doc)?;
+            let element = xmlNewDocNodeEatName(doc, ptr::null_mut(), name, ptr::null());
+            xmlAddChild(parent, element);
+            // Declarations come first so that the element and its attributes can use them
+            for _ in 0..self.varint()? {
+              let prefix = self.optional_string()?;
+              let href = self.string()?;
+              xmlNewNs(element, as_xml(href), prefix.map_or(ptr::null(), as_xml));
+            }
+            if let Some((prefix, href)) = self.namespace()? {
+              xmlSetNs(element, find_namespace(doc, element, prefix, href));
+            }
+            for _ in 0..self.varint()? {
+              let name = self.name(doc)?;
+              let ns = match self.namespace()? {
+                Some((prefix, href)) => find_namespace(doc, element, prefix, href),
+                None => ptr::null_mut(),
+              };
+              let value = self.string()?;
+              xmlNewNsPropEatName(element, ns, name, as_xml(value));
+            }
+            parents.push(element);
+            continue;
+          }
+          TAG_TEXT => xmlNewDocText(doc, as_xml(self.string()?)),
+          TAG_CDATA => {
+            let content = self.string()?;
+            xmlNewCDataBlock(doc, as_xml(content), content.to_bytes().len() as c_int)
+          }
+          TAG_COMMENT => xmlNewDocComment(doc, as_xml(self.string()?)),
+          TAG_PI => {
+            let name = self.string()?;
+            let content = self.optional_string()?;
+            xmlNewDocPI(doc, as_xml(name), content.map_or(ptr::null(), as_xml))
+          }
+          TAG_ENTITY_REF => {
+            let reference = xmlNewReference(doc, as_xml(self.string()?));
+            if !reference.is_null() {
+              expand_entity((*reference).children as xmlEntityPtr);
+            }
+            reference
+          }
+          _ => return Err(SnapshotError::Corrupt),
+        }
+      };
+      if node.is_null() {
+        return Err(SnapshotError::Corrupt);
+      }
+      unsafe { xmlAddChild(parent, node) };
+    }
+    Ok(())
+  }
+}
+
+/// Gives an internal entity the node list of its replacement text, which the parser only
+/// builds when it meets a reference
+pub(crate) unsafe fn expand_entity(entity: xmlEntityPtr) {
+  unsafe {
+    if entity.is_null()
+      || !(*entity).children.is_null()
+      || (*entity).content.is_null()
+      || (*entity).etype != xmlEntityType_XML_INTERNAL_GENERAL_ENTITY
+    {
+      return;
+    }
+    let mut list = ptr::null_mut();
+    let doc = (*entity).doc;
+    if xmlParseBalancedChunkMemory(doc, ptr::null_mut(), ptr::null_mut(), 0, (*entity).content, &mut list)
+      != 0
+    {
+      xmlFreeNodeList(list);
+      return;
+    }
+    (*entity).children = list;
+    (*entity).owner = 1;
+    let mut node = list;
+    while !node.is_null() {
+      (*node).parent = entity as xmlNodePtr;
+      (*entity).last = node;
+      node = (*node).next;
+    }
+  }
+}
+
+fn as_xml(string: &CStr) -> *const xmlChar {
+  string.as_ptr() as *const xmlChar
+}
+
+/// The namespace in scope at `node` for `prefix`, declared on `node` when it is missing or
+/// bound to another URI
+fn find_namespace(doc: xmlDocPtr, node: xmlNodePtr, prefix: Option<&CStr>, href: &CStr) -> xmlNsPtr {
+  let prefix_ptr = prefix.map_or(ptr::null(), as_xml);
+  unsafe {
+    let ns = xmlSearchNs(doc, node, prefix_ptr);
+    if !ns.is_null() && raw_bytes((*ns).href) == Some(href.to_bytes()) {
+      return ns;
+    }
+    xmlNewNs(node, as_xml(href), prefix_ptr)
+  }
+}
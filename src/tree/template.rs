@@ -0,0 +1,66 @@
+//! Documents instantiated many times
+//!
+//! A [`DocumentTemplate`] keeps a private copy of a document which any number of threads
+//! can copy into documents of their own at once:
+//!
+//! ```
+//! use std::thread;
+//! use libxml::parser::Parser;
+//! use libxml::tree::DocumentTemplate;
+//!
+//! let doc = Parser::default().parse_string("<greeting/>").unwrap();
+//! let template = DocumentTemplate::new(&doc).unwrap();
+//! thread::scope(|scope| {
+//!   for name in ["Ada", "Grace"] {
+//!     let template = &template;
+//!     scope.spawn(move || {
+//!       let doc = template.instantiate().unwrap();
+//!       let mut root = doc.get_root_element().unwrap();
+//!       root.set_attribute("to", name).unwrap();
+//!       assert_eq!(doc.node_to_string(&root), format!(r#"<greeting to="{name}"/>"#));
+//!     });
+//!   }
+//! });
+//! ```
+//!
+use crate::bindings::*;
+use crate::tree::document::copy_independent;
+use crate::tree::Document;
+
+/// A document which can only be copied, from any thread
+pub struct DocumentTemplate {
+  doc_ptr: xmlDocPtr,
+}
+
+// The copy is owned by the template and only read to instantiate it, and shares no
+// dictionary with other documents
+unsafe impl Send for DocumentTemplate {}
+unsafe impl Sync for DocumentTemplate {}
+
+impl DocumentTemplate {
+  /// Makes a template of a copy of `doc`
+  pub fn new(doc: &Document) -> Result<Self, ()> {
+    let doc_ptr = copy_independent(doc.doc_ptr());
+    if doc_ptr.is_null() {
+      Err(())
+    } else {
+      Ok(DocumentTemplate { doc_ptr })
+    }
+  }
+
+  /// A new document copied from the template
+  pub fn instantiate(&self) -> Result<Document, ()> {
+    let doc_ptr = unsafe { xmlCopyDoc(self.doc_ptr, 1) };
+    if doc_ptr.is_null() {
+      Err(())
+    } else {
+      Ok(Document::new_ptr(doc_ptr))
+    }
+  }
+}
+
+impl Drop for DocumentTemplate {
+  fn drop(&mut self) {
+    unsafe { xmlFreeDoc(self.doc_ptr) }
+  }
+}
@@ -0,0 +1,585 @@
+//! Lazy traversals of the tree
+//!
+//! The iterators here hold on to the node they returned last and find the next one from it
+//! when asked, so that they cost nothing up front and see the tree as it is then. Nodes
+//! inserted ahead are visited. The node just returned may be removed or moved: the traversal
+//! then goes on with the node which came after it, leaving out its descendants, as found when
+//! it was returned. The read-only [`RoAxis`] assumes the tree is not changed while walking.
+//!
+//! Besides [`Node::children`] and [`Node::descendants`], they cover the other major axes of
+//! XPath: ancestors, siblings, and the nodes following or preceding a node in document order.
+//! As in XPath, attributes are not part of these axes, but an attribute has the ancestors,
+//! following and preceding nodes of its element.
+//!
+use crate::bindings::{xmlGetLastChild, xmlNodePtr};
+use crate::c_helpers::*;
+use crate::readonly::RoNode;
+use crate::tree::node::HeldNode;
+use crate::tree::{DocumentWeak, Node, NodeType};
+
+/// How an axis finds the next node from the last one
+#[derive(Clone, Copy)]
+enum Axis {
+  Children { started: bool, elements_only: bool },
+  Descendants { root: xmlNodePtr },
+  Ancestors,
+  FollowingSiblings,
+  PrecedingSiblings,
+  Following { started: bool },
+  Preceding { ancestor: xmlNodePtr },
+}
+
+/// The node a traversal returned last, from which it finds the next one
+struct Walk {
+  last: xmlNodePtr,
+  axis: Axis,
+}
+
+impl Walk {
+  fn children(node: xmlNodePtr) -> Self {
+    Walk {
+      last: node,
+      axis: Axis::Children {
+        started: false,
+        elements_only: false,
+      },
+    }
+  }
+
+  fn element_children(node: xmlNodePtr) -> Self {
+    Walk {
+      last: node,
+      axis: Axis::Children {
+        started: false,
+        elements_only: true,
+      },
+    }
+  }
+
+  fn descendants(node: xmlNodePtr) -> Self {
+    Walk {
+      last: node,
+      axis: Axis::Descendants { root: node },
+    }
+  }
+
+  fn ancestors(node: xmlNodePtr) -> Self {
+    Walk {
+      last: node,
+      axis: Axis::Ancestors,
+    }
+  }
+
+  fn following_siblings(node: xmlNodePtr) -> Self {
+    Walk {
+      last: sibling_start(node),
+      axis: Axis::FollowingSiblings,
+    }
+  }
+
+  fn preceding_siblings(node: xmlNodePtr) -> Self {
+    Walk {
+      last: sibling_start(node),
+      axis: Axis::PrecedingSiblings,
+    }
+  }
+
+  fn following(node: xmlNodePtr) -> Self {
+    // The content of an element follows its attributes
+    match attribute_parent(node) {
+      Some(parent) => Walk {
+        last: parent,
+        axis: Axis::Following { started: true },
+      },
+      None => Walk {
+        last: node,
+        axis: Axis::Following { started: false },
+      },
+    }
+  }
+
+  fn preceding(node: xmlNodePtr) -> Self {
+    let node = attribute_parent(node).unwrap_or(node);
+    Walk {
+      last: node,
+      axis: Axis::Preceding {
+        ancestor: xmlGetParent(node),
+      },
+    }
+  }
+
+  /// Moves to the next node, ending the traversal on null
+  fn advance(&mut self) -> Option<xmlNodePtr> {
+    if self.last.is_null() {
+      return None;
+    }
+    self.last = self.axis.step(self.last);
+    (!self.last.is_null()).then_some(self.last)
+  }
+
+  /// The walk past the last node and its descendants, should the last node be removed
+  fn past(&self) -> Walk {
+    let mut axis = self.axis;
+    let last = if self.last.is_null() {
+      self.last
+    } else {
+      axis.skip(self.last)
+    };
+    Walk { last, axis }
+  }
+}
+
+impl Axis {
+  /// The next node after `node`, leaving out its descendants
+  fn skip(&mut self, node: xmlNodePtr) -> xmlNodePtr {
+    match self {
+      Axis::Descendants { root } => next_outside(node, *root),
+      Axis::Following { started } => {
+        *started = true;
+        next_outside(node, std::ptr::null_mut())
+      }
+      _ => self.step(node),
+    }
+  }
+
+  fn step(&mut self, node: xmlNodePtr) -> xmlNodePtr {
+    let null = std::ptr::null_mut();
+    match self {
+      Axis::Children {
+        started,
+        elements_only,
+      } => {
+        let mut child = if *started {
+          xmlNextSibling(node)
+        } else {
+          *started = true;
+          xmlGetFirstChild(node)
+        };
+        while *elements_only
+          && !child.is_null()
+          && NodeType::from_int(xmlGetNodeType(child)) != Some(NodeType::ElementNode)
+        {
+          child = xmlNextSibling(child);
+        }
+        child
+      }
+      Axis::Descendants { root } => next_in_document_order(node, *root),
+      Axis::Ancestors => xmlGetParent(node),
+      Axis::FollowingSiblings => xmlNextSibling(node),
+      Axis::PrecedingSiblings => xmlPrevSibling(node),
+      Axis::Following { started: true } => next_in_document_order(node, null),
+      Axis::Following { started } => {
+        *started = true;
+        next_outside(node, null)
+      }
+      Axis::Preceding { ancestor } => {
+        let mut node = node;
+        loop {
+          let sibling = xmlPrevSibling(node);
+          if !sibling.is_null() {
+            return last_descendant(sibling);
+          }
+          node = xmlGetParent(node);
+          if node.is_null() || node != *ancestor {
+            return node;
+          }
+          // Ancestors come before in document order, but are no part of the axis
+          *ancestor = xmlGetParent(node);
+        }
+      }
+    }
+  }
+}
+
+/// A traversal wrapping the nodes it finds
+struct Cursor {
+  walk: Walk,
+  document: DocumentWeak,
+  /// The node the walk is at, held so that removing it meanwhile does not free it
+  current: Option<HeldNode>,
+  /// The parent of `current` when returned, with the walk past it and the node this one is
+  /// at, to go on with if `current` has since been removed or moved
+  past: Option<(xmlNodePtr, Walk, Option<HeldNode>)>,
+}
+
+impl Cursor {
+  fn new(node: &Node, walk: fn(xmlNodePtr) -> Walk) -> Self {
+    let walk = walk(node.node_ptr());
+    let document = node.get_docref();
+    let current = match document.upgrade() {
+      Some(document) if !walk.last.is_null() => {
+        Some(HeldNode::new(Node::wrap(walk.last, &document)))
+      }
+      _ => None,
+    };
+    Cursor {
+      walk,
+      document,
+      current,
+      past: None,
+    }
+  }
+
+  fn advance(&mut self) -> Option<Node> {
+    let document = self.document.upgrade()?;
+    let moved = match (&self.current, &self.past) {
+      (Some(current), Some((parent, _, _))) => xmlGetParent(current.node().node_ptr()) != *parent,
+      _ => false,
+    };
+    let node = if moved {
+      // The node held rather than wrapped anew, as it may since have been removed
+      let (_, walk, held) = self.past.take()?;
+      self.walk = walk;
+      held.map(|held| held.node().clone())
+    } else {
+      self.walk.advance().map(|node_ptr| Node::wrap(node_ptr, &document))
+    };
+    let Some(node) = node else {
+      self.current = None;
+      self.past = None;
+      return None;
+    };
+    let past = self.walk.past();
+    let ahead = (!past.last.is_null()).then(|| HeldNode::new(Node::wrap(past.last, &document)));
+    self.past = Some((xmlGetParent(node.node_ptr()), past, ahead));
+    self.current = Some(HeldNode::new(node.clone()));
+    Some(node)
+  }
+}
+
+/// The children of a node, see [`Node::children`]
+pub struct Children {
+  cursor: Cursor,
+}
+
+/// The element children of a node, see [`Node::element_children`]
+pub struct ElementChildren {
+  cursor: Cursor,
+}
+
+/// The descendants of a node in document order, see [`Node::descendants`]
+pub struct Descendants {
+  cursor: Cursor,
+}
+
+/// The ancestors of a node, nearest first, see [`Node::ancestors`]
+pub struct Ancestors {
+  cursor: Cursor,
+}
+
+/// The siblings after a node, see [`Node::following_siblings`]
+pub struct FollowingSiblings {
+  cursor: Cursor,
+}
+
+/// The siblings before a node, nearest first, see [`Node::preceding_siblings`]
+pub struct PrecedingSiblings {
+  cursor: Cursor,
+}
+
+/// The nodes after a node in document order, see [`Node::following`]
+pub struct Following {
+  cursor: Cursor,
+}
+
+/// The nodes before a node in reverse document order, see [`Node::preceding`]
+pub struct Preceding {
+  cursor: Cursor,
+}
+
+/// The nodes along an axis from a read-only node, see [`RoNode::descendants`] and the
+/// other axes of `RoNode`
+pub struct RoAxis {
+  walk: Walk,
+}
+
+impl Node {
+  /// Iterates over the children of this node, wrapping each one only when reached, unlike
+  /// [`Node::get_child_nodes`]. The child just returned may be unlinked, which goes on with
+  /// the next one.
+  pub fn children(&self) -> Children {
+    Children {
+      cursor: Cursor::new(self, Walk::children),
+    }
+  }
+
+  /// Iterates over the children of this node which are elements, see [`Node::children`]
+  pub fn element_children(&self) -> ElementChildren {
+    ElementChildren {
+      cursor: Cursor::new(self, Walk::element_children),
+    }
+  }
+
+  /// Iterates over the descendants of this node in document order: its children, each
+  /// followed by its own descendants. Entity references and DTDs are not entered.
+  pub fn descendants(&self) -> Descendants {
+    Descendants {
+      cursor: Cursor::new(self, Walk::descendants),
+    }
+  }
+
+  /// Iterates over the ancestors of this node, from its parent up to the document node
+  pub fn ancestors(&self) -> Ancestors {
+    Ancestors {
+      cursor: Cursor::new(self, Walk::ancestors),
+    }
+  }
+
+  /// Iterates over the siblings after this node, none for an attribute
+  pub fn following_siblings(&self) -> FollowingSiblings {
+    FollowingSiblings {
+      cursor: Cursor::new(self, Walk::following_siblings),
+    }
+  }
+
+  /// Iterates over the siblings before this node, from the nearest one, none for an attribute
+  pub fn preceding_siblings(&self) -> PrecedingSiblings {
+    PrecedingSiblings {
+      cursor: Cursor::new(self, Walk::preceding_siblings),
+    }
+  }
+
+  /// Iterates over the nodes after this node in document order, leaving out its descendants
+  pub fn following(&self) -> Following {
+    Following {
+      cursor: Cursor::new(self, Walk::following),
+    }
+  }
+
+  /// Iterates over the nodes before this node in reverse document order, leaving out its
+  /// ancestors
+  pub fn preceding(&self) -> Preceding {
+    Preceding {
+      cursor: Cursor::new(self, Walk::preceding),
+    }
+  }
+}
+
+impl RoNode {
+  /// Iterates over the children of this node, without collecting them like
+  /// [`RoNode::get_child_nodes`]
+  pub fn children(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::children(self.0),
+    }
+  }
+
+  /// Iterates over the children of this node which are elements
+  pub fn element_children(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::element_children(self.0),
+    }
+  }
+
+  /// Iterates over the descendants of this node in document order, see
+  /// [`Node::descendants`]
+  pub fn descendants(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::descendants(self.0),
+    }
+  }
+
+  /// Iterates over the ancestors of this node, from its parent up to the document node
+  pub fn ancestors(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::ancestors(self.0),
+    }
+  }
+
+  /// Iterates over the siblings after this node, none for an attribute
+  pub fn following_siblings(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::following_siblings(self.0),
+    }
+  }
+
+  /// Iterates over the siblings before this node, from the nearest one, none for an attribute
+  pub fn preceding_siblings(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::preceding_siblings(self.0),
+    }
+  }
+
+  /// Iterates over the nodes after this node in document order, leaving out its descendants
+  pub fn following(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::following(self.0),
+    }
+  }
+
+  /// Iterates over the nodes before this node in reverse document order, leaving out its
+  /// ancestors
+  pub fn preceding(self) -> RoAxis {
+    RoAxis {
+      walk: Walk::preceding(self.0),
+    }
+  }
+}
+
+/// The element of `node` if it is an attribute
+fn attribute_parent(node: xmlNodePtr) -> Option<xmlNodePtr> {
+  if NodeType::from_int(xmlGetNodeType(node)) == Some(NodeType::AttributeNode) {
+    Some(xmlGetParent(node)).filter(|parent| !parent.is_null())
+  } else {
+    None
+  }
+}
+
+/// Where the sibling axes of `node` start, null for an attribute which has none
+fn sibling_start(node: xmlNodePtr) -> xmlNodePtr {
+  if NodeType::from_int(xmlGetNodeType(node)) == Some(NodeType::AttributeNode) {
+    std::ptr::null_mut()
+  } else {
+    node
+  }
+}
+
+impl Iterator for Children {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for ElementChildren {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for Descendants {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for Ancestors {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for FollowingSiblings {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for PrecedingSiblings {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for Following {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for Preceding {
+  type Item = Node;
+
+  fn next(&mut self) -> Option<Node> {
+    self.cursor.advance()
+  }
+}
+
+impl Iterator for RoAxis {
+  type Item = RoNode;
+
+  fn next(&mut self) -> Option<RoNode> {
+    self.walk.advance().map(RoNode)
+  }
+}
+
+/// Whether the descendants of `node` are walked, which the children of entity references,
+/// belonging to the entity declaration, and the declarations of DTDs are not
+fn is_entered(node: xmlNodePtr) -> bool {
+  !matches!(
+    NodeType::from_int(xmlGetNodeType(node)),
+    Some(NodeType::EntityRefNode) | Some(NodeType::DTDNode)
+  )
+}
+
+/// The position of `node` among the children of its parent, none for an attribute or a
+/// node without a parent
+pub(crate) fn index_in_parent(node: xmlNodePtr) -> Option<usize> {
+  if xmlGetParent(node).is_null()
+    || NodeType::from_int(xmlGetNodeType(node)) == Some(NodeType::AttributeNode)
+  {
+    return None;
+  }
+  let mut index = 0;
+  let mut sibling = xmlPrevSibling(node);
+  while !sibling.is_null() {
+    index += 1;
+    sibling = xmlPrevSibling(sibling);
+  }
+  Some(index)
+}
+
+/// The child of `parent` at `index`, counting only elements if `elements_only`, or null
+pub(crate) fn nth_child(parent: xmlNodePtr, index: usize, elements_only: bool) -> xmlNodePtr {
+  let mut remaining = index;
+  let mut child = xmlGetFirstChild(parent);
+  while !child.is_null() {
+    if !elements_only || NodeType::from_int(xmlGetNodeType(child)) == Some(NodeType::ElementNode) {
+      if remaining == 0 {
+        return child;
+      }
+      remaining -= 1;
+    }
+    child = xmlNextSibling(child);
+  }
+  child
+}
+
+/// The node following `node` in document order within the subtree of `root`, or null
+pub(crate) fn next_in_document_order(node: xmlNodePtr, root: xmlNodePtr) -> xmlNodePtr {
+  if is_entered(node) {
+    let child = xmlGetFirstChild(node);
+    if !child.is_null() {
+      return child;
+    }
+  }
+  next_outside(node, root)
+}
+
+/// The node following the subtree of `node` in document order within the subtree of
+/// `root`, or null
+pub(crate) fn next_outside(mut node: xmlNodePtr, root: xmlNodePtr) -> xmlNodePtr {
+  while node != root && !node.is_null() {
+    let sibling = xmlNextSibling(node);
+    if !sibling.is_null() {
+      return sibling;
+    }
+    node = xmlGetParent(node);
+  }
+  std::ptr::null_mut()
+}
+
+/// The last node of the subtree of `node` in document order
+fn last_descendant(mut node: xmlNodePtr) -> xmlNodePtr {
+  while is_entered(node) {
+    let child = unsafe { xmlGetLastChild(node) };
+    if child.is_null() {
+      break;
+    }
+    node = child;
+  }
+  node
+}
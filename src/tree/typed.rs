@@ -0,0 +1,145 @@
+//! Typed attribute values
+//!
+//! Attribute values are strings; these accessors parse them into numbers, booleans or any
+//! other [`FromStr`] type, reporting missing attributes and malformed values apart:
+//!
+//! ```
+//! use libxml::parser::Parser;
+//! use libxml::tree::AttributeError;
+//!
+//! let doc = Parser::default()
+//!   .parse_string(r#"<server port="8080" ratio="0.5" tls="true" name="main"/>"#)
+//!   .unwrap();
+//! let server = doc.get_root_element().unwrap();
+//! assert_eq!(server.attr_i64("port"), Ok(8080));
+//! assert_eq!(server.attr_f64("ratio"), Ok(0.5));
+//! assert_eq!(server.attr_bool("tls"), Ok(true));
+//! assert!(matches!(server.attr_i64("name"), Err(AttributeError::Invalid { .. })));
+//! assert!(matches!(server.attr_i64("timeout"), Err(AttributeError::Missing(_))));
+//! ```
+//!
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::readonly::RoNode;
+use crate::tree::Node;
+
+/// Why a typed attribute value could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeError {
+  /// The element has no attribute of this name
+  Missing(String),
+  /// The value of the attribute does not parse as the requested type
+  Invalid {
+    /// The name of the attribute
+    name: String,
+    /// Its value
+    value: String,
+    /// Why it does not parse
+    reason: String,
+  },
+}
+
+impl Error for AttributeError {}
+
+impl fmt::Display for AttributeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AttributeError::Missing(name) => write!(f, "missing attribute {name:?}"),
+      AttributeError::Invalid { name, value, reason } => {
+        write!(f, "invalid value {value:?} of attribute {name:?}: {reason}")
+      }
+    }
+  }
+}
+
+/// Parses the value of the attribute `name`, if there is one
+fn parse<T>(name: &str, value: Option<String>) -> Result<T, AttributeError>
+where
+  T: FromStr,
+  T::Err: fmt::Display,
+{
+  let value = value.ok_or_else(|| AttributeError::Missing(name.to_owned()))?;
+  value.parse().map_err(|e: T::Err| AttributeError::Invalid {
+    name: name.to_owned(),
+    reason: e.to_string(),
+    value,
+  })
+}
+
+/// Parses the value of the attribute `name` as an XML Schema type, surrounding whitespace
+/// aside
+fn parse_schema<T>(name: &str, value: Option<String>) -> Result<T, AttributeError>
+where
+  T: FromStr,
+  T::Err: fmt::Display,
+{
+  parse(name, value.map(|value| value.trim().to_owned()))
+}
+
+/// Parses the value of the attribute `name` as an `xs:boolean`
+fn parse_bool(name: &str, value: Option<String>) -> Result<bool, AttributeError> {
+  let value = value.ok_or_else(|| AttributeError::Missing(name.to_owned()))?;
+  match value.trim() {
+    "true" | "1" => Ok(true),
+    "false" | "0" => Ok(false),
+    _ => Err(AttributeError::Invalid {
+      name: name.to_owned(),
+      value,
+      reason: "expected true, false, 1 or 0".to_owned(),
+    }),
+  }
+}
+
+impl Node {
+  /// Parses the value of the attribute `name` into a `T`
+  pub fn get_attribute_as<T>(&self, name: &str) -> Result<T, AttributeError>
+  where
+    T: FromStr,
+    T::Err: fmt::Display,
+  {
+    parse(name, self.get_attribute(name))
+  }
+
+  /// Parses the value of the attribute `name` as an integer, like `xs:long`
+  pub fn attr_i64(&self, name: &str) -> Result<i64, AttributeError> {
+    parse_schema(name, self.get_attribute(name))
+  }
+
+  /// Parses the value of the attribute `name` as a floating point number, like `xs:double`
+  pub fn attr_f64(&self, name: &str) -> Result<f64, AttributeError> {
+    parse_schema(name, self.get_attribute(name))
+  }
+
+  /// Parses the value of the attribute `name` as an `xs:boolean`: `true` or `1`, `false` or `0`
+  pub fn attr_bool(&self, name: &str) -> Result<bool, AttributeError> {
+    parse_bool(name, self.get_attribute(name))
+  }
+}
+
+impl RoNode {
+  /// Parses the value of the attribute `name` into a `T`
+  pub fn get_attribute_as<T>(self, name: &str) -> Result<T, AttributeError>
+  where
+    T: FromStr,
+    T::Err: fmt::Display,
+  {
+    parse(name, self.get_attribute(name))
+  }
+
+  /// Parses the value of the attribute `name` as an integer, like `xs:long`
+  pub fn attr_i64(self, name: &str) -> Result<i64, AttributeError> {
+    parse_schema(name, self.get_attribute(name))
+  }
+
+  /// Parses the value of the attribute `name` as a floating point number, like `xs:double`
+  pub fn attr_f64(self, name: &str) -> Result<f64, AttributeError> {
+    parse_schema(name, self.get_attribute(name))
+  }
+
+  /// Parses the value of the attribute `name` as an `xs:boolean`: `true` or `1`, `false` or `0`
+  pub fn attr_bool(self, name: &str) -> Result<bool, AttributeError> {
+    parse_bool(name, self.get_attribute(name))
+  }
+}
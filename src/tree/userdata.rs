@@ -0,0 +1,95 @@
+//! Data attached to nodes
+//!
+//! A node can carry one value of any type, kept in the `_private` field libxml2 reserves for
+//! applications and released when libxml2 frees the node, so that higher layers can annotate
+//! nodes without side tables keyed by pointer. The value is shared through an `Rc`, so that
+//! the handles given out stay valid after the node or its document is gone:
+//!
+//! ```
+//! use libxml::parser::Parser;
+//!
+//! let doc = Parser::default().parse_string("<r><a/></r>").unwrap();
+//! let mut a = doc.get_root_element().unwrap().get_first_child().unwrap();
+//! a.set_user_data(42u32).unwrap();
+//! let again = doc.get_root_element().unwrap().get_first_child().unwrap();
+//! assert_eq!(again.get_user_data::<u32>().as_deref(), Some(&42));
+//! assert_eq!(again.get_user_data::<String>(), None);
+//! ```
+//!
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::error::Error;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use crate::bindings::*;
+use crate::tree::Node;
+use crate::tree::noderef::hook_node_deregistration;
+
+thread_local! {
+  /// The nodes whose `_private` field holds data set here, as other code may use it too
+  static OWNED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+type UserData = Rc<dyn Any>;
+
+impl Node {
+  /// Attaches `data` to this node, replacing any data attached before. The data is dropped
+  /// once the node is freed and no handle from [`Node::get_user_data`] is left; it must not
+  /// hold nodes, which could be freed along with it.
+  pub fn set_user_data<T: Any>(&mut self, data: T) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let node_ptr = self.node_ptr_mut()?;
+    hook_node_deregistration();
+    release(node_ptr);
+    let data: Box<UserData> = Box::new(Rc::new(data));
+    unsafe { (*node_ptr)._private = Box::into_raw(data) as *mut c_void };
+    OWNED.with(|owned| owned.borrow_mut().insert(node_ptr as usize));
+    Ok(())
+  }
+
+  /// The data attached to this node, if there is some of type `T`
+  pub fn get_user_data<T: Any>(&self) -> Option<Rc<T>> {
+    let data = user_data(self.node_ptr())?;
+    unsafe { (*data).clone() }.downcast().ok()
+  }
+
+  /// Detaches the data attached to this node and returns it, if it is of type `T`
+  pub fn take_user_data<T: Any>(&mut self) -> Result<Option<Rc<T>>, Box<dyn Error + Send + Sync>> {
+    let node_ptr = self.node_ptr_mut()?;
+    match user_data(node_ptr) {
+      Some(data) if unsafe { (*data).is::<T>() } => {
+        OWNED.with(|owned| owned.borrow_mut().remove(&(node_ptr as usize)));
+        unsafe { (*node_ptr)._private = std::ptr::null_mut() };
+        let data = unsafe { Box::from_raw(data) };
+        Ok((*data).downcast().ok())
+      }
+      _ => Ok(None),
+    }
+  }
+}
+
+/// The data attached to `node_ptr` here, if any
+fn user_data(node_ptr: xmlNodePtr) -> Option<*mut UserData> {
+  let owned = OWNED.with(|owned| owned.borrow().contains(&(node_ptr as usize)));
+  let data = unsafe { (*node_ptr)._private as *mut UserData };
+  (owned && !data.is_null()).then_some(data)
+}
+
+/// Drops the data attached to `node_ptr`, which libxml2 is freeing
+pub(crate) fn release(node_ptr: xmlNodePtr) {
+  let owned = OWNED
+    .try_with(|owned| {
+      owned
+        .try_borrow_mut()
+        .is_ok_and(|mut owned| owned.remove(&(node_ptr as usize)))
+    })
+    .unwrap_or(false);
+  if owned {
+    unsafe {
+      let data = (*node_ptr)._private as *mut UserData;
+      (*node_ptr)._private = std::ptr::null_mut();
+      drop(Box::from_raw(data));
+    }
+  }
+}
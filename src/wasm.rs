@@ -0,0 +1,180 @@
+//! WASI imports for `wasm32-unknown-unknown`
+//!
+//! On this target libxml2 is linked against wasi-libc, whose system calls are imports from the
+//! `wasi_snapshot_preview1` module. No such module exists in a browser or an edge runtime, so
+//! the imports are defined here instead: the linker resolves wasi-libc's references to these
+//! definitions and the final module has no WASI imports left.
+//!
+//! There is no file system, network, environment or clock. Reads and opens fail with `EBADF`,
+//! `ENOSYS` or `ENOENT`, which libxml2 reports like any other I/O error; writes (diagnostics on
+//! stderr) are discarded.
+//!
+#![allow(clippy::missing_safety_doc)]
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+const ERRNO_NOENT: i32 = 44;
+const ERRNO_NOSYS: i32 = 52;
+
+#[repr(C)]
+pub struct Ciovec {
+  buf: *const u8,
+  len: usize,
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn __imported_wasi_snapshot_preview1_args_sizes_get(
+  count: *mut usize,
+  size: *mut usize,
+) -> i32 {
+  unsafe {
+    *count = 0;
+    *size = 0;
+  }
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_args_get(_argv: i32, _buf: i32) -> i32 {
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn __imported_wasi_snapshot_preview1_environ_sizes_get(
+  count: *mut usize,
+  size: *mut usize,
+) -> i32 {
+  unsafe {
+    *count = 0;
+    *size = 0;
+  }
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_environ_get(_environ: i32, _buf: i32) -> i32 {
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn __imported_wasi_snapshot_preview1_clock_time_get(
+  _id: i32,
+  _precision: i64,
+  time: *mut u64,
+) -> i32 {
+  unsafe { *time = 0 };
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn __imported_wasi_snapshot_preview1_random_get(buf: *mut u8, len: usize) -> i32 {
+  // Only used to seed hash tables, a fixed seed is good enough
+  unsafe { core::ptr::write_bytes(buf, 0x5a, len) };
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn __imported_wasi_snapshot_preview1_fd_write(
+  _fd: i32,
+  iovs: *const Ciovec,
+  iovs_len: usize,
+  nwritten: *mut usize,
+) -> i32 {
+  unsafe {
+    let iovs = core::slice::from_raw_parts(iovs, iovs_len);
+    *nwritten = iovs.iter().map(|iov| iov.len).sum();
+  }
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_read(
+  _fd: i32,
+  _iovs: i32,
+  _iovs_len: i32,
+  _nread: i32,
+) -> i32 {
+  ERRNO_BADF
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_close(_fd: i32) -> i32 {
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_seek(
+  _fd: i32,
+  _offset: i64,
+  _whence: i32,
+  _new_offset: i32,
+) -> i32 {
+  ERRNO_BADF
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_fdstat_get(_fd: i32, _stat: i32) -> i32 {
+  ERRNO_BADF
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_fdstat_set_flags(_fd: i32, _flags: i32) -> i32 {
+  ERRNO_NOSYS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_filestat_get(_fd: i32, _stat: i32) -> i32 {
+  ERRNO_BADF
+}
+
+// Reporting no preopened directories makes every path lookup fail with ENOENT
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_prestat_get(_fd: i32, _prestat: i32) -> i32 {
+  ERRNO_BADF
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_fd_prestat_dir_name(
+  _fd: i32,
+  _path: i32,
+  _len: i32,
+) -> i32 {
+  ERRNO_BADF
+}
+
+#[unsafe(no_mangle)]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_path_open(
+  _fd: i32,
+  _dirflags: i32,
+  _path: i32,
+  _path_len: i32,
+  _oflags: i32,
+  _rights_base: i64,
+  _rights_inheriting: i64,
+  _fdflags: i32,
+  _opened_fd: i32,
+) -> i32 {
+  ERRNO_NOENT
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_path_filestat_get(
+  _fd: i32,
+  _flags: i32,
+  _path: i32,
+  _path_len: i32,
+  _stat: i32,
+) -> i32 {
+  ERRNO_NOENT
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_sched_yield() -> i32 {
+  ERRNO_SUCCESS
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn __imported_wasi_snapshot_preview1_proc_exit(_code: i32) -> ! {
+  core::arch::wasm32::unreachable()
+}
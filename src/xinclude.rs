@@ -0,0 +1,422 @@
+//! XInclude processing with provenance tracking
+//!
+//! [`process`] substitutes `xi:include` elements like libxml2's `xmlXIncludeProcess`, and can
+//! additionally record where each included node came from, either as attributes in the
+//! [`PROVENANCE_NS`] namespace or as the `XIncludeStart`/`XIncludeEnd` marker nodes libxml2
+//! keeps around the included content. [`uninclude`] reverses the processing, splitting the
+//! included content back out of a flattened document.
+//!
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+use crate::bindings::*;
+use crate::c_helpers::*;
+use crate::tree::{Document, Node, NodeType};
+
+/// The XInclude namespace
+pub const XINCLUDE_NS: &str = "http://www.w3.org/2001/XInclude";
+/// Namespace of the provenance attributes written in [`ProvenanceMode::Attributes`] mode
+pub const PROVENANCE_NS: &str = "urn:rust-libxml:xinclude-provenance";
+const PROVENANCE_PREFIX: &str = "xip";
+
+/// How the origin of included nodes is recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProvenanceMode {
+  /// Do not record provenance, `xi:include` elements are removed
+  #[default]
+  None,
+  /// Add `xip:href` (and `xip:xpointer`, `xip:parse`) attributes to included elements
+  Attributes,
+  /// Keep the `XIncludeStart`/`XIncludeEnd` marker nodes around included content.
+  /// This is the only mode in which [`uninclude`] restores the exact original includes.
+  Markers,
+}
+
+/// Options for [`process`]
+#[derive(Debug, Clone, Copy)]
+pub struct XIncludeOptions {
+  /// How to record the origin of included nodes
+  pub provenance: ProvenanceMode,
+  /// Add `xml:base` attributes to included elements coming from another directory
+  pub base_fixup: bool,
+  /// Fail the includes of http and ftp URLs instead of fetching them (`XML_PARSE_NONET`)
+  pub no_net: bool,
+}
+
+impl Default for XIncludeOptions {
+  fn default() -> Self {
+    XIncludeOptions {
+      provenance: ProvenanceMode::None,
+      base_fixup: true,
+      no_net: false,
+    }
+  }
+}
+
+/// Where an included node came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inclusion {
+  /// The `href` of the `xi:include` element
+  pub href: String,
+  /// The `xpointer` fragment, if any
+  pub xpointer: Option<String>,
+  /// The `parse` attribute, if given (`xml` or `text`)
+  pub parse: Option<String>,
+}
+
+impl Inclusion {
+  fn is_text(&self) -> bool {
+    self.parse.as_deref() == Some("text")
+  }
+}
+
+/// Content split out of a document by [`uninclude`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Split {
+  /// The `xi:include` the content was restored to
+  pub inclusion: Inclusion,
+  /// Serialization of the included nodes (the plain text for `parse="text"`)
+  pub content: String,
+}
+
+/// Processes the XInclude elements of `doc`, returning the number of substitutions.
+///
+/// Any `Node` previously obtained for an `xi:include` element must not be used afterwards.
+pub fn process(doc: &mut Document, options: &XIncludeOptions) -> Result<usize, ()> {
+  let mut flags = 0;
+  if options.provenance == ProvenanceMode::None {
+    flags |= xmlParserOption_XML_PARSE_NOXINCNODE as i32;
+  }
+  if !options.base_fixup {
+    flags |= xmlParserOption_XML_PARSE_NOBASEFIX as i32;
+  }
+  if options.no_net {
+    flags |= xmlParserOption_XML_PARSE_NONET as i32;
+  }
+  let count = unsafe { xmlXIncludeProcessFlags(doc.doc_ptr(), flags) };
+  if count < 0 {
+    return Err(());
+  }
+
+  if options.provenance == ProvenanceMode::Attributes {
+    let root = unsafe { xmlDocGetRootElement(doc.doc_ptr()) };
+    for start in markers(doc.doc_ptr() as xmlNodePtr) {
+      let inclusion = marker_inclusion(start);
+      let end = matching_end(start);
+      let mut cur = xmlNextSibling(start);
+      while !cur.is_null() && cur != end {
+        if node_type(cur) == Some(NodeType::ElementNode) {
+          annotate(root, cur, &inclusion);
+        }
+        cur = xmlNextSibling(cur);
+      }
+      remove(start);
+      if !end.is_null() {
+        remove(end);
+      }
+    }
+  }
+  Ok(count as usize)
+}
+
+/// Returns the origin of a node included at the top level of an inclusion
+pub fn inclusion_of(node: &Node) -> Option<Inclusion> {
+  if let Some(href) = node.get_property_ns("href", PROVENANCE_NS) {
+    return Some(Inclusion {
+      href,
+      xpointer: node.get_property_ns("xpointer", PROVENANCE_NS),
+      parse: node.get_property_ns("parse", PROVENANCE_NS),
+    });
+  }
+  let mut cur = xmlPrevSibling(node.node_ptr());
+  while !cur.is_null() {
+    match node_type(cur) {
+      Some(NodeType::XIncludeStart) => return Some(marker_inclusion(cur)),
+      Some(NodeType::XIncludeEnd) => return None,
+      _ => cur = xmlPrevSibling(cur),
+    }
+  }
+  None
+}
+
+/// Replaces included content by `xi:include` elements again, returning the removed content
+/// in document order of the includes.
+///
+/// `xml:base` attributes added by the base fixup are dropped from the split content.
+/// In [`ProvenanceMode::Attributes`] mode every annotated element becomes its own include.
+/// Any `Node` previously obtained for removed content must not be used afterwards.
+pub fn uninclude(doc: &mut Document) -> Result<Vec<Split>, ()> {
+  let doc_ptr = doc.doc_ptr();
+  let mut splits = Vec::new();
+
+  // Restore inner includes first, so that they end up in the content of outer ones
+  for start in markers(doc_ptr as xmlNodePtr).into_iter().rev() {
+    let inclusion = marker_inclusion(start);
+    let end = matching_end(start);
+    let mut content = String::new();
+    let mut cur = xmlNextSibling(start);
+    while !cur.is_null() && cur != end {
+      let next = xmlNextSibling(cur);
+      content.push_str(&take_content(cur, inclusion.is_text()));
+      cur = next;
+    }
+    if !end.is_null() {
+      remove(end);
+    }
+    let include = include_element(doc_ptr, start, None)?;
+    splits.push(Split { inclusion, content });
+    unsafe { xmlReplaceNode(start, include) };
+    free(start);
+  }
+
+  for element in annotated(doc_ptr as xmlNodePtr).into_iter().rev() {
+    let inclusion = annotated_inclusion(element);
+    let include = include_element(doc_ptr, element, Some(&inclusion))?;
+    unsafe { xmlReplaceNode(element, include) };
+    for name in ["href", "xpointer", "parse"] {
+      let c_name = CString::new(name).unwrap();
+      let c_ns = CString::new(PROVENANCE_NS).unwrap();
+      unsafe {
+        let attr = xmlHasNsProp(element, c_name.as_bytes().as_ptr(), c_ns.as_bytes().as_ptr());
+        if !attr.is_null() {
+          xmlRemoveProp(attr);
+        }
+      }
+    }
+    splits.push(Split {
+      inclusion,
+      content: take_content(element, false),
+    });
+  }
+
+  splits.reverse();
+  Ok(splits)
+}
+
+fn node_type(node: xmlNodePtr) -> Option<NodeType> {
+  NodeType::from_int(xmlGetNodeType(node))
+}
+
+/// All `XIncludeStart` nodes below `node`, in document order
+fn markers(node: xmlNodePtr) -> Vec<xmlNodePtr> {
+  let mut found = Vec::new();
+  collect(node, &mut found, &|cur| {
+    node_type(cur) == Some(NodeType::XIncludeStart)
+  });
+  found
+}
+
+/// All elements below `node` carrying provenance attributes, in document order
+fn annotated(node: xmlNodePtr) -> Vec<xmlNodePtr> {
+  let c_href = CString::new("href").unwrap();
+  let c_ns = CString::new(PROVENANCE_NS).unwrap();
+  let mut found = Vec::new();
+  collect(node, &mut found, &|cur| {
+    node_type(cur) == Some(NodeType::ElementNode)
+      && !unsafe { xmlHasNsProp(cur, c_href.as_bytes().as_ptr(), c_ns.as_bytes().as_ptr()) }
+        .is_null()
+  });
+  found
+}
+
+fn collect(node: xmlNodePtr, found: &mut Vec<xmlNodePtr>, predicate: &dyn Fn(xmlNodePtr) -> bool) {
+  let mut cur = xmlGetFirstChild(node);
+  while !cur.is_null() {
+    if predicate(cur) {
+      found.push(cur);
+    }
+    if matches!(
+      node_type(cur),
+      Some(NodeType::ElementNode) | Some(NodeType::XIncludeStart)
+    ) {
+      collect(cur, found, predicate);
+    }
+    cur = xmlNextSibling(cur);
+  }
+}
+
+fn matching_end(start: xmlNodePtr) -> xmlNodePtr {
+  let mut cur = xmlNextSibling(start);
+  while !cur.is_null() && node_type(cur) != Some(NodeType::XIncludeEnd) {
+    cur = xmlNextSibling(cur);
+  }
+  cur
+}
+
+fn marker_inclusion(start: xmlNodePtr) -> Inclusion {
+  Inclusion {
+    href: property(start, "href", None).unwrap_or_default(),
+    xpointer: property(start, "xpointer", None),
+    parse: property(start, "parse", None),
+  }
+}
+
+fn annotated_inclusion(element: xmlNodePtr) -> Inclusion {
+  Inclusion {
+    href: property(element, "href", Some(PROVENANCE_NS)).unwrap_or_default(),
+    xpointer: property(element, "xpointer", Some(PROVENANCE_NS)),
+    parse: property(element, "parse", Some(PROVENANCE_NS)),
+  }
+}
+
+fn property(node: xmlNodePtr, name: &str, ns: Option<&str>) -> Option<String> {
+  // xmlGetProp and friends only look at element nodes, so walk the list of the markers by hand
+  let mut attr = xmlGetFirstProperty(node);
+  while !attr.is_null() {
+    let attr_name = unsafe { CStr::from_ptr(xmlAttrName(attr)) }.to_string_lossy();
+    let attr_ns = xmlAttrNs(attr);
+    let attr_href = if attr_ns.is_null() {
+      None
+    } else {
+      Some(unsafe { CStr::from_ptr(xmlNsHref(attr_ns)) }.to_string_lossy())
+    };
+    if attr_name == name && attr_href.as_deref() == ns {
+      let value_ptr = unsafe { xmlNodeGetContent(attr as xmlNodePtr) };
+      if value_ptr.is_null() {
+        return Some(String::new());
+      }
+      let value = unsafe { CStr::from_ptr(value_ptr as *const c_char) }
+        .to_string_lossy()
+        .into_owned();
+      bindgenFree(value_ptr as *mut c_void);
+      return Some(value);
+    }
+    attr = xmlNextPropertySibling(attr);
+  }
+  None
+}
+
+fn annotate(root: xmlNodePtr, element: xmlNodePtr, inclusion: &Inclusion) {
+  let c_href = CString::new(PROVENANCE_NS).unwrap();
+  let c_prefix = CString::new(PROVENANCE_PREFIX).unwrap();
+  unsafe {
+    let mut ns = xmlSearchNsByHref(xmlGetDoc(element), element, c_href.as_bytes().as_ptr());
+    if ns.is_null() {
+      ns = xmlNewNs(root, c_href.as_bytes().as_ptr(), c_prefix.as_bytes().as_ptr());
+    }
+    let values = [
+      ("href", Some(&inclusion.href)),
+      ("xpointer", inclusion.xpointer.as_ref()),
+      ("parse", inclusion.parse.as_ref()),
+    ];
+    for (name, value) in values {
+      if let Some(value) = value {
+        let c_name = CString::new(name).unwrap();
+        let c_value = CString::new(value.as_str()).unwrap();
+        xmlSetNsProp(
+          element,
+          ns,
+          c_name.as_bytes().as_ptr(),
+          c_value.as_bytes().as_ptr(),
+        );
+      }
+    }
+  }
+}
+
+/// Builds an `xi:include` element, copying the attributes of a marker node or taking
+/// `inclusion` when given
+fn include_element(
+  doc_ptr: xmlDocPtr,
+  template: xmlNodePtr,
+  inclusion: Option<&Inclusion>,
+) -> Result<xmlNodePtr, ()> {
+  let c_name = CString::new("include").unwrap();
+  let c_href = CString::new(XINCLUDE_NS).unwrap();
+  let c_prefix = CString::new("xi").unwrap();
+  unsafe {
+    let include = xmlNewDocNode(doc_ptr, ptr::null_mut(), c_name.as_bytes().as_ptr(), ptr::null());
+    if include.is_null() {
+      return Err(());
+    }
+    let parent = xmlGetParent(template);
+    let mut ns = xmlSearchNsByHref(doc_ptr, parent, c_href.as_bytes().as_ptr());
+    if ns.is_null() {
+      ns = xmlNewNs(include, c_href.as_bytes().as_ptr(), c_prefix.as_bytes().as_ptr());
+    }
+    xmlSetNs(include, ns);
+
+    match inclusion {
+      None => {
+        // xmlCopyPropList refuses attributes owned by marker nodes, so copy them one by one
+        let mut attr = xmlGetFirstProperty(template);
+        while !attr.is_null() {
+          let mut attr_ns = xmlAttrNs(attr);
+          if !attr_ns.is_null() {
+            let href = xmlNsHref(attr_ns) as *const xmlChar;
+            attr_ns = match xmlSearchNsByHref(doc_ptr, include, href) {
+              found if found.is_null() => {
+                xmlNewNs(include, href, xmlNsPrefix(attr_ns) as *const xmlChar)
+              }
+              found => found,
+            };
+          }
+          let value_ptr = xmlNodeGetContent(attr as xmlNodePtr);
+          xmlSetNsProp(include, attr_ns, xmlAttrName(attr) as *const xmlChar, value_ptr);
+          bindgenFree(value_ptr as *mut c_void);
+          attr = xmlNextPropertySibling(attr);
+        }
+      }
+      Some(inclusion) => {
+        let values = [
+          ("href", Some(&inclusion.href)),
+          ("xpointer", inclusion.xpointer.as_ref()),
+          ("parse", inclusion.parse.as_ref()),
+        ];
+        for (name, value) in values {
+          if let Some(value) = value {
+            let c_name = CString::new(name).unwrap();
+            let c_value = CString::new(value.as_str()).unwrap();
+            xmlSetProp(include, c_name.as_bytes().as_ptr(), c_value.as_bytes().as_ptr());
+          }
+        }
+      }
+    }
+    Ok(include)
+  }
+}
+
+/// Serializes and unlinks an included node
+fn take_content(node: xmlNodePtr, raw_text: bool) -> String {
+  unsafe {
+    let content = match node_type(node) {
+      Some(NodeType::TextNode) if raw_text => {
+        let content_ptr = xmlNodeGetContent(node);
+        let content = CStr::from_ptr(content_ptr as *const c_char)
+          .to_string_lossy()
+          .into_owned();
+        bindgenFree(content_ptr as *mut c_void);
+        content
+      }
+      _ => {
+        if node_type(node) == Some(NodeType::ElementNode) {
+          let c_base = CString::new("base").unwrap();
+          let c_xml_ns = CString::new("http://www.w3.org/XML/1998/namespace").unwrap();
+          let attr = xmlHasNsProp(node, c_base.as_bytes().as_ptr(), c_xml_ns.as_bytes().as_ptr());
+          if !attr.is_null() {
+            xmlRemoveProp(attr);
+          }
+        }
+        let buf = xmlBufferCreate();
+        xmlNodeDump(buf, xmlGetDoc(node), node, 0, 0);
+        let content = CStr::from_ptr(xmlBufferContent(buf) as *const c_char)
+          .to_string_lossy()
+          .into_owned();
+        xmlBufferFree(buf);
+        content
+      }
+    };
+    remove(node);
+    content
+  }
+}
+
+fn remove(node: xmlNodePtr) {
+  unsafe { xmlUnlinkNode(node) };
+  free(node);
+}
+
+fn free(node: xmlNodePtr) {
+  unsafe { xmlFreeNode(node) };
+}
@@ -0,0 +1,352 @@
+//! An `xml-rs` compatible event reader over libxml2's pull parser
+//!
+//! [`EventReader`] mirrors the interface of `xml::reader::EventReader`: it wraps any
+//! [`Read`] source and hands out [`XmlEvent`]s one at a time, either through
+//! [`EventReader::next`] or by iterating over it. The events themselves are the ones of the
+//! `xml-rs` crate, so code written against `xml-rs` can switch parsers by changing a `use`.
+//!
+//! Parsing is done by libxml2's `xmlTextReader`, entities are substituted and namespace
+//! declarations are reported in the `namespace` of [`XmlEvent::StartElement`] rather than
+//! as attributes, like `xml-rs` does. Like `xml-rs` the reader never loads external
+//! entities: their declarations are read, their references fail to resolve.
+//!
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::OnceLock;
+
+use xml::attribute::OwnedAttribute;
+use xml::common::XmlVersion;
+use xml::name::OwnedName;
+use xml::namespace::{Namespace, NamespaceStack};
+use xml::reader::XmlEvent;
+
+use crate::bindings::*;
+use crate::error::StructuredError;
+
+const READER_ELEMENT: c_int = 1;
+const READER_TEXT: c_int = 3;
+const READER_CDATA: c_int = 4;
+const READER_PROCESSING_INSTRUCTION: c_int = 7;
+const READER_COMMENT: c_int = 8;
+const READER_WHITESPACE: c_int = 13;
+const READER_SIGNIFICANT_WHITESPACE: c_int = 14;
+const READER_END_ELEMENT: c_int = 15;
+
+/// The entity loader in place before ours, which loads the entities of every other parse
+static PREVIOUS_LOADER: OnceLock<xmlExternalEntityLoader> = OnceLock::new();
+
+thread_local! {
+  /// Whether this thread is inside an [`EventReader`] read, where loading is refused
+  static READING: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe extern "C" fn load_entity(
+  url: *const c_char,
+  id: *const c_char,
+  context: xmlParserCtxtPtr,
+) -> xmlParserInputPtr {
+  if READING.with(Cell::get) {
+    return ptr::null_mut();
+  }
+  match PREVIOUS_LOADER.get().copied().flatten() {
+    Some(previous) => unsafe { previous(url, id, context) },
+    None => ptr::null_mut(),
+  }
+}
+
+/// Error raised while pulling events
+#[derive(Debug)]
+pub struct EventReaderError {
+  /// The error reported by libxml2, if any
+  pub error: Option<StructuredError>,
+}
+
+impl Error for EventReaderError {}
+
+impl fmt::Display for EventReaderError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.error.as_ref().and_then(|e| e.message.as_deref()) {
+      Some(message) => write!(f, "malformed XML: {}", message.trim_end()),
+      None => write!(f, "malformed XML"),
+    }
+  }
+}
+
+/// A pull parser producing `xml-rs` events
+pub struct EventReader<R: Read> {
+  reader: xmlTextReaderPtr,
+  // Boxed so that the address handed to libxml2 stays valid when the reader moves
+  source: Box<R>,
+  started: bool,
+  finished: bool,
+  pending: VecDeque<XmlEvent>,
+  namespaces: NamespaceStack,
+}
+
+unsafe extern "C" fn read_source<R: Read>(
+  context: *mut c_void,
+  buffer: *mut c_char,
+  len: c_int,
+) -> c_int {
+  unsafe {
+    let source = context as *mut R;
+    let buf = slice::from_raw_parts_mut(buffer as *mut u8, len as usize);
+    loop {
+      match (*source).read(buf) {
+        Ok(v) => return v as c_int,
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(_) => return -1,
+      }
+    }
+  }
+}
+
+impl<R: Read> EventReader<R> {
+  /// Creates a reader pulling its input from `source`
+  pub fn new(source: R) -> Self {
+    PREVIOUS_LOADER.get_or_init(|| {
+      let previous = unsafe { xmlGetExternalEntityLoader() };
+      unsafe { xmlSetExternalEntityLoader(Some(load_entity)) };
+      previous
+    });
+    let mut source = Box::new(source);
+    let context = &mut *source as *mut R as *mut c_void;
+    let reader = unsafe {
+      xmlReaderForIO(
+        Some(read_source::<R>),
+        None,
+        context,
+        ptr::null(),
+        ptr::null(),
+        (xmlParserOption_XML_PARSE_NOENT | xmlParserOption_XML_PARSE_NONET) as c_int,
+      )
+    };
+    EventReader {
+      reader,
+      source,
+      started: false,
+      finished: false,
+      pending: VecDeque::new(),
+      namespaces: NamespaceStack::default(),
+    }
+  }
+
+  /// Pulls the next event. Once [`XmlEvent::EndDocument`] has been returned, every further
+  /// call returns it again.
+  #[allow(clippy::should_implement_trait)]
+  pub fn next(&mut self) -> Result<XmlEvent, EventReaderError> {
+    if let Some(event) = self.pending.pop_front() {
+      return Ok(event);
+    }
+    if self.finished {
+      return Ok(XmlEvent::EndDocument);
+    }
+    if self.reader.is_null() {
+      return Err(EventReaderError { error: None });
+    }
+    loop {
+      unsafe { xmlResetLastError() };
+      let was_reading = READING.with(|reading| reading.replace(true));
+      let status = unsafe { xmlTextReaderRead(self.reader) };
+      READING.with(|reading| reading.set(was_reading));
+      if status < 0 {
+        return Err(self.error());
+      }
+      if !self.started {
+        self.started = true;
+        let start = self.start_document();
+        if status == 0 {
+          self.finished = true;
+        } else if let Some(event) = self.current_event() {
+          self.pending.push_back(event);
+        }
+        return Ok(start);
+      }
+      if status == 0 {
+        self.finished = true;
+        return Ok(XmlEvent::EndDocument);
+      }
+      if let Some(event) = self.current_event() {
+        return Ok(event);
+      }
+    }
+  }
+
+  /// Borrows the underlying source
+  pub fn source(&self) -> &R {
+    &self.source
+  }
+
+  fn error(&self) -> EventReaderError {
+    let last = unsafe { xmlGetLastError() };
+    EventReaderError {
+      error: if last.is_null() {
+        None
+      } else {
+        Some(unsafe { StructuredError::from_raw(last) })
+      },
+    }
+  }
+
+  fn start_document(&self) -> XmlEvent {
+    let version = match self.string(unsafe { xmlTextReaderConstXmlVersion(self.reader) }) {
+      Some(version) if version == "1.1" => XmlVersion::Version11,
+      _ => XmlVersion::Version10,
+    };
+    let encoding = self
+      .string(unsafe { xmlTextReaderConstEncoding(self.reader) })
+      .unwrap_or_else(|| "UTF-8".to_string());
+    let standalone = match unsafe { xmlTextReaderStandalone(self.reader) } {
+      1 => Some(true),
+      0 => Some(false),
+      _ => None,
+    };
+    XmlEvent::StartDocument {
+      version,
+      encoding,
+      standalone,
+    }
+  }
+
+  /// Translates the node the reader is positioned on, `None` for skipped node types
+  fn current_event(&mut self) -> Option<XmlEvent> {
+    let node_type = unsafe { xmlTextReaderNodeType(self.reader) };
+    match node_type {
+      READER_ELEMENT => Some(self.start_element()),
+      READER_END_ELEMENT => {
+        let name = self.name();
+        self.namespaces.try_pop();
+        Some(XmlEvent::EndElement { name })
+      }
+      READER_TEXT => Some(XmlEvent::Characters(self.value())),
+      READER_WHITESPACE | READER_SIGNIFICANT_WHITESPACE => Some(XmlEvent::Whitespace(self.value())),
+      READER_CDATA => Some(XmlEvent::CData(self.value())),
+      READER_COMMENT => Some(XmlEvent::Comment(self.value())),
+      READER_PROCESSING_INSTRUCTION => {
+        let name = self
+          .string(unsafe { xmlTextReaderConstLocalName(self.reader) })
+          .unwrap_or_default();
+        let data = self.value();
+        Some(XmlEvent::ProcessingInstruction {
+          name,
+          data: if data.is_empty() { None } else { Some(data) },
+        })
+      }
+      _ => None,
+    }
+  }
+
+  fn start_element(&mut self) -> XmlEvent {
+    let name = self.name();
+    let empty = unsafe { xmlTextReaderIsEmptyElement(self.reader) } == 1;
+    self.namespaces.push_empty();
+    let mut attributes = Vec::new();
+    while unsafe { xmlTextReaderMoveToNextAttribute(self.reader) } == 1 {
+      if unsafe { xmlTextReaderIsNamespaceDecl(self.reader) } == 1 {
+        let prefix = match self.string(unsafe { xmlTextReaderConstPrefix(self.reader) }) {
+          // xmlns:prefix="..."
+          Some(_) => self
+            .string(unsafe { xmlTextReaderConstLocalName(self.reader) })
+            .unwrap_or_default(),
+          // xmlns="..."
+          None => String::new(),
+        };
+        let uri = self.value();
+        self.namespaces.put(prefix, uri);
+      } else {
+        attributes.push(OwnedAttribute::new(self.name(), self.value()));
+      }
+    }
+    let namespace: Namespace = self.namespaces.squash();
+    if empty {
+      self.namespaces.try_pop();
+      self.pending.push_back(XmlEvent::EndElement { name: name.clone() });
+    }
+    XmlEvent::StartElement {
+      name,
+      attributes,
+      namespace,
+    }
+  }
+
+  fn name(&self) -> OwnedName {
+    let local = self
+      .string(unsafe { xmlTextReaderConstLocalName(self.reader) })
+      .unwrap_or_default();
+    let namespace = self.string(unsafe { xmlTextReaderConstNamespaceUri(self.reader) });
+    let prefix = self.string(unsafe { xmlTextReaderConstPrefix(self.reader) });
+    OwnedName {
+      local_name: local,
+      namespace,
+      prefix,
+    }
+  }
+
+  fn value(&self) -> String {
+    self
+      .string(unsafe { xmlTextReaderConstValue(self.reader) })
+      .unwrap_or_default()
+  }
+
+  fn string(&self, value: *const xmlChar) -> Option<String> {
+    if value.is_null() {
+      None
+    } else {
+      Some(
+        unsafe { CStr::from_ptr(value as *const c_char) }
+          .to_string_lossy()
+          .into_owned(),
+      )
+    }
+  }
+}
+
+impl<R: Read> Drop for EventReader<R> {
+  fn drop(&mut self) {
+    // The reader has to go first, it still points into `source`
+    if !self.reader.is_null() {
+      unsafe { xmlFreeTextReader(self.reader) };
+    }
+  }
+}
+
+impl<R: Read> IntoIterator for EventReader<R> {
+  type Item = Result<XmlEvent, EventReaderError>;
+  type IntoIter = Events<R>;
+
+  fn into_iter(self) -> Events<R> {
+    Events {
+      reader: self,
+      done: false,
+    }
+  }
+}
+
+/// Iterator over the events of an [`EventReader`], ending after
+/// [`XmlEvent::EndDocument`] or the first error
+pub struct Events<R: Read> {
+  reader: EventReader<R>,
+  done: bool,
+}
+
+impl<R: Read> Iterator for Events<R> {
+  type Item = Result<XmlEvent, EventReaderError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let result = self.reader.next();
+    if matches!(result, Ok(XmlEvent::EndDocument) | Err(_)) {
+      self.done = true;
+    }
+    Some(result)
+  }
+}
@@ -4,11 +4,12 @@ use crate::bindings::*;
 use crate::c_helpers::*;
 use crate::readonly::RoNode;
 use crate::tree::{Document, DocumentRef, DocumentWeak, Node};
-use libc::{c_char, c_void, size_t};
+use std::os::raw::{c_char, c_void};
 use std::cell::RefCell;
 use std::ffi::{CStr, CString};
 use std::fmt;
-use std::rc::Rc;
+use std::ptr;
+use std::rc::{Rc, Weak};
 use std::str;
 
 ///Thinly wrapped libxml2 xpath context
@@ -35,6 +36,14 @@ pub struct Context {
   pub(crate) document: DocumentWeak,
 }
 
+/// An XPath expression compiled once, to evaluate with [`Context::evaluate_compiled`] and
+/// its variants against any number of nodes and documents. Namespace prefixes are resolved
+/// by the context of each evaluation.
+#[derive(Debug)]
+pub struct CompiledExpression {
+  ptr: xmlXPathCompExprPtr,
+}
+
 ///Essentially, the result of the evaluation of some xpath expression
 #[derive(Debug)]
 pub struct Object {
@@ -57,7 +66,7 @@ impl Context {
     }
   }
   pub(crate) fn new_ptr(docref: &DocumentRef) -> Result<Context, ()> {
-    let ctxtptr = unsafe { xmlXPathNewContext(docref.borrow().doc_ptr) };
+    let ctxtptr = unsafe { xmlXPathNewContext(docref.doc_ptr()) };
     if ctxtptr.is_null() {
       Err(())
     } else {
@@ -68,6 +77,20 @@ impl Context {
     }
   }
 
+  /// A context on a document which no `Document` wraps, to evaluate read-only expressions;
+  /// the nodes it finds can only be taken as `RoNode`s
+  pub(crate) fn new_detached(doc_ptr: xmlDocPtr) -> Result<Context, ()> {
+    let ctxtptr = unsafe { xmlXPathNewContext(doc_ptr) };
+    if ctxtptr.is_null() {
+      Err(())
+    } else {
+      Ok(Context {
+        context_ptr: Rc::new(RefCell::new(_Context(ctxtptr))),
+        document: Weak::new(),
+      })
+    }
+  }
+
   /// Returns the raw libxml2 context pointer behind the struct
   pub fn as_ptr(&self) -> xmlXPathContextPtr {
     self.context_ptr.borrow().0
@@ -141,6 +164,56 @@ impl Context {
     }
   }
 
+  ///evaluate a compiled xpath
+  pub fn evaluate_compiled(&self, expression: &CompiledExpression) -> Result<Object, ()> {
+    let ptr = unsafe { xmlXPathCompiledEval(expression.ptr, self.as_ptr()) };
+    self.wrap_object(ptr)
+  }
+
+  ///evaluate a compiled xpath on a context Node, the context node of this context being
+  ///restored afterwards
+  pub fn node_evaluate_compiled(
+    &self,
+    expression: &CompiledExpression,
+    node: &Node,
+  ) -> Result<Object, ()> {
+    self.node_evaluate_compiled_ptr(expression, node.node_ptr())
+  }
+
+  ///evaluate a compiled xpath on a context RoNode, see [`Context::node_evaluate_compiled`]
+  pub fn node_evaluate_compiled_readonly(
+    &self,
+    expression: &CompiledExpression,
+    node: RoNode,
+  ) -> Result<Object, ()> {
+    self.node_evaluate_compiled_ptr(expression, node.0)
+  }
+
+  fn node_evaluate_compiled_ptr(
+    &self,
+    expression: &CompiledExpression,
+    node_ptr: xmlNodePtr,
+  ) -> Result<Object, ()> {
+    let previous = unsafe { (*self.as_ptr()).node };
+    if unsafe { xmlXPathSetContextNode(node_ptr, self.as_ptr()) } != 0 {
+      return Err(());
+    }
+    let result = self.evaluate_compiled(expression);
+    unsafe { (*self.as_ptr()).node = previous };
+    result
+  }
+
+  fn wrap_object(&self, ptr: xmlXPathObjectPtr) -> Result<Object, ()> {
+    if ptr.is_null() {
+      Err(())
+    } else {
+      Ok(Object {
+        ptr,
+        document: self.document.clone(),
+      })
+    }
+  }
+
   /// localize xpath context to a specific Node
   pub fn set_context_node(&mut self, node: &Node) -> Result<(), ()> {
     unsafe {
@@ -183,6 +256,33 @@ impl Context {
   }
 }
 
+impl CompiledExpression {
+  /// Compiles `xpath`, independently of any document
+  pub fn new(xpath: &str) -> Result<CompiledExpression, ()> {
+    let c_xpath = CString::new(xpath).map_err(|_| ())?;
+    let ptr = unsafe { xmlXPathCtxtCompile(ptr::null_mut(), c_xpath.as_bytes().as_ptr()) };
+    if ptr.is_null() {
+      Err(())
+    } else {
+      Ok(CompiledExpression { ptr })
+    }
+  }
+
+  /// Returns the raw libxml2 compiled expression pointer behind the struct
+  pub fn as_ptr(&self) -> xmlXPathCompExprPtr {
+    self.ptr
+  }
+}
+
+impl Drop for CompiledExpression {
+  /// free the compiled expression when it goes out of scope
+  fn drop(&mut self) {
+    unsafe {
+      xmlXPathFreeCompExpr(self.ptr);
+    }
+  }
+}
+
 impl Drop for Object {
   /// free the memory allocated
   fn drop(&mut self) {
@@ -214,7 +314,7 @@ impl Object {
     let n = self.get_number_of_nodes();
     let mut vec: Vec<Node> = Vec::with_capacity(n);
     let slice = if n > 0 {
-      xmlXPathObjectGetNodes(self.ptr, n as size_t)
+      xmlXPathObjectGetNodes(self.ptr, n)
     } else {
       Vec::new()
     };
@@ -233,7 +333,7 @@ impl Object {
     let n = self.get_number_of_nodes();
     let mut vec: Vec<RoNode> = Vec::with_capacity(n);
     let slice = if n > 0 {
-      xmlXPathObjectGetNodes(self.ptr, n as size_t)
+      xmlXPathObjectGetNodes(self.ptr, n)
     } else {
       Vec::new()
     };
@@ -251,7 +351,7 @@ impl Object {
     let n = self.get_number_of_nodes();
     let mut vec: Vec<String> = Vec::with_capacity(n);
     let slice = if n > 0 {
-      xmlXPathObjectGetNodes(self.ptr, n as size_t)
+      xmlXPathObjectGetNodes(self.ptr, n)
     } else {
       Vec::new()
     };
@@ -0,0 +1,105 @@
+//! Tests for parsing from tokio readers
+//!
+#![cfg(feature = "async")]
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use libxml::parser::{Parser, ParserOptions, XmlParseError};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Yields its input a few bytes at a time, pending before each chunk like a slow socket
+struct Trickle {
+  input: &'static [u8],
+  ready: bool,
+}
+
+impl AsyncRead for Trickle {
+  fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+    if !self.ready {
+      self.ready = true;
+      cx.waker().wake_by_ref();
+      return Poll::Pending;
+    }
+    self.ready = false;
+    let len = buf.remaining().min(self.input.len()).min(3);
+    buf.put_slice(&self.input[..len]);
+    self.input = &self.input[len..];
+    Poll::Ready(Ok(()))
+  }
+}
+
+fn assert_send<T: Send>(value: T) -> T {
+  value
+}
+
+#[tokio::test]
+async fn parses_from_async_readers() {
+  let parser = Parser::default();
+  let reader = Trickle {
+    input: "<list><item>é</item><item/></list>".as_bytes(),
+    ready: false,
+  };
+  let doc = assert_send(parser.parse_async_reader(reader)).await.unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_child_elements().len(), 2);
+  assert_eq!(root.get_content(), "é");
+
+  let doc = parser.parse_async_reader(&b"<root> </root>"[..]).await.unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), " ");
+}
+
+#[tokio::test]
+async fn async_reader_errors() {
+  let parser = Parser::default();
+  let options = ParserOptions {
+    recover: false,
+    ..Default::default()
+  };
+  let error = parser
+    .parse_async_reader_with_options(&b"<root><open></root>"[..], options)
+    .await
+    .unwrap_err();
+  assert!(matches!(error, XmlParseError::Malformed(_)));
+
+  // Recovering by default
+  let doc = parser.parse_async_reader(&b"<root><open></root>"[..]).await.unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "root");
+
+  struct Failing;
+  impl AsyncRead for Failing {
+    fn poll_read(self: Pin<&mut Self>, _: &mut Context<'_>, _: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+      Poll::Ready(Err(io::Error::other("connection reset")))
+    }
+  }
+  let error = parser.parse_async_reader(Failing).await.unwrap_err();
+  assert!(matches!(error, XmlParseError::ReadError(_)));
+}
+
+#[tokio::test]
+async fn async_reader_diagnostics_and_limits() {
+  let parser = Parser::default();
+  parser.parse_async_reader(&b"<root><open></root>"[..]).await.unwrap();
+  assert!(!parser.last_diagnostics().is_empty());
+  parser.parse_async_reader(&b"<root/>"[..]).await.unwrap();
+  assert!(parser.last_diagnostics().is_empty());
+
+  let options = ParserOptions {
+    max_depth: Some(2),
+    ..Default::default()
+  };
+  let error = parser
+    .parse_async_reader_with_options(&b"<a><b><c/></b></a>"[..], options)
+    .await
+    .unwrap_err();
+  assert!(matches!(error, XmlParseError::LimitExceeded));
+  let options = ParserOptions {
+    max_nodes: Some(2),
+    ..Default::default()
+  };
+  let error = parser
+    .parse_async_reader_with_options(&b"<a><b/><c/></a>"[..], options)
+    .await
+    .unwrap_err();
+  assert!(matches!(error, XmlParseError::LimitExceeded));
+}
@@ -4,8 +4,9 @@ use std::env;
 use std::fs::File;
 use std::io::Read;
 
-use libxml::parser::{Parser, ParserOptions};
-use libxml::tree::{Document, Node, SaveOptions};
+use libxml::error::XmlErrorLevel;
+use libxml::parser::{Parser, ParserOptions, XmlParseError};
+use libxml::tree::{Document, DocumentTemplate, Node, SaveOptions, SharedDocument};
 
 #[test]
 /// Build a hello world XML doc
@@ -62,6 +63,38 @@ fn create_pi() {
   assert!(doc_string.len() > 1);
 }
 
+#[test]
+/// Processing instructions are authored before the root element and read back
+fn prolog_processing_instructions() {
+  let mut doc = Document::new().unwrap();
+  let mut generator = doc.create_processing_instruction("generator", "rust").unwrap();
+  doc.add_to_prolog(&mut generator).unwrap();
+  let root = Node::new("root", None, &doc).unwrap();
+  doc.set_root_element(&root);
+  let mut stylesheet = doc
+    .create_processing_instruction("xml-stylesheet", r#"href="style.xsl" type="text/xsl""#)
+    .unwrap();
+  assert_eq!(stylesheet.get_pi_target().as_deref(), Some("xml-stylesheet"));
+  assert_eq!(
+    stylesheet.get_pi_data().as_deref(),
+    Some(r#"href="style.xsl" type="text/xsl""#)
+  );
+  doc.add_to_prolog(&mut stylesheet).unwrap();
+  let mut element = Node::new("other", None, &doc).unwrap();
+  assert!(doc.add_to_prolog(&mut element).is_err());
+  assert_eq!(
+    doc.to_string(),
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<?generator rust?>\n<?xml-stylesheet href=\"style.xsl\" type=\"text/xsl\"?>\n<root/>\n"
+  );
+
+  let parsed = Parser::default().parse_string(doc.to_string()).unwrap();
+  let pi = parsed.get_root_element().unwrap().get_prev_sibling().unwrap();
+  assert_eq!(pi.get_pi_target().as_deref(), Some("xml-stylesheet"));
+  assert_eq!(parsed.get_root_element().unwrap().get_pi_target(), None);
+  let pi = parsed.get_root_readonly().unwrap().get_prev_sibling().unwrap();
+  assert_eq!(pi.get_pi_data().as_deref(), Some(r#"href="style.xsl" type="text/xsl""#));
+}
+
 #[test]
 /// Duplicate an xml file
 fn duplicate_file() {
@@ -76,6 +109,125 @@ fn duplicate_file() {
   }
 }
 
+#[test]
+/// Duplicated documents and template instances share nothing with the original
+fn duplicate_documents() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<!DOCTYPE r [<!ENTITY e "entity">]><r xmlns:p="urn:p"><p:a id="1">&e;</p:a></r>"#)
+    .unwrap();
+  let copy = doc.duplicate().unwrap();
+  let template = DocumentTemplate::new(&doc).unwrap();
+  let original = doc.to_string();
+  drop(doc);
+  assert_eq!(copy.to_string(), original);
+  let mut root = copy.get_root_element().unwrap();
+  root.set_attribute("changed", "yes").unwrap();
+  assert_ne!(copy.to_string(), original);
+
+  let instances: Vec<String> = std::thread::scope(|scope| {
+    let handles: Vec<_> = (0..4)
+      .map(|i| {
+        let template = &template;
+        scope.spawn(move || {
+          let doc = template.instantiate().unwrap();
+          let mut root = doc.get_root_element().unwrap();
+          root.new_child(None, &format!("n{i}")).unwrap();
+          doc.to_string()
+        })
+      })
+      .collect();
+    handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+  });
+  for (i, instance) in instances.iter().enumerate() {
+    assert_eq!(
+      *instance,
+      original.replace("</p:a></r>", &format!("</p:a><n{i}/></r>"))
+    );
+  }
+}
+
+#[test]
+/// Threads take turns using a shared document
+fn shared_documents() {
+  let parser = Parser::default();
+  let doc = parser.parse_string(r#"<counter value="0"/>"#).unwrap();
+  let shared = SharedDocument::new(&doc).unwrap();
+  drop(doc);
+  std::thread::scope(|scope| {
+    for _ in 0..4 {
+      let shared = shared.clone();
+      scope.spawn(move || {
+        for _ in 0..25 {
+          shared
+            .with(|doc| {
+              let mut counter = doc.get_root_element().unwrap();
+              let value: u32 = counter.get_attribute_as("value").unwrap();
+              counter.set_attribute("value", &(value + 1).to_string()).unwrap();
+              counter.new_child(None, "tick").unwrap();
+            })
+            .unwrap();
+        }
+      });
+    }
+  });
+  let copy = shared.to_document().unwrap();
+  let root = copy.get_root_element().unwrap();
+  assert_eq!(root.get_attribute("value").as_deref(), Some("100"));
+  assert_eq!(root.get_child_elements().len(), 100);
+
+  // Nodes kept are of the copy, which no longer changes the shared document
+  let mut kept = shared.with(|doc| doc.get_root_element()).unwrap().unwrap();
+  kept.set_attribute("value", "kept").unwrap();
+  let value = shared.with(|doc| doc.get_root_element().unwrap().get_attribute("value"));
+  assert_eq!(value.unwrap().as_deref(), Some("100"));
+
+  // A panic leaves the document as it was
+  let panicked = std::panic::catch_unwind(|| {
+    shared.with(|doc| {
+      doc.get_root_element().unwrap().set_attribute("value", "lost").unwrap();
+      panic!("dropped change");
+    })
+  });
+  assert!(panicked.is_err());
+  let value = shared.with(|doc| doc.get_root_element().unwrap().get_attribute("value"));
+  assert_eq!(value.unwrap().as_deref(), Some("100"));
+}
+
+
+#[test]
+/// Threads read a shared document at once, while another changes it
+fn shared_document_reads() {
+  let doc = Parser::default().parse_string(r#"<r count="0"/>"#).unwrap();
+  let shared = SharedDocument::new(&doc).unwrap();
+  std::thread::scope(|scope| {
+    for _ in 0..4 {
+      scope.spawn(|| {
+        for _ in 0..50 {
+          let read = shared.read();
+          let root = read.get_root_readonly().unwrap();
+          let count: usize = root.get_attribute("count").unwrap().parse().unwrap();
+          assert_eq!(read.findnodes("item", Some(root)).unwrap().len(), count);
+        }
+      });
+    }
+    scope.spawn(|| {
+      for count in 1..=20 {
+        shared
+          .with(|doc| {
+            let mut root = doc.get_root_element().unwrap();
+            root.new_child(None, "item").unwrap();
+            root.set_attribute("count", &count.to_string()).unwrap();
+          })
+          .unwrap();
+      }
+    });
+  });
+  let read = shared.read();
+  assert_eq!(read.findvalues("/r/@count", None), Ok(vec!["20".to_string()]));
+  assert_eq!(read.to_document().unwrap().get_root_element().unwrap().get_child_elements().len(), 20);
+}
+
 #[test]
 // Can parse an xml string in memory
 fn can_parse_xml_string() {
@@ -87,8 +239,117 @@ fn can_parse_xml_string() {
   assert_eq!(doc.get_root_element().unwrap().get_name(), "root");
 }
 
+#[test]
+/// Can parse bytes in any encoding
+fn can_parse_bytes() {
+  let parser = Parser::default();
+  // "café" in latin-1, declared
+  let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><a>caf\xe9</a>";
+  let doc = parser.parse_bytes(latin1, ParserOptions::default()).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "café");
+
+  // Undeclared, from the options
+  let options = ParserOptions {
+    encoding: Some("ISO-8859-1"),
+    ..ParserOptions::default()
+  };
+  let doc = parser.parse_bytes(b"<a>caf\xe9</a>", options).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "café");
+
+  // UTF-16 with a byte order mark
+  let utf16: Vec<u8> = "\u{feff}<a>café</a>"
+    .encode_utf16()
+    .flat_map(|unit| unit.to_le_bytes())
+    .collect();
+  let doc = parser.parse_bytes(&utf16, ParserOptions::default()).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "café");
+}
+
+#[test]
+/// The encoding given wins over a wrong declaration
+fn can_override_the_encoding() {
+  let parser = Parser::default();
+  let mislabeled = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><a>caf\xe9</a>";
+  let doc = parser.parse_bytes_with_encoding(mislabeled, "ISO-8859-1").unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "café");
+
+  let windows = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><a>\x80</a>";
+  let doc = parser.parse_bytes_with_encoding(windows, "windows-1252").unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "€");
+
+  assert!(matches!(
+    parser.parse_bytes_with_encoding(b"<a/>", "no-such-encoding"),
+    Err(libxml::parser::XmlParseError::UnknownEncoding(_))
+  ));
+}
+
+#[test]
+/// Can parse from a reader
+fn can_parse_from_reader() {
+  let parser = Parser::default();
+  let file = File::open("tests/resources/file01.xml").unwrap();
+  let doc = parser.parse_reader(file).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "root");
+
+  let doc = parser.parse_reader(&b"<a><b/></a>"[..]).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_child_elements().len(), 1);
+
+  // A reader failing midway fails the parse instead of truncating the document
+  let failing = (&b"<a><b/>"[..]).chain(FailingReader);
+  assert!(matches!(
+    parser.parse_reader(failing),
+    Err(libxml::parser::XmlParseError::ReadError(_))
+  ));
+}
+
+#[test]
+/// Can follow and cancel the parse
+fn can_cancel_parsing() {
+  use std::ops::ControlFlow;
+
+  let parser = Parser::default();
+  let xml = format!("<list>{}</list>", "<item>value</item>".repeat(10_000));
+  let mut reports = Vec::new();
+  let doc = parser
+    .parse_reader_with_progress(xml.as_bytes(), ParserOptions::default(), 50_000, |read| {
+      reports.push(read);
+      ControlFlow::Continue(())
+    })
+    .unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_child_elements().len(), 10_000);
+  assert_eq!(reports, [50_000, 100_000, 150_000]);
+
+  let mut calls = 0;
+  let result = parser.parse_reader_with_progress(xml.as_bytes(), ParserOptions::default(), 1000, |read| {
+    calls += 1;
+    if read >= 20_000 {
+      ControlFlow::Break(())
+    } else {
+      ControlFlow::Continue(())
+    }
+  });
+  assert!(matches!(result, Err(libxml::parser::XmlParseError::Cancelled)));
+  assert_eq!(calls, 20);
+
+  // Errors of the reader itself are still reported as such
+  let failing = (&b"<a><b/>"[..]).chain(FailingReader);
+  assert!(matches!(
+    parser.parse_reader_with_progress(failing, ParserOptions::default(), 4, |_| ControlFlow::Continue(())),
+    Err(libxml::parser::XmlParseError::ReadError(_))
+  ));
+}
+
+struct FailingReader;
+
+impl Read for FailingReader {
+  fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+    Err(std::io::Error::other("connection reset"))
+  }
+}
+
 #[test]
 /// Can load an HTML file
+#[cfg(feature = "html")]
 fn can_load_html_file() {
   let parser = Parser::default_html();
   {
@@ -111,7 +372,7 @@ fn create_test_document(file: Option<&str>) -> Document {
 #[test]
 fn document_can_import_node() {
   let doc1 = create_test_document(None);
-  let mut doc2 = create_test_document(None);
+  let doc2 = create_test_document(None);
 
   assert_eq!(
     doc2.get_root_element().unwrap().get_child_elements().len(),
@@ -119,9 +380,8 @@ fn document_can_import_node() {
   );
 
   let mut elements = doc1.get_root_element().unwrap().get_child_elements();
-  let mut node = elements.pop().unwrap();
-  node.unlink();
-  let mut imported = doc2.import_node(&mut node).unwrap();
+  let node = elements.pop().unwrap();
+  let mut imported = doc2.import_node(&node, true).unwrap();
   assert!(doc2
     .get_root_element()
     .unwrap()
@@ -152,6 +412,7 @@ fn document_formatted_serialization() {
 #[test]
 /// Test well-formedness of a Rust string
 /// IMPORTANT: Currenlty NOT THREAD-SAFE, use in single-threaded apps only!
+#[cfg(feature = "html")]
 fn well_formed_html() {
   let parser = Parser::default_html();
 
@@ -171,6 +432,7 @@ fn well_formed_html() {
 
 #[test]
 /// Parse & serialize HTML fragment
+#[cfg(feature = "html")]
 fn html_fragment() {
   let fragment = r#"<figure><a href="tar-flac-subset-compress.svg"><img src="tar-flac-subset-compress.svg" alt="Compression results on incompressible data."></a><figcaption><p>Compression results on incompressible data.</p></figcaption></figure>"#;
 
@@ -281,3 +543,166 @@ fn serialization_as_html() {
 
   assert_eq!(strip_whitespace(&result_file), strip_whitespace(&doc_str));
 }
+
+#[test]
+/// Recovering from malformed input reports the errors next to the document
+fn recover_with_errors() {
+  let parser = Parser::default();
+  let (doc, errors) = parser
+    .parse_string_recover("<feed><entry>one</entry><entry>two & three</feed>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_name(), "feed");
+  assert_eq!(root.get_child_elements().len(), 2);
+  assert!(!errors.is_empty());
+  assert!(errors.iter().all(|error| error.message.is_some()));
+
+  let (_, errors) = parser.parse_string_recover("<clean/>").unwrap();
+  assert!(errors.is_empty());
+  assert!(parser.parse_string_recover("").is_err());
+}
+
+#[test]
+/// Every problem libxml2 reports is kept with its location, and returned on failure
+fn parse_diagnostics() {
+  let parser = Parser::default();
+  let strict = ParserOptions {
+    recover: false,
+    ..ParserOptions::default()
+  };
+  let error = parser
+    .parse_string_with_options("<root>\n  <open>\n</root>", strict)
+    .unwrap_err();
+  assert!(matches!(error, XmlParseError::Failed(_)));
+  let diagnostics = error.diagnostics();
+  assert!(!diagnostics.is_empty());
+  let first = &diagnostics[0];
+  assert_eq!(first.level, XmlErrorLevel::Fatal);
+  assert_eq!(first.line, Some(3));
+  assert!(first.col.is_some());
+  assert!(first.message.as_deref().unwrap().contains("open"));
+  assert_eq!(parser.last_diagnostics().len(), diagnostics.len());
+
+  // Recovered problems are only in the diagnostics of the parser
+  let doc = parser.parse_string("<feed><entry>two & three</entry></feed>").unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "feed");
+  let diagnostics = parser.last_diagnostics();
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0].line, Some(1));
+  assert!(diagnostics[0].code != 0);
+
+  parser.parse_reader(&b"<clean/>"[..]).unwrap();
+  assert!(parser.last_diagnostics().is_empty());
+  assert!(parser.parse_reader(&b"<a><b></a>"[..]).is_ok());
+  assert!(!parser.last_diagnostics().is_empty());
+}
+
+#[test]
+/// Warnings are kept apart from errors, also when the parse succeeds
+fn parse_warnings() {
+  let parser = Parser::default();
+  let doc = parser.parse_string(r#"<root xmlns="relative/uri"><a/></root>"#).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "root");
+  let warnings = parser.last_warnings();
+  assert_eq!(warnings.len(), 1);
+  assert_eq!(warnings[0].level, XmlErrorLevel::Warning);
+  assert!(warnings[0].message.as_deref().unwrap().contains("relative/uri"));
+  assert!(parser.last_diagnostics().is_empty());
+
+  parser
+    .parse_reader(&br#"<root xmlns="relative/uri"><a></b></root>"#[..])
+    .unwrap();
+  assert_eq!(parser.last_warnings().len(), 1);
+  assert!(!parser.last_diagnostics().is_empty());
+  assert!(
+    parser
+      .last_diagnostics()
+      .iter()
+      .all(|error| error.level != XmlErrorLevel::Warning)
+  );
+
+  parser.parse_string("<clean/>").unwrap();
+  assert!(parser.last_warnings().is_empty());
+}
+
+#[test]
+/// Parser contexts reused between documents don't carry options over
+fn reused_parser_contexts() {
+  let parser = Parser::default();
+  let no_blanks = || ParserOptions {
+    no_blanks: true,
+    ..ParserOptions::default()
+  };
+  let strict = || ParserOptions {
+    recover: false,
+    ..ParserOptions::default()
+  };
+  for i in 0..2000 {
+    let xml = format!("<r{i}>\n  <a/>\n</r{i}>");
+    let doc = parser.parse_string_with_options(&xml, no_blanks()).unwrap();
+    assert_eq!(doc.get_root_element().unwrap().get_child_nodes().len(), 1);
+    let doc = parser.parse_string(&xml).unwrap();
+    let root = doc.get_root_element().unwrap();
+    assert_eq!(root.get_name(), format!("r{i}"));
+    assert_eq!(root.get_child_nodes().len(), 3);
+    assert!(parser.parse_string_with_options("<r><open></r>", strict()).is_err());
+  }
+
+  // Documents outlive the contexts they were parsed with
+  let docs: Vec<_> = (0..3000)
+    .map(|i| parser.parse_string(format!("<doc n=\"{i}\"/>")).unwrap())
+    .collect();
+  assert_eq!(docs[5].get_root_element().unwrap().get_attribute("n").as_deref(), Some("5"));
+  let handles: Vec<_> = (0..4)
+    .map(|_| std::thread::spawn(|| Parser::default().parse_string("<a><b/></a>").unwrap().to_string()))
+    .collect();
+  for handle in handles {
+    assert!(handle.join().unwrap().contains("<a><b/></a>"));
+  }
+}
+
+#[test]
+/// The huge option lifts the size and depth limits of libxml2
+fn huge_documents() {
+  let strict = |huge| ParserOptions {
+    recover: false,
+    huge,
+    ..ParserOptions::default()
+  };
+  let parser = Parser::default();
+
+  let value = "a".repeat(11 * 1024 * 1024);
+  let large = format!("<r a=\"{value}\"/>");
+  assert!(parser.parse_string_with_options(&large, strict(false)).is_err());
+  let doc = parser.parse_string_with_options(&large, strict(true)).unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_attribute("a").unwrap().len(), value.len());
+
+  let deep = format!("{}{}", "<a>".repeat(300), "</a>".repeat(300));
+  assert!(parser.parse_string_with_options(&deep, strict(false)).is_err());
+  assert!(parser.parse_string_with_options(&deep, strict(true)).is_ok());
+}
+
+#[test]
+/// Elements are found by their xml:id or DTD-declared ID
+fn elements_by_id() {
+  let parser = Parser::default();
+  let doc = parser.parse_file("tests/resources/ids.xml").unwrap();
+  let end = doc.get_element_by_id("end").unwrap();
+  assert_eq!(end.get_content(), "World!");
+  assert_eq!(doc.get_element_by_id_readonly("start").unwrap().get_content(), "Hello");
+  assert!(doc.get_element_by_id("nowhere").is_none());
+
+  let doc = parser
+    .parse_string(r#"<!DOCTYPE r [<!ATTLIST item key ID #IMPLIED>]><r><item key="a"/><item key="b"/></r>"#)
+    .unwrap();
+  let mut b = doc.get_element_by_id("b").unwrap();
+  assert_eq!(b.get_attribute("key").as_deref(), Some("b"));
+  let mut c = Node::new("p", None, &doc).unwrap();
+  c.set_attribute("xml:id", "c").unwrap();
+  doc.get_root_element().unwrap().add_child(&mut c).unwrap();
+  assert_eq!(doc.get_element_by_id("c"), Some(c));
+
+  b.unlink();
+  assert!(doc.get_element_by_id("b").is_none());
+}
@@ -0,0 +1,64 @@
+//! Tests for programmatic catalog management
+//!
+use libxml::catalog::{Catalog, CatalogEntry};
+
+#[test]
+fn resolve_added_entries() {
+  let mut catalog = Catalog::new().unwrap();
+  assert!(catalog.is_empty());
+  catalog
+    .add_public("-//Example//DTD Doc//EN", "file:///dtds/doc.dtd")
+    .unwrap();
+  catalog
+    .add_system("http://example.com/doc.dtd", "file:///dtds/doc.dtd")
+    .unwrap();
+  catalog
+    .add_rewrite_system("http://example.com/schemas/", "file:///schemas/")
+    .unwrap();
+  catalog
+    .add(CatalogEntry::Uri, "urn:example:style", "file:///style.xsl")
+    .unwrap();
+  assert!(!catalog.is_empty());
+
+  assert_eq!(
+    catalog.resolve_public("-//Example//DTD Doc//EN").as_deref(),
+    Some("file:///dtds/doc.dtd")
+  );
+  assert_eq!(
+    catalog.resolve_system("http://example.com/doc.dtd").as_deref(),
+    Some("file:///dtds/doc.dtd")
+  );
+  assert_eq!(
+    catalog
+      .resolve(None, Some("http://example.com/schemas/a/b.xsd"))
+      .as_deref(),
+    Some("file:///schemas/a/b.xsd")
+  );
+  assert_eq!(
+    catalog.resolve_uri("urn:example:style").as_deref(),
+    Some("file:///style.xsl")
+  );
+  assert_eq!(catalog.resolve_system("http://elsewhere.org/x.dtd"), None);
+}
+
+#[test]
+fn remove_entries() {
+  let mut catalog = Catalog::new().unwrap();
+  catalog.add_system("http://example.com/a.dtd", "a.dtd").unwrap();
+  assert!(catalog.resolve_system("http://example.com/a.dtd").is_some());
+  catalog.remove("http://example.com/a.dtd").unwrap();
+  assert_eq!(catalog.resolve_system("http://example.com/a.dtd"), None);
+}
+
+#[test]
+fn serialize_catalog() {
+  let mut catalog = Catalog::new().unwrap();
+  catalog.add_system("http://example.com/a.dtd", "a.dtd").unwrap();
+  catalog
+    .add_rewrite_uri("http://example.com/", "file:///local/")
+    .unwrap();
+  let xml = catalog.to_xml().unwrap();
+  assert!(xml.contains("urn:oasis:names:tc:entity:xmlns:xml:catalog"));
+  assert!(xml.contains(r#"<system systemId="http://example.com/a.dtd" uri="a.dtd"/>"#));
+  assert!(xml.contains(r#"<rewriteURI uriStartString="http://example.com/" rewritePrefix="file:///local/"/>"#));
+}
@@ -86,3 +86,33 @@ fn utf16be_bom_test() {
 fn nonbmp_path_test() {
   run_test("tests/resources/file01_🔥🔥🔥.xml");
 }
+
+#[test]
+fn path_types_test() {
+  let parser = Parser::default();
+  file01_test(parser.parse_file(std::path::Path::new("tests/resources/file01_🔥🔥🔥.xml")));
+  file01_test(parser.parse_file(std::path::PathBuf::from("tests/resources/file01.xml")));
+  file01_test(parser.parse_file(String::from("tests/resources/file01.xml")));
+}
+
+#[test]
+#[cfg(unix)]
+fn non_utf8_path_test() {
+  use std::ffi::OsStr;
+  use std::os::unix::ffi::OsStrExt;
+
+  let dir = std::env::temp_dir();
+  let path = dir.join(OsStr::from_bytes(b"rust_libxml_file01_\xff.xml"));
+  fs::copy("tests/resources/file01.xml", &path).unwrap();
+  file01_test(Parser::default().parse_file(&path));
+  fs::remove_file(&path).unwrap();
+
+  // Compressed files are opened by libxml2 itself
+  if libxml::parser::Compression::Xz.is_available() {
+    let path = dir.join(OsStr::from_bytes(b"rust_libxml_file01_\xff.xml.xz"));
+    fs::copy("tests/resources/file01.xml.xz", &path).unwrap();
+    let doc = Parser::default().parse_file(&path).unwrap();
+    assert_eq!(doc.get_root_element().unwrap().get_name(), "root");
+    fs::remove_file(&path).unwrap();
+  }
+}
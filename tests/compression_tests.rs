@@ -0,0 +1,90 @@
+//! Tests for compressed document I/O
+//!
+use std::env;
+use std::fs;
+
+use libxml::parser::{Compression, Parser};
+
+#[test]
+fn compression_is_recognized() {
+  assert_eq!(Compression::detect(&[0x1f, 0x8b, 8, 0]), Some(Compression::Gzip));
+  assert_eq!(
+    Compression::detect(&fs::read("tests/resources/file01.xml.xz").unwrap()),
+    Some(Compression::Xz)
+  );
+  assert_eq!(Compression::detect(b"<?xml version=\"1.0\"?>"), None);
+  assert_eq!(Compression::from_path("feed.xml.gz"), Some(Compression::Gzip));
+  assert_eq!(Compression::from_path("dump.xz"), Some(Compression::Xz));
+  assert_eq!(Compression::from_path("plain.xml"), None);
+}
+
+#[test]
+#[cfg(feature = "zlib")]
+fn gzip_round_trip() {
+  let parser = Parser::default();
+  let doc = parser.parse_file("tests/resources/file01.xml").unwrap();
+  let path = env::temp_dir().join("rust_libxml_tests_compressed.xml.gz");
+  let path = path.to_str().unwrap();
+  assert!(doc.save_file(path).is_ok());
+  assert_eq!(Compression::detect(&fs::read(path).unwrap()), Some(Compression::Gzip));
+
+  // Saving under a plain name is not compressed, even for a document read from gzip
+  let reloaded = parser.parse_file(path).unwrap();
+  assert_eq!(reloaded.to_string(), doc.to_string());
+  let plain = env::temp_dir().join("rust_libxml_tests_decompressed.xml");
+  let plain = plain.to_str().unwrap();
+  assert!(reloaded.save_file(plain).is_ok());
+  assert_eq!(Compression::detect(&fs::read(plain).unwrap()), None);
+}
+
+#[test]
+#[cfg(feature = "zlib")]
+fn decompression_can_be_forbidden() {
+  let parser = Parser::default();
+  let doc = parser.parse_file("tests/resources/file01.xml").unwrap();
+  let path = env::temp_dir().join("rust_libxml_tests_forbidden.xml.gz");
+  assert!(doc.save_file(path.to_str().unwrap()).is_ok());
+
+  let raw = libxml::parser::ParserOptions {
+    decompress: false,
+    recover: false,
+    ..Default::default()
+  };
+  assert!(parser.parse_file_with_options(&path, raw).is_err());
+  let forced = parser.parse_compressed_file_with_options(&path, raw).unwrap();
+  assert_eq!(forced.to_string(), doc.to_string());
+  // Plain files are read either way
+  assert!(parser.parse_compressed_file("tests/resources/file01.xml").is_ok());
+}
+
+#[test]
+#[cfg(feature = "lzma")]
+fn parses_xz_files() {
+  let doc = Parser::default().parse_file("tests/resources/file01.xml.xz").unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_name(), "root");
+  assert_eq!(root.get_child_elements().len(), 2);
+  assert!(doc.save_file(env::temp_dir().join("rust_libxml_tests.xml.xz").to_str().unwrap()).is_err());
+}
+
+#[test]
+#[cfg(not(feature = "zlib"))]
+fn gzip_needs_the_feature() {
+  assert!(!Compression::Gzip.is_available());
+  let path = env::temp_dir().join("rust_libxml_tests_unsupported.xml.gz");
+  fs::write(&path, [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3]).unwrap();
+  let path = path.to_str().unwrap();
+  let error = Parser::default().parse_file(path).unwrap_err();
+  assert!(matches!(
+    error,
+    libxml::parser::XmlParseError::CompressionUnavailable(Compression::Gzip)
+  ));
+  assert!(error.to_string().contains("`zlib` feature"));
+  assert!(matches!(
+    Parser::default().parse_compressed_file(path),
+    Err(libxml::parser::XmlParseError::CompressionUnavailable(Compression::Gzip))
+  ));
+
+  let doc = Parser::default().parse_string("<root/>").unwrap();
+  assert!(doc.save_file(path).is_err());
+}
@@ -0,0 +1,121 @@
+//! Tests for the structural diff
+//!
+
+use libxml::diff::{diff, DiffOptions, Edit};
+use libxml::parser::Parser;
+
+fn parse(input: &str) -> libxml::tree::Document {
+  Parser::default().parse_string(input).unwrap()
+}
+
+#[test]
+fn identical_documents_have_empty_script() {
+  let old = parse("<config><a x='1'/><b>text</b></config>");
+  let new = parse("<config><a x='1'/><b>text</b></config>");
+  assert!(diff(&old, &new, &DiffOptions::default()).is_empty());
+}
+
+#[test]
+fn text_and_attribute_updates() {
+  let old = parse("<config><server port='80' host='a'>old</server></config>");
+  let new = parse("<config><server port='8080' tls='on' host='a'>new</server></config>");
+  let edits = diff(&old, &new, &DiffOptions { ignore_attribute_order: true, ..Default::default() });
+  assert_eq!(
+    edits,
+    vec![
+      Edit::UpdateAttribute {
+        path: "/config/server".into(),
+        name: "port".into(),
+        old: Some("80".into()),
+        new: Some("8080".into()),
+      },
+      Edit::UpdateAttribute {
+        path: "/config/server".into(),
+        name: "tls".into(),
+        old: None,
+        new: Some("on".into()),
+      },
+      Edit::UpdateText {
+        path: "/config/server/text()".into(),
+        old: "old".into(),
+        new: "new".into(),
+      },
+    ]
+  );
+}
+
+#[test]
+fn inserts_deletes_and_moves() {
+  let old = parse("<r><a><keep id='1'/></a><b/><gone/></r>");
+  let new = parse("<r><a/><b><keep id='1'/></b><added/></r>");
+  let edits = diff(&old, &new, &DiffOptions::default());
+  assert!(edits.contains(&Edit::Move {
+    from: "/r/a/keep".into(),
+    to_parent: "/r/b".into(),
+    position: 0,
+  }));
+  assert!(edits.contains(&Edit::Delete { path: "/r/gone".into() }));
+  assert!(edits.contains(&Edit::Insert {
+    parent: "/r".into(),
+    position: 2,
+    xml: "<added/>".into(),
+  }));
+  assert_eq!(edits.len(), 3);
+}
+
+#[test]
+fn options_ignore_whitespace_comments_and_attribute_order() {
+  let old = parse("<r b='2' a='1'>\n  <x>some   text</x>\n  <!-- note -->\n</r>");
+  let new = parse("<r a='1' b='2'><x>some text</x></r>");
+  let strict = diff(&old, &new, &DiffOptions::default());
+  assert!(strict.iter().any(|e| matches!(e, Edit::ReorderAttributes { .. })));
+  assert!(strict.iter().any(|e| matches!(e, Edit::Delete { .. })));
+
+  let relaxed = DiffOptions {
+    ignore_whitespace: true,
+    ignore_comments: true,
+    ignore_attribute_order: true,
+  };
+  assert!(diff(&old, &new, &relaxed).is_empty());
+}
+
+#[test]
+fn root_rename_is_reported() {
+  let edits = diff(&parse("<a/>"), &parse("<b/>"), &DiffOptions::default());
+  assert_eq!(
+    edits,
+    vec![Edit::Rename { path: "/a".into(), old: "a".into(), new: "b".into() }]
+  );
+}
+
+#[test]
+fn namespaced_attributes_match_by_uri() {
+  let old = parse("<r xmlns:a='urn:x' a:k='1' a:v='old'/>");
+  let new = parse("<r xmlns:b='urn:x' b:k='1' b:v='new'/>");
+  assert_eq!(
+    diff(&old, &new, &DiffOptions::default()),
+    vec![Edit::UpdateAttribute {
+      path: "/r".into(),
+      name: "a:v".into(),
+      old: Some("old".into()),
+      new: Some("new".into()),
+    }]
+  );
+  // Another namespace is another attribute, whatever the prefix
+  let other = parse("<r xmlns:a='urn:y' a:k='1' a:v='old'/>");
+  assert_eq!(diff(&old, &other, &DiffOptions::default()).len(), 4);
+}
+
+#[test]
+fn long_child_lists() {
+  let old: String = (0..3000).map(|k| format!("<a{k}/>")).collect();
+  let new: String = (0..3000)
+    .filter(|k| k % 10 != 0)
+    .map(|k| if k % 100 == 1 { format!("<b{k}/><a{k}/>") } else { format!("<a{k}/>") })
+    .collect();
+  let edits = diff(&parse(&format!("<r>{old}</r>")), &parse(&format!("<r>{new}</r>")), &DiffOptions::default());
+  let deleted = edits.iter().filter(|e| matches!(e, Edit::Delete { .. })).count();
+  let inserted = edits.iter().filter(|e| matches!(e, Edit::Insert { .. })).count();
+  assert_eq!((deleted, inserted, edits.len()), (300, 30, 330));
+  assert!(edits.contains(&Edit::Delete { path: "/r/a2990".into() }));
+}
@@ -0,0 +1,79 @@
+//! Tests for the DOM-style facade
+//!
+use libxml::dom::{DomDocument, DomNode};
+
+#[test]
+fn build_document_with_dom_names() {
+  let doc = DomDocument::create_document(Some("urn:books"), "b:library").unwrap();
+  let mut root = doc.document_element().unwrap();
+  assert_eq!(root.tag_name(), "b:library");
+  assert_eq!(root.local_name().as_deref(), Some("library"));
+  assert_eq!(root.namespace_uri().as_deref(), Some("urn:books"));
+  assert_eq!(root.prefix().as_deref(), Some("b"));
+
+  let mut book = doc.create_element_ns(Some("urn:books"), "book").unwrap();
+  let mut title = doc.create_element("title").unwrap();
+  let mut text = doc.create_text_node("Dune").unwrap();
+  title.append_child(&mut text).unwrap();
+  book.append_child(&mut title).unwrap();
+  root.append_child(&mut book).unwrap();
+  let mut comment = doc.create_comment("first").unwrap();
+  root.insert_before(&mut comment, Some(&mut book)).unwrap();
+
+  assert_eq!(
+    doc.save_xml(Some(&root)),
+    r#"<b:library xmlns:b="urn:books"><!--first--><book xmlns="urn:books"><title>Dune</title></book></b:library>"#
+  );
+  assert_eq!(comment.node_name(), "#comment");
+  assert_eq!(comment.node_value().as_deref(), Some("first"));
+  assert_eq!(book.node_value(), None);
+  assert!(root.has_child_nodes());
+}
+
+#[test]
+fn elements_by_tag_name() {
+  let doc = DomDocument::load_xml(
+    r#"<a xmlns:x="urn:x"><b/><x:b/><c><b id="3"/></c></a>"#,
+  )
+  .unwrap();
+  assert_eq!(doc.get_elements_by_tag_name("b").len(), 2);
+  assert_eq!(doc.get_elements_by_tag_name("x:b").len(), 1);
+  assert_eq!(doc.get_elements_by_tag_name("*").len(), 5);
+  assert_eq!(doc.get_elements_by_tag_name_ns("urn:x", "*").len(), 1);
+  assert_eq!(doc.get_elements_by_tag_name_ns("", "b").len(), 2);
+  assert_eq!(doc.get_elements_by_tag_name_ns("*", "b").len(), 3);
+
+  let c = doc.get_elements_by_tag_name("c").pop().unwrap();
+  let nested = c.get_elements_by_tag_name("*");
+  assert_eq!(nested.len(), 1);
+  assert!(nested[0].has_attributes());
+}
+
+#[test]
+fn import_clone_and_text_content() {
+  let source = DomDocument::load_xml("<src><item k='v'>text</item></src>").unwrap();
+  let item = source.document_element().unwrap().get_first_child().unwrap();
+
+  let target = DomDocument::load_xml("<dst/>").unwrap();
+  let mut root = target.document_element().unwrap();
+  let mut deep = target.import_node(&item, true).unwrap();
+  let mut shallow = target.import_node(&item, false).unwrap();
+  root.append_child(&mut deep).unwrap();
+  root.append_child(&mut shallow).unwrap();
+  assert_eq!(
+    target.save_xml(None).lines().last().unwrap(),
+    r#"<dst><item k="v">text</item><item k="v"/></dst>"#
+  );
+  // the source document is untouched
+  assert_eq!(item.text_content(), "text");
+
+  let mut copy = deep.clone_node(true).unwrap();
+  copy.set_text_content("a < b").unwrap();
+  root.append_child(&mut copy).unwrap();
+  root.remove_child(&mut shallow).unwrap();
+  assert_eq!(
+    target.save_xml(Some(&root)),
+    r#"<dst><item k="v">text</item><item k="v">a &lt; b</item></dst>"#
+  );
+  assert!(!root.is_same_node(&copy));
+}
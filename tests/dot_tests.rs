@@ -0,0 +1,47 @@
+//! Tests for the GraphViz DOT export
+//!
+use libxml::parser::Parser;
+use libxml::tree::DotOptions;
+
+const FEED: &str = "<feed><!-- v2 --><entry id=\"e1\" type=\"a \\&quot;b\\&quot;\"><title>Hello</title></entry><entry/></feed>";
+
+#[test]
+fn elements_only_by_default() {
+  let doc = Parser::default().parse_string(FEED).unwrap();
+  assert_eq!(
+    doc.to_dot(&DotOptions::default()),
+    "digraph document {
+  node [shape=box, fontname=\"monospace\"];
+  n0 [label=\"feed\"];
+  n1 [label=\"entry\"];
+  n0 -> n1;
+  n2 [label=\"title\"];
+  n1 -> n2;
+  n3 [label=\"entry\"];
+  n0 -> n3;
+}
+"
+  );
+}
+
+#[test]
+fn attributes_text_and_depth() {
+  let doc = Parser::default().parse_string(FEED).unwrap();
+  let dot = doc.to_dot(&DotOptions {
+    attributes: true,
+    text: true,
+    comments: true,
+    max_depth: Some(1),
+    ..DotOptions::default()
+  });
+  assert!(dot.contains("n1 [label=\"<!-- v2 -->\", shape=note];"));
+  assert!(dot.contains("n2 [label=\"entry\\nid=e1\\ntype=a \\\\\\\"b\\\\\\\"\"];"));
+  // The title is below the depth limit
+  assert!(!dot.contains("title"));
+
+  let dot = doc.to_dot(&DotOptions {
+    text: true,
+    ..DotOptions::default()
+  });
+  assert!(dot.contains("n3 [label=\"\\\"Hello\\\"\", shape=plaintext];\n  n2 -> n3;"));
+}
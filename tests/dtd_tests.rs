@@ -0,0 +1,200 @@
+//! Tests for DTD loading and validation while parsing
+//!
+use libxml::parser::{Parser, ParserOptions, XmlParseError};
+use libxml::tree::{Document, EntityKind, Node};
+
+const NOTE_DTD: &str = r#"
+<!ELEMENT note (to, body)>
+<!ATTLIST note priority (low|high) "low">
+<!ELEMENT to (#PCDATA)>
+<!ELEMENT body (#PCDATA)>
+"#;
+
+#[test]
+fn loads_external_subsets() {
+  let xml = format!(
+    "<!DOCTYPE note SYSTEM \"{}/tests/resources/note.dtd\"><note><to>Tove</to><body>Hi</body></note>",
+    env!("CARGO_MANIFEST_DIR")
+  );
+  let parser = Parser::default();
+  let doc = parser.parse_string(&xml).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_attribute("priority"), None);
+
+  let options = ParserOptions {
+    dtd_load: true,
+    dtd_attr: true,
+    ..ParserOptions::default()
+  };
+  let doc = parser.parse_string_with_options(&xml, options).unwrap();
+  assert_eq!(
+    doc.get_root_element().unwrap().get_attribute("priority").as_deref(),
+    Some("low")
+  );
+
+  let (_, errors) = parser
+    .parse_string_validating(xml.replace("<to>Tove</to>", ""), None, ParserOptions::default())
+    .unwrap();
+  assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn validates_against_the_doctype() {
+  let parser = Parser::default();
+  let valid = format!("<!DOCTYPE note [{NOTE_DTD}]><note><to>Tove</to><body>Hi</body></note>");
+  let (doc, errors) = parser
+    .parse_string_validating(&valid, None, ParserOptions::default())
+    .unwrap();
+  assert!(errors.is_empty());
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "note");
+
+  let invalid = format!("<!DOCTYPE note [{NOTE_DTD}]><note priority=\"urgent\"><body>Hi</body></note>");
+  let (doc, errors) = parser
+    .parse_string_validating(&invalid, None, ParserOptions::default())
+    .unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "note");
+  assert_eq!(errors.len(), 2);
+  assert!(errors.iter().all(|error| error.message.is_some()));
+}
+
+#[test]
+fn validates_against_a_given_dtd() {
+  let parser = Parser::default();
+  let (_, errors) = parser
+    .parse_string_validating(
+      "<note><to>Tove</to><body>Hi</body></note>",
+      Some(NOTE_DTD),
+      ParserOptions::default(),
+    )
+    .unwrap();
+  assert!(errors.is_empty());
+
+  let (_, errors) = parser
+    .parse_string_validating("<note><body>Hi</body><to/></note>", Some(NOTE_DTD), ParserOptions::default())
+    .unwrap();
+  assert_eq!(errors.len(), 1);
+
+  assert!(matches!(
+    parser.parse_string_validating("<note/>", Some("<!ELEMENT note"), ParserOptions::default()),
+    Err(XmlParseError::Malformed(_))
+  ));
+
+  let options = ParserOptions {
+    recover: false,
+    ..ParserOptions::default()
+  };
+  assert!(matches!(
+    parser.parse_string_validating("<note><to></note>", Some(NOTE_DTD), options),
+    Err(XmlParseError::Malformed(_))
+  ));
+}
+
+#[test]
+fn inspects_subsets() {
+  let system_id = format!("{}/tests/resources/note.dtd", env!("CARGO_MANIFEST_DIR"));
+  let xml = format!(
+    r#"<!DOCTYPE note PUBLIC "-//Example//Note//EN" "{system_id}" [
+  <!ENTITY % extra "<!ELEMENT extra EMPTY>">
+  <!ENTITY sig "Jani &amp; co">
+  <!ENTITY logo SYSTEM "logo.gif" NDATA gif>
+  <!ENTITY chapter PUBLIC "-//Example//Chapter//EN" "chapter.xml">
+  <!NOTATION gif SYSTEM "image/gif">
+]><note><to>Tove</to><body>&sig;</body></note>"#
+  );
+  let parser = Parser::default();
+  let doc = parser.parse_string(&xml).unwrap();
+  assert!(doc.get_external_subset().is_none());
+  let dtd = doc.get_internal_subset().unwrap();
+  assert_eq!(dtd.name(), "note");
+  assert_eq!(dtd.public_id().as_deref(), Some("-//Example//Note//EN"));
+  assert_eq!(dtd.system_id().as_deref(), Some(system_id.as_str()));
+
+  let entities: Vec<_> = dtd.entities().collect();
+  let kinds: Vec<_> = entities
+    .iter()
+    .map(|entity| (entity.name.as_str(), entity.kind))
+    .collect();
+  assert_eq!(
+    kinds,
+    [
+      ("extra", EntityKind::InternalParameter),
+      ("sig", EntityKind::InternalGeneral),
+      ("logo", EntityKind::ExternalUnparsed),
+      ("chapter", EntityKind::ExternalParsed),
+    ]
+  );
+  assert_eq!(entities[1].value.as_deref(), Some("Jani &amp; co"));
+  assert_eq!(entities[2].system_id.as_deref(), Some("logo.gif"));
+  assert_eq!(entities[3].public_id.as_deref(), Some("-//Example//Chapter//EN"));
+  assert_eq!(entities[3].value, None);
+  assert_eq!(dtd.get_entity("sig"), Some(entities[1].clone()));
+  assert_eq!(dtd.get_entity("extra"), None);
+
+  let options = ParserOptions {
+    dtd_load: true,
+    ..ParserOptions::default()
+  };
+  let doc = parser.parse_string_with_options(&xml, options).unwrap();
+  let external = doc.get_external_subset().unwrap();
+  assert_eq!(external.name(), "note");
+  assert_eq!(external.entities().count(), 0);
+}
+
+#[test]
+fn declares_doctype() {
+  let mut doc = Parser::default()
+    .parse_string(r#"<!DOCTYPE html [<!ENTITY e "x">]><html/>"#)
+    .unwrap();
+  let old = doc.get_doctype().unwrap();
+  let dtd = doc
+    .set_internal_subset(
+      "html",
+      Some("-//W3C//DTD XHTML 1.0 Strict//EN"),
+      Some("http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"),
+    )
+    .unwrap();
+  assert_eq!(dtd.public_id().as_deref(), Some("-//W3C//DTD XHTML 1.0 Strict//EN"));
+  // The replaced subset stays readable
+  assert_eq!(old.get_entity("e").unwrap().value.as_deref(), Some("x"));
+  assert!(doc.get_doctype().unwrap().get_entity("e").is_none());
+  assert!(doc.to_string().contains(
+    r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd">"#
+  ));
+
+  doc.set_internal_subset("html", None, None).unwrap();
+  assert!(doc.to_string().contains("<!DOCTYPE html>\n<html/>"));
+  assert!(doc.set_internal_subset("html", Some("-//X//EN"), None).is_err());
+
+  let mut generated = Document::new().unwrap();
+  let root = Node::new("note", None, &generated).unwrap();
+  generated.set_root_element(&root);
+  generated.set_internal_subset("note", None, Some("note.dtd")).unwrap();
+  assert_eq!(
+    generated.to_string(),
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE note SYSTEM \"note.dtd\">\n<note/>\n"
+  );
+}
+
+#[test]
+fn declares_entities() {
+  let mut doc = Parser::default().parse_string("<letter/>").unwrap();
+  let entity = doc.add_entity("sig", "<b>Jani</b> &amp; co").unwrap();
+  assert_eq!(entity.kind, EntityKind::InternalGeneral);
+  assert_eq!(doc.get_doctype().unwrap().name(), "letter");
+  assert_eq!(doc.get_entity("sig"), Some(entity));
+  assert_eq!(doc.get_entity("amp").map(|e| e.kind), Some(EntityKind::Predefined));
+  assert!(doc.get_entity("missing").is_none());
+  assert!(doc.add_entity("sig", "again").is_err());
+
+  let mut root = doc.get_root_element().unwrap();
+  let mut reference = Node::new_entity_ref("sig", &doc).unwrap();
+  root.add_child(&mut reference).unwrap();
+  assert_eq!(reference.get_referenced_entity().unwrap().name, "sig");
+  assert_eq!(root.get_content(), "Jani & co");
+  assert!(doc.to_string().contains("<letter>&sig;</letter>"));
+  assert!(root.get_referenced_entity().is_none());
+
+  let empty = Document::new().unwrap();
+  assert!(empty.clone().add_entity("e", "x").is_err());
+  assert!(doc.add_entity("broken", "<b>").is_err());
+  assert!(doc.get_entity("broken").is_none());
+}
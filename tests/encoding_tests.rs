@@ -0,0 +1,81 @@
+//! Tests for the `encoding_rs` based transcoding
+//!
+#![cfg(feature = "encoding_rs")]
+
+use encoding_rs::{SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+use libxml::encoding::{decode, detect};
+use libxml::parser::Parser;
+use libxml::tree::SaveOptions;
+
+#[test]
+fn detects_declared_encodings() {
+  assert_eq!(
+    detect(b"<?xml version=\"1.0\" encoding='Shift_JIS'?><a/>"),
+    Some(SHIFT_JIS)
+  );
+  assert_eq!(
+    detect(b"<html><head><meta charset=\"windows-1252\"></head></html>"),
+    Some(WINDOWS_1252)
+  );
+  assert_eq!(
+    detect(b"<html><head><META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=ISO-8859-1\"></head></html>"),
+    Some(WINDOWS_1252)
+  );
+  assert_eq!(detect(b"\xFF\xFE<\0a\0/\0>\0"), Some(UTF_16LE));
+  assert_eq!(detect(b"<a/>"), None);
+
+  let (text, encoding) = decode(b"<a>caf\xE9</a>");
+  assert_eq!(encoding, UTF_8);
+  assert_eq!(text, "<a>caf\u{FFFD}</a>");
+}
+
+#[test]
+#[cfg(feature = "html")]
+fn parses_legacy_encodings() {
+  let mut input = b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?><greeting>".to_vec();
+  input.extend_from_slice(&SHIFT_JIS.encode("こんにちは").0);
+  input.extend_from_slice(b"</greeting>");
+
+  let doc = Parser::default().parse_string_transcoded(&input).unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_content(), "こんにちは");
+
+  let html = b"<html><head><meta charset=\"windows-1252\"></head><body><p>\x93quoted\x94</p></body></html>";
+  let doc = Parser::default_html().parse_string_transcoded(html).unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert!(root.get_content().contains("\u{201C}quoted\u{201D}"));
+}
+
+#[test]
+fn labels_end_before_non_ascii_characters() {
+  // A character straddling the end of the sniffed head, right after the label
+  let mut input = String::from("<?xml version=\"1.0\" encoding=");
+  input.push_str(&"a".repeat(1023 - input.len()));
+  input.push_str("é?><r/>");
+  let doc = Parser::default().parse_string_transcoded(&input).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "r");
+  assert_eq!(detect(input.as_bytes()), None);
+
+  let html = "<html><head><meta charset=\"windows-1252é\"></head><body>x</body></html>";
+  let doc = Parser::default_html().parse_string_transcoded(html).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "x");
+}
+
+#[test]
+fn serializes_to_legacy_encodings() {
+  let doc = Parser::default()
+    .parse_string("<?xml version=\"1.0\"?><text>na\u{EF}ve \u{2603}</text>")
+    .unwrap();
+  let bytes = doc.to_encoded_bytes(WINDOWS_1252, SaveOptions::default());
+  let (text, _, _) = WINDOWS_1252.decode(&bytes);
+  assert!(text.starts_with("<?xml version=\"1.0\" encoding=\"windows-1252\"?>"));
+  assert!(bytes.windows(5).any(|w| w == b"na\xEFve"));
+  // The snowman has no windows-1252 representation
+  assert!(text.contains("&#9731;"));
+
+  let reparsed = Parser::default().parse_string_transcoded(&bytes).unwrap();
+  assert_eq!(
+    reparsed.get_root_element().unwrap().get_content(),
+    "na\u{EF}ve \u{2603}"
+  );
+}
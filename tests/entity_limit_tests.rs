@@ -0,0 +1,124 @@
+//! Tests for the entity expansion limits of the parser
+//!
+use std::env;
+use std::fs;
+
+use libxml::parser::{Parser, ParserOptions, XmlParseError};
+
+/// The first levels of the "billion laughs", few enough to pass libxml2's own checks
+fn laughs(levels: usize) -> String {
+  let mut doctype = String::from("<!DOCTYPE lolz [\n<!ENTITY lol0 \"lol\">\n");
+  for level in 1..levels {
+    doctype.push_str(&format!(
+      "<!ENTITY lol{level} \"{}\">\n",
+      format!("&lol{};", level - 1).repeat(4)
+    ));
+  }
+  format!("{doctype}]>\n<lolz>&lol{};</lolz>", levels - 1)
+}
+
+/// Whether a parse failed on the entity limits
+fn exceeded<T>(result: Result<T, XmlParseError>) -> bool {
+  matches!(result, Err(XmlParseError::EntityLimitExceeded))
+}
+
+#[test]
+fn entity_expansions_are_limited() {
+  let input = laughs(3);
+  let parser = Parser::default();
+  // 4 + 16 expansions below the reference in the document
+  let limited = |max| ParserOptions {
+    max_entity_expansions: Some(max),
+    ..ParserOptions::default()
+  };
+  assert!(parser.parse_string_with_options(&input, limited(21)).is_ok());
+  assert!(matches!(
+    parser.parse_string_with_options(&input, limited(20)),
+    Err(XmlParseError::EntityLimitExceeded)
+  ));
+
+  // Entities declared but not referenced don't count, nor do predefined ones
+  let unused = "<!DOCTYPE r [<!ENTITY a \"x\">]><r>&amp;&lt;</r>";
+  assert!(parser.parse_string_with_options(unused, limited(0)).is_ok());
+}
+
+#[test]
+fn amplification_is_limited() {
+  let input = laughs(3);
+  let parser = Parser::default();
+  let limited = |factor, huge| ParserOptions {
+    max_amplification: Some(factor),
+    recover: false,
+    huge,
+    ..ParserOptions::default()
+  };
+  // 16 * "lol" grow the document by a third
+  assert!(parser.parse_string_with_options(&input, limited(5, false)).is_ok());
+  // Newer releases enforce the limit themselves, past an allowance of about 1MB
+  if libxml::runtime_info().version_number < 21100 {
+    assert!(parser.parse_string_with_options(&input, limited(1, false)).is_err());
+  }
+  assert!(parser.parse_string_with_options(&input, limited(1, true)).is_ok());
+}
+
+#[test]
+fn secure_parser_preset() {
+  let parser = Parser::secure();
+  assert!(parser.options().no_net);
+  assert!(!parser.options().dtd_load && !parser.options().huge);
+  assert!(parser.parse_string("<r a=\"1\">&amp;text</r>").is_ok());
+  assert!(matches!(
+    parser.parse_string(laughs(2)),
+    Err(XmlParseError::EntityLimitExceeded)
+  ));
+
+  // Malformed documents are not recovered
+  assert!(parser.parse_string("<r><a></r>").is_err());
+
+  // Read as a stream, the external subset stays unread and references are still checked
+  let dtd = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/resources/note.dtd");
+  let input = format!("<!DOCTYPE note SYSTEM \"{dtd}\" [<!ENTITY e \"expanded\">]><note/>");
+  let doc = parser.parse_reader(input.as_bytes()).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_attribute("priority"), None);
+  let referenced = input.replace("<note/>", "<note>&e;</note>");
+  assert!(exceeded(parser.parse_reader(referenced.as_bytes())));
+  assert!(exceeded(parser.parse_string_recover(&referenced)));
+}
+
+#[test]
+fn limits_apply_to_files_and_readers() {
+  let input = laughs(3);
+  let parser = Parser::default();
+  let limited = ParserOptions {
+    max_entity_expansions: Some(20),
+    ..ParserOptions::default()
+  };
+  assert!(exceeded(parser.parse_reader_with_options(input.as_bytes(), limited)));
+  assert!(parser.parse_reader_with_options(laughs(2).as_bytes(), limited).is_ok());
+
+  let path = env::temp_dir().join("rust_libxml_tests_laughs.xml");
+  fs::write(&path, &input).unwrap();
+  assert!(exceeded(parser.parse_file_with_options(&path, limited)));
+  assert!(exceeded(parser.parse_compressed_file_with_options(&path, limited)));
+  assert!(parser.parse_file(&path).is_ok());
+
+  // In recovery mode as well, with the options of the parser
+  let parser = Parser::with_options(libxml::parser::ParseFormat::XML, limited);
+  assert!(exceeded(parser.parse_string_recover(&input)));
+  assert!(parser.parse_string_recover(laughs(2)).is_ok());
+}
+
+#[test]
+#[cfg(feature = "zlib")]
+fn limits_apply_to_compressed_files() {
+  let parser = Parser::default();
+  let doc = parser.parse_string(laughs(3)).unwrap();
+  let path = env::temp_dir().join("rust_libxml_tests_laughs.xml.gz");
+  assert!(doc.save_file(path.to_str().unwrap()).is_ok());
+  let limited = ParserOptions {
+    max_entity_expansions: Some(20),
+    ..ParserOptions::default()
+  };
+  assert!(exceeded(parser.parse_file_with_options(&path, limited)));
+  assert!(parser.parse_file(&path).is_ok());
+}
@@ -0,0 +1,79 @@
+//! Tests for the lxml / ElementTree style facade
+//!
+use libxml::etree::{fromstring, sub_element, tostring, Element};
+
+#[test]
+fn build_tree_with_text_and_tail() {
+  let mut root = Element::new("root").unwrap();
+  let mut first = sub_element(&mut root, "first").unwrap();
+  first.set_text(Some("one")).unwrap();
+  first.set_tail(Some("after")).unwrap();
+  let mut second = root.sub_element("{urn:x}second").unwrap();
+  second.set("id", "2").unwrap();
+  second.set("{urn:y}kind", "b").unwrap();
+
+  assert_eq!(
+    tostring(&root),
+    "<root><first>one</first>after<ns0:second xmlns:ns0=\"urn:x\" xmlns:ns1=\"urn:y\" id=\"2\" ns1:kind=\"b\"/></root>"
+  );
+  assert_eq!(root.len(), 2);
+  assert_eq!(root.child(1).unwrap().tag(), "{urn:x}second");
+  assert_eq!(first.tail().as_deref(), Some("after"));
+  assert_eq!(second.get("{urn:y}kind").as_deref(), Some("b"));
+  assert_eq!(second.keys(), vec!["id", "{urn:y}kind"]);
+  assert_eq!(second.getparent().unwrap().tag(), "root");
+  assert_eq!(second.getprevious().unwrap().tag(), "first");
+}
+
+#[test]
+fn find_with_element_path() {
+  let root = fromstring(
+    "<feed xmlns:a=\"urn:a\"><entry id=\"1\"><title>One</title></entry>\
+     <entry id=\"2\"><title>Two</title><a:extra>x</a:extra></entry></feed>",
+  )
+  .unwrap();
+
+  let titles: Vec<String> = root
+    .iterfind("entry/title")
+    .unwrap()
+    .map(|title| title.text().unwrap())
+    .collect();
+  assert_eq!(titles, vec!["One", "Two"]);
+  assert_eq!(
+    root.findtext("entry[@id='2']/title").unwrap().as_deref(),
+    Some("Two")
+  );
+  assert_eq!(root.findall(".//{urn:a}extra").unwrap().len(), 1);
+  assert_eq!(root.findall(".//{*}extra").unwrap().len(), 1);
+  assert!(root.find("missing").unwrap().is_none());
+  assert!(root.find("/feed").is_err());
+
+  let tags: Vec<String> = root.iter(Some("entry")).map(|e| e.get("id").unwrap()).collect();
+  assert_eq!(tags, vec!["1", "2"]);
+  assert_eq!(root.iter(None).count(), 6);
+  assert_eq!(root.itertext().collect::<String>(), "OneTwox");
+}
+
+#[test]
+fn attrib_and_moving_elements() {
+  let mut root = fromstring("<root a=\"1\" b=\"2\"><x/>tail<y/></root>").unwrap();
+  {
+    let mut attrib = root.attrib();
+    assert_eq!(attrib.len(), 2);
+    assert!(attrib.contains_key("a"));
+    assert_eq!(attrib.remove("a").unwrap().as_deref(), Some("1"));
+    attrib.insert("c", "3").unwrap();
+    assert_eq!(attrib.items(), vec![("b".to_string(), "2".to_string()), ("c".to_string(), "3".to_string())]);
+  }
+
+  let mut x = root.find("x").unwrap().unwrap();
+  root.append(&mut x).unwrap();
+  assert_eq!(tostring(&root), "<root b=\"2\" c=\"3\"><y/><x/>tail</root>");
+
+  let mut other = Element::new("z").unwrap();
+  other.set_text(Some("copied")).unwrap();
+  root.insert(0, &mut other).unwrap();
+  assert_eq!(other.getparent().unwrap().tag(), "root");
+  root.remove(&mut x).unwrap();
+  assert_eq!(tostring(&root), "<root b=\"2\" c=\"3\"><z>copied</z><y/></root>");
+}
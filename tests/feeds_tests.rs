@@ -0,0 +1,142 @@
+//! Tests for the RSS, Atom and sitemap parsers
+//!
+use libxml::feeds::{Feed, FeedError, FeedKind, Sitemap, SitemapKind};
+
+#[test]
+fn parse_rss() {
+  let feed = Feed::parse(
+    r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/"
+     xmlns:content="http://purl.org/rss/1.0/modules/content/"
+     xmlns:atom="http://www.w3.org/2005/Atom">
+  <channel>
+    <title>Example News</title>
+    <atom:link href="https://example.com/feed.xml" rel="self"/>
+    <link>https://example.com/</link>
+    <description>All the news</description>
+    <lastBuildDate>Mon, 02 Jan 2023 10:00:00 GMT</lastBuildDate>
+    <item>
+      <title>First post</title>
+      <link>https://example.com/first</link>
+      <guid isPermaLink="false">post-1</guid>
+      <description>Short &lt;b&gt;summary&lt;/b&gt;</description>
+      <content:encoded><![CDATA[<p>Full text</p>]]></content:encoded>
+      <dc:creator>Ann</dc:creator>
+      <category>news</category>
+      <category>tech</category>
+      <pubDate>Mon, 02 Jan 2023 09:00:00 GMT</pubDate>
+      <enclosure url="https://example.com/a.mp3" type="audio/mpeg" length="1234"/>
+    </item>
+    <item><title>Second</title></item>
+  </channel>
+</rss>"#,
+  )
+  .unwrap();
+  assert_eq!(feed.kind, FeedKind::Rss);
+  assert_eq!(feed.title.as_deref(), Some("Example News"));
+  assert_eq!(feed.link.as_deref(), Some("https://example.com/"));
+  assert_eq!(feed.description.as_deref(), Some("All the news"));
+  assert_eq!(feed.updated.as_deref(), Some("Mon, 02 Jan 2023 10:00:00 GMT"));
+  assert_eq!(feed.entries.len(), 2);
+
+  let first = &feed.entries[0];
+  assert_eq!(first.id.as_deref(), Some("post-1"));
+  assert_eq!(first.link.as_deref(), Some("https://example.com/first"));
+  assert_eq!(first.summary.as_deref(), Some("Short <b>summary</b>"));
+  assert_eq!(first.content.as_deref(), Some("<p>Full text</p>"));
+  assert_eq!(first.authors, ["Ann"]);
+  assert_eq!(first.categories, ["news", "tech"]);
+  assert_eq!(first.published.as_deref(), Some("Mon, 02 Jan 2023 09:00:00 GMT"));
+  assert_eq!(first.enclosures.len(), 1);
+  assert_eq!(first.enclosures[0].media_type.as_deref(), Some("audio/mpeg"));
+  assert_eq!(first.enclosures[0].length, Some(1234));
+  assert_eq!(feed.entries[1].link, None);
+
+  let rdf = Feed::parse(
+    r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/">
+  <channel rdf:about="https://example.org/"><title>RDF feed</title><link>https://example.org/</link></channel>
+  <item rdf:about="https://example.org/1"><title>One</title><link>https://example.org/1</link></item>
+</rdf:RDF>"#,
+  )
+  .unwrap();
+  assert_eq!(rdf.kind, FeedKind::Rdf);
+  assert_eq!(rdf.title.as_deref(), Some("RDF feed"));
+  assert_eq!(rdf.entries.len(), 1);
+  assert_eq!(rdf.entries[0].id.as_deref(), Some("https://example.org/1"));
+}
+
+#[test]
+fn parse_atom() {
+  let feed = Feed::parse(
+    r#"<feed xmlns="http://www.w3.org/2005/Atom" xml:base="https://example.com/blog/">
+  <id>urn:uuid:60a76c80</id>
+  <title type="text">Example Blog</title>
+  <subtitle>Thoughts</subtitle>
+  <link rel="self" href="feed.atom"/>
+  <link href="index.html"/>
+  <updated>2023-01-02T10:00:00Z</updated>
+  <entry>
+    <id>urn:uuid:1225c695</id>
+    <title>Atom entry</title>
+    <link rel="alternate" type="text/html" href="2023/entry.html"/>
+    <link rel="enclosure" href="/media/clip.mp4" type="video/mp4"/>
+    <published>2023-01-01T09:00:00Z</published>
+    <updated>2023-01-02T09:00:00Z</updated>
+    <author><name>Bob</name><email>bob@example.com</email></author>
+    <category term="rust"/>
+    <summary>A summary</summary>
+    <content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>Body</p></div></content>
+  </entry>
+</feed>"#,
+  )
+  .unwrap();
+  assert_eq!(feed.kind, FeedKind::Atom);
+  assert_eq!(feed.id.as_deref(), Some("urn:uuid:60a76c80"));
+  assert_eq!(feed.title.as_deref(), Some("Example Blog"));
+  assert_eq!(feed.link.as_deref(), Some("https://example.com/blog/index.html"));
+  assert_eq!(feed.description.as_deref(), Some("Thoughts"));
+  assert_eq!(feed.entries.len(), 1);
+
+  let entry = &feed.entries[0];
+  assert_eq!(entry.title.as_deref(), Some("Atom entry"));
+  assert_eq!(
+    entry.link.as_deref(),
+    Some("https://example.com/blog/2023/entry.html")
+  );
+  assert_eq!(entry.authors, ["Bob"]);
+  assert_eq!(entry.categories, ["rust"]);
+  assert_eq!(entry.summary.as_deref(), Some("A summary"));
+  assert!(entry.content.as_deref().unwrap().contains("<p>Body</p>"));
+  assert_eq!(entry.enclosures[0].url, "https://example.com/media/clip.mp4");
+
+  match Feed::parse("<html/>") {
+    Err(FeedError::UnknownFormat(name)) => assert_eq!(name, "html"),
+    other => panic!("expected an unknown format error, got {other:?}"),
+  }
+}
+
+#[test]
+fn parse_sitemaps() {
+  let sitemap = Sitemap::parse(
+    r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc> https://example.com/ </loc><lastmod>2023-01-02</lastmod><changefreq>Daily</changefreq><priority>0.8</priority></url>
+  <url><loc>https://example.com/about</loc></url>
+  <url><lastmod>2023-01-01</lastmod></url>
+</urlset>"#,
+  )
+  .unwrap();
+  assert_eq!(sitemap.kind, SitemapKind::UrlSet);
+  assert_eq!(sitemap.entries.len(), 2);
+  assert_eq!(sitemap.entries[0].loc, "https://example.com/");
+  assert_eq!(sitemap.entries[0].lastmod.as_deref(), Some("2023-01-02"));
+  assert_eq!(sitemap.entries[0].changefreq.as_deref(), Some("daily"));
+  assert_eq!(sitemap.entries[0].priority, Some(0.8));
+  assert_eq!(sitemap.entries[1].priority, None);
+
+  let index = Sitemap::parse(
+    "<sitemapindex><sitemap><loc>https://example.com/sitemap1.xml</loc></sitemap></sitemapindex>",
+  )
+  .unwrap();
+  assert_eq!(index.kind, SitemapKind::Index);
+  assert_eq!(index.entries[0].loc, "https://example.com/sitemap1.xml");
+}
@@ -0,0 +1,227 @@
+//! Tests for the HTML utilities
+//!
+#![cfg(feature = "html")]
+
+use libxml::parser::{ParseFormat, Parser, ParserOptions};
+use libxml::tree::Node;
+
+#[test]
+fn html_to_xhtml() {
+  let html = Parser::default_html()
+    .parse_string(
+      "<!DOCTYPE html><HTML><head><TITLE>T</TITLE><script>if (a < b && c) {}</script></head>\
+       <body><P CLASS=x class=y>one<br>two<INPUT type=checkbox checked><p></p><!-- a -- b --></body></HTML>",
+    )
+    .unwrap();
+  let xhtml = html.to_xhtml().unwrap();
+  let serialized = xhtml.to_string();
+  assert!(serialized.contains("<!DOCTYPE html>"));
+  assert!(serialized.contains("<html xmlns=\"http://www.w3.org/1999/xhtml\">"));
+  assert!(serialized.contains("<script><![CDATA[if (a < b && c) {}]]></script>"));
+  assert!(serialized.contains("<p class=\"x\">one<br/>two<input type=\"checkbox\" checked=\"checked\"/></p><p></p>"));
+  assert!(serialized.contains("<!-- a - - b -->"));
+
+  // The result is well-formed XML
+  let reparsed = Parser::default().parse_string(&serialized).unwrap();
+  let root = reparsed.get_root_element().unwrap();
+  assert_eq!(root.get_namespace().unwrap().get_href(), "http://www.w3.org/1999/xhtml");
+}
+
+#[test]
+fn xhtml_keeps_content_of_invalid_elements() {
+  let html = Parser::default_html()
+    .parse_string("<html><body><div><b>kept</b></div></body></html>")
+    .unwrap();
+  // Names the HTML parser of newer libxml2 releases lets through
+  let mut div = html.get_root_element().unwrap().findnodes("//div").unwrap().remove(0);
+  let mut invalid = Node::new("1x", None, &html).unwrap();
+  let mut b = div.get_first_child().unwrap();
+  b.unlink();
+  invalid.add_child(&mut b).unwrap();
+  invalid.append_text(" text").unwrap();
+  div.add_child(&mut invalid).unwrap();
+  let serialized = html.to_xhtml().unwrap().to_string();
+  assert!(serialized.contains("<div><b>kept</b> text</div>"), "{serialized}");
+}
+
+#[test]
+fn extract_readable_text() {
+  let doc = Parser::default_html()
+    .parse_string(
+      "<html><head><title>Ignored</title><style>p { color: red }</style></head><body>\
+       <h1>Title</h1>\n<p>First   paragraph\n with <b>bold</b> text.</p>\
+       <div>Line one<br>Line two</div>\
+       <ul><li>apple</li><li>pear</li></ul>\
+       <table><tr><td>a</td><td>b</td></tr><tr><td>c</td><td>d</td></tr></table>\
+       <p hidden>secret</p><span style=\"display: none\">gone</span><script>var x;</script>\
+       <pre>  keep\n    this</pre>end</body></html>",
+    )
+    .unwrap();
+  assert_eq!(
+    libxml::html::text_extract(&doc),
+    "Title\n\nFirst paragraph with bold text.\n\nLine one\nLine two\n\napple\npear\n\na\tb\nc\td\n\n  keep\n    this\n\nend"
+  );
+}
+
+#[test]
+fn extract_and_rewrite_links() {
+  let mut doc = Parser::default_html()
+    .parse_string(
+      "<html><head><base href=\"https://example.com/docs/\">\
+       <link rel=\"stylesheet\" href=\"/style.css\"></head><body>\
+       <a href=\"page.html#top\">page</a><a href=\"\">empty</a><a name=\"anchor\">no link</a>\
+       <img src=\"../img/logo.png\"><a href=\"https://other.org/\">other</a>\
+       <form action=\"?q=1\"></form></body></html>",
+    )
+    .unwrap();
+  let links: Vec<(String, String, String)> = libxml::html::links(&doc)
+    .into_iter()
+    .map(|link| (link.element.get_name(), link.attribute, link.url))
+    .collect();
+  let expected = [
+    ("link", "href", "https://example.com/style.css"),
+    ("a", "href", "https://example.com/docs/page.html#top"),
+    ("img", "src", "https://example.com/img/logo.png"),
+    ("a", "href", "https://other.org/"),
+    ("form", "action", "https://example.com/docs/?q=1"),
+  ];
+  assert_eq!(links.len(), expected.len());
+  for (link, (element, attribute, url)) in links.iter().zip(expected) {
+    assert_eq!((link.0.as_str(), link.1.as_str(), link.2.as_str()), (element, attribute, url));
+  }
+
+  libxml::html::rewrite_links(&mut doc, |url| {
+    url
+      .starts_with("https://example.com/")
+      .then(|| url.replacen("https://example.com/", "https://mirror.example.net/", 1))
+  });
+  let rewritten: Vec<String> = libxml::html::links(&doc)
+    .into_iter()
+    .map(|link| link.element.get_attribute(&link.attribute).unwrap())
+    .collect();
+  assert_eq!(
+    rewritten,
+    [
+      "https://mirror.example.net/style.css",
+      "https://mirror.example.net/docs/page.html#top",
+      "https://mirror.example.net/img/logo.png",
+      "https://other.org/",
+      "https://mirror.example.net/docs/?q=1",
+    ]
+  );
+}
+
+#[test]
+fn extract_forms() {
+  let doc = Parser::default_html()
+    .parse_string(
+      "<html><body><form id=\"login\" action=\"/session\" method=\"POST\">\
+       <input name=\"user\" value=\"alice\"><input type=\"password\" name=\"password\">\
+       <input type=\"checkbox\" name=\"remember\" checked><input type=\"checkbox\" name=\"spam\" value=\"yes\">\
+       <input type=\"radio\" name=\"lang\" value=\"en\"><input type=\"radio\" name=\"lang\" value=\"fr\" checked>\
+       <select name=\"theme\"><option>light</option><optgroup><option value=\"d\">dark</option></optgroup></select>\
+       <select name=\"tags\" multiple><option selected>a</option><option>b</option><option selected>c</option></select>\
+       <fieldset disabled><input name=\"locked\" value=\"x\"></fieldset>\
+       <textarea name=\"note\">hello</textarea><input value=\"unnamed\">\
+       <button name=\"go\" value=\"1\">Go</button></form>\
+       <input name=\"outside\" form=\"login\" value=\"o\"><input name=\"stray\"></body></html>",
+    )
+    .unwrap();
+  let forms = libxml::html::forms(&doc);
+  assert_eq!(forms.len(), 1);
+  let form = &forms[0];
+  assert_eq!(form.name.as_deref(), Some("login"));
+  assert_eq!(form.action.as_deref(), Some("/session"));
+  assert_eq!(form.method, "post");
+
+  let names: Vec<&str> = form.fields.iter().map(|field| field.name.as_str()).collect();
+  assert_eq!(
+    names,
+    ["user", "password", "remember", "spam", "lang", "lang", "theme", "tags", "locked", "note", "go", "outside"]
+  );
+  assert_eq!(form.fields[2].kind, "checkbox");
+  assert_eq!(form.fields[3].options, ["yes"]);
+  assert_eq!(form.fields[6].options, ["light", "d"]);
+  assert_eq!(form.fields[7].kind, "select-multiple");
+  assert!(form.fields[8].disabled);
+
+  let expected = [
+    ("user", "alice"),
+      ("password", ""),
+      ("remember", "on"),
+      ("lang", "fr"),
+      ("theme", "light"),
+      ("tags", "a"),
+      ("tags", "c"),
+      ("note", "hello"),
+      ("outside", "o"),
+  ];
+  let pairs = form.default_values();
+  assert_eq!(pairs.len(), expected.len());
+  for ((name, value), (expected_name, expected_value)) in pairs.iter().zip(expected) {
+    assert_eq!((name.as_str(), value.as_str()), (expected_name, expected_value));
+  }
+}
+
+#[test]
+fn extract_tables() {
+  let doc = Parser::default_html()
+    .parse_string(
+      "<html><body><table>\
+       <thead><tr><th>Name</th><th colspan=\"2\">Contact</th></tr></thead>\
+       <tbody><tr><td rowspan=\"2\">Ann</td><td>mail</td><td>ann@example.com</td></tr>\
+       <tr><td>phone</td><td>555</td></tr>\
+       <tr><td>Bob</td></tr></tbody></table>\
+       <table><tr><td>  lone \n cell </td></tr></table></body></html>",
+    )
+    .unwrap();
+  let tables = libxml::html::tables(&doc);
+  assert_eq!(tables.len(), 2);
+  assert_eq!(
+    tables[0],
+    [
+      ["Name", "Contact", "Contact"],
+      ["Ann", "mail", "ann@example.com"],
+      ["Ann", "phone", "555"],
+      ["Bob", "", ""],
+    ]
+  );
+  assert_eq!(tables[1], [["lone cell"]]);
+}
+
+#[test]
+fn html_parser_options() {
+  let fragment = "<p>one</p><p>two</p>";
+  let doc = Parser::default_html().parse_string(fragment).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "html");
+
+  let parser = Parser::with_options(
+    ParseFormat::HTML,
+    ParserOptions {
+      no_implied: true,
+      no_def_dtd: true,
+      ..ParserOptions::default()
+    },
+  );
+  assert!(parser.options().no_implied);
+  let doc = parser.parse_string(fragment).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "p");
+  assert!(!doc.to_string().contains("DOCTYPE"));
+
+  // Per call options still win
+  let doc = parser
+    .parse_string_with_options(fragment, ParserOptions::default())
+    .unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "html");
+}
+
+#[test]
+fn html_parse_diagnostics() {
+  let parser = Parser::default_html();
+  let doc = parser.parse_string("<p>one <b>two</p>\n</div>").unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_name(), "html");
+  let diagnostics = parser.last_diagnostics();
+  assert_eq!(diagnostics.len(), 2);
+  assert_eq!(diagnostics[1].line, Some(2));
+  assert!(diagnostics[1].message.as_deref().unwrap().contains("div"));
+}
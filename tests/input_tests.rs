@@ -0,0 +1,73 @@
+//! Tests for custom input sources
+//!
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read};
+use std::sync::Arc;
+
+use libxml::input;
+use libxml::parser::{Parser, ParserOptions, XmlParseError};
+use libxml::xinclude::{self, XIncludeOptions};
+
+/// Serves the files of an in-memory filesystem under `scheme`
+fn register_files(scheme: &'static str, files: &[(&str, &str)]) -> input::InputId {
+  let files: Arc<HashMap<String, String>> = Arc::new(
+    files
+      .iter()
+      .map(|(name, content)| (format!("{scheme}{name}"), content.to_string()))
+      .collect(),
+  );
+  input::register(
+    move |uri| uri.starts_with(scheme),
+    move |uri| match files.get(uri) {
+      Some(content) => Ok(Box::new(Cursor::new(content.clone().into_bytes())) as Box<dyn Read>),
+      None => Err(io::Error::from(io::ErrorKind::NotFound)),
+    },
+  )
+}
+
+#[test]
+fn parse_file_reads_registered_sources() {
+  let id = register_files("vfs1://", &[("doc.xml", "<doc><item/></doc>")]);
+  let parser = Parser::default();
+  let doc = parser.parse_file("vfs1://doc.xml").unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_child_elements().len(), 1);
+  assert!(matches!(
+    parser.parse_file("vfs1://missing.xml"),
+    Err(XmlParseError::FileOpenError)
+  ));
+
+  assert!(input::unregister(id));
+  assert!(!input::unregister(id));
+  assert!(parser.parse_file("vfs1://doc.xml").is_err());
+}
+
+#[test]
+fn includes_and_dtds_resolve_through_sources() {
+  let _id = register_files(
+    "vfs2://",
+    &[
+      (
+        "dir/doc.xml",
+        r#"<!DOCTYPE doc SYSTEM "doc.dtd"><doc xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="part.xml"/><xi:include href="vfs2://note.txt" parse="text"/></doc>"#,
+      ),
+      ("dir/doc.dtd", r#"<!ATTLIST doc version CDATA "2">"#),
+      ("dir/part.xml", "<part/>"),
+      ("note.txt", "plain"),
+    ],
+  );
+  let options = ParserOptions {
+    dtd_load: true,
+    dtd_attr: true,
+    ..ParserOptions::default()
+  };
+  let mut doc = Parser::default()
+    .parse_file_with_options("vfs2://dir/doc.xml", options)
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_attribute("version").as_deref(), Some("2"));
+
+  assert_eq!(xinclude::process(&mut doc, &XIncludeOptions::default()), Ok(2));
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_first_element_child().unwrap().get_name(), "part");
+  assert_eq!(root.get_content(), "plain");
+}
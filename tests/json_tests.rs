@@ -0,0 +1,82 @@
+//! Tests for the XML <-> JSON mapping
+//!
+#![cfg(feature = "json")]
+
+use libxml::json::JsonOptions;
+use libxml::parser::Parser;
+use libxml::tree::Document;
+use serde_json::json;
+
+const INPUT: &str = r#"<library xmlns:x="urn:x" name="main">
+  <book id="1"><title>Dune</title></book>
+  <book id="2" x:rating="5">Mixed<title>Emma</title></book>
+  <empty/>
+</library>"#;
+
+#[test]
+fn to_json_attribute_prefix_style() {
+  let doc = Parser::default().parse_string(INPUT).unwrap();
+  let value = doc.to_json(&JsonOptions::default());
+  assert_eq!(
+    value,
+    json!({
+      "library": {
+        "@xmlns:x": "urn:x",
+        "@name": "main",
+        "book": [
+          { "@id": "1", "title": "Dune" },
+          { "@id": "2", "@x:rating": "5", "title": "Emma", "#text": "Mixed" }
+        ],
+        "empty": null
+      }
+    })
+  );
+}
+
+#[test]
+fn to_json_badgerfish_style() {
+  let doc = Parser::default()
+    .parse_string(r#"<alice xmlns="urn:a" charlie="david">bob<x>1</x></alice>"#)
+    .unwrap();
+  let value = doc.to_json(&JsonOptions::badgerfish());
+  assert_eq!(
+    value,
+    json!({
+      "alice": {
+        "@xmlns": { "$": "urn:a" },
+        "@charlie": "david",
+        "x": { "$": "1" },
+        "$": "bob"
+      }
+    })
+  );
+}
+
+#[test]
+fn json_roundtrip_in_both_styles() {
+  let doc = Parser::default().parse_string(INPUT).unwrap();
+  for options in [JsonOptions::default(), JsonOptions::badgerfish()] {
+    let value = doc.to_json(&options);
+    let rebuilt = Document::from_json(&value, &options).unwrap();
+    assert_eq!(rebuilt.to_json(&options), value);
+    let root = rebuilt.get_root_element().unwrap();
+    let rated = root.findnodes("book[2]").unwrap();
+    assert_eq!(rated[0].get_property_ns("rating", "urn:x"), Some("5".to_string()));
+  }
+}
+
+#[test]
+fn from_json_rejects_invalid_roots() {
+  let options = JsonOptions::default();
+  assert!(Document::from_json(&json!({"a": 1, "b": 2}), &options).is_err());
+  assert!(Document::from_json(&json!({"a": [1, 2]}), &options).is_err());
+  assert!(Document::from_json(&json!("text"), &options).is_err());
+  let doc = Document::from_json(&json!({"a": {"@n": 3, "b": true}}), &options).unwrap();
+  assert_eq!(
+    doc.to_string_with_options(libxml::tree::SaveOptions {
+      no_declaration: true,
+      ..Default::default()
+    }).trim(),
+    r#"<a n="3"><b>true</b></a>"#
+  );
+}
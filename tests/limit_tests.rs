@@ -0,0 +1,72 @@
+//! Tests for the depth and node limits of the parser
+//!
+use libxml::parser::{Parser, ParserOptions, XmlParseError};
+
+fn nested(depth: usize) -> String {
+  format!("{}{}", "<e>".repeat(depth), "</e>".repeat(depth))
+}
+
+fn limited(max_depth: Option<usize>, max_nodes: Option<u64>) -> ParserOptions<'static> {
+  ParserOptions {
+    max_depth,
+    max_nodes,
+    ..ParserOptions::default()
+  }
+}
+
+#[test]
+fn depth_is_limited() {
+  let parser = Parser::default();
+  let input = nested(10);
+  assert!(parser.parse_string_with_options(&input, limited(Some(10), None)).is_ok());
+  assert!(matches!(
+    parser.parse_string_with_options(&input, limited(Some(9), None)),
+    Err(XmlParseError::LimitExceeded)
+  ));
+  // Also past libxml2's own limit, which `huge` lifts
+  let deep = nested(5000);
+  let options = ParserOptions {
+    huge: true,
+    ..limited(Some(1000), None)
+  };
+  assert!(matches!(
+    parser.parse_string_with_options(&deep, options),
+    Err(XmlParseError::LimitExceeded)
+  ));
+  assert!(matches!(
+    parser.parse_reader_with_options(deep.as_bytes(), options),
+    Err(XmlParseError::LimitExceeded)
+  ));
+
+  // The pooled context doesn't keep the limits
+  assert!(parser.parse_string(&input).is_ok());
+}
+
+#[test]
+fn nodes_are_limited() {
+  let parser = Parser::default();
+  // 1 + 3 elements, 3 text nodes, a comment
+  let input = format!("<r><a>{}</a><b>two</b><c>three</c><!--c--></r>", "long text ".repeat(1000));
+  assert!(parser.parse_string_with_options(&input, limited(None, Some(8))).is_ok());
+  assert!(matches!(
+    parser.parse_string_with_options(&input, limited(None, Some(7))),
+    Err(XmlParseError::LimitExceeded)
+  ));
+  assert!(matches!(
+    parser.parse_reader_with_options(input.as_bytes(), limited(None, Some(7))),
+    Err(XmlParseError::LimitExceeded)
+  ));
+}
+
+#[cfg(feature = "html")]
+#[test]
+fn html_is_limited() {
+  let parser = Parser::default_html();
+  // html, body and the nested elements
+  let input = "<div><div><div><p>text</p></div></div></div>";
+  assert!(parser.parse_string_with_options(input, limited(Some(6), None)).is_ok());
+  assert!(matches!(
+    parser.parse_string_with_options(input, limited(Some(5), None)),
+    Err(XmlParseError::LimitExceeded)
+  ));
+}
@@ -0,0 +1,101 @@
+//! Tests for the three-way merge
+//!
+use libxml::merge::{merge, ConflictMarker, MergePolicy};
+use libxml::parser::Parser;
+use libxml::tree::Document;
+
+fn parse(xml: &str) -> Document {
+  Parser::default().parse_string(xml).unwrap()
+}
+
+fn root_string(doc: &Document) -> String {
+  doc.node_to_string(&doc.get_root_element().unwrap())
+}
+
+#[test]
+fn non_conflicting_changes_are_combined() {
+  let base = parse(r#"<list><item id="a">one</item><item id="b">two</item></list>"#);
+  let ours = parse(r#"<list version="2"><item id="a">uno</item><item id="b">two</item></list>"#);
+  let theirs =
+    parse(r#"<list><item id="a">one</item><item id="c">three</item><item id="b">two</item></list>"#);
+  let result = merge(&base, &ours, &theirs, MergePolicy::OursWins).unwrap();
+  assert!(result.conflicts.is_empty());
+  assert_eq!(
+    root_string(&result.document),
+    r#"<list version="2"><item id="a">uno</item><item id="c">three</item><item id="b">two</item></list>"#
+  );
+}
+
+#[test]
+fn deletions_are_applied() {
+  let base = parse("<r><a/><b/><c/></r>");
+  let ours = parse("<r><b/><c/></r>");
+  let theirs = parse("<r><a/><b/></r>");
+  let result = merge(&base, &ours, &theirs, MergePolicy::OursWins).unwrap();
+  assert!(result.conflicts.is_empty());
+  assert_eq!(root_string(&result.document), "<r><b/></r>");
+}
+
+#[test]
+fn conflicts_follow_policy() {
+  let base = parse(r#"<r><v>1</v><w k="x"/></r>"#);
+  let ours = parse(r#"<r><v>2</v><w k="y"/></r>"#);
+  let theirs = parse(r#"<r><v>3</v><w k="z"/></r>"#);
+
+  let result = merge(&base, &ours, &theirs, MergePolicy::OursWins).unwrap();
+  assert_eq!(result.conflicts.len(), 2);
+  assert_eq!(root_string(&result.document), r#"<r><v>2</v><w k="y"/></r>"#);
+
+  let result = merge(&base, &ours, &theirs, MergePolicy::TheirsWins).unwrap();
+  assert_eq!(result.conflicts.len(), 2);
+  assert_eq!(root_string(&result.document), r#"<r><v>3</v><w k="z"/></r>"#);
+  assert_eq!(result.conflicts[0].path, "/r/v");
+  assert_eq!(result.conflicts[0].ours.as_deref(), Some("2"));
+  assert_eq!(result.conflicts[0].theirs.as_deref(), Some("3"));
+  assert_eq!(result.conflicts[1].path, "/r/w/@k");
+}
+
+#[test]
+fn conflicts_can_be_marked() {
+  let base = parse("<r><v>1</v><gone>x</gone></r>");
+  let ours = parse("<r><v>2</v></r>");
+  let theirs = parse("<r><v>3</v><gone>changed</gone></r>");
+
+  let result = merge(&base, &ours, &theirs, MergePolicy::Mark(ConflictMarker::Comment)).unwrap();
+  assert_eq!(result.conflicts.len(), 2);
+  assert_eq!(result.conflicts[1].description, "deleted in ours, modified in theirs");
+  assert_eq!(result.conflicts[1].ours, None);
+  assert_eq!(
+    root_string(&result.document),
+    "<r><!-- merge conflict at /r/v: text changed on both sides; ours: 2; theirs: 3 --><v>2</v>\
+     <!-- merge conflict at /r/gone: deleted in ours, modified in theirs; ours: (deleted); theirs: <gone>changed</gone> --></r>"
+  );
+
+  // Runs of dashes are broken up for the comment to stay well-formed
+  let dashes = merge(
+    &parse("<r><v>1</v></r>"),
+    &parse("<r><v>a---b</v></r>"),
+    &parse("<r><v>----</v></r>"),
+    MergePolicy::Mark(ConflictMarker::Comment),
+  )
+  .unwrap();
+  let marked = root_string(&dashes.document);
+  assert_eq!(
+    marked,
+    "<r><!-- merge conflict at /r/v: text changed on both sides; ours: a- - -b; theirs: - - - - --><v>a---b</v></r>"
+  );
+  let strict = libxml::parser::ParserOptions {
+    recover: false,
+    ..Default::default()
+  };
+  assert!(Parser::default().parse_string_with_options(&marked, strict).is_ok());
+
+  let result = merge(
+    &base,
+    &ours,
+    &theirs,
+    MergePolicy::Mark(ConflictMarker::ProcessingInstruction),
+  )
+  .unwrap();
+  assert!(root_string(&result.document).starts_with("<r><?merge-conflict merge conflict at /r/v:"));
+}
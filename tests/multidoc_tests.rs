@@ -0,0 +1,61 @@
+//! Tests for parsing streams of concatenated documents
+//!
+use std::io::Read;
+
+use libxml::parser::{MultiDocParser, ParserOptions};
+
+/// A reader handing out its input a few bytes at a time
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let n = buf.len().min(self.0.len()).min(3);
+    buf[..n].copy_from_slice(&self.0[..n]);
+    self.0 = &self.0[n..];
+    Ok(n)
+  }
+}
+
+#[test]
+fn concatenated_documents() {
+  let stream = r#"<?xml version="1.0"?>
+<!-- first -->
+<log level="info"><msg a="1>2">started</msg><![CDATA[</log>]]></log>
+<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE log [ <!ENTITY who "world"> ]>
+<log><msg>hello &who;</msg></log><empty/>
+<log/>
+"#;
+  for reader in [
+    Box::new(stream.as_bytes()) as Box<dyn Read>,
+    Box::new(Trickle(stream.as_bytes())),
+  ] {
+    let docs: Vec<String> = MultiDocParser::new(reader)
+      .map(|doc| {
+        let doc = doc.unwrap();
+        let root = doc.get_root_element().unwrap();
+        format!("{}:{}", root.get_name(), root.get_content())
+      })
+      .collect();
+    assert_eq!(
+      docs,
+      ["log:started</log>", "log:hello world", "empty:", "log:"]
+    );
+  }
+}
+
+#[test]
+fn resynchronizes_after_truncated_document() {
+  let stream = "<?xml version=\"1.0\"?><a><b>cut short\n<?xml version=\"1.0\"?><c/>trailing text";
+  let options = ParserOptions {
+    recover: false,
+    ..ParserOptions::default()
+  };
+  let results: Vec<_> = MultiDocParser::with_options(stream.as_bytes(), options).collect();
+  assert_eq!(results.len(), 2);
+  assert!(results[0].is_err());
+  assert_eq!(
+    results[1].as_ref().unwrap().get_root_element().unwrap().get_name(),
+    "c"
+  );
+}
@@ -0,0 +1,113 @@
+//! Tests for stable node references and node paths
+//!
+use std::collections::HashMap;
+
+use libxml::parser::Parser;
+use libxml::tree::{NodePath, NodeRef};
+
+const XML: &str = r#"<catalog xmlns="urn:catalog"><!-- books --><book id="1">First</book><book id="2"><title>Second</title></book><?sort asc?></catalog>"#;
+
+#[test]
+fn references_survive_moves() {
+  let doc = Parser::default().parse_string(XML).unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  let books = root.get_child_elements();
+  let first = books[0].node_ref();
+  let mut second = books[1].node_ref();
+  assert_eq!(first.path().to_string(), "/{urn:catalog}catalog[1]/{urn:catalog}book[1]");
+  assert_eq!(Some(second.clone()), NodeRef::from_ronode(doc.get_root_readonly().unwrap().get_child_elements()[1], &doc));
+  assert_ne!(first, second);
+
+  let mut notes: HashMap<NodeRef, &str> = HashMap::new();
+  notes.insert(second.clone(), "has a title");
+
+  // Moving the second book in front of the first one keeps the reference valid
+  drop(books);
+  let mut moved = second.resolve().unwrap();
+  moved.unlink();
+  assert!(second.is_valid());
+  root.get_first_element_child().unwrap().add_prev_sibling(&mut moved).unwrap();
+  let resolved = second.resolve().unwrap();
+  assert_eq!(resolved.get_attribute("id").as_deref(), Some("2"));
+  assert_eq!(notes[&resolved.node_ref()], "has a title");
+  assert_eq!(second.path().to_string(), "/{urn:catalog}catalog[1]/{urn:catalog}book[2]");
+  assert!(second.update_path());
+  assert_eq!(second.path().to_string(), "/{urn:catalog}catalog[1]/{urn:catalog}book[1]");
+
+  // Replacing the content of the root frees the books
+  let title = second.resolve().unwrap().get_first_element_child().unwrap().node_ref();
+  drop(moved);
+  drop(resolved);
+  root.set_content("gone").unwrap();
+  assert!(!first.is_valid());
+  assert!(first.resolve().is_none());
+  assert!(!title.is_valid());
+
+  let text = root.get_first_child().unwrap().node_ref();
+  assert!(text.is_valid());
+  drop(root);
+  drop(doc);
+  assert!(!text.is_valid());
+}
+
+#[test]
+fn references_follow_nodes_into_other_documents() {
+  let source = Parser::default().parse_string(XML).unwrap();
+  let target = Parser::default().parse_string("<shelf/>").unwrap();
+  let mut book = source.get_root_element().unwrap().get_child_elements().remove(1);
+  let reference = book.node_ref();
+  let title = source.get_root_readonly().unwrap().get_child_elements()[1].get_first_element_child();
+  let title = NodeRef::from_ronode(title.unwrap(), &source).unwrap();
+  assert_eq!(NodeRef::from_ronode(target.get_root_readonly().unwrap(), &source), None);
+
+  book.move_to(&mut target.get_root_element().unwrap()).unwrap();
+  drop(book);
+  drop(source);
+  assert!(reference.is_valid());
+  assert!(title.is_valid());
+  let resolved = reference.resolve().unwrap();
+  assert_eq!(resolved.get_parent().unwrap(), target.get_root_element().unwrap());
+  assert_eq!(title.resolve().unwrap().get_parent().unwrap(), resolved);
+}
+
+#[test]
+fn paths_resolve_after_round_trip() {
+  let doc = Parser::default().parse_string(XML).unwrap();
+  let root = doc.get_root_readonly().unwrap();
+  let title_text = root.get_child_elements()[1]
+    .get_first_child()
+    .unwrap()
+    .get_first_child()
+    .unwrap();
+  let id = root.get_child_elements()[1].get_attribute_node("id").unwrap();
+  let pi = root.get_last_child().unwrap();
+  let comment = root.get_first_child().unwrap();
+
+  let reloaded = Parser::default().parse_string(doc.to_string()).unwrap();
+  for (node, expected) in [
+    (
+      title_text,
+      "/{urn:catalog}catalog[1]/{urn:catalog}book[2]/{urn:catalog}title[1]/text()[1]",
+    ),
+    (id, "/{urn:catalog}catalog[1]/{urn:catalog}book[2]/@id"),
+    (pi, "/{urn:catalog}catalog[1]/processing-instruction(sort)[1]"),
+    (comment, "/{urn:catalog}catalog[1]/comment()[1]"),
+  ] {
+    let reference = NodeRef::from_ronode(node, &doc).unwrap();
+    assert_eq!(reference.path().to_string(), expected);
+    let path: NodePath = expected.parse().unwrap();
+    assert_eq!(&path, reference.path());
+    let found = reference.resolve_in(&reloaded).unwrap();
+    assert_eq!(found.get_content(), node.get_content());
+    assert_ne!(found.node_ptr(), node.node_ptr());
+  }
+
+  assert!("book[1]".parse::<NodePath>().is_err());
+  assert!("/book[0]".parse::<NodePath>().is_err());
+  assert!("/book[1]/".parse::<NodePath>().is_err());
+  assert!("/{urn:catalog}catalog[1]/{urn:catalog}book[3]"
+    .parse::<NodePath>()
+    .unwrap()
+    .resolve(&reloaded)
+    .is_none());
+}
@@ -0,0 +1,117 @@
+//! Tests for document mutation observers
+//!
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use libxml::parser::Parser;
+use libxml::tree::{Mutation, Node};
+
+fn describe(mutation: &Mutation) -> String {
+  match mutation {
+    Mutation::Inserted { node, parent } => format!("+{} in {}", node.get_name(), parent.get_name()),
+    Mutation::Removed { node, parent } => format!("-{} from {}", node.get_name(), parent.get_name()),
+    Mutation::AttributeChanged {
+      element,
+      name,
+      namespace,
+      old_value,
+      new_value,
+    } => format!(
+      "@{}/{}{}: {:?} -> {:?}",
+      element.get_name(),
+      namespace.as_deref().map(|ns| format!("{{{ns}}}")).unwrap_or_default(),
+      name,
+      old_value,
+      new_value
+    ),
+    Mutation::TextChanged {
+      node,
+      old_value,
+      new_value,
+    } => format!("~{}: {:?} -> {:?}", node.get_name(), old_value, new_value),
+  }
+}
+
+#[test]
+fn observers_see_node_mutations() {
+  let doc = Parser::default()
+    .parse_string(r#"<list xmlns:x="urn:x"><item id="1">one</item></list>"#)
+    .unwrap();
+  let log = Rc::new(RefCell::new(Vec::new()));
+  let sink = log.clone();
+  let id = doc.observe(move |mutation| sink.borrow_mut().push(describe(mutation)));
+
+  let mut root = doc.get_root_element().unwrap();
+  let mut item = root.get_first_element_child().unwrap();
+  item.set_attribute("id", "1").unwrap();
+  item.set_attribute("id", "first").unwrap();
+  item.set_attribute("class", "a").unwrap();
+  let ns = root.get_namespace_declarations().pop().unwrap();
+  item.set_property_ns("flag", "y", &ns).unwrap();
+  item.remove_attribute("class").unwrap();
+  item.remove_attribute("missing").unwrap();
+  item.append_text("!").unwrap();
+  item.set_content("uno").unwrap();
+
+  let mut second = Node::new("item", None, &doc).unwrap();
+  root.add_child(&mut second).unwrap();
+  root.new_child(None, "end").unwrap();
+  item.unlink();
+
+  assert_eq!(
+    *log.borrow(),
+    [
+      r#"@item/id: Some("1") -> Some("first")"#,
+      r#"@item/class: None -> Some("a")"#,
+      r#"@item/{urn:x}flag: None -> Some("y")"#,
+      r#"@item/class: Some("a") -> None"#,
+      r#"~item: "one" -> "one!""#,
+      r#"~item: "one!" -> "uno""#,
+      "+item in list",
+      "+end in list",
+      "-item from list",
+    ]
+  );
+
+  assert!(doc.unobserve(id));
+  assert!(!doc.unobserve(id));
+  root.new_child(None, "unseen").unwrap();
+  assert_eq!(log.borrow().len(), 9);
+}
+
+#[test]
+fn observers_may_modify_the_document() {
+  let mut doc = Parser::default().parse_string("<old/>").unwrap();
+  let log = Rc::new(RefCell::new(Vec::new()));
+  let sink = log.clone();
+  let handle = doc.clone();
+  let id = doc.observe(move |mutation| {
+    sink.borrow_mut().push(describe(mutation));
+    // Stamp every inserted element, the stamp being reported in turn
+    if let Mutation::Inserted { node, .. } = mutation
+      && node.get_name() == "item"
+    {
+      let mut item = handle.get_root_element().unwrap().get_last_element_child().unwrap();
+      item.set_attribute("stamped", "yes").unwrap();
+    }
+  });
+
+  let mut root = Node::new("new", None, &doc).unwrap();
+  doc.set_root_element(&root);
+  root.new_child(None, "item").unwrap();
+  assert_eq!(
+    *log.borrow(),
+    [
+      "-old from ",
+      "+new in ",
+      "+item in new",
+      r#"@item/stamped: None -> Some("yes")"#,
+    ]
+  );
+  assert_eq!(
+    doc.to_string(),
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<new><item stamped=\"yes\"/></new>\n"
+  );
+  // The callback owns a handle to the document, release it
+  doc.unobserve(id);
+}
@@ -0,0 +1,34 @@
+//! Tests for the tree outlines printed by `Debug`
+//!
+use libxml::parser::Parser;
+
+#[test]
+fn debug_prints_tree_outline() {
+  let doc = Parser::default()
+    .parse_string(
+      "<catalog xmlns:x=\"urn:x\"><!-- books --><book id=\"b1\" x:lang=\"en\">\n  <title>A rather long title that goes on and on and on</title>\n</book><?sort asc?></catalog>",
+    )
+    .unwrap();
+  assert_eq!(
+    format!("{doc:?}"),
+    "#document
+  <catalog>
+    <!-- books -->
+    <book id=\"b1\" x:lang=\"en\">
+      <title>
+        \"A rather long title that goes on and on…\"
+    <?sort asc?>"
+  );
+
+  let root = doc.get_root_element().unwrap();
+  let book = root.get_first_element_child().unwrap();
+  assert_eq!(
+    book.outline().text_width(6).to_string(),
+    "<book id=\"b1\" x:lang=\"en\">\n  <title>\n    \"A rath…\""
+  );
+  assert_eq!(
+    root.outline().max_depth(1).to_string(),
+    "<catalog>\n  <!-- books -->\n  <book id=\"b1\" x:lang=\"en\">\n    … (3 children)\n  <?sort asc?>"
+  );
+  assert_eq!(format!("{:?}", root.get_first_element_child().unwrap()), format!("{:?}", book));
+}
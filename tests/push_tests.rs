@@ -0,0 +1,118 @@
+//! Tests for the push parser
+//!
+use std::io;
+
+use libxml::parser::{ParseFormat, ParserOptions, PushParser, XmlParseError};
+
+#[test]
+fn chunks_split_anywhere() {
+  let input = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<list><item>caf\u{e9}</item><item/></list>";
+  let bytes = input.as_bytes();
+  let mut parser = PushParser::new().unwrap();
+  for chunk in bytes.chunks(3) {
+    parser.parse_chunk(chunk).unwrap();
+  }
+  let doc = parser.finish().unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_name(), "list");
+  assert_eq!(root.get_child_elements().len(), 2);
+  assert_eq!(root.get_content(), "caf\u{e9}");
+}
+
+#[test]
+fn push_parser_is_writable() {
+  let mut parser = PushParser::new().unwrap();
+  io::copy(&mut &b"<root><child/></root>"[..], &mut parser).unwrap();
+  let doc = parser.finish().unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_child_elements().len(), 1);
+}
+
+#[test]
+fn malformed_input_without_recovery() {
+  let options = ParserOptions {
+    recover: false,
+    ..ParserOptions::default()
+  };
+  let mut parser = PushParser::with_options(ParseFormat::XML, options).unwrap();
+  parser.parse_chunk(b"<root><a>").unwrap();
+  let error = parser.parse_chunk(b"</b></root>").unwrap_err();
+  assert!(matches!(error, XmlParseError::Malformed(_)));
+  assert!(parser.finish().is_err());
+
+  // Recovering by default, unfinished input still makes a document
+  let mut parser = PushParser::new().unwrap();
+  parser.parse_chunk(b"<root><a>text").unwrap();
+  let doc = parser.finish().unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "text");
+
+  // Dropping an unfinished parser frees what it parsed
+  let mut parser = PushParser::new().unwrap();
+  parser.parse_chunk(b"<root>").unwrap();
+  drop(parser);
+}
+
+#[test]
+fn declared_encoding() {
+  let options = ParserOptions {
+    encoding: Some("ISO-8859-1"),
+    ..ParserOptions::default()
+  };
+  let mut parser = PushParser::with_options(ParseFormat::XML, options).unwrap();
+  parser.parse_chunk(b"<r>caf\xe9</r>").unwrap();
+  let doc = parser.finish().unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_content(), "caf\u{e9}");
+
+  let options = ParserOptions {
+    encoding: Some("no-such-encoding"),
+    ..ParserOptions::default()
+  };
+  assert!(matches!(
+    PushParser::with_options(ParseFormat::XML, options),
+    Err(XmlParseError::UnknownEncoding(_))
+  ));
+}
+
+#[test]
+#[cfg(feature = "html")]
+fn html_chunks() {
+  let mut parser = PushParser::with_options(ParseFormat::HTML, ParserOptions::default()).unwrap();
+  for chunk in ["<html><body><p>one", "<p>two</bo", "dy></html>"] {
+    parser.parse_chunk(chunk.as_bytes()).unwrap();
+  }
+  let doc = parser.finish().unwrap();
+  let body = doc.get_root_element().unwrap().get_first_element_child().unwrap();
+  assert_eq!(body.get_name(), "body");
+  assert_eq!(body.get_child_elements().len(), 2);
+}
+
+#[test]
+#[cfg(feature = "html")]
+fn html_early_termination() {
+  let page = format!(
+    "<html><head><title>Target</title></head><body>{}</body></html>",
+    "<p>filler</p>".repeat(1000)
+  );
+  let mut parser = PushParser::new_html().unwrap();
+  assert!(parser.get_root_readonly().is_none());
+  let mut fed = 0;
+  for chunk in page.as_bytes().chunks(16) {
+    parser.parse_chunk(chunk).unwrap();
+    fed += chunk.len();
+    let title = parser
+      .get_root_readonly()
+      .and_then(|root| root.get_first_element_child())
+      .and_then(|head| head.get_first_element_child());
+    if title.is_some_and(|title| title.get_content() == "Target") {
+      break;
+    }
+  }
+  assert!(fed < page.len() / 10);
+  let doc = parser.finish().unwrap();
+  let title = doc
+    .get_root_readonly()
+    .and_then(|root| root.get_first_element_child())
+    .and_then(|head| head.get_first_element_child())
+    .unwrap();
+  assert_eq!(title.get_name(), "title");
+  assert_eq!(title.get_content(), "Target");
+}
@@ -0,0 +1,93 @@
+//! Tests for the quick-xml event bridge
+//!
+#![cfg(feature = "quick-xml")]
+
+use libxml::parser::Parser;
+use libxml::quickxml::{document_from_reader, document_to_events, node_to_events, QuickXmlError};
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+
+#[test]
+fn document_events_roundtrip_through_writer() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<root xmlns:a="urn:a"><a:item id="1">x &amp; y</a:item><!--c--><empty/></root>"#)
+    .unwrap();
+  let events = document_to_events(&doc);
+  assert!(matches!(events.first(), Some(Event::Decl(_))));
+  assert!(matches!(events.last(), Some(Event::Eof)));
+
+  let mut writer = Writer::new(Vec::new());
+  for event in events.into_iter().skip(1) {
+    if event == Event::Eof {
+      break;
+    }
+    writer.write_event(event).unwrap();
+  }
+  let output = String::from_utf8(writer.into_inner()).unwrap();
+  assert_eq!(
+    output,
+    r#"<root xmlns:a="urn:a"><a:item id="1">x &amp; y</a:item><!--c--><empty/></root>"#
+  );
+}
+
+#[test]
+fn node_events_cover_subtree_only() {
+  let parser = Parser::default();
+  let doc = parser.parse_string("<root><a><b/></a><c/></root>").unwrap();
+  let a = doc.get_root_element().unwrap().get_first_child().unwrap();
+  let events = node_to_events(&a);
+  assert_eq!(events.len(), 3);
+  assert!(matches!(&events[0], Event::Start(e) if e.name().as_ref() == b"a"));
+  assert!(matches!(&events[1], Event::Empty(e) if e.name().as_ref() == b"b"));
+  assert!(matches!(&events[2], Event::End(e) if e.name().as_ref() == b"a"));
+}
+
+#[test]
+fn document_from_quick_xml_reader() {
+  let input = r#"<?xml version="1.0"?>
+<?style href="a.css"?>
+<r:root xmlns:r="urn:r" xmlns="urn:default" r:flag="yes"><child attr="&lt;1&gt;">text &amp; more<![CDATA[<raw>]]></child></r:root>"#;
+  let mut reader = Reader::from_str(input);
+  let doc = document_from_reader(&mut reader).unwrap();
+
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_name(), "root");
+  assert_eq!(root.get_namespace().unwrap().get_href(), "urn:r");
+  assert_eq!(root.get_property_ns("flag", "urn:r"), Some("yes".to_string()));
+
+  let child = root.get_first_element_child().unwrap();
+  assert_eq!(child.get_namespace().unwrap().get_href(), "urn:default");
+  assert_eq!(child.get_attribute("attr"), Some("<1>".to_string()));
+  assert_eq!(child.get_content(), "text & more<raw>");
+
+  let serialized = doc.to_string();
+  assert!(serialized.contains("<?style href=\"a.css\"?>"));
+  assert!(serialized.contains("<![CDATA[<raw>]]>"));
+}
+
+#[test]
+fn document_from_reader_rejects_unbalanced_input() {
+  let mut reader = Reader::from_str("<a><b></a>");
+  reader.config_mut().check_end_names = false;
+  assert!(document_from_reader(&mut reader).is_err());
+}
+
+#[test]
+fn document_from_reader_rejects_nul_bytes() {
+  let mut reader = Reader::from_str("<a><!--x\0y--></a>");
+  assert!(document_from_reader(&mut reader).is_err());
+  let mut reader = Reader::from_str("<a>&x\0y;</a>");
+  assert!(document_from_reader(&mut reader).is_err());
+}
+
+#[test]
+fn document_from_reader_rejects_text_outside_root() {
+  let mut reader = Reader::from_str("<a/>\n  ");
+  assert!(document_from_reader(&mut reader).is_ok());
+  let mut reader = Reader::from_str("stray<a/>");
+  assert!(matches!(
+    document_from_reader(&mut reader),
+    Err(QuickXmlError::TextOutsideRoot(text)) if text == "stray"
+  ));
+}
@@ -0,0 +1,194 @@
+//! Tests for the pull parser
+//!
+use std::io::{self, Read};
+
+use libxml::parser::{ParserOptions, XmlParseError};
+use libxml::reader::{ReaderNodeType, TextReader};
+
+#[test]
+fn walks_nodes_in_document_order() {
+  let xml = r#"<root xmlns:x="urn:x"><x:item id="1">one</x:item><!--note--><empty/></root>"#;
+  let mut reader = TextReader::new(xml.as_bytes());
+  let mut seen = Vec::new();
+  while reader.read().unwrap() {
+    seen.push((reader.node_type(), reader.name(), reader.depth()));
+  }
+  assert_eq!(
+    seen,
+    [
+      (ReaderNodeType::Element, "root".to_owned(), 0),
+      (ReaderNodeType::Element, "x:item".to_owned(), 1),
+      (ReaderNodeType::Text, "#text".to_owned(), 2),
+      (ReaderNodeType::EndElement, "x:item".to_owned(), 1),
+      (ReaderNodeType::Comment, "#comment".to_owned(), 1),
+      (ReaderNodeType::Element, "empty".to_owned(), 1),
+      (ReaderNodeType::EndElement, "root".to_owned(), 0),
+    ]
+  );
+  assert_eq!(reader.node_type(), ReaderNodeType::None);
+  assert!(!reader.read().unwrap());
+}
+
+#[test]
+fn reads_names_values_and_attributes() {
+  let xml = r#"<root xmlns:x="urn:x"><x:item id="1" x:kind="a &amp; b">one</x:item><empty/></root>"#;
+  let mut reader = TextReader::new(xml.as_bytes());
+  assert!(reader.read().unwrap());
+  assert_eq!(reader.attribute_count(), 1);
+  assert!(reader.read().unwrap());
+  assert_eq!(reader.local_name(), "item");
+  assert_eq!(reader.prefix().as_deref(), Some("x"));
+  assert_eq!(reader.namespace_uri().as_deref(), Some("urn:x"));
+  assert!(!reader.is_empty_element());
+  assert_eq!(reader.get_attribute("id").as_deref(), Some("1"));
+  assert_eq!(reader.get_attribute_ns("kind", "urn:x").as_deref(), Some("a & b"));
+  assert_eq!(reader.get_attribute("missing"), None);
+
+  let mut attributes = Vec::new();
+  while reader.move_to_next_attribute() {
+    assert_eq!(reader.node_type(), ReaderNodeType::Attribute);
+    attributes.push((reader.name(), reader.value().unwrap()));
+  }
+  assert!(reader.move_to_element());
+  assert_eq!(reader.name(), "x:item");
+  assert_eq!(
+    attributes,
+    [("id".to_owned(), "1".to_owned()), ("x:kind".to_owned(), "a & b".to_owned())]
+  );
+  assert_eq!(reader.read_inner_xml(), "one");
+
+  assert!(reader.read().unwrap());
+  assert_eq!(reader.value().as_deref(), Some("one"));
+  assert!(reader.read().unwrap());
+  assert!(reader.read().unwrap());
+  assert_eq!(reader.name(), "empty");
+  assert!(reader.is_empty_element());
+  assert_eq!(reader.value(), None);
+}
+
+#[test]
+fn skips_subtrees() {
+  let xml = "<root><skip><deep><deeper/></deep></skip><keep/></root>";
+  let mut reader = TextReader::new(xml.as_bytes());
+  assert!(reader.read().unwrap());
+  assert!(reader.read().unwrap());
+  assert_eq!(reader.name(), "skip");
+  assert_eq!(reader.read_outer_xml(), "<skip><deep><deeper/></deep></skip>");
+  assert!(reader.next_sibling().unwrap());
+  assert_eq!(reader.name(), "keep");
+}
+
+#[test]
+fn streams_large_documents() {
+  // Produces `<root><item>0</item>...</root>` on the fly, never holding the whole document
+  struct Items {
+    next: usize,
+    count: usize,
+    pending: Vec<u8>,
+  }
+  impl Read for Items {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+      if self.pending.is_empty() {
+        self.pending = if self.next == 0 {
+          b"<root>".to_vec()
+        } else if self.next <= self.count {
+          format!("<item>{}</item>", self.next - 1).into_bytes()
+        } else if self.next == self.count + 1 {
+          b"</root>".to_vec()
+        } else {
+          return Ok(0);
+        };
+        self.next += 1;
+      }
+      let len = buf.len().min(self.pending.len());
+      buf[..len].copy_from_slice(&self.pending[..len]);
+      self.pending.drain(..len);
+      Ok(len)
+    }
+  }
+
+  let count = 200_000;
+  let mut reader = TextReader::new(Items {
+    next: 0,
+    count,
+    pending: Vec::new(),
+  });
+  let mut items = 0;
+  let mut sum = 0;
+  while reader.read().unwrap() {
+    if reader.node_type() == ReaderNodeType::Text {
+      items += 1;
+      sum += reader.value().unwrap().parse::<usize>().unwrap();
+    }
+  }
+  assert_eq!(items, count);
+  assert_eq!(sum, count * (count - 1) / 2);
+}
+
+#[test]
+fn reports_malformed_documents() {
+  let mut reader = TextReader::new(&b"<root><open></root>"[..]);
+  let error = loop {
+    match reader.read() {
+      Ok(true) => continue,
+      Ok(false) => panic!("malformed document read to the end"),
+      Err(error) => break error,
+    }
+  };
+  assert!(matches!(error, XmlParseError::Malformed(_)));
+
+  let options = ParserOptions {
+    no_blanks: true,
+    ..Default::default()
+  };
+  let mut reader = TextReader::with_options(&b"<root>\n  <a/>\n</root>"[..], options);
+  let mut types = Vec::new();
+  while reader.read().unwrap() {
+    types.push(reader.node_type());
+  }
+  assert!(!types.contains(&ReaderNodeType::Whitespace));
+}
+
+/// Fails every other read with `Interrupted`
+struct Interrupting<R> {
+  inner: R,
+  interrupt: bool,
+}
+
+impl<R: Read> Read for Interrupting<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.interrupt = !self.interrupt;
+    if self.interrupt {
+      return Err(io::ErrorKind::Interrupted.into());
+    }
+    let len = buf.len().min(4);
+    self.inner.read(&mut buf[..len])
+  }
+}
+
+#[test]
+fn retries_interrupted_reads_and_reports_skip_errors() {
+  let source = Interrupting {
+    inner: &b"<root><a/><b>text</b></root>"[..],
+    interrupt: false,
+  };
+  let mut reader = TextReader::new(source);
+  let mut names = Vec::new();
+  while reader.read().unwrap() {
+    names.push(reader.name());
+  }
+  assert!(names.contains(&"b".to_string()));
+
+  // Long enough that the first read does not reach the broken end tag
+  let xml = format!("<root>{}<open></root>", "<a/>".repeat(10_000));
+  let mut reader = TextReader::new(xml.as_bytes());
+  assert!(reader.read().unwrap());
+  let error = loop {
+    match reader.next_sibling() {
+      Ok(true) => continue,
+      Ok(false) => panic!("malformed document skipped to the end"),
+      Err(error) => break error,
+    }
+  };
+  assert!(matches!(error, XmlParseError::Malformed(_)));
+}
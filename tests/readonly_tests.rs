@@ -1,5 +1,7 @@
 //! Tree module tests
 //!
+#![cfg(feature = "html")]
+
 use libxml::parser::Parser;
 use libxml::readonly::RoNode;
 use libxml::tree::NodeType;
@@ -57,3 +59,30 @@ fn readonly_scan_test() {
   assert_eq!(sibling.get_type(), Some(NodeType::ElementNode));
   assert_eq!(sibling.get_name(), "body");
 }
+
+#[test]
+fn readonly_axes_and_xpath() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<r xmlns:p="urn:p"><a n="1"><b/></a><c n="2"/><p:d/></r>"#)
+    .unwrap();
+  let root = doc.get_root_readonly().unwrap();
+  let names = |nodes: libxml::tree::RoAxis| nodes.map(|node| node.get_name()).collect::<Vec<_>>();
+  assert_eq!(names(root.descendants()), ["a", "b", "c", "d"]);
+
+  let b = root.descendants().nth(1).unwrap();
+  let ancestors = b.ancestors().collect::<Vec<_>>();
+  assert_eq!(ancestors.len(), 3);
+  assert_eq!(ancestors[1], root);
+  assert_eq!(ancestors[2].get_type(), Some(NodeType::DocumentNode));
+  assert_eq!(names(b.following()), ["c", "d"]);
+  let c = root.at_xpath("c", &doc, &[]).unwrap().unwrap();
+  assert_eq!(names(c.following_siblings()), ["d"]);
+  assert_eq!(names(c.preceding_siblings()), ["a"]);
+  assert_eq!(names(c.preceding()), ["b", "a"]);
+
+  assert_eq!(root.findvalues("*/@n", &doc), Ok(vec!["1".to_string(), "2".to_string()]));
+  let d = root.at_xpath("q:d", &doc, &[("q", "urn:p")]).unwrap();
+  assert_eq!(d.map(|d| d.get_name()).as_deref(), Some("d"));
+  assert_eq!(root.at_xpath("missing", &doc, &[]), Ok(None));
+}
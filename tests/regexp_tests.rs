@@ -0,0 +1,38 @@
+//! Tests for the XML Schema regular expression wrapper
+//!
+use libxml::regexp::Regexp;
+
+#[test]
+fn patterns_match_the_whole_value() {
+  let re = Regexp::new("[A-Z]{2}[0-9]+").unwrap();
+  assert_eq!(re.as_str(), "[A-Z]{2}[0-9]+");
+  assert!(re.is_match("AB123"));
+  assert!(!re.is_match("xAB123"));
+  assert!(!re.is_match("AB123x"));
+  assert!(!re.is_match(""));
+}
+
+#[test]
+fn schema_character_classes() {
+  let name = Regexp::new(r"\i\c*").unwrap();
+  assert!(name.is_match("_foo-bar.1"));
+  assert!(!name.is_match("1foo"));
+
+  let letters = Regexp::new(r"\p{Lu}\p{Ll}+").unwrap();
+  assert!(letters.is_match("Émile"));
+  assert!(!letters.is_match("émile"));
+}
+
+#[test]
+fn invalid_pattern_is_reported() {
+  let err = Regexp::new("[a-").unwrap_err();
+  assert_eq!(err.pattern, "[a-");
+  assert!(err.error.is_some());
+  assert!(err.to_string().starts_with("invalid pattern \"[a-\""));
+}
+
+#[test]
+fn determinism() {
+  assert!(Regexp::new("ab|cd").unwrap().is_deterministic());
+  assert!(!Regexp::new("a*a").unwrap().is_deterministic());
+}
@@ -0,0 +1,19 @@
+//! Tests for the runtime introspection of libxml2
+//!
+use libxml::runtime::Feature;
+
+#[test]
+fn runtime_info_reports_the_loaded_library() {
+  let info = libxml::runtime_info();
+  assert!(info.version.starts_with("2."));
+  assert_eq!(info.version_number / 10000, 2);
+  assert!(info.version_number >= 20900);
+  assert!(info.bindings_version.starts_with("2."));
+
+  // Every build has the tree API and XPath, which the wrapper relies on
+  assert!(info.has_feature(Feature::Tree));
+  assert!(info.has_feature(Feature::XPath));
+  assert!(info.missing_features(&[Feature::Tree, Feature::Output]).is_empty());
+  assert!(info.features().iter().all(|feature| info.has_feature(*feature)));
+  assert!(info.to_string().starts_with(&format!("libxml2 {} (", info.version)));
+}
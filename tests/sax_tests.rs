@@ -0,0 +1,132 @@
+//! Tests for SAX parsing
+//!
+use libxml::parser::{ParserOptions, XmlParseError};
+use libxml::sax::{self, Attribute, NamespaceDeclaration, QName, SaxHandler};
+
+/// Records the events as strings
+#[derive(Default)]
+struct Recorder {
+  events: Vec<String>,
+  errors: usize,
+}
+
+fn qname(name: &QName) -> String {
+  match (name.prefix, name.namespace) {
+    (Some(prefix), Some(ns)) => format!("{prefix}:{}{{{ns}}}", name.local_name),
+    (None, Some(ns)) => format!("{}{{{ns}}}", name.local_name),
+    _ => name.local_name.to_string(),
+  }
+}
+
+impl SaxHandler for Recorder {
+  fn start_document(&mut self) {
+    self.events.push("start".into());
+  }
+
+  fn end_document(&mut self) {
+    self.events.push("end".into());
+  }
+
+  fn start_element(
+    &mut self,
+    name: QName,
+    attributes: &[Attribute],
+    namespaces: &[NamespaceDeclaration],
+  ) {
+    let mut event = format!("<{}", qname(&name));
+    for ns in namespaces {
+      event.push_str(&format!(" xmlns:{}={}", ns.prefix.unwrap_or(""), ns.uri));
+    }
+    for attr in attributes {
+      event.push_str(&format!(" {}={:?}", qname(&attr.name), attr.value));
+    }
+    self.events.push(event + ">");
+  }
+
+  fn end_element(&mut self, name: QName) {
+    self.events.push(format!("</{}>", qname(&name)));
+  }
+
+  fn characters(&mut self, text: &str) {
+    // Merge text split over several calls
+    match self.events.last_mut() {
+      Some(last) if last.starts_with('"') => {
+        last.pop();
+        last.push_str(text);
+        last.push('"');
+      }
+      _ => self.events.push(format!("\"{text}\"")),
+    }
+  }
+
+  fn comment(&mut self, text: &str) {
+    self.events.push(format!("<!--{text}-->"));
+  }
+
+  fn processing_instruction(&mut self, target: &str, data: Option<&str>) {
+    self.events.push(format!("<?{target} {}?>", data.unwrap_or("")));
+  }
+
+  fn error(&mut self, _error: libxml::error::StructuredError) {
+    self.errors += 1;
+  }
+}
+
+#[test]
+fn events_in_document_order() {
+  let xml = r#"<?xml version="1.0"?>
+<?style sheet?><feed xmlns="urn:feed" xmlns:x="urn:x"><!-- note --><item x:id="1" kind="a &amp; b">one <![CDATA[& two]]></item></feed>"#;
+  let mut recorder = Recorder::default();
+  sax::parse(xml.as_bytes(), &mut recorder).unwrap();
+  assert_eq!(
+    recorder.events,
+    [
+      "start",
+      "<?style sheet?>",
+      "<feed{urn:feed} xmlns:=urn:feed xmlns:x=urn:x>",
+      "<!-- note -->",
+      r#"<item{urn:feed} x:id{urn:x}="1" kind="a & b">"#,
+      "\"one & two\"",
+      "</item{urn:feed}>",
+      "</feed{urn:feed}>",
+      "end",
+    ]
+  );
+  assert_eq!(recorder.errors, 0);
+}
+
+#[test]
+fn large_input_is_streamed() {
+  let items = 20_000;
+  let xml = format!("<list>{}</list>", "<item>text</item>".repeat(items));
+
+  #[derive(Default)]
+  struct Counter(usize);
+  impl SaxHandler for Counter {
+    fn start_element(&mut self, name: QName, _: &[Attribute], _: &[NamespaceDeclaration]) {
+      if name.local_name == "item" {
+        self.0 += 1;
+      }
+    }
+  }
+  let mut counter = Counter::default();
+  sax::parse(xml.as_bytes(), &mut counter).unwrap();
+  assert_eq!(counter.0, items);
+}
+
+#[test]
+fn malformed_input() {
+  let xml = "<a><b></a>";
+  let mut recorder = Recorder::default();
+  sax::parse(xml.as_bytes(), &mut recorder).unwrap();
+  assert!(recorder.errors > 0);
+
+  let strict = ParserOptions {
+    recover: false,
+    ..ParserOptions::default()
+  };
+  let mut recorder = Recorder::default();
+  let result = sax::parse_with_options(xml.as_bytes(), &mut recorder, strict);
+  assert!(matches!(result, Err(XmlParseError::Malformed(_))));
+  assert!(recorder.errors > 0);
+}
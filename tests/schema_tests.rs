@@ -1,6 +1,8 @@
 //!
 //! Test Schema Loading, XML Validating
 //!
+#![cfg(feature = "schemas")]
+
 use libxml::schemas::SchemaParserContext;
 use libxml::schemas::SchemaValidationContext;
 
@@ -0,0 +1,67 @@
+//! Tests for binary document snapshots
+//!
+use libxml::parser::Parser;
+use libxml::tree::{Document, SnapshotError};
+
+#[test]
+fn snapshot_round_trip() {
+  let xml = r#"<?xml version="1.0" encoding="ISO-8859-1" standalone="yes"?>
+<!DOCTYPE catalog [
+  <!ENTITY company "Example &amp; Co">
+  <!ATTLIST book id ID #IMPLIED>
+]>
+<?xml-stylesheet href="style.css"?>
+<!-- catalog of books -->
+<catalog xmlns="urn:catalog" xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <book id="b1" xml:lang="en" dc:format="paper">
+    <dc:title>Caf&#233; &company;</dc:title>
+    <notes><![CDATA[<b>bold</b> & more]]></notes>
+    <other xmlns="urn:other" attr="1"><inner/></other>
+  </book>
+</catalog>
+"#;
+  let doc = Parser::default().parse_string(xml).unwrap();
+  let snapshot = doc.dump_snapshot();
+  let loaded = Document::load_snapshot(&snapshot).unwrap();
+  assert_eq!(loaded.to_string(), doc.to_string());
+
+  let root = loaded.get_root_element().unwrap();
+  let book = root.get_first_element_child().unwrap();
+  assert_eq!(book.get_namespace().unwrap().get_href(), "urn:catalog");
+  assert_eq!(
+    book.get_property_ns("format", "http://purl.org/dc/elements/1.1/").as_deref(),
+    Some("paper")
+  );
+  let title = book.get_first_element_child().unwrap();
+  assert_eq!(title.get_content(), "Café Example & Co");
+
+  // Strings are shared, the snapshot of a repetitive document is smaller than its markup
+  let mut repetitive = String::from("<list>");
+  for _ in 0..100 {
+    repetitive.push_str("<item kind=\"entry\">same text</item>");
+  }
+  repetitive.push_str("</list>");
+  let doc = Parser::default().parse_string(&repetitive).unwrap();
+  assert!(doc.dump_snapshot().len() < repetitive.len() / 3);
+}
+
+#[test]
+fn snapshot_errors() {
+  assert_eq!(
+    Document::load_snapshot(b"<root/>").err(),
+    Some(SnapshotError::NotASnapshot)
+  );
+  let doc = Parser::default().parse_string("<a><b>text</b></a>").unwrap();
+  let mut snapshot = doc.dump_snapshot();
+  snapshot.pop();
+  assert_eq!(
+    Document::load_snapshot(&snapshot).err(),
+    Some(SnapshotError::Corrupt)
+  );
+  snapshot = doc.dump_snapshot();
+  snapshot[7] = 99;
+  assert_eq!(
+    Document::load_snapshot(&snapshot).err(),
+    Some(SnapshotError::UnsupportedVersion(99))
+  );
+}
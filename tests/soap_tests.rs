@@ -0,0 +1,130 @@
+//! Tests for the SOAP envelope helpers
+//!
+#![cfg(feature = "soap")]
+
+use libxml::soap::{Envelope, Fault, FaultCode, SoapError, SoapVersion};
+use libxml::tree::{Namespace, Node};
+
+#[test]
+fn build_envelope_with_header_and_body() {
+  let mut envelope = Envelope::new(SoapVersion::Soap12).unwrap();
+  assert!(envelope.header().is_none());
+
+  let doc = envelope.document();
+  let mut block = Node::new("Auth", None, doc).unwrap();
+  let ns = Namespace::new("a", "urn:auth", &mut block).unwrap();
+  block.set_namespace(&ns).unwrap();
+  let mut payload = Node::new("Ping", None, doc).unwrap();
+  envelope.add_header_block(&mut block).unwrap();
+  envelope.add_body_element(&mut payload).unwrap();
+
+  assert_eq!(
+    envelope
+      .document()
+      .node_to_string(&envelope.document().get_root_element().unwrap()),
+    r#"<soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope"><soap:Header><a:Auth xmlns:a="urn:auth"/></soap:Header><soap:Body><Ping/></soap:Body></soap:Envelope>"#
+  );
+  assert_eq!(envelope.header_blocks().len(), 1);
+  assert_eq!(envelope.body_elements()[0].get_name(), "Ping");
+  assert_eq!(envelope.version().content_type(), "application/soap+xml");
+}
+
+#[test]
+fn reject_non_envelopes() {
+  assert!(matches!(
+    Envelope::parse("<Envelope/>"),
+    Err(SoapError::NotAnEnvelope)
+  ));
+  assert!(matches!(
+    Envelope::parse(r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"/>"#),
+    Err(SoapError::MissingBody)
+  ));
+}
+
+#[test]
+fn parse_soap11_fault() {
+  let envelope = Envelope::parse(
+    r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><s:Fault>
+      <faultcode>s:Client.Authentication</faultcode>
+      <faultstring>Bad credentials</faultstring>
+      <faultactor>urn:gateway</faultactor>
+      <detail><e:info xmlns:e="urn:e">expired</e:info></detail>
+    </s:Fault></s:Body></s:Envelope>"#,
+  )
+  .unwrap();
+  assert_eq!(envelope.version(), SoapVersion::Soap11);
+  let fault = envelope.fault().unwrap();
+  assert_eq!(fault.code, FaultCode::Sender);
+  assert_eq!(fault.subcodes, vec!["Authentication".to_string()]);
+  assert_eq!(fault.reason, "Bad credentials");
+  assert_eq!(fault.role.as_deref(), Some("urn:gateway"));
+  assert_eq!(
+    fault.detail.as_deref(),
+    Some(r#"<e:info xmlns:e="urn:e">expired</e:info>"#)
+  );
+}
+
+#[test]
+fn soap12_fault_roundtrip() {
+  let mut envelope = Envelope::new(SoapVersion::Soap12).unwrap();
+  let fault = Fault {
+    code: FaultCode::Receiver,
+    subcodes: vec!["Timeout".to_string()],
+    reason: "Backend did not answer".to_string(),
+    node: Some("urn:node".to_string()),
+    role: None,
+    detail: Some("<retry>5</retry>".to_string()),
+  };
+  envelope.set_fault(&fault).unwrap();
+  let serialized = envelope.document().to_string();
+  assert!(serialized.contains("<soap:Value>soap:Receiver</soap:Value>"));
+  assert!(serialized.contains(r#"<soap:Text xml:lang="en">Backend did not answer</soap:Text>"#));
+
+  let reparsed = Envelope::parse(serialized).unwrap();
+  assert_eq!(reparsed.fault(), Some(fault));
+}
+
+#[test]
+fn malformed_fault_detail_keeps_body() {
+  let mut envelope = Envelope::new(SoapVersion::Soap11).unwrap();
+  let mut payload = Node::new("payload", None, envelope.document()).unwrap();
+  envelope.add_body_element(&mut payload).unwrap();
+  for detail in ["<open>", "</detail><extra/>", "a & b"] {
+    let mut fault = Fault::new(FaultCode::Sender, "Bad request");
+    fault.detail = Some(detail.to_string());
+    assert!(matches!(envelope.set_fault(&fault), Err(SoapError::InvalidDetail(_))));
+    assert_eq!(envelope.body_elements().len(), 1);
+    assert_eq!(envelope.fault(), None);
+  }
+
+  envelope.body().unwrap().unlink();
+  assert!(matches!(envelope.body(), Err(SoapError::MissingBody)));
+  assert!(envelope.body_elements().is_empty());
+  let fault = Fault::new(FaultCode::Sender, "Bad request");
+  assert!(matches!(envelope.set_fault(&fault), Err(SoapError::MissingBody)));
+}
+
+#[test]
+fn must_understand_headers() {
+  let envelope = Envelope::parse(
+    r#"<env:Envelope xmlns:env="http://www.w3.org/2003/05/soap-envelope"><env:Header>
+      <t:Transaction xmlns:t="urn:tx" env:mustUnderstand="true">5</t:Transaction>
+      <l:Log xmlns:l="urn:log" env:mustUnderstand="1" env:role="urn:logger"/>
+      <h:Hint xmlns:h="urn:hint"/>
+    </env:Header><env:Body/></env:Envelope>"#,
+  )
+  .unwrap();
+  assert_eq!(envelope.must_understand_blocks(&[]).len(), 1);
+  assert_eq!(envelope.must_understand_blocks(&["urn:logger"]).len(), 2);
+
+  assert!(
+    envelope
+      .check_must_understand(&[], &[("urn:tx", "Transaction")])
+      .is_none()
+  );
+  let fault = envelope
+    .check_must_understand(&["urn:logger"], &[("urn:tx", "Transaction")])
+    .unwrap();
+  assert_eq!(fault.code, FaultCode::MustUnderstand);
+  assert!(fault.reason.contains("{urn:log}Log"));
+}
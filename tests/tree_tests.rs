@@ -1,8 +1,13 @@
 //! Tree module tests
 //!
 
-use libxml::parser::Parser;
-use libxml::tree::{Document, Namespace, Node, NodeType};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use libxml::parser::{Parser, ParserOptions};
+use libxml::tree::{
+  Attribute, AttributeError, BuildEvent, Document, DocumentFragment, EqualityOptions, Mutation,
+  Namespace, Node, NodeType};
 
 #[test]
 /// Root node and first child of root node are different
@@ -598,3 +603,1145 @@ fn can_replace_child() {
     "document initialized correctly."
   );
 }
+
+#[test]
+/// Strip the whitespace between elements
+fn can_strip_ignorable_whitespace() {
+  let xml = "<list>\n  <item>\n    <name> a </name>\n    <empty>  </empty>\n  </item>\n  <!-- note -->\n  <p>Some <b>bold</b> <i>text</i></p>\n  <pre xml:space=\"preserve\">\n    <line/>\n  </pre>\n</list>";
+  let parser = Parser::default();
+  let mut doc = parser.parse_string(xml).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_child_nodes().len(), 9);
+
+  assert_eq!(doc.strip_ignorable_whitespace(), 8);
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_child_nodes().len(), 4);
+  assert_eq!(
+    doc.node_to_string(&root),
+    "<list><item><name> a </name><empty>  </empty></item><!-- note --><p>Some <b>bold</b> <i>text</i></p><pre xml:space=\"preserve\">\n    <line/>\n  </pre></list>"
+  );
+  assert_eq!(doc.strip_ignorable_whitespace(), 0);
+
+  let options = ParserOptions {
+    no_blanks: true,
+    ..ParserOptions::default()
+  };
+  let doc = parser.parse_string_with_options(xml, options).unwrap();
+  assert_eq!(doc.get_root_element().unwrap().get_child_nodes().len(), 4);
+}
+
+#[test]
+/// Element trees can be built in one expression
+fn can_build_elements() {
+  let mut doc = Document::new().unwrap();
+  let root = Node::build("feed")
+    .ns("", "http://www.w3.org/2005/Atom")
+    .child(Node::build("title").text("News"))
+    .children((1..=2).map(|i| {
+      Node::build("entry")
+        .attr("id", &i.to_string())
+        .child(Node::build("extra").ns("x", "urn:x").text("a & b"))
+    }))
+    .into_root(&mut doc)
+    .unwrap();
+  assert_eq!(
+    doc.node_to_string(&root),
+    concat!(
+      r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>News</title>"#,
+      r#"<entry id="1"><x:extra xmlns:x="urn:x">a &amp; b</x:extra></entry>"#,
+      r#"<entry id="2"><x:extra xmlns:x="urn:x">a &amp; b</x:extra></entry></feed>"#
+    )
+  );
+  let entries = root.get_child_elements();
+  assert_eq!(
+    entries[1].get_namespace().unwrap().get_href(),
+    "http://www.w3.org/2005/Atom"
+  );
+  assert_eq!(entries[1].get_attribute("id").as_deref(), Some("1"));
+
+  drop(entries);
+  let mut title = root.get_first_child().unwrap();
+  let added = Node::build("sub").text("t").append_to(&mut title).unwrap();
+  assert_eq!(added.get_parent().unwrap(), title);
+  assert_eq!(title.get_content(), "Newst");
+}
+
+#[test]
+/// Parsed nodes know the line they come from
+fn node_line_numbers() {
+  let parser = Parser::default();
+  let doc = parser.parse_string("<root>\n  <a/>\n\n  <b>text</b>\n</root>").unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_line_number(), Some(1));
+  let children = root.get_child_elements();
+  assert_eq!(children[0].get_line_number(), Some(2));
+  assert_eq!(children[1].get_line_number(), Some(4));
+  assert_eq!(doc.get_root_readonly().unwrap().get_line_number(), Some(1));
+  let built = Node::new("new", None, &doc).unwrap();
+  assert_eq!(built.get_line_number(), None);
+
+  let far = format!("<root>{}<far>text</far></root>", "\n".repeat(70_000));
+  let far_line = |options| {
+    let doc = parser.parse_string_with_options(&far, options).unwrap();
+    let root = doc.get_root_element().unwrap();
+    root.get_first_element_child().unwrap().get_line_number()
+  };
+  assert_eq!(far_line(ParserOptions::default()), Some(65_535));
+  let big_lines = ParserOptions {
+    big_lines: true,
+    ..ParserOptions::default()
+  };
+  assert_eq!(far_line(big_lines), Some(70_001));
+}
+
+#[test]
+/// Nodes can tell where they are in their document
+fn node_paths() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<root><items><item/><item/><item a="1">text</item></items><d xmlns="urn:d"/></root>"#)
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_path().as_deref(), Some("/root"));
+  let items = root.get_first_element_child().unwrap();
+  let third = items.get_last_element_child().unwrap();
+  assert_eq!(third.get_path().as_deref(), Some("/root/items/item[3]"));
+  assert_eq!(
+    third.get_first_child().unwrap().get_path().as_deref(),
+    Some("/root/items/item[3]/text()")
+  );
+  assert_eq!(
+    root.get_last_element_child().unwrap().get_path().as_deref(),
+    Some("/root/*[2]")
+  );
+  assert_eq!(
+    doc.get_root_readonly().unwrap().get_path().as_deref(),
+    Some("/root")
+  );
+}
+
+#[test]
+/// Descendants are visited lazily, in document order and within the subtree
+fn node_descendants() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string("<root><a><b>one</b><!--c--><d/></a><e>two</e></root>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let names: Vec<String> = root.descendants().map(|node| node.get_name()).collect();
+  assert_eq!(names, ["a", "b", "text", "comment", "d", "e", "text"]);
+
+  let a = root.get_first_element_child().unwrap();
+  let names: Vec<String> = a.descendants().map(|node| node.get_name()).collect();
+  assert_eq!(names, ["b", "text", "comment", "d"]);
+  let leaf = a.get_last_element_child().unwrap();
+  assert_eq!(leaf.descendants().count(), 0);
+
+  // Stopping early and resuming where it left off
+  let mut descendants = root.descendants();
+  let found = descendants.find(|node| node.get_name() == "d").unwrap();
+  assert_eq!(found.get_name(), "d");
+  assert_eq!(descendants.next().unwrap().get_name(), "e");
+  assert_eq!(descendants.next().unwrap().get_content(), "two");
+  assert!(descendants.next().is_none());
+  assert!(descendants.next().is_none());
+}
+
+#[test]
+/// Removing the node just visited goes on after it, without freeing it under the walk
+fn node_descendants_removed_while_walking() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string("<root><x><b/></x><a><x>one</x><c/></a><x/><d>two</d></root>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let mut names = Vec::new();
+  for mut node in root.descendants() {
+    names.push(node.get_name());
+    if node.get_name() == "x" {
+      node.unlink();
+    }
+  }
+  assert_eq!(names, ["x", "a", "x", "c", "x", "d", "text"]);
+  assert_eq!(doc.node_to_string(&root), "<root><a><c/></a><d>two</d></root>");
+
+  // Removing the node coming next is seen, as is inserting one
+  let mut descendants = root.descendants();
+  let mut a = descendants.next().unwrap();
+  a.get_first_child().unwrap().unlink();
+  a.add_child(&mut Node::new("e", None, &doc).unwrap()).unwrap();
+  let names: Vec<String> = descendants.map(|node| node.get_name()).collect();
+  assert_eq!(names, ["e", "d", "text"]);
+}
+
+#[test]
+/// The axes around a node are walked lazily, in the order of XPath
+fn node_axes() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<root><a><a1/><a2/></a><b id="b"><b1/><b2/><b3/></b><c><c1/></c></root>"#)
+    .unwrap();
+  let names = |nodes: &mut dyn Iterator<Item = Node>| nodes.map(|node| node.get_name()).collect::<Vec<_>>();
+  let b = doc.get_root_element().unwrap().findnodes("b").unwrap().remove(0);
+  let b2 = b.findnodes("b2").unwrap().remove(0);
+  let id = b.get_property_node("id").unwrap();
+
+  let ancestors: Vec<Node> = b2.ancestors().collect();
+  assert_eq!(ancestors.len(), 3);
+  assert_eq!(ancestors[0], b);
+  assert_eq!(ancestors[2].get_type(), Some(NodeType::DocumentNode));
+  assert_eq!(id.ancestors().next(), Some(b.clone()));
+
+  assert_eq!(names(&mut b2.following_siblings()), ["b3"]);
+  assert_eq!(names(&mut b2.preceding_siblings()), ["b1"]);
+  assert_eq!(names(&mut b.preceding_siblings()), ["a"]);
+  assert_eq!(id.following_siblings().count(), 0);
+
+  assert_eq!(names(&mut b.following()), ["c", "c1"]);
+  assert_eq!(names(&mut b2.following()), ["b3", "c", "c1"]);
+  assert_eq!(names(&mut id.following()), ["b1", "b2", "b3", "c", "c1"]);
+  assert_eq!(names(&mut b.preceding()), ["a2", "a1", "a"]);
+  assert_eq!(names(&mut b2.preceding()), ["b1", "a2", "a1", "a"]);
+  assert_eq!(names(&mut id.preceding()), ["a2", "a1", "a"]);
+
+  // Nodes inserted ahead of the traversal are visited
+  let mut following = b2.following();
+  assert_eq!(following.next().unwrap().get_name(), "b3");
+  let mut c = following.next().unwrap();
+  c.add_child(&mut Node::new("c2", None, &doc).unwrap()).unwrap();
+  assert_eq!(names(&mut following), ["c1", "c2"]);
+}
+
+#[test]
+/// Attribute values parse into typed values
+fn typed_attributes() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<config port=" 8080 " ratio="2.5e-1" debug="0" verbose="yes" mode="fast"/>"#)
+    .unwrap();
+  let config = doc.get_root_element().unwrap();
+  assert_eq!(config.attr_i64("port"), Ok(8080));
+  assert_eq!(config.attr_f64("ratio"), Ok(0.25));
+  assert_eq!(config.attr_bool("debug"), Ok(false));
+  assert_eq!(config.get_attribute_as::<String>("mode").as_deref(), Ok("fast"));
+  assert_eq!(config.get_attribute_as::<u16>("port").ok(), None);
+
+  assert_eq!(
+    config.attr_i64("timeout"),
+    Err(AttributeError::Missing("timeout".to_owned()))
+  );
+  match config.attr_bool("verbose") {
+    Err(AttributeError::Invalid { name, value, .. }) => {
+      assert_eq!(name, "verbose");
+      assert_eq!(value, "yes");
+    }
+    other => panic!("expected an invalid value, got {other:?}"),
+  }
+  let error = config.attr_f64("mode").unwrap_err();
+  assert!(error.to_string().starts_with(r#"invalid value "fast" of attribute "mode""#));
+
+  let config = doc.get_root_readonly().unwrap();
+  assert_eq!(config.attr_i64("port"), Ok(8080));
+  assert_eq!(config.get_attribute_as::<char>("debug"), Ok('0'));
+}
+
+#[test]
+/// Attributes are listed in order with their namespaces
+fn node_attributes_iterator() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(
+      r#"<root xmlns="urn:default" xmlns:x="urn:x" z="last &amp; least" x:a="1" a="2"/>"#,
+    )
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let attributes: Vec<Attribute> = root.attributes().collect();
+  assert_eq!(
+    attributes,
+    [
+      Attribute {
+        local_name: "z".to_owned(),
+        prefix: None,
+        namespace: None,
+        value: "last & least".to_owned(),
+      },
+      Attribute {
+        local_name: "a".to_owned(),
+        prefix: Some("x".to_owned()),
+        namespace: Some("urn:x".to_owned()),
+        value: "1".to_owned(),
+      },
+      Attribute {
+        local_name: "a".to_owned(),
+        prefix: None,
+        namespace: None,
+        value: "2".to_owned(),
+      },
+    ]
+  );
+  assert_eq!(attributes[1].qualified_name(), "x:a");
+  assert_eq!(attributes[2].qualified_name(), "a");
+  let text = Node::new_text("text", &doc).unwrap();
+  assert_eq!(text.attributes().count(), 0);
+  let attribute = root.get_attribute_node("z").unwrap();
+  assert_eq!(attribute.attributes().count(), 0);
+}
+
+#[test]
+/// Namespace declarations are reconciled and cleaned up after moving nodes
+fn namespace_cleanup() {
+  let parser = Parser::default();
+  let mut doc = parser
+    .parse_string(r#"<r xmlns:a="urn:a"><s xmlns:b="urn:b"><b:x/></s><t xmlns:a="urn:a" xmlns:c="urn:c"><a:y/></t></r>"#)
+    .unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  // Moved out of the scope of its namespace declaration
+  let mut x = root.findnodes("s/*").unwrap().remove(0);
+  x.unlink();
+  root.add_child(&mut x).unwrap();
+  drop(x);
+  root.reconcile_namespaces().unwrap();
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<r xmlns:a="urn:a" xmlns:b="urn:b"><s xmlns:b="urn:b"/><t xmlns:a="urn:a" xmlns:c="urn:c"><a:y/></t><b:x/></r>"#
+  );
+  drop(root);
+
+  // The redundant xmlns:a, unused xmlns:c and the xmlns:b no longer used on s
+  assert_eq!(doc.cleanup_namespaces(), 3);
+  assert_eq!(
+    doc.node_to_string(&doc.get_root_element().unwrap()),
+    r#"<r xmlns:a="urn:a" xmlns:b="urn:b"><s/><t><a:y/></t><b:x/></r>"#
+  );
+  assert_eq!(doc.cleanup_namespaces(), 0);
+}
+
+#[test]
+/// In-scope namespaces resolve by prefix and by URI
+fn namespace_lookups() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<r xmlns="urn:d" xmlns:p="urn:p" xmlns:q="urn:d"><s xmlns:p="urn:other"><t/></s><u xmlns=""/></r>"#)
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let t = root.findnodes("//*[local-name()='t']").unwrap().remove(0);
+  assert_eq!(t.lookup_namespace_uri("p").as_deref(), Some("urn:other"));
+  assert_eq!(t.lookup_namespace_uri("").as_deref(), Some("urn:d"));
+  assert_eq!(t.lookup_namespace_uri("x"), None);
+  // The default namespace is also bound to q
+  assert_eq!(t.lookup_prefix("urn:d").as_deref(), Some("q"));
+  assert_eq!(t.lookup_prefix("urn:other").as_deref(), Some("p"));
+  // Shadowed by the nearer p
+  assert_eq!(t.lookup_prefix("urn:p"), None);
+  assert_eq!(root.lookup_prefix("urn:p").as_deref(), Some("p"));
+
+  let u = root.get_last_element_child().unwrap();
+  assert_eq!(u.lookup_namespace_uri(""), None);
+  assert_eq!(u.lookup_prefix("urn:d").as_deref(), Some("q"));
+
+  let t = doc.get_root_readonly().unwrap().findnodes("//*[local-name()='t']", &doc).unwrap()[0];
+  assert_eq!(t.lookup_namespace_uri("").as_deref(), Some("urn:d"));
+  assert_eq!(t.lookup_prefix("urn:p"), None);
+}
+
+#[test]
+/// CDATA sections are created and serialized unescaped
+fn cdata_sections() {
+  let parser = Parser::default();
+  let input = "<config><script><![CDATA[if (a < b && c) { run(); }]]></script></config>";
+  let doc = parser.parse_string(input).unwrap();
+  let root = doc.get_root_element().unwrap();
+  let section = root.get_first_element_child().unwrap().get_first_child().unwrap();
+  assert!(section.is_cdata());
+  assert!(!section.is_text_node());
+  assert_eq!(section.get_content(), "if (a < b && c) { run(); }");
+  assert_eq!(doc.node_to_string(&root), input);
+  assert!(doc.get_root_readonly().unwrap().get_first_child().unwrap().get_first_child().unwrap().is_cdata());
+
+  let mut doc = Document::new().unwrap();
+  let mut query = Node::new("query", None, &doc).unwrap();
+  doc.set_root_element(&query);
+  let mut sql = doc
+    .create_cdata_section("SELECT * FROM t WHERE a < 1 AND b = ']]>'")
+    .unwrap();
+  assert!(sql.is_cdata());
+  query.add_child(&mut sql).unwrap();
+  let serialized = doc.node_to_string(&query);
+  assert_eq!(
+    serialized,
+    "<query><![CDATA[SELECT * FROM t WHERE a < 1 AND b = ']]]]><![CDATA[>']]></query>"
+  );
+  let reparsed = parser.parse_string(&serialized).unwrap();
+  assert_eq!(
+    reparsed.get_root_element().unwrap().get_content(),
+    "SELECT * FROM t WHERE a < 1 AND b = ']]>'"
+  );
+}
+
+#[test]
+/// Fragments collect sibling nodes and splice them into a parent in order
+fn document_fragments() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string("<root><a/>text<b><moved/></b>tail</root>")
+    .unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  let mut fragment = DocumentFragment::new(&doc).unwrap();
+  assert!(fragment.is_empty());
+  let mut text = Node::new_text(" more", &doc).unwrap();
+  fragment.append(&mut text).unwrap();
+  let mut moved = root.findnodes("b/moved").unwrap().remove(0);
+  fragment.append(&mut moved).unwrap();
+  let mut c = Node::new("c", None, &doc).unwrap();
+  fragment.append(&mut c).unwrap();
+  assert_eq!(fragment.len(), 3);
+  assert_eq!(doc.node_to_string(&root), "<root><a/>text<b/>tail</root>");
+
+  let mut attribute = root.get_first_element_child().unwrap();
+  attribute.set_attribute("x", "1").unwrap();
+  let mut attribute = attribute.get_attribute_node("x").unwrap();
+  assert!(fragment.append(&mut attribute).is_err());
+  let other = Document::new().unwrap();
+  let mut foreign = Node::new("foreign", None, &other).unwrap();
+  assert!(fragment.append(&mut foreign).is_err());
+
+  let mut b = root.get_last_element_child().unwrap();
+  let mut first = DocumentFragment::new(&doc).unwrap();
+  let mut leading = Node::new_text("leading", &doc).unwrap();
+  first.append(&mut leading).unwrap();
+  let inserted = first.append_to(&mut b).unwrap();
+  assert_eq!(inserted.len(), 1);
+
+  let inserted = fragment.append_to(&mut root).unwrap();
+  assert_eq!(inserted.len(), 3);
+  assert_eq!(inserted[1], moved);
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<root><a x="1"/>text<b>leading</b>tail more<moved/><c/></root>"#
+  );
+  // The text node spliced next to another is not merged into it
+  assert_eq!(text.get_content(), " more");
+  assert_eq!(text.get_prev_sibling().unwrap().get_content(), "tail");
+
+  let mut rejected = DocumentFragment::new(&doc).unwrap();
+  let mut d = Node::new("d", None, &doc).unwrap();
+  rejected.append(&mut d).unwrap();
+  let mut inner = Node::new("inner", None, &doc).unwrap();
+  d.add_child(&mut inner).unwrap();
+  assert!(rejected.append_to(&mut inner).is_err());
+  let mut document_node = root.get_parent().unwrap();
+  let mut rejected = DocumentFragment::new(&doc).unwrap();
+  rejected.append(&mut Node::new("e", None, &doc).unwrap()).unwrap();
+  assert!(rejected.append_to(&mut document_node).is_err());
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<root><a x="1"/>text<b>leading</b>tail more<moved/><c/></root>"#
+  );
+}
+
+#[test]
+/// Nodes are copied or moved between documents with their namespaces
+fn import_and_adopt_nodes() {
+  let parser = Parser::default();
+  let source = parser
+    .parse_string(
+      r#"<s:root xmlns:s="urn:s" xmlns:x="urn:x"><s:item x:a="1"><s:leaf xmlns:t="urn:t" t:b="2">text</s:leaf></s:item></s:root>"#,
+    )
+    .unwrap();
+  let mut target = parser.parse_string("<target/>").unwrap();
+  let mut target_root = target.get_root_element().unwrap();
+  let item = source.get_root_element().unwrap().get_first_element_child().unwrap();
+
+  let mut copy = target.import_node(&item, true).unwrap();
+  target_root.add_child(&mut copy).unwrap();
+  let mut shallow = target.import_node(&item, false).unwrap();
+  target_root.add_child(&mut shallow).unwrap();
+  assert_eq!(
+    target.node_to_string(&target_root),
+    r#"<target><s:item xmlns:s="urn:s" xmlns:x="urn:x" x:a="1"><s:leaf xmlns:t="urn:t" t:b="2">text</s:leaf></s:item><s:item xmlns:s="urn:s" xmlns:x="urn:x" x:a="1"/></target>"#
+  );
+  drop(item);
+
+  let mut item = source.get_root_element().unwrap().get_first_element_child().unwrap();
+  let leaf = item.get_first_element_child().unwrap();
+  target.adopt_node(&mut item).unwrap();
+  assert_eq!(
+    source.node_to_string(&source.get_root_element().unwrap()),
+    r#"<s:root xmlns:s="urn:s" xmlns:x="urn:x"/>"#
+  );
+  drop(source);
+  target_root.add_child(&mut item).unwrap();
+  assert_eq!(leaf.get_parent().unwrap(), item);
+  assert_eq!(leaf.get_content(), "text");
+  assert_eq!(
+    target.node_to_string(&target_root.get_last_element_child().unwrap()),
+    r#"<s:item xmlns:s="urn:s" xmlns:x="urn:x" x:a="1"><s:leaf xmlns:t="urn:t" t:b="2">text</s:leaf></s:item>"#
+  );
+  assert!(target.adopt_node(&mut target.as_node()).is_err());
+}
+
+#[test]
+/// Subtrees compare equal by content, whatever their prefixes and attribute order
+fn deep_equality() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<a:r xmlns:a="urn:a" x="1" y="2"><b>te<![CDATA[xt]]></b><!--note--><c/></a:r>"#)
+    .unwrap();
+  let same = parser
+    .parse_string(r#"<p:r xmlns:p="urn:a" y="2" x="1"><b>text</b><!--note--><c></c></p:r>"#)
+    .unwrap();
+  let spaced = parser
+    .parse_string("<p:r xmlns:p=\"urn:a\" y=\"2\" x=\"1\">\n  <b> text </b>\n  <c/>\n</p:r>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let strict = EqualityOptions::default();
+  let lenient = EqualityOptions {
+    ignore_whitespace: true,
+    ignore_comments: true,
+  };
+  assert!(root.deep_equals(&same.get_root_element().unwrap(), &strict));
+  assert!(doc.equivalent_to(&same));
+  assert!(!doc.equivalent_to(&spaced));
+  assert!(!root.deep_equals(&spaced.get_root_element().unwrap(), &strict));
+  assert!(root.deep_equals(&spaced.get_root_element().unwrap(), &lenient));
+
+  for different in [
+    r#"<p:r xmlns:p="urn:other" x="1" y="2"><b>text</b><!--note--><c/></p:r>"#,
+    r#"<p:r xmlns:p="urn:a" x="1" y="3"><b>text</b><!--note--><c/></p:r>"#,
+    r#"<p:r xmlns:p="urn:a" x="1"><b>text</b><!--note--><c/></p:r>"#,
+    r#"<p:r xmlns:p="urn:a" x="1" y="2"><b>text</b><!--other--><c/></p:r>"#,
+    r#"<p:r xmlns:p="urn:a" x="1" y="2"><b>text</b><!--note--><c>more</c></p:r>"#,
+    r#"<p:r xmlns:p="urn:a" x="1" y="2"><c/><!--note--><b>text</b></p:r>"#,
+  ] {
+    let other = parser.parse_string(different).unwrap();
+    assert!(!doc.equivalent_to(&other), "{different}");
+  }
+  let b = root.get_first_element_child().unwrap();
+  assert!(b.deep_equals(&same.get_root_element().unwrap().get_first_element_child().unwrap(), &strict));
+  assert!(!b.deep_equals(&root, &strict));
+}
+
+#[test]
+/// Children are found by position and nodes know their own
+fn child_positions() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<r id="x">one<a/><!--c--><b/>two<c/></r>"#)
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.nth_child(0).unwrap().get_content(), "one");
+  assert_eq!(root.nth_child(2).unwrap().get_type(), Some(NodeType::CommentNode));
+  assert!(root.nth_child(6).is_none());
+  let names: Vec<_> = (0..4)
+    .map(|i| root.nth_element_child(i).map(|n| n.get_name()))
+    .collect();
+  assert_eq!(
+    names,
+    [Some("a".to_owned()), Some("b".to_owned()), Some("c".to_owned()), None]
+  );
+
+  let b = root.nth_element_child(1).unwrap();
+  assert_eq!(b.index_in_parent(), Some(3));
+  assert_eq!(root.nth_child(0).unwrap().index_in_parent(), Some(0));
+  assert_eq!(root.index_in_parent(), Some(0));
+  assert_eq!(root.get_attribute_node("id").unwrap().index_in_parent(), None);
+  let ro_root = doc.get_root_readonly().unwrap();
+  assert_eq!(ro_root.nth_element_child(2).unwrap().index_in_parent(), Some(5));
+  let unlinked = Node::new("u", None, &doc).unwrap();
+  assert_eq!(unlinked.index_in_parent(), None);
+  assert!(unlinked.nth_child(0).is_none());
+}
+
+#[test]
+/// Normalizing merges adjacent text nodes and drops empty ones
+fn normalize_text_nodes() {
+  let parser = Parser::default();
+  let doc = parser.parse_string("<r><a/><b>x<![CDATA[y]]></b></r>").unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  root.add_text_child(None, "t", "").unwrap();
+  let mut a = root.get_first_element_child().unwrap();
+  // Fragments link text nodes as they are, where add_child would merge them
+  let mut fragment = DocumentFragment::new(&doc).unwrap();
+  for text in ["one", "", " two", "three"] {
+    fragment.append(&mut Node::new_text(text, &doc).unwrap()).unwrap();
+  }
+  fragment.append(&mut Node::new("c", None, &doc).unwrap()).unwrap();
+  fragment.append(&mut Node::new_text("", &doc).unwrap()).unwrap();
+  let mut held = Node::new_text("four", &doc).unwrap();
+  fragment.append(&mut held).unwrap();
+  let mut merged = Node::new_text("five", &doc).unwrap();
+  fragment.append(&mut merged).unwrap();
+  fragment.append_to(&mut a).unwrap();
+  assert_eq!(a.get_child_nodes().len(), 8);
+
+  root.normalize().unwrap();
+  let contents: Vec<_> = a.get_child_nodes().iter().map(|n| n.get_content()).collect();
+  assert_eq!(contents, ["one twothree", "", "fourfive"]);
+  assert_eq!(
+    doc.node_to_string(&root),
+    "<r><a>one twothree<c/>fourfive</a><b>x<![CDATA[y]]></b><t/></r>"
+  );
+  // Text nodes still held are merged into, never away
+  assert_eq!(held.get_content(), "fourfive");
+  assert!(!held.is_unlinked());
+  assert!(merged.is_unlinked());
+}
+
+#[test]
+/// Observers are told of the merges once normalizing is done, and may change the document
+fn normalize_with_observer() {
+  let doc = Parser::default().parse_string("<r><a/><c/></r>").unwrap();
+  let mut a = doc.get_root_element().unwrap().get_first_child().unwrap();
+  let mut fragment = DocumentFragment::new(&doc).unwrap();
+  for text in ["x", "", "y"] {
+    fragment.append(&mut Node::new_text(text, &doc).unwrap()).unwrap();
+  }
+  fragment.append_to(&mut a).unwrap();
+  let handle = doc.clone();
+  let log = Rc::new(Cell::new(0));
+  let count = log.clone();
+  // Every change removes the text node the walk would go on with, had it not finished
+  let id = doc.observe(move |_| {
+    count.set(count.get() + 1);
+    let root = handle.get_root_element().unwrap();
+    if let Some(mut last) = root.get_last_child().filter(|last| last.get_name() == "c") {
+      last.unlink();
+    }
+  });
+  let mut root = doc.get_root_element().unwrap();
+  root.normalize().unwrap();
+  doc.unobserve(id);
+  // The empty text and the merged one removed, the merge itself, and the removal of `c`
+  assert_eq!(log.get(), 4);
+  assert_eq!(doc.node_to_string(&root), "<r><a>xy</a></r>");
+}
+
+#[test]
+/// Text is set as is, and markup is parsed, whatever the node
+fn text_and_raw_xml_content() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<r xmlns:x="urn:x" a="1"><old/></r>"#)
+    .unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  assert!(!root.escapes_content());
+  root.set_text("fish & <chips> &amp;").unwrap();
+  assert_eq!(root.get_content(), "fish & <chips> &amp;");
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<r xmlns:x="urn:x" a="1">fish &amp; &lt;chips&gt; &amp;amp;</r>"#
+  );
+  let mut attribute = root.get_attribute_node("a").unwrap();
+  assert!(!attribute.escapes_content());
+  attribute.set_text("a&b").unwrap();
+  assert_eq!(root.get_attribute("a").as_deref(), Some("a&b"));
+
+  let mut text = root.get_first_child().unwrap();
+  assert!(text.escapes_content());
+  text.set_text("1 < 2").unwrap();
+  assert_eq!(text.get_content(), "1 < 2");
+
+  let added = root.append_raw_xml(" <x:b>bold &amp; <i/></x:b>tail").unwrap();
+  assert_eq!(added.len(), 3);
+  assert_eq!(added[1].get_name(), "b");
+  assert_eq!(added[1].get_namespace().unwrap().get_href(), "urn:x");
+  assert_eq!(added[1].get_content(), "bold & ");
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<r xmlns:x="urn:x" a="a&amp;b">1 &lt; 2 <x:b>bold &amp; <i/></x:b>tail</r>"#
+  );
+  assert!(root.append_raw_xml("<open>").is_err());
+  assert!(text.append_raw_xml("<b/>").is_err());
+  assert_eq!(root.get_child_nodes().len(), 4);
+}
+
+#[test]
+/// Base URIs and xml:space are inherited from ancestors
+fn base_uri_and_space() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(
+      r#"<r xml:base="http://example.com/docs/" xml:space="preserve"><a xml:base="guide/"><b xml:space="default"/></a><c/></r>"#,
+    )
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let mut a = root.get_first_element_child().unwrap();
+  let b = a.get_first_element_child().unwrap();
+  let c = root.get_last_element_child().unwrap();
+  assert_eq!(b.get_base_uri().as_deref(), Some("http://example.com/docs/guide/"));
+  assert_eq!(c.get_base_uri().as_deref(), Some("http://example.com/docs/"));
+  assert_eq!(root.preserves_space(), Some(true));
+  assert_eq!(a.preserves_space(), Some(true));
+  assert_eq!(b.preserves_space(), Some(false));
+  let ro_c = doc.get_root_readonly().unwrap().get_last_element_child().unwrap();
+  assert_eq!(ro_c.preserves_space(), Some(true));
+  assert_eq!(ro_c.get_base_uri().as_deref(), Some("http://example.com/docs/"));
+
+  a.set_base_uri("/other/").unwrap();
+  assert_eq!(b.get_base_uri().as_deref(), Some("http://example.com/other/"));
+  assert_eq!(
+    a.get_attribute_ns("base", "http://www.w3.org/XML/1998/namespace").as_deref(),
+    Some("/other/")
+  );
+  let mut text = Node::new_text("t", &doc).unwrap();
+  assert!(text.set_base_uri("x").is_err());
+
+  let plain = parser.parse_string("<r/>").unwrap();
+  assert_eq!(plain.get_root_element().unwrap().preserves_space(), None);
+}
+
+#[test]
+/// Nodes are replaced, wrapped and unwrapped in place with their namespaces
+fn replace_wrap_and_unwrap() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<r><s xmlns:x="urn:x"><x:a>1</x:a>text<x:b/></s><old/><tail/></r>"#)
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let mut s = root.get_first_element_child().unwrap();
+  let mut old = s.get_next_element_sibling().unwrap();
+
+  let mut new = Node::new("new", None, &doc).unwrap();
+  old.replace_with(&mut new).unwrap();
+  assert!(old.is_unlinked());
+  assert_eq!(new.get_parent(), Some(root.clone()));
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<r><s xmlns:x="urn:x"><x:a>1</x:a>text<x:b/></s><new/><tail/></r>"#
+  );
+
+  // Moving a node out of the scope of its namespace declares it again
+  let mut a = s.get_first_element_child().unwrap();
+  let mut tail = root.get_last_element_child().unwrap();
+  tail.replace_with(&mut a).unwrap();
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<r><s xmlns:x="urn:x">text<x:b/></s><new/><x:a xmlns:x="urn:x">1</x:a></r>"#
+  );
+
+  let mut wrapper = Node::new("w", None, &doc).unwrap();
+  new.wrap_in(&mut wrapper).unwrap();
+  assert_eq!(new.get_parent(), Some(wrapper.clone()));
+  assert_eq!(new.index_in_parent(), Some(0));
+
+  let children = s.unwrap().unwrap();
+  assert_eq!(children.len(), 2);
+  assert!(s.is_unlinked());
+  assert_eq!(
+    doc.node_to_string(&root),
+    r#"<r>text<x:b xmlns:x="urn:x"/><w><new/></w><x:a xmlns:x="urn:x">1</x:a></r>"#
+  );
+
+  let mut w = root.nth_element_child(1).unwrap();
+  let mut inner = w.get_first_child().unwrap();
+  assert!(inner.replace_with(&mut w).is_err());
+  assert!(doc.as_node().replace_with(&mut inner).is_err());
+  assert!(Node::new_text("t", &doc).unwrap().unwrap().is_err());
+  let mut text = root.get_first_child().unwrap();
+  assert!(text.wrap_in(&mut Node::new_text("t", &doc).unwrap()).is_err());
+}
+
+#[test]
+/// Attributes are listed in document order or sorted by name
+fn ordered_attributes() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<r xmlns:x="urn:x" z="3" x:b="2" a="1" xml:lang="en"/>"#)
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+    pairs
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect()
+  };
+  assert_eq!(
+    root.attributes_ordered(),
+    pairs(&[("z", "3"), ("x:b", "2"), ("a", "1"), ("xml:lang", "en")])
+  );
+  assert_eq!(
+    root.attributes_sorted().into_iter().collect::<Vec<_>>(),
+    pairs(&[("a", "1"), ("x:b", "2"), ("xml:lang", "en"), ("z", "3")])
+  );
+  assert!(Node::new_text("t", &doc).unwrap().attributes_sorted().is_empty());
+}
+
+#[test]
+/// Namespaced attributes are set by URI, declaring the namespace when needed
+fn qualified_attributes() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string(r#"<r xmlns:l="http://www.w3.org/1999/xlink" xmlns:p="urn:other"><a/></r>"#)
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let mut a = root.get_first_element_child().unwrap();
+  let xlink = "http://www.w3.org/1999/xlink";
+  // The prefix bound in scope wins over the one asked for
+  a.set_qualified_attribute(xlink, "xlink:href", "#top").unwrap();
+  a.set_qualified_attribute("urn:new", "n:id", "1").unwrap();
+  a.set_qualified_attribute("urn:taken", "p:x", "2").unwrap();
+  a.set_qualified_attribute("urn:anon", "y", "3").unwrap();
+  a.set_qualified_attribute("http://www.w3.org/XML/1998/namespace", "xml:lang", "en").unwrap();
+  assert!(a.set_qualified_attribute("", "z", "4").is_err());
+  assert_eq!(
+    doc.node_to_string(&a),
+    concat!(
+      r##"<a xmlns:n="urn:new" xmlns:ns0="urn:taken" xmlns:ns1="urn:anon" l:href="#top" "##,
+      r#"n:id="1" ns0:x="2" ns1:y="3" xml:lang="en"/>"#
+    )
+  );
+  assert_eq!(a.get_attribute_ns("href", xlink).as_deref(), Some("#top"));
+
+  a.remove_attribute_ns("xlink:href", xlink).unwrap();
+  a.remove_attribute_ns("id", "urn:new").unwrap();
+  assert_eq!(a.get_attribute_ns("href", xlink), None);
+  assert_eq!(a.get_attribute_ns("id", "urn:new"), None);
+  assert_eq!(a.attributes().count(), 3);
+}
+
+#[test]
+/// All children are removed at once, those still held being unlinked
+fn remove_all_children() {
+  let parser = Parser::default();
+  let items = "<item><sub/></item>text".repeat(10_000);
+  let doc = parser.parse_string(format!("<r>{items}<kept/></r>")).unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  let mut kept = root.get_last_child().unwrap();
+  root.remove_children().unwrap();
+  assert!(root.get_first_child().is_none());
+  assert_eq!(doc.node_to_string(&root), "<r/>");
+  assert!(kept.is_unlinked());
+  assert!(kept.get_parent().is_none());
+  root.add_child(&mut kept).unwrap();
+  assert_eq!(doc.node_to_string(&root), "<r><kept/></r>");
+  root.remove_children().unwrap();
+  root.remove_children().unwrap();
+  assert_eq!(doc.node_to_string(&root), "<r/>");
+}
+
+#[test]
+/// Descendants held across a removal stay valid, detached from the freed subtree
+fn remove_children_keeps_held_descendants() {
+  let doc = Parser::default()
+    .parse_string(r#"<r><a><b><deep n="1">x</deep></b></a><c/></r>"#)
+    .unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  let deep = root.descendants().find(|node| node.get_name() == "deep").unwrap();
+  let attribute = deep.get_attribute_node("n").unwrap();
+  root.remove_children().unwrap();
+  // Allocations reuse the freed memory, which the handles must not see
+  let _filler = Parser::default().parse_string("<t>text</t>".repeat(100)).unwrap();
+  assert_eq!(deep.get_name(), "deep");
+  assert_eq!(deep.get_type(), Some(NodeType::ElementNode));
+  assert_eq!(deep.get_content(), "x");
+  assert!(deep.is_unlinked());
+  assert!(deep.get_parent().is_none());
+  assert_eq!(deep.get_attribute("n").as_deref(), Some("1"));
+  assert_eq!(attribute.get_name(), "n");
+  assert_eq!(doc.node_to_string(&root), "<r/>");
+
+  // The same holds for the descendants of an unlinked node when it is dropped
+  let other = Parser::default().parse_string("<r><b><c/></b></r>").unwrap();
+  let mut outer = other.get_root_element().unwrap().get_first_child().unwrap();
+  let inner = outer.get_first_child().unwrap();
+  outer.unlink();
+  drop(outer);
+  let _filler = Parser::default().parse_string("<t>text</t>".repeat(100)).unwrap();
+  assert_eq!(inner.get_name(), "c");
+  assert!(inner.get_parent().is_none());
+}
+
+#[test]
+/// Children are pruned with a predicate
+fn retain_matching_children() {
+  let parser = Parser::default();
+  let doc = parser
+    .parse_string("<body><script>a()</script><p>1</p><!--c--><script/><p>2</p></body>")
+    .unwrap();
+  let mut body = doc.get_root_element().unwrap();
+  let mut script = body.get_first_child().unwrap();
+  let removed = body
+    .retain_children(|child| child.get_name() != "script")
+    .unwrap();
+  assert_eq!(removed, 2);
+  assert!(script.is_unlinked());
+  assert_eq!(doc.node_to_string(&body), "<body><p>1</p><!--c--><p>2</p></body>");
+  assert_eq!(body.retain_children(|child| child.is_element_node()).unwrap(), 1);
+  assert_eq!(doc.node_to_string(&body), "<body><p>1</p><p>2</p></body>");
+  script.set_content("b()").unwrap();
+  assert_eq!(script.get_content(), "b()");
+}
+
+#[test]
+/// Observers changing the document while children are pruned don't disturb the walk
+fn retain_children_with_observer() {
+  let doc = Parser::default()
+    .parse_string("<r><a/><b/><a/><c/><a/></r>")
+    .unwrap();
+  let handle = doc.clone();
+  // Each removal of an `a` also removes the sibling after it, the one the walk goes on with
+  let id = doc.observe(move |mutation| {
+    if let Mutation::Removed { node, .. } = mutation
+      && node.get_name() == "a"
+    {
+      let root = handle.get_root_element().unwrap();
+      if let Some(mut sibling) = root.element_children().find(|child| child.get_name() != "a") {
+        sibling.unlink();
+      }
+    }
+  });
+  let mut root = doc.get_root_element().unwrap();
+  assert_eq!(root.retain_children(|child| child.get_name() != "a").unwrap(), 3);
+  doc.unobserve(id);
+  assert_eq!(doc.node_to_string(&root), "<r/>");
+}
+
+#[test]
+/// Data attached to nodes is dropped along with them
+fn node_user_data() {
+  struct Counted(Rc<Cell<u32>>);
+  impl Drop for Counted {
+    fn drop(&mut self) {
+      self.0.set(self.0.get() + 1);
+    }
+  }
+  let dropped = Rc::new(Cell::new(0));
+  let parser = Parser::default();
+  let doc = parser.parse_string("<r><a/><b/><c/></r>").unwrap();
+  let root = doc.get_root_element().unwrap();
+  let mut a = root.get_first_element_child().unwrap();
+  a.set_user_data(String::from("note")).unwrap();
+  assert_eq!(a.get_user_data::<String>().as_deref().map(String::as_str), Some("note"));
+  assert_eq!(a.get_user_data::<u8>(), None);
+  assert_eq!(a.take_user_data::<u8>().unwrap(), None);
+  assert_eq!(a.take_user_data::<String>().unwrap().as_deref().map(String::as_str), Some("note"));
+  assert_eq!(a.get_user_data::<String>(), None);
+
+  a.set_user_data(Counted(dropped.clone())).unwrap();
+  a.set_user_data(Counted(dropped.clone())).unwrap();
+  assert_eq!(dropped.get(), 1);
+  let mut b = root.nth_element_child(1).unwrap();
+  b.set_user_data(Counted(dropped.clone())).unwrap();
+  b.unlink();
+  drop(b);
+  assert_eq!(dropped.get(), 2);
+  let mut c = root.get_last_element_child().unwrap();
+  c.set_user_data(Counted(dropped.clone())).unwrap();
+  drop((a, c, root));
+  drop(doc);
+  assert_eq!(dropped.get(), 4);
+}
+
+#[test]
+/// Data handed out outlives the node and document it was attached to
+fn node_user_data_outlives_document() {
+  let doc = Parser::default().parse_string("<r><a/></r>").unwrap();
+  let mut a = doc.get_root_element().unwrap().get_first_child().unwrap();
+  a.set_user_data(String::from("note")).unwrap();
+  let data = a.get_user_data::<String>().unwrap();
+  drop(a);
+  drop(doc);
+  // Reuse the freed memory
+  let _filler = Parser::default().parse_string("<t>text</t>".repeat(100)).unwrap();
+  assert_eq!(data.as_str(), "note");
+  assert_eq!(Rc::strong_count(&data), 1);
+}
+
+#[test]
+fn nested_traversal_and_mutation() {
+  let doc = Parser::default()
+    .parse_string("<r><a><x/></a><b><x/></b><c/></r>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  // Walk the tree while removing the `x` elements through handles found in an inner walk
+  for outer in root.get_child_nodes() {
+    for mut inner in outer.descendants().collect::<Vec<_>>() {
+      let parent = inner.get_parent().unwrap();
+      assert_eq!(parent, outer);
+      inner.unlink();
+      drop(inner);
+      assert!(doc.get_root_element().unwrap().descendants().count() > 0);
+    }
+  }
+  // Observer callbacks may use and drop any handle, including the last one to a removed node
+  let handle = doc.clone();
+  let id = doc.observe(move |_| {
+    let root = handle.get_root_element().unwrap();
+    for mut child in root.get_child_nodes() {
+      if child.get_name() == "c" {
+        child.unlink();
+      }
+    }
+  });
+  let mut b = root.get_first_element_child().unwrap().get_next_element_sibling().unwrap();
+  b.set_attribute("seen", "yes").unwrap();
+  doc.unobserve(id);
+  assert_eq!(doc.node_to_string(&root), r#"<r><a/><b seen="yes"/></r>"#);
+}
+
+#[test]
+fn lazy_children() {
+  let doc = Parser::default()
+    .parse_string("<r>text<a/><!--c--><b id='x'/><c/></r>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let names = root.children().map(|child| child.get_name()).collect::<Vec<_>>();
+  assert_eq!(names, ["text", "a", "comment", "b", "c"]);
+  let elements = root.element_children().map(|child| child.get_name()).collect::<Vec<_>>();
+  assert_eq!(elements, ["a", "b", "c"]);
+  let b = root.element_children().find(|child| child.has_attribute("id"));
+  assert_eq!(b.map(|b| b.get_name()).as_deref(), Some("b"));
+  assert_eq!(root.get_first_element_child().unwrap().children().count(), 0);
+
+  let ro_root = doc.get_root_readonly().unwrap();
+  assert_eq!(ro_root.children().count(), 5);
+  assert_eq!(ro_root.element_children().nth(2).map(|c| c.get_name()).as_deref(), Some("c"));
+}
+
+#[test]
+fn lazy_children_unlinked_while_iterating() {
+  let doc = Parser::default()
+    .parse_string("<r>text<a/><!--c--><b id='x'/><c/></r>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let mut count = 0;
+  for mut child in root.children() {
+    child.unlink();
+    count += 1;
+  }
+  assert_eq!(count, 5);
+  assert!(root.get_first_child().is_none());
+
+  let doc = Parser::default().parse_string("<r><a/>text<b/><c/></r>").unwrap();
+  let root = doc.get_root_element().unwrap();
+  let mut names = Vec::new();
+  for mut child in root.element_children() {
+    names.push(child.get_name());
+    if child.get_name() != "c" {
+      child.unlink();
+    }
+  }
+  assert_eq!(names, ["a", "b", "c"]);
+  assert_eq!(doc.node_to_string(&root), "<r>text<c/></r>");
+}
+
+#[test]
+fn aggregated_text_content() {
+  let doc = Parser::default()
+    .parse_string("<r>one <b>two <i>three</i></b><!--no--><![CDATA[ four]]></r>")
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.text_content(), "one two three four");
+  assert_eq!(root.text_content(), root.get_content());
+
+  let mut text = String::from("> ");
+  for child in root.element_children() {
+    child.text_content_into(&mut text);
+    text.push('|');
+  }
+  assert_eq!(text, "> two three|");
+  let comment = root.children().find(|child| child.get_type() == Some(NodeType::CommentNode));
+  assert_eq!(comment.unwrap().text_content(), "no");
+}
+
+#[test]
+fn find_children_by_name() {
+  let doc = Parser::default()
+    .parse_string(
+      r#"<config xmlns:x="urn:x"><server port="1"/><x:server port="2"/><server port="3"/><!--server--></config>"#,
+    )
+    .unwrap();
+  let root = doc.get_root_element().unwrap();
+  let server = root.find_child(None, "server").unwrap();
+  assert_eq!(server.get_attribute("port").as_deref(), Some("1"));
+  let x_server = root.find_child(Some("urn:x"), "server").unwrap();
+  assert_eq!(x_server.get_attribute("port").as_deref(), Some("2"));
+  assert!(root.find_child(Some("urn:y"), "server").is_none());
+  assert!(root.find_child(None, "x:server").is_none());
+
+  let ports = root
+    .find_children(None, "server")
+    .iter()
+    .map(|server| server.get_attribute("port").unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(ports, ["1", "3"]);
+  assert!(server.find_children(None, "server").is_empty());
+}
+
+#[test]
+fn move_subtree_between_documents() {
+  let target = Parser::default().parse_string("<target/>").unwrap();
+  let mut target_root = target.get_root_element().unwrap();
+  let mut item = {
+    let source = Parser::default()
+      .parse_string(concat!(
+        r#"<s:list xmlns:s="urn:s" xmlns:t="urn:t">"#,
+        r#"<s:item t:n="1">one</s:item></s:list>"#
+      ))
+      .unwrap();
+    let mut item = source.get_root_element().unwrap().get_first_child().unwrap();
+    item.move_to(&mut target_root).unwrap();
+    assert!(source.get_root_element().unwrap().get_first_child().is_none());
+    item
+  };
+  // The source document is gone, the moved subtree carries its own declarations
+  assert_eq!(item.get_namespace().unwrap().get_href(), "urn:s");
+  assert_eq!(item.get_attribute_ns("n", "urn:t").as_deref(), Some("1"));
+  assert_eq!(
+    target.node_to_string(&target_root),
+    r#"<target><s:item xmlns:s="urn:s" xmlns:t="urn:t" t:n="1">one</s:item></target>"#
+  );
+
+  // Within a document, a node moves like any other and can't go below itself
+  let mut child = Node::new("child", None, &target).unwrap();
+  item.add_child(&mut child).unwrap();
+  assert!(item.move_to(&mut child).is_err());
+  child.move_to(&mut target_root).unwrap();
+  assert_eq!(target_root.get_child_elements().len(), 2);
+}
+
+#[test]
+fn bulk_append() {
+  let doc = Parser::default().parse_string("<r><moved/></r>").unwrap();
+  let mut root = doc.get_root_element().unwrap();
+  let mut list = Node::new("list", None, &doc).unwrap();
+  root.add_child(&mut list).unwrap();
+  let mut children = Vec::new();
+  for i in 0..3 {
+    children.push(Node::new_text(&i.to_string(), &doc).unwrap());
+  }
+  children.push(root.get_first_child().unwrap());
+  assert_eq!(list.append_children(children).unwrap(), 4);
+  // Adjacent text nodes are kept apart
+  assert_eq!(list.get_child_nodes().len(), 4);
+  assert_eq!(doc.node_to_string(&root), "<r><list>012<moved/></list></r>");
+  assert!(list.append_children([root.clone()]).is_err());
+
+  let mut item = list.get_last_child().unwrap();
+  let attributes = [("id", "a"), ("kind", "x & y")];
+  let events = [
+    BuildEvent::StartElement { name: "entry", attributes: &attributes },
+    BuildEvent::Text("one < two"),
+    BuildEvent::EndElement,
+    BuildEvent::Comment("done"),
+  ];
+  assert_eq!(item.append_events(events).unwrap(), 2);
+  assert_eq!(
+    doc.node_to_string(&item),
+    r#"<moved><entry id="a" kind="x &amp; y">one &lt; two</entry><!--done--></moved>"#
+  );
+  let unbalanced = [BuildEvent::StartElement { name: "open", attributes: &[] }];
+  assert!(item.append_events(unbalanced).is_err());
+  assert!(item.append_events([BuildEvent::EndElement]).is_err());
+  assert_eq!(item.get_child_nodes().len(), 2);
+
+  let mut generated = Document::new().unwrap();
+  let events = [
+    BuildEvent::StartElement { name: "root", attributes: &[] },
+    BuildEvent::StartElement { name: "leaf", attributes: &[] },
+    BuildEvent::EndElement,
+    BuildEvent::EndElement,
+  ];
+  let root = generated.build_from_events(events).unwrap();
+  assert_eq!(generated.node_to_string(&root), "<root><leaf/></root>");
+  assert!(generated.build_from_events([BuildEvent::Text("loose")]).is_err());
+}
@@ -0,0 +1,141 @@
+//! Tests for XInclude processing with provenance tracking
+//!
+use libxml::parser::Parser;
+use libxml::tree::Document;
+use libxml::xinclude::{
+  inclusion_of, process, uninclude, Inclusion, ProvenanceMode, XIncludeOptions, PROVENANCE_NS,
+};
+
+const BOOK: &str = "tests/resources/xinclude/book.xml";
+const ORIGINAL: &str = r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><title>Manual</title><xi:include href="chapter.xml"/><xi:include href="sections.xml" xpointer="xpointer(/sections/section)"/><license><xi:include href="license.txt" parse="text"/></license></book>"#;
+
+fn book() -> Document {
+  let mut doc = Parser::default().parse_file(BOOK).unwrap();
+  doc.set_url(BOOK);
+  doc
+}
+
+fn root_string(doc: &Document) -> String {
+  doc.node_to_string(&doc.get_root_element().unwrap())
+}
+
+fn options(provenance: ProvenanceMode) -> XIncludeOptions {
+  XIncludeOptions {
+    provenance,
+    ..Default::default()
+  }
+}
+
+#[test]
+fn plain_processing() {
+  let mut doc = book();
+  assert_eq!(process(&mut doc, &XIncludeOptions::default()).unwrap(), 3);
+  assert_eq!(
+    root_string(&doc),
+    r#"<book xmlns:xi="http://www.w3.org/2001/XInclude"><title>Manual</title><chapter id="intro">Getting started</chapter><section n="1"/><section n="2"/><license>MIT &amp; friends</license></book>"#
+  );
+  let chapter = doc.get_root_element().unwrap().get_child_elements()[1].clone();
+  assert_eq!(inclusion_of(&chapter), None);
+}
+
+#[test]
+fn in_memory_documents_with_a_base_url() {
+  // Without a URL, relative includes have nothing to resolve against
+  let mut doc = Parser::default().parse_string(ORIGINAL).unwrap();
+  assert!(process(&mut doc, &XIncludeOptions::default()).is_err());
+
+  let mut doc = Parser::default()
+    .parse_string_with_base_url(ORIGINAL, BOOK)
+    .unwrap();
+  assert_eq!(process(&mut doc, &XIncludeOptions::default()).unwrap(), 3);
+  let root = doc.get_root_element().unwrap();
+  assert_eq!(root.get_child_elements()[1].get_name(), "chapter");
+}
+
+#[test]
+fn provenance_markers_roundtrip() {
+  let mut doc = book();
+  process(&mut doc, &options(ProvenanceMode::Markers)).unwrap();
+  let elements = doc.get_root_element().unwrap().get_child_elements();
+  assert_eq!(elements.len(), 5);
+  assert_eq!(
+    inclusion_of(&elements[2]),
+    Some(Inclusion {
+      href: "sections.xml".to_string(),
+      xpointer: Some("xpointer(/sections/section)".to_string()),
+      parse: None,
+    })
+  );
+  assert_eq!(inclusion_of(&elements[0]), None);
+
+  let splits = uninclude(&mut doc).unwrap();
+  assert_eq!(root_string(&doc), ORIGINAL);
+  let contents: Vec<(&str, &str)> = splits
+    .iter()
+    .map(|split| (split.inclusion.href.as_str(), split.content.as_str()))
+    .collect();
+  assert_eq!(
+    contents,
+    vec![
+      ("chapter.xml", r#"<chapter id="intro">Getting started</chapter>"#),
+      ("sections.xml", r#"<section n="1"/><section n="2"/>"#),
+      ("license.txt", "MIT & friends"),
+    ]
+  );
+}
+
+#[test]
+fn provenance_attributes() {
+  let mut doc = book();
+  process(&mut doc, &options(ProvenanceMode::Attributes)).unwrap();
+  let root = doc.get_root_element().unwrap();
+  let chapter = root.get_child_elements()[1].clone();
+  assert_eq!(
+    chapter.get_property_ns("href", PROVENANCE_NS).as_deref(),
+    Some("chapter.xml")
+  );
+  assert_eq!(inclusion_of(&chapter).unwrap().href, "chapter.xml");
+
+  let splits = uninclude(&mut doc).unwrap();
+  assert_eq!(splits.len(), 3);
+  assert_eq!(splits[0].content, r#"<chapter id="intro">Getting started</chapter>"#);
+  assert_eq!(splits[1].inclusion.xpointer.as_deref(), Some("xpointer(/sections/section)"));
+  assert!(root_string(&doc).contains(r#"<xi:include href="chapter.xml"/>"#));
+}
+
+#[test]
+fn no_net_keeps_includes_local() {
+  use std::net::TcpListener;
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::Arc;
+
+  // A server hanging up on every connection, recording whether any was made
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let port = listener.local_addr().unwrap().port();
+  let contacted = Arc::new(AtomicBool::new(false));
+  let seen = contacted.clone();
+  std::thread::spawn(move || {
+    for stream in listener.incoming() {
+      seen.store(true, Ordering::SeqCst);
+      drop(stream);
+    }
+  });
+
+  let xml = format!(
+    r#"<doc xmlns:xi="http://www.w3.org/2001/XInclude"><xi:include href="http://127.0.0.1:{port}/remote.xml"/></doc>"#
+  );
+  let mut doc = Parser::default().parse_string(&xml).unwrap();
+  let options = XIncludeOptions {
+    no_net: true,
+    ..Default::default()
+  };
+  assert!(process(&mut doc, &options).is_err());
+  assert!(!contacted.load(Ordering::SeqCst));
+
+  // Without it, libxml2 builds with HTTP support do connect
+  if libxml::runtime_info().has_feature(libxml::runtime::Feature::Http) {
+    let mut doc = Parser::default().parse_string(&xml).unwrap();
+    assert!(process(&mut doc, &XIncludeOptions::default()).is_err());
+    assert!(contacted.load(Ordering::SeqCst));
+  }
+}
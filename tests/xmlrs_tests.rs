@@ -0,0 +1,167 @@
+//! Tests for the `xml-rs` compatible event reader
+//!
+#![cfg(feature = "xml-rs")]
+
+use std::io::{self, Read};
+
+use libxml::xmlrs::EventReader;
+use xml::common::XmlVersion;
+use xml::reader::XmlEvent;
+
+fn events(input: &str) -> Vec<XmlEvent> {
+  EventReader::new(input.as_bytes())
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap()
+}
+
+#[test]
+fn reads_document_events() {
+  let events = events(
+    "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\
+     <root a=\"1\"><!--note--><?pi data?><item/>text<![CDATA[<raw>]]></root>",
+  );
+  assert_eq!(
+    events[0],
+    XmlEvent::StartDocument {
+      version: XmlVersion::Version10,
+      encoding: "UTF-8".to_string(),
+      standalone: Some(true),
+    }
+  );
+  let summary: Vec<String> = events[1..]
+    .iter()
+    .map(|event| match event {
+      XmlEvent::StartElement {
+        name, attributes, ..
+      } => {
+        let attrs: Vec<String> = attributes
+          .iter()
+          .map(|a| format!("{}={}", a.name.local_name, a.value))
+          .collect();
+        format!("start {} {:?}", name.local_name, attrs)
+      }
+      XmlEvent::EndElement { name } => format!("end {}", name.local_name),
+      XmlEvent::Comment(text) => format!("comment {text}"),
+      XmlEvent::ProcessingInstruction { name, data } => format!("pi {name} {data:?}"),
+      XmlEvent::Characters(text) => format!("text {text}"),
+      XmlEvent::CData(text) => format!("cdata {text}"),
+      XmlEvent::EndDocument => "end-document".to_string(),
+      other => format!("{other:?}"),
+    })
+    .collect();
+  assert_eq!(
+    summary,
+    vec![
+      "start root [\"a=1\"]",
+      "comment note",
+      "pi pi Some(\"data\")",
+      "start item []",
+      "end item",
+      "text text",
+      "cdata <raw>",
+      "end root",
+      "end-document",
+    ]
+  );
+}
+
+#[test]
+fn reports_namespaces_outside_attributes() {
+  let events = events("<a:root xmlns:a=\"urn:a\" xmlns=\"urn:d\" a:x=\"1\"><child/></a:root>");
+  match &events[1] {
+    XmlEvent::StartElement {
+      name,
+      attributes,
+      namespace,
+    } => {
+      assert_eq!(name.namespace.as_deref(), Some("urn:a"));
+      assert_eq!(name.prefix.as_deref(), Some("a"));
+      assert_eq!(attributes.len(), 1);
+      assert_eq!(attributes[0].name.namespace.as_deref(), Some("urn:a"));
+      assert_eq!(namespace.get("a"), Some("urn:a"));
+      assert_eq!(namespace.get(""), Some("urn:d"));
+    }
+    other => panic!("unexpected event {other:?}"),
+  }
+  match &events[2] {
+    XmlEvent::StartElement {
+      name, namespace, ..
+    } => {
+      assert_eq!(name.namespace.as_deref(), Some("urn:d"));
+      assert_eq!(namespace.get("a"), Some("urn:a"));
+    }
+    other => panic!("unexpected event {other:?}"),
+  }
+}
+
+#[test]
+fn reports_malformed_input() {
+  let mut reader = EventReader::new("<root><open></root>".as_bytes());
+  let mut failed = false;
+  for _ in 0..10 {
+    match reader.next() {
+      Ok(XmlEvent::EndDocument) => break,
+      Ok(_) => {}
+      Err(e) => {
+        assert!(!e.to_string().is_empty());
+        failed = true;
+        break;
+      }
+    }
+  }
+  assert!(failed);
+}
+
+#[test]
+fn does_not_load_external_entities() {
+  let path = std::env::temp_dir().join(format!("xmlrs-xxe-{}.txt", std::process::id()));
+  std::fs::write(&path, "secret").unwrap();
+  let input = format!(
+    "<!DOCTYPE r [<!ENTITY local \"inline\"><!ENTITY file SYSTEM \"file://{}\">]>\
+     <r>&local;&file;</r>",
+    path.display()
+  );
+  let text: String = EventReader::new(input.as_bytes())
+    .into_iter()
+    .filter_map(|event| match event {
+      Ok(XmlEvent::Characters(text)) => Some(text),
+      _ => None,
+    })
+    .collect();
+  std::fs::remove_file(&path).unwrap();
+  assert!(text.contains("inline"));
+  assert!(!text.contains("secret"));
+}
+
+/// Fails every other read with `Interrupted`
+struct Interrupting<'a> {
+  input: &'a [u8],
+  interrupt: bool,
+}
+
+impl Read for Interrupting<'_> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.interrupt = !self.interrupt;
+    if self.interrupt {
+      return Err(io::ErrorKind::Interrupted.into());
+    }
+    let len = buf.len().min(self.input.len()).min(4);
+    buf[..len].copy_from_slice(&self.input[..len]);
+    self.input = &self.input[len..];
+    Ok(len)
+  }
+}
+
+#[test]
+fn retries_interrupted_reads() {
+  let source = Interrupting {
+    input: b"<root><item>text</item></root>",
+    interrupt: false,
+  };
+  let events = EventReader::new(source)
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap();
+  assert_eq!(events.len(), 7);
+}
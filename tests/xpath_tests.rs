@@ -2,7 +2,7 @@
 //!
 
 use libxml::parser::Parser;
-use libxml::xpath::Context;
+use libxml::xpath::{CompiledExpression, Context};
 
 #[test]
 /// Test the evaluation of an xpath expression yields the correct number of nodes
@@ -81,6 +81,7 @@ fn xpath_with_namespaces() {
 #[test]
 /// Test that an xpath expression finds the correct node and
 /// that the class names are interpreted correctly.
+#[cfg(feature = "html")]
 fn class_names() {
   let parser = Parser::default_html();
   let doc_result = parser.parse_file("tests/resources/file02.xml");
@@ -103,6 +104,7 @@ fn class_names() {
 
 #[test]
 /// Test that an xpath string() function processed correctly
+#[cfg(feature = "html")]
 fn xpath_string_function() {
   let parser = Parser::default_html();
   let doc_result = parser.parse_file("tests/resources/file01.xml");
@@ -121,6 +123,7 @@ fn xpath_string_function() {
 
 #[test]
 /// Test that the dual findnodes interfaces are operational
+#[cfg(feature = "html")]
 fn findnodes_interfaces() {
   let parser = Parser::default_html();
   let doc_result = parser.parse_file("tests/resources/file02.xml");
@@ -145,6 +148,7 @@ fn findnodes_interfaces() {
 
 #[test]
 /// Clone is safe on Context objects
+#[cfg(feature = "html")]
 fn safe_context_clone() {
   let parser = Parser::default_html();
   let doc_result = parser.parse_file("tests/resources/file02.xml");
@@ -232,3 +236,38 @@ mod compile_tests {
     assert!(!compiles);
   }
 }
+
+#[test]
+fn compiled_expressions() {
+  let expression = CompiledExpression::new("p:item[@n > 1]/text()").unwrap();
+  assert!(CompiledExpression::new("//[").is_err());
+  let parser = Parser::default();
+  let mut found = Vec::new();
+  for (xml, items) in [
+    (r#"<r xmlns:x="urn:x"><x:list><x:item n="1">a</x:item><x:item n="2">b</x:item></x:list></r>"#, 2),
+    (r#"<x:list xmlns:x="urn:x"><x:item n="3">c</x:item></x:list>"#, 1),
+  ] {
+    let doc = parser.parse_string(xml).unwrap();
+    let context = Context::new(&doc).unwrap();
+    context.register_namespace("p", "urn:x").unwrap();
+    let lists = context.evaluate("//p:list").unwrap().get_nodes_as_vec();
+    for list in &lists {
+      let result = context.node_evaluate_compiled(&expression, list).unwrap();
+      found.extend(result.get_nodes_as_str());
+    }
+    let root = doc.get_root_readonly().unwrap();
+    let count = CompiledExpression::new("count(//p:item)").unwrap();
+    let total = context.node_evaluate_compiled_readonly(&count, root).unwrap();
+    assert_eq!(total.to_string(), items.to_string());
+    let all = context.evaluate_compiled(&CompiledExpression::new("//p:item").unwrap()).unwrap();
+    let names: Vec<String> = all.get_nodes_as_vec().iter().map(|item| item.get_name()).collect();
+    assert_eq!(names, vec!["item"; items]);
+
+    // The context node is restored after evaluating at another one
+    let children = CompiledExpression::new("count(p:item)").unwrap();
+    let list = &lists[0];
+    assert_eq!(context.node_evaluate_compiled(&children, list).unwrap().to_string(), items.to_string());
+    assert_eq!(context.evaluate_compiled(&children).unwrap().to_string(), "0");
+  }
+  assert_eq!(found, ["b", "c"]);
+}